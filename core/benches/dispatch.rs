@@ -0,0 +1,40 @@
+//! Compares the cost of publishing an event through a Publisher, which supports handlers of any
+//! event type, with a TypedPublisher, which only supports one
+use std::hint::black_box;
+
+use crier::{Event, Publisher, TypedPublisher};
+use criterion::{Criterion, criterion_group, criterion_main};
+
+#[derive(Clone, Event)]
+struct Tick(u64);
+
+const HANDLERS: usize = 4;
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("publish to 4 handlers");
+
+    let mut publisher = Publisher::default();
+    for _ in 0..HANDLERS {
+        publisher.subscribe_with(|tick: Tick| {
+            black_box(tick.0);
+        });
+    }
+    group.bench_function("Publisher", |b| {
+        b.iter(|| publisher.publish(Tick(black_box(1))))
+    });
+
+    let mut typed = TypedPublisher::default();
+    for _ in 0..HANDLERS {
+        typed.subscribe(|tick: &Tick| {
+            black_box(tick.0);
+        });
+    }
+    group.bench_function("TypedPublisher", |b| {
+        b.iter(|| typed.publish(Tick(black_box(1))))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
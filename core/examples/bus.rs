@@ -0,0 +1,30 @@
+use crier::{Event, define_bus};
+
+#[derive(Clone, Event)]
+struct PlayerJoined {
+    name: String,
+}
+
+#[derive(Clone, Event)]
+struct PacketLost {
+    sequence: u32,
+}
+
+define_bus! {
+    GameBus {
+        player: PlayerJoined,
+        net: PacketLost,
+    }
+}
+
+fn main() {
+    let mut bus = GameBus::default();
+
+    bus.on_player(|event| println!("{} joined", event.name));
+    bus.on_net(|event| println!("Lost packet {}", event.sequence));
+
+    let _ = bus.publish_player(PlayerJoined {
+        name: String::from("Ferris"),
+    });
+    let _ = bus.publish_net(PacketLost { sequence: 7 });
+}
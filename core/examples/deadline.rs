@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+use crier::{Deadline, Event, Publisher};
+
+#[derive(Clone, Event)]
+#[event(deadline)]
+struct MotorCommand {
+    speed: f32,
+    due: Instant,
+}
+
+impl Deadline for MotorCommand {
+    fn deadline(&self) -> Instant {
+        self.due
+    }
+}
+
+fn main() {
+    let mut publisher = Publisher::default();
+
+    publisher.subscribe_with(|command: MotorCommand| println!("Set speed to {}", command.speed));
+
+    let now = Instant::now();
+    // Delivered earliest deadline first, so the second command runs before the first
+    let _ = publisher.publish_all(vec![
+        MotorCommand {
+            speed: 1.0,
+            due: now + Duration::from_millis(20),
+        },
+        MotorCommand {
+            speed: 0.5,
+            due: now + Duration::from_millis(10),
+        },
+    ]);
+
+    println!("Missed deadlines: {}", publisher.missed_deadlines());
+}
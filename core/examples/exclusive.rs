@@ -0,0 +1,31 @@
+use std::{fs::File, sync::Arc};
+
+use crier::{Event, Owned, Publisher};
+
+// Neither event is Clone, so they choose how they are delivered instead
+
+/// Every handler gets an Arc pointing to the same report
+#[derive(Event)]
+#[event(shared)]
+struct Report(Vec<u8>);
+
+/// The first handler that accepts the file takes ownership of it
+#[derive(Event)]
+#[event(exclusive)]
+struct Upload(File);
+
+fn main() {
+    let mut publisher = Publisher::default();
+
+    publisher.subscribe_with(|report: Arc<Report>| println!("Report is {} bytes", report.0.len()));
+    publisher.subscribe_with(|upload: Owned<Upload>| {
+        let Owned(Upload(file)) = upload;
+        println!("Took ownership of {:?}", file);
+    });
+
+    let _ = publisher.publish(Report(vec![0; 1024]));
+
+    if let Ok(file) = File::open("Cargo.toml") {
+        let _ = publisher.publish(Upload(file));
+    }
+}
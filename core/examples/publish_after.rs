@@ -0,0 +1,26 @@
+use std::{thread, time::Duration};
+
+use crier::{Event, Publisher};
+
+#[derive(Clone, Event)]
+struct Reminder(String);
+
+fn main() {
+    let mut publisher = Publisher::default();
+
+    publisher.subscribe_with(|reminder: Reminder| println!("Reminder: {}", reminder.0));
+
+    publisher.publish_after(
+        Reminder(String::from("Stretch your legs")),
+        Duration::from_millis(100),
+    );
+
+    // This reminder is cancelled before it is published
+    let handle = publisher.publish_after(
+        Reminder(String::from("Never mind")),
+        Duration::from_millis(200),
+    );
+    handle.cancel();
+
+    thread::sleep(Duration::from_millis(300));
+}
@@ -20,5 +20,5 @@ fn main() {
     // warning_handler
     let _ = publisher.publish(Info(String::from("All good")));
 
-    publisher.unsubscribe(warning_id);
+    let _ = publisher.unsubscribe(warning_id);
 }
@@ -11,5 +11,5 @@ fn main() {
 
     let _ = publisher.publish(Message(String::from("Hello, world!")));
 
-    publisher.unsubscribe(handler_id);
+    let _ = publisher.unsubscribe(handler_id);
 }
@@ -32,5 +32,5 @@ fn main() {
 
     let _ = publisher.publish(Info(String::from("All good")));
 
-    publisher.unsubscribe(handler_id);
+    let _ = publisher.unsubscribe(handler_id);
 }
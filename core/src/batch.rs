@@ -0,0 +1,47 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{DynEvent, DynHandleBatch};
+
+/// A batch handler subscribed to a Publisher, along with the events buffered for its next batch
+pub(crate) struct Batched {
+    pub(crate) handler: Arc<dyn DynHandleBatch>,
+    max_size: usize,
+    buffer: Mutex<Vec<Arc<dyn DynEvent>>>,
+}
+
+impl Batched {
+    pub(crate) fn new(handler: Arc<dyn DynHandleBatch>, max_size: usize) -> Self {
+        Batched {
+            handler,
+            max_size: max_size.max(1),
+            buffer: Mutex::default(),
+        }
+    }
+
+    /// Buffer the events the handler accepts. Returns every full batch that has been buffered,
+    /// plus any partial batch left over if `flush` is true.
+    pub(crate) fn push(
+        &self,
+        events: &[Arc<dyn DynEvent>],
+        flush: bool,
+    ) -> Vec<Vec<Arc<dyn DynEvent>>> {
+        let mut buffer = self.buffer.lock().expect("Batch buffer mutex poisoned");
+        buffer.extend(
+            events
+                .iter()
+                .filter(|event| self.handler.accepts(event.as_ref()))
+                .cloned(),
+        );
+
+        let mut batches = Vec::new();
+        while buffer.len() >= self.max_size {
+            let rest = buffer.split_off(self.max_size);
+            batches.push(std::mem::replace(&mut *buffer, rest));
+        }
+        if flush && !buffer.is_empty() {
+            batches.push(std::mem::take(&mut *buffer));
+        }
+
+        batches
+    }
+}
@@ -0,0 +1,255 @@
+use std::{any::Any, collections::HashMap, fmt};
+
+use crate::{Event, Publisher};
+
+/// Published by `Bootstrap::run` once every module in a stage of startup has been initialized.
+/// Stage 0 holds the modules with no dependencies, and each later stage holds the modules whose
+/// dependencies were all initialized in earlier stages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InitStage {
+    pub stage: usize,
+    /// Names of the modules initialized in this stage, in the order they were registered
+    pub modules: Vec<String>,
+}
+
+impl Event for InitStage {}
+
+/// Reasons startup can't go ahead
+#[derive(Debug)]
+pub enum BootstrapError {
+    /// Two modules were registered with the same name
+    DuplicateModule(String),
+    /// A module depends on one that was never registered
+    MissingDependency { module: String, dependency: String },
+    /// The named modules depend on each other in a cycle, so none of them can go first
+    Cycle(Vec<String>),
+    /// Handlers of an `InitStage` event panicked, so startup stopped after that stage
+    Handler {
+        stage: usize,
+        errors: Vec<Box<dyn Any + Send + 'static>>,
+    },
+}
+
+impl fmt::Display for BootstrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootstrapError::DuplicateModule(module) => {
+                write!(f, "module {module} is registered more than once")
+            }
+            BootstrapError::MissingDependency { module, dependency } => {
+                write!(
+                    f,
+                    "module {module} depends on unregistered module {dependency}"
+                )
+            }
+            BootstrapError::Cycle(modules) => {
+                write!(
+                    f,
+                    "modules depend on each other in a cycle: {}",
+                    modules.join(", ")
+                )
+            }
+            BootstrapError::Handler { stage, errors } => {
+                write!(
+                    f,
+                    "{} handlers panicked in init stage {stage}",
+                    errors.len()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BootstrapError {}
+
+type Init = Box<dyn FnOnce(&mut Publisher)>;
+
+struct Module {
+    name: String,
+    depends_on: Vec<String>,
+    init: Init,
+}
+
+/// Initializes an application's modules in dependency order, publishing an `InitStage` event as
+/// each stage of startup completes, so that startup sequencing can be handled, and tested, like
+/// any other events.
+/// # Examples
+/// ```
+/// use crier::{Bootstrap, InitStage, Publisher};
+///
+/// let mut publisher = Publisher::default();
+/// let mut bootstrap = Bootstrap::default();
+/// bootstrap
+///     .register("renderer", &["config"], |publisher| {
+///         publisher.subscribe_with(|stage: InitStage| println!("ready: {:?}", stage.modules));
+///     })
+///     .register("config", &[], |_publisher| {});
+///
+/// bootstrap.run(&mut publisher).unwrap();
+/// ```
+#[derive(Default)]
+pub struct Bootstrap {
+    modules: Vec<Module>,
+}
+
+impl Bootstrap {
+    /// Register a module whose `init` runs once every module in `depends_on` has been
+    /// initialized. `init` receives the Publisher so that the module can subscribe its handlers.
+    pub fn register<F>(
+        &mut self,
+        name: impl Into<String>,
+        depends_on: &[&str],
+        init: F,
+    ) -> &mut Self
+    where
+        F: FnOnce(&mut Publisher) + 'static,
+    {
+        self.modules.push(Module {
+            name: name.into(),
+            depends_on: depends_on
+                .iter()
+                .map(|dependency| dependency.to_string())
+                .collect(),
+            init: Box::new(init),
+        });
+        self
+    }
+
+    /// Initialize every module, one stage at a time, publishing an `InitStage` after each stage.
+    /// Nothing is initialized if the modules' dependencies can't be satisfied.
+    pub fn run(self, publisher: &mut Publisher) -> Result<(), BootstrapError> {
+        let stages = self.stages()?;
+        let mut modules: Vec<Option<Module>> = self.modules.into_iter().map(Some).collect();
+        for (stage, indices) in stages.into_iter().enumerate() {
+            let mut names = Vec::new();
+            for index in indices {
+                if let Some(module) = modules[index].take() {
+                    (module.init)(publisher);
+                    names.push(module.name);
+                }
+            }
+
+            publisher
+                .publish(InitStage {
+                    stage,
+                    modules: names,
+                })
+                .map_err(|errors| BootstrapError::Handler { stage, errors })?;
+        }
+
+        Ok(())
+    }
+
+    /// Group the modules into stages, each holding the indices of the modules whose dependencies
+    /// are all in earlier stages
+    fn stages(&self) -> Result<Vec<Vec<usize>>, BootstrapError> {
+        let mut indices = HashMap::new();
+        for (index, module) in self.modules.iter().enumerate() {
+            if indices.insert(module.name.as_str(), index).is_some() {
+                return Err(BootstrapError::DuplicateModule(module.name.clone()));
+            }
+        }
+        for module in &self.modules {
+            if let Some(dependency) = module
+                .depends_on
+                .iter()
+                .find(|dependency| !indices.contains_key(dependency.as_str()))
+            {
+                return Err(BootstrapError::MissingDependency {
+                    module: module.name.clone(),
+                    dependency: dependency.clone(),
+                });
+            }
+        }
+
+        let mut stage_of: Vec<Option<usize>> = vec![None; self.modules.len()];
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        while stage_of.iter().any(Option::is_none) {
+            let stage = stages.len();
+            let ready: Vec<usize> = (0..self.modules.len())
+                .filter(|index| stage_of[*index].is_none())
+                .filter(|index| {
+                    self.modules[*index].depends_on.iter().all(|dependency| {
+                        stage_of[indices[dependency.as_str()]].is_some_and(|done| done < stage)
+                    })
+                })
+                .collect();
+            if ready.is_empty() {
+                return Err(BootstrapError::Cycle(
+                    (0..self.modules.len())
+                        .filter(|index| stage_of[*index].is_none())
+                        .map(|index| self.modules[index].name.clone())
+                        .collect(),
+                ));
+            }
+
+            for index in &ready {
+                stage_of[*index] = Some(stage);
+            }
+            stages.push(ready);
+        }
+
+        Ok(stages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_modules_initialize_after_their_dependencies() {
+        let mut publisher = Publisher::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let stage_log = log.clone();
+        publisher.subscribe_with(move |stage: InitStage| {
+            stage_log
+                .lock()
+                .unwrap()
+                .push(format!("stage {}", stage.stage));
+        });
+
+        let mut bootstrap = Bootstrap::default();
+        for (name, depends_on) in [
+            ("ui", &["renderer", "config"][..]),
+            ("renderer", &["config"][..]),
+            ("config", &[][..]),
+            ("audio", &[][..]),
+        ] {
+            let log = log.clone();
+            bootstrap.register(name, depends_on, move |_publisher| {
+                log.lock().unwrap().push(name.to_string());
+            });
+        }
+        bootstrap.run(&mut publisher).unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "config", "audio", "stage 0", "renderer", "stage 1", "ui", "stage 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsatisfiable_dependencies_initialize_nothing() {
+        let mut publisher = Publisher::default();
+        let mut bootstrap = Bootstrap::default();
+        bootstrap
+            .register("a", &["b"], |_publisher| panic!("should not run"))
+            .register("b", &["a"], |_publisher| panic!("should not run"))
+            .register("c", &[], |_publisher| panic!("should not run"));
+        assert!(matches!(
+            bootstrap.run(&mut publisher),
+            Err(BootstrapError::Cycle(modules)) if modules == ["a", "b"]
+        ));
+
+        let mut bootstrap = Bootstrap::default();
+        bootstrap.register("a", &["missing"], |_publisher| {});
+        assert!(matches!(
+            bootstrap.run(&mut publisher),
+            Err(BootstrapError::MissingDependency { .. })
+        ));
+    }
+}
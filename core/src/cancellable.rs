@@ -0,0 +1,72 @@
+use std::{
+    ops::Deref,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::{Deadline, Delivery, Event, Partition};
+
+/// An event published with `Publisher::publish_cancellable`, which any handler can cancel to tell
+/// the publisher, and the other handlers, that it has been dealt with. Useful for input handling,
+/// where the first widget to handle a click should swallow it. Handlers receive it as
+/// `Arc<Cancellable<T>>`, since every handler shares the same cancelled flag.
+pub struct Cancellable<T> {
+    event: T,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> Cancellable<T> {
+    pub(crate) fn new(event: T, cancelled: Arc<AtomicBool>) -> Self {
+        Cancellable { event, cancelled }
+    }
+
+    /// Mark the event as cancelled. Returns true if this call cancelled it, or false if another
+    /// handler already had, so that when handlers run in parallel exactly one of them wins.
+    pub fn cancel(&self) -> bool {
+        !self.cancelled.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Deref for Cancellable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.event
+    }
+}
+
+impl<T: Event> Event for Cancellable<T> {
+    const DELIVERY: Delivery = Delivery::Shared;
+
+    fn as_deadline(&self) -> Option<&dyn Deadline> {
+        self.event.as_deadline()
+    }
+
+    fn as_partition(&self) -> Option<&dyn Partition> {
+        self.event.as_partition()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_first_cancel_wins() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let event = Cancellable::new(5, cancelled.clone());
+
+        assert!(!event.is_cancelled());
+        assert!(event.cancel());
+        assert!(!event.cancel());
+        assert!(event.is_cancelled());
+        assert!(cancelled.load(Ordering::SeqCst));
+        assert_eq!(*event, 5);
+    }
+}
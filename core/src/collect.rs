@@ -0,0 +1,116 @@
+use std::{
+    marker::PhantomData,
+    panic::RefUnwindSafe,
+    sync::{Arc, Mutex},
+};
+
+use crate::{Delivery, DynEvent, DynHandle, Event, FromEvent, HandleCollect};
+
+/// An event published with `Publisher::publish_collect`, along with somewhere for the handlers
+/// subscribed with `Publisher::subscribe_collect` to put what they return. Only those handlers
+/// receive it.
+pub(crate) struct Collecting<R> {
+    pub(crate) event: Box<dyn DynEvent>,
+    pub(crate) outputs: Arc<Mutex<Vec<R>>>,
+}
+
+impl<R> RefUnwindSafe for Collecting<R> {}
+
+impl<R: Send + 'static> Event for Collecting<R> {
+    const DELIVERY: Delivery = Delivery::Shared;
+}
+
+/// Handler subscribed with `Publisher::subscribe_collect`
+pub(crate) struct Collector<H>(pub(crate) H);
+
+impl<H> RefUnwindSafe for Collector<H> {}
+
+impl<H: HandleCollect + Send + Sync> DynHandle for Collector<H> {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        if let Some(collecting) = event.get_data().downcast_ref::<Collecting<H::Output>>()
+            && let Some(event) = H::EventType::from_event(collecting.event.as_ref())
+        {
+            let output = self.0.handle_collect(event);
+            collecting
+                .outputs
+                .lock()
+                .expect("Collect mutex poisoned")
+                .push(output);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event
+            .get_data()
+            .downcast_ref::<Collecting<H::Output>>()
+            .is_some_and(|collecting| {
+                collecting
+                    .event
+                    .get_data()
+                    .is::<<H::EventType as FromEvent>::Source>()
+            })
+    }
+}
+
+/// Closure subscribed with `Publisher::subscribe_collect_with`
+pub(crate) struct CollectFn<T, R, F> {
+    f: F,
+    types: PhantomData<fn(T) -> R>,
+}
+
+impl<T, R, F> CollectFn<T, R, F> {
+    pub(crate) fn new(f: F) -> Self {
+        CollectFn {
+            f,
+            types: PhantomData,
+        }
+    }
+}
+
+impl<T, R, F> HandleCollect for CollectFn<T, R, F>
+where
+    T: FromEvent,
+    R: Send + 'static,
+    F: Fn(T) -> R,
+{
+    type EventType = T;
+    type Output = R;
+
+    fn handle_collect(&self, event: T) -> R {
+        (self.f)(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct Vote(bool);
+    impl Event for Vote {}
+
+    #[derive(Clone)]
+    struct Other;
+    impl Event for Other {}
+
+    #[test]
+    fn test_collector_only_handles_its_event_type() {
+        let collector = Collector(CollectFn::new(|vote: Vote| vote.0));
+        let outputs = Arc::new(Mutex::new(Vec::new()));
+        let vote = Collecting {
+            event: Box::new(Vote(true)),
+            outputs: outputs.clone(),
+        };
+        let other = Collecting::<bool> {
+            event: Box::new(Other),
+            outputs: outputs.clone(),
+        };
+
+        assert!(collector.accepts(&vote));
+        assert!(!collector.accepts(&other));
+        assert!(!collector.accepts(&Vote(false)));
+        collector.dyn_handle(&vote);
+        collector.dyn_handle(&other);
+        assert_eq!(*outputs.lock().unwrap(), vec![true]);
+    }
+}
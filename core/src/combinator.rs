@@ -0,0 +1,219 @@
+use core::{marker::PhantomData, panic::RefUnwindSafe};
+
+use crate::{FromEvent, Handle};
+
+/// Combinators for wrapping existing handlers without modifying them. Everything they return is
+/// itself a Handle, so it can be subscribed to a Publisher or wrapped again.
+pub trait HandleExt: Handle + Sized {
+    /// Only run the handler for events that match `predicate`
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        P: Fn(&Self::EventType) -> bool,
+    {
+        Filter {
+            handler: self,
+            predicate,
+        }
+    }
+
+    /// Run the handler for events of type `U`, converted with `f`
+    fn map_event<U, F>(self, f: F) -> MapEvent<Self, F, U>
+    where
+        U: FromEvent,
+        F: Fn(U) -> Self::EventType,
+    {
+        MapEvent {
+            handler: self,
+            f,
+            event_type: PhantomData,
+        }
+    }
+
+    /// Run the handler for events of type `U` that `f` converts to `Some`
+    fn filter_map_event<U, F>(self, f: F) -> FilterMapEvent<Self, F, U>
+    where
+        U: FromEvent,
+        F: Fn(U) -> Option<Self::EventType>,
+    {
+        FilterMapEvent {
+            handler: self,
+            f,
+            event_type: PhantomData,
+        }
+    }
+
+    /// Call `f` with each event before passing it on to the handler
+    fn inspect<F>(self, f: F) -> Inspect<Self, F>
+    where
+        F: Fn(&Self::EventType),
+    {
+        Inspect { handler: self, f }
+    }
+}
+
+impl<H: Handle> HandleExt for H {}
+
+/// Handle returned by `HandleExt::filter`
+pub struct Filter<H, P> {
+    handler: H,
+    predicate: P,
+}
+
+impl<H: RefUnwindSafe, P> RefUnwindSafe for Filter<H, P> {}
+
+impl<H, P> Handle for Filter<H, P>
+where
+    H: Handle,
+    P: Fn(&H::EventType) -> bool,
+{
+    type EventType = H::EventType;
+
+    fn handle(&self, event: Self::EventType) {
+        if (self.predicate)(&event) {
+            self.handler.handle(event)
+        }
+    }
+}
+
+/// Handle returned by `HandleExt::map_event`
+pub struct MapEvent<H, F, U> {
+    handler: H,
+    f: F,
+    event_type: PhantomData<fn(U)>,
+}
+
+impl<H: RefUnwindSafe, F, U> RefUnwindSafe for MapEvent<H, F, U> {}
+
+impl<H, F, U> Handle for MapEvent<H, F, U>
+where
+    H: Handle,
+    U: FromEvent,
+    F: Fn(U) -> H::EventType,
+{
+    type EventType = U;
+
+    fn handle(&self, event: U) {
+        self.handler.handle((self.f)(event))
+    }
+}
+
+/// Handle returned by `HandleExt::filter_map_event`
+pub struct FilterMapEvent<H, F, U> {
+    handler: H,
+    f: F,
+    event_type: PhantomData<fn(U)>,
+}
+
+impl<H: RefUnwindSafe, F, U> RefUnwindSafe for FilterMapEvent<H, F, U> {}
+
+impl<H, F, U> Handle for FilterMapEvent<H, F, U>
+where
+    H: Handle,
+    U: FromEvent,
+    F: Fn(U) -> Option<H::EventType>,
+{
+    type EventType = U;
+
+    fn handle(&self, event: U) {
+        if let Some(event) = (self.f)(event) {
+            self.handler.handle(event)
+        }
+    }
+}
+
+/// Handle returned by `HandleExt::inspect`
+pub struct Inspect<H, F> {
+    handler: H,
+    f: F,
+}
+
+impl<H: RefUnwindSafe, F> RefUnwindSafe for Inspect<H, F> {}
+
+impl<H, F> Handle for Inspect<H, F>
+where
+    H: Handle,
+    F: Fn(&H::EventType),
+{
+    type EventType = H::EventType;
+
+    fn handle(&self, event: Self::EventType) {
+        (self.f)(&event);
+        self.handler.handle(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DynHandle, Event, Handler};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Celsius(i32);
+    impl Event for Celsius {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Fahrenheit(i32);
+    impl Event for Fahrenheit {}
+
+    fn recording_handler() -> (Handler<Celsius>, Arc<Mutex<Vec<i32>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let handler = Handler::new(move |event: Celsius| {
+            received_clone.lock().unwrap().push(event.0);
+        });
+        (handler, received)
+    }
+
+    #[test]
+    fn test_filter_skips_events_that_do_not_match() {
+        let (handler, received) = recording_handler();
+        let handler = handler.filter(|event| event.0 > 0);
+
+        handler.dyn_handle(&Celsius(-5));
+        handler.dyn_handle(&Celsius(5));
+
+        assert_eq!(*received.lock().unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn test_map_event_converts_events_of_another_type() {
+        let (handler, received) = recording_handler();
+        let handler = handler.map_event(|event: Fahrenheit| Celsius((event.0 - 32) * 5 / 9));
+
+        handler.dyn_handle(&Fahrenheit(212));
+        handler.dyn_handle(&Celsius(1));
+
+        assert_eq!(*received.lock().unwrap(), vec![100]);
+        assert!(handler.accepts(&Fahrenheit(0)));
+        assert!(!handler.accepts(&Celsius(0)));
+    }
+
+    #[test]
+    fn test_filter_map_event_skips_events_mapped_to_none() {
+        let (handler, received) = recording_handler();
+        let handler = handler
+            .filter_map_event(|event: Fahrenheit| (event.0 >= 32).then(|| Celsius(event.0 - 32)));
+
+        handler.dyn_handle(&Fahrenheit(0));
+        handler.dyn_handle(&Fahrenheit(50));
+
+        assert_eq!(*received.lock().unwrap(), vec![18]);
+    }
+
+    #[test]
+    fn test_inspect_sees_events_before_the_handler() {
+        let (handler, received) = recording_handler();
+        let inspected = Arc::new(Mutex::new(Vec::new()));
+        let inspected_clone = inspected.clone();
+        let handler = handler
+            .inspect(move |event| inspected_clone.lock().unwrap().push(event.0))
+            .filter(|event| event.0 % 2 == 0);
+
+        handler.dyn_handle(&Celsius(1));
+        handler.dyn_handle(&Celsius(2));
+
+        assert_eq!(*inspected.lock().unwrap(), vec![2]);
+        assert_eq!(*received.lock().unwrap(), vec![2]);
+    }
+}
@@ -0,0 +1,199 @@
+//! A stable boundary for registering handlers across crier versions, e.g. from plugins compiled
+//! against an older version of crier than their host. Events cross it serialized, as a type tag
+//! and payload, through `#[repr(C)]` types whose layout only changes along with `ABI_VERSION`.
+use std::{ffi::c_void, fmt, slice};
+
+/// Version of the layout of `RawHandler` and `RawEvent`, which is only bumped when they change
+pub const ABI_VERSION: u32 = 1;
+
+/// A serialized event as passed to a `RawHandler`. Its pointers are only valid for the duration
+/// of the call.
+#[repr(C)]
+pub struct RawEvent {
+    pub tag: *const u8,
+    pub tag_len: usize,
+    pub payload: *const u8,
+    pub payload_len: usize,
+}
+
+impl RawEvent {
+    /// The event's UTF-8 type tag
+    /// # Safety
+    /// `tag` must point to `tag_len` bytes of valid UTF-8 for the lifetime of the RawEvent
+    pub unsafe fn tag(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(slice::from_raw_parts(self.tag, self.tag_len)) }
+    }
+
+    /// The event's serialized payload
+    /// # Safety
+    /// `payload` must point to `payload_len` bytes for the lifetime of the RawEvent
+    pub unsafe fn payload(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.payload, self.payload_len) }
+    }
+}
+
+/// A handler of serialized events that can be passed between crier versions. `handle` is called
+/// with `context` for every event, possibly from several threads at once, and `drop` is called
+/// with `context` once the handler has been unsubscribed.
+#[repr(C)]
+pub struct RawHandler {
+    pub abi_version: u32,
+    pub context: *mut c_void,
+    pub handle: unsafe extern "C" fn(context: *mut c_void, event: RawEvent),
+    pub drop: unsafe extern "C" fn(context: *mut c_void),
+}
+
+// SAFETY: constructing a RawHandler requires its context to be usable from any thread, which
+// `from_fn` guarantees by requiring a Send + Sync closure
+unsafe impl Send for RawHandler {}
+unsafe impl Sync for RawHandler {}
+
+impl RawHandler {
+    /// Create a RawHandler for this version of the ABI that calls `handle` with the tag and
+    /// payload of each event
+    pub fn from_fn<F>(handle: F) -> Self
+    where
+        F: Fn(&str, &[u8]) + Send + Sync + 'static,
+    {
+        unsafe extern "C" fn call<F: Fn(&str, &[u8])>(context: *mut c_void, event: RawEvent) {
+            // SAFETY: context was created from a Box<F> in from_fn, and the event's pointers come
+            // from a SerializedEvent that outlives the call
+            let handle = unsafe { &*(context as *const F) };
+            unsafe { handle(event.tag(), event.payload()) }
+        }
+
+        unsafe extern "C" fn drop<F>(context: *mut c_void) {
+            // SAFETY: context was created from a Box<F> in from_fn, and is only dropped once
+            unsafe { std::mem::drop(Box::from_raw(context as *mut F)) }
+        }
+
+        RawHandler {
+            abi_version: ABI_VERSION,
+            context: Box::into_raw(Box::new(handle)) as *mut c_void,
+            handle: call::<F>,
+            drop: drop::<F>,
+        }
+    }
+
+    pub(crate) fn call(&self, tag: &str, payload: &[u8]) {
+        let event = RawEvent {
+            tag: tag.as_ptr(),
+            tag_len: tag.len(),
+            payload: payload.as_ptr(),
+            payload_len: payload.len(),
+        };
+        // SAFETY: whoever constructed the RawHandler guarantees that handle accepts its context
+        unsafe { (self.handle)(self.context, event) }
+    }
+}
+
+impl Drop for RawHandler {
+    fn drop(&mut self) {
+        // SAFETY: whoever constructed the RawHandler guarantees that drop accepts its context
+        unsafe { (self.drop)(self.context) }
+    }
+}
+
+/// A RawHandler was built against a different version of the ABI than the Publisher's
+#[derive(Debug, PartialEq, Eq)]
+pub struct AbiMismatch {
+    pub expected: u32,
+    pub found: u32,
+}
+
+impl fmt::Display for AbiMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "handler was built for ABI version {} but the publisher uses version {}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for AbiMismatch {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{Event, EventRegistry, Publisher, SerializableEvent};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Loaded {
+        plugin: String,
+    }
+    impl Event for Loaded {}
+    impl SerializableEvent for Loaded {
+        const TYPE_TAG: &'static str = "loaded";
+    }
+
+    #[test]
+    fn test_raw_handlers_receive_serialized_events_until_unsubscribed() {
+        let mut registry = EventRegistry::default();
+        registry.register::<Loaded>();
+        let registry = Arc::new(registry);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let mut publisher = Publisher::default();
+
+        let id = publisher
+            .subscribe_raw(
+                registry.clone(),
+                RawHandler::from_fn(move |tag, payload| {
+                    received_clone
+                        .lock()
+                        .unwrap()
+                        .push((tag.to_string(), payload.to_vec()));
+                }),
+            )
+            .unwrap();
+        publisher
+            .publish(Loaded {
+                plugin: "a".to_string(),
+            })
+            .unwrap();
+        publisher.unsubscribe(id).unwrap();
+        publisher
+            .publish(Loaded {
+                plugin: "b".to_string(),
+            })
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "loaded");
+        let event = registry
+            .deserialize(&crate::SerializedEvent {
+                tag: received[0].0.clone(),
+                payload: received[0].1.clone(),
+            })
+            .unwrap();
+        assert_eq!(
+            event.get_data().downcast_ref::<Loaded>(),
+            Some(&Loaded {
+                plugin: "a".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_raw_handlers_for_other_abi_versions_are_rejected() {
+        let mut handler = RawHandler::from_fn(|_, _| {});
+        handler.abi_version = ABI_VERSION + 1;
+
+        let result =
+            Publisher::default().subscribe_raw(Arc::new(EventRegistry::default()), handler);
+
+        assert_eq!(
+            result,
+            Err(AbiMismatch {
+                expected: ABI_VERSION,
+                found: ABI_VERSION + 1
+            })
+        );
+    }
+}
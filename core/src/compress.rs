@@ -0,0 +1,193 @@
+use std::sync::Mutex;
+
+use crate::{SerializeError, SerializedEvent};
+
+/// Type tag of the record that carries a trained dictionary. It starts with a NUL so that it
+/// can't clash with the tag of a real event type.
+pub const DICTIONARY_TAG: &str = "\0dictionary";
+
+/// Compresses payloads with a dictionary trained on samples of earlier ones. Small events of the
+/// same few types compress poorly on their own, but well against a dictionary of what they have
+/// in common. Implement it with e.g. zstd's dictionary trainer and bulk compressor.
+pub trait Compressor: Send + Sync + 'static {
+    /// Build a dictionary from sampled payloads
+    fn train(&self, samples: &[Vec<u8>]) -> Vec<u8>;
+
+    fn compress(&self, dictionary: &[u8], payload: &[u8]) -> Vec<u8>;
+
+    fn decompress(&self, dictionary: &[u8], compressed: &[u8]) -> Result<Vec<u8>, SerializeError>;
+}
+
+#[derive(Default)]
+struct Trained {
+    samples: Vec<Vec<u8>>,
+    dictionary: Option<Vec<u8>>,
+}
+
+/// Compresses or decompresses a stream of serialized events, such as a capture written by a
+/// `SerializingHandler`, with a dictionary trained on the stream itself.
+///
+/// When compressing, the first `samples` events pass through uncompressed while their payloads
+/// are sampled. The dictionary trained on them is then written to the stream as a record tagged
+/// `DICTIONARY_TAG`, and every later payload is compressed with it. When decompressing, the
+/// dictionary is picked up from the stream, so records must be read in the order they were
+/// written.
+pub struct DictionaryCompression<C> {
+    compressor: C,
+    samples: usize,
+    trained: Mutex<Trained>,
+}
+
+impl<C: Compressor> DictionaryCompression<C> {
+    /// Compress with `compressor`, training its dictionary on the first `samples` events
+    pub fn new(compressor: C, samples: usize) -> Self {
+        DictionaryCompression {
+            compressor,
+            samples: samples.max(1),
+            trained: Mutex::default(),
+        }
+    }
+
+    /// Compress an event, passing the records to write to `emit` in order. The lock is held
+    /// while emitting, so that records from concurrent events can't overtake the dictionary.
+    pub fn compress(&self, event: SerializedEvent, mut emit: impl FnMut(SerializedEvent)) {
+        let mut trained = self.trained.lock().expect("Dictionary mutex poisoned");
+        if let Some(dictionary) = &trained.dictionary {
+            let payload = self.compressor.compress(dictionary, &event.payload);
+            emit(SerializedEvent { payload, ..event });
+            return;
+        }
+
+        trained.samples.push(event.payload.clone());
+        emit(event);
+        if trained.samples.len() == self.samples {
+            let samples = std::mem::take(&mut trained.samples);
+            let dictionary = self.compressor.train(&samples);
+            emit(SerializedEvent {
+                tag: String::from(DICTIONARY_TAG),
+                payload: dictionary.clone(),
+            });
+            trained.dictionary = Some(dictionary);
+        }
+    }
+
+    /// Decompress a record read from a compressed stream. Returns None for the dictionary record,
+    /// which is kept for decompressing the records after it.
+    pub fn decompress(
+        &self,
+        record: SerializedEvent,
+    ) -> Result<Option<SerializedEvent>, SerializeError> {
+        let mut trained = self.trained.lock().expect("Dictionary mutex poisoned");
+        if record.tag == DICTIONARY_TAG {
+            trained.dictionary = Some(record.payload);
+            return Ok(None);
+        }
+
+        match &trained.dictionary {
+            Some(dictionary) => {
+                let payload = self.compressor.decompress(dictionary, &record.payload)?;
+                Ok(Some(SerializedEvent { payload, ..record }))
+            }
+            None => Ok(Some(record)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{Event, EventRegistry, Publisher, SerializableEvent, SerializingHandler};
+
+    /// Uses the prefix shared by every sample as the dictionary, and strips it from payloads
+    struct CommonPrefix;
+
+    impl Compressor for CommonPrefix {
+        fn train(&self, samples: &[Vec<u8>]) -> Vec<u8> {
+            let first = &samples[0];
+            let len = samples.iter().fold(first.len(), |len, sample| {
+                first
+                    .iter()
+                    .zip(sample)
+                    .take(len)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+            first[..len].to_vec()
+        }
+
+        fn compress(&self, dictionary: &[u8], payload: &[u8]) -> Vec<u8> {
+            match payload.strip_prefix(dictionary) {
+                Some(rest) => [&[1], rest].concat(),
+                None => [&[0], payload].concat(),
+            }
+        }
+
+        fn decompress(
+            &self,
+            dictionary: &[u8],
+            compressed: &[u8],
+        ) -> Result<Vec<u8>, SerializeError> {
+            match compressed.split_first() {
+                Some((1, rest)) => Ok([dictionary, rest].concat()),
+                Some((0, rest)) => Ok(rest.to_vec()),
+                _ => Err(SerializeError::Custom("unknown compression flag".into())),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: u8,
+    }
+    impl Event for Reading {}
+    impl SerializableEvent for Reading {
+        const TYPE_TAG: &'static str = "reading";
+    }
+
+    #[test]
+    fn test_captures_are_compressed_after_training_and_decompress_in_order() {
+        let mut registry = EventRegistry::default();
+        registry.register::<Reading>();
+        let registry = Arc::new(registry);
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let captured_clone = captured.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe(
+            SerializingHandler::new(registry.clone(), move |record| {
+                captured_clone.lock().unwrap().push(record)
+            })
+            .with_compression(DictionaryCompression::new(CommonPrefix, 2)),
+        );
+
+        let readings: Vec<Reading> = (1..=4)
+            .map(|value| Reading {
+                sensor: String::from("greenhouse"),
+                value,
+            })
+            .collect();
+        for reading in &readings {
+            publisher.publish(reading.clone()).unwrap();
+        }
+
+        let captured = captured.lock().unwrap().clone();
+        assert_eq!(captured.len(), 5);
+        assert_eq!(captured[2].tag, DICTIONARY_TAG);
+        assert!(captured[3].payload.len() < captured[0].payload.len());
+
+        let decompression = DictionaryCompression::new(CommonPrefix, 2);
+        let decompressed: Vec<Reading> = captured
+            .into_iter()
+            .filter_map(|record| decompression.decompress(record).unwrap())
+            .map(|record| {
+                let event = registry.deserialize(&record).unwrap();
+                event.get_data().downcast_ref::<Reading>().unwrap().clone()
+            })
+            .collect();
+        assert_eq!(decompressed, readings);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    any::{self, Any},
+    fmt,
+    panic::RefUnwindSafe,
+    sync::Arc,
+};
+
+use crate::{DynEvent, DynHandle, Event, Metadata};
+
+/// How far down the list of handlers a fallback subscription sits. A fallback only receives the
+/// events that no handler ranked ahead of it takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Fallback {
+    /// Subscribed with `Publisher::subscribe_default`
+    Default,
+    /// Subscribed with `Publisher::subscribe_dead_letter`, so behind default handlers too
+    DeadLetter,
+}
+
+/// An event that no other handler took, passed to the handlers subscribed with
+/// `Publisher::subscribe_dead_letter`
+#[derive(Clone)]
+pub struct DeadLetter {
+    /// Name of the event's concrete type
+    pub type_name: &'static str,
+    /// The metadata the event was published with
+    pub metadata: Option<Metadata>,
+    payload: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl DeadLetter {
+    /// The event, if it is of type `T`
+    pub fn downcast_ref<T: Event>(&self) -> Option<&T> {
+        self.payload.as_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Debug for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadLetter")
+            .field("type_name", &self.type_name)
+            .field("metadata", &self.metadata)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Passes every event it is given to a closure as a DeadLetter
+pub(crate) struct DeadLetterHandler {
+    handle: Box<dyn Fn(DeadLetter) + Send + Sync>,
+    label: &'static str,
+}
+
+impl RefUnwindSafe for DeadLetterHandler {}
+
+impl DeadLetterHandler {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(DeadLetter) + Send + Sync + 'static,
+    {
+        DeadLetterHandler {
+            handle: Box::new(f),
+            label: any::type_name::<F>(),
+        }
+    }
+}
+
+impl DynHandle for DeadLetterHandler {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        (self.handle)(DeadLetter {
+            type_name: event.type_name(),
+            metadata: event.metadata().cloned(),
+            payload: event.shared_data(),
+        })
+    }
+
+    fn label(&self) -> &'static str {
+        self.label
+    }
+}
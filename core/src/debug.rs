@@ -0,0 +1,303 @@
+//! A tiny HTTP endpoint for inspecting the Publisher of a running service.
+//!
+//! Start one with `Publisher::serve_debug`. Every response is JSON:
+//!
+//! - `GET /` describes the Publisher: its handlers, subscription groups, middleware and settings
+//! - `GET /metrics` gives counters such as how many events have been published
+//! - `GET /events` lists the most recently published events, oldest first
+//! - `POST /groups/{group}/enable` and `POST /groups/{group}/disable` toggle a subscription group
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+
+use crate::EventInfo;
+
+/// Number of recently published events kept for `GET /events`
+const RECENT_EVENTS: usize = 100;
+
+/// How long to wait for a client to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a client of the debug endpoint asked for
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum DebugRequest {
+    Introspect,
+    Metrics,
+    Events,
+    SetGroupEnabled { group: String, enabled: bool },
+}
+
+/// The most recently published events, recorded by a metadata handler
+#[derive(Default)]
+pub(crate) struct RecentEvents {
+    events: Mutex<VecDeque<EventInfo>>,
+}
+
+impl RecentEvents {
+    pub(crate) fn record(&self, info: EventInfo) {
+        let mut events = self.events.lock().expect("Recent events mutex poisoned");
+        if events.len() == RECENT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(info);
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let events = self.events.lock().expect("Recent events mutex poisoned");
+        events
+            .iter()
+            .map(|info| {
+                let timestamp = info
+                    .metadata
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                json!({
+                    "type_name": info.type_name,
+                    "size": info.size,
+                    "timestamp_ms": timestamp.as_millis() as u64,
+                    "sequence": info.metadata.sequence,
+                    "correlation_id": info.metadata.correlation_id,
+                    "source": info.metadata.source,
+                    "remote": info.metadata.remote,
+                    "topic": info.metadata.topic,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Serves the debug endpoint. Created by `Publisher::serve_debug`. Stops serving and stops
+/// recording events when dropped.
+pub struct DebugServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    unsubscribe: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl DebugServer {
+    /// Listen on `addr`, answering each request with what `respond` returns for it, until
+    /// `respond` returns None
+    pub(crate) fn bind<F>(addr: impl ToSocketAddrs, respond: F) -> io::Result<Self>
+    where
+        F: Fn(DebugRequest) -> Option<Value> + Send + 'static,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stopped = stopped.clone();
+            thread::Builder::new()
+                .name(String::from("crier-debug"))
+                .spawn(move || accept(listener, &stopped, &respond))?
+        };
+
+        Ok(DebugServer {
+            local_addr,
+            stopped,
+            thread: Some(thread),
+            unsubscribe: None,
+        })
+    }
+
+    /// Run `unsubscribe` when the server is dropped, to stop recording events
+    pub(crate) fn on_drop(&mut self, unsubscribe: impl FnOnce() + Send + 'static) {
+        self.unsubscribe = Some(Box::new(unsubscribe));
+    }
+
+    /// The address the server is bound to. Useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // wake the accept loop so that it sees it has been stopped
+        let mut wake = self.local_addr;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(match wake.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake, Duration::from_secs(5));
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer requests one at a time, since they are cheap and rare
+fn accept<F>(listener: TcpListener, stopped: &AtomicBool, respond: &F)
+where
+    F: Fn(DebugRequest) -> Option<Value>,
+{
+    for stream in listener.incoming() {
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if serve(stream, respond).is_none() {
+            break;
+        }
+    }
+}
+
+/// Answer a single request. Returns None if the Publisher is gone.
+fn serve<F>(mut stream: TcpStream, respond: &F) -> Option<()>
+where
+    F: Fn(DebugRequest) -> Option<Value>,
+{
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let Ok(Some((method, path))) = read_request(&stream) else {
+        return Some(());
+    };
+    let (status, body) = match parse_request(&method, &path) {
+        Ok(request) => ("200 OK", respond(request)?),
+        Err(status) => (status, json!({ "error": status })),
+    };
+
+    let body = body.to_string();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    Some(())
+}
+
+/// Read the method and path of a request, skipping its headers
+fn read_request(stream: &TcpStream) -> io::Result<Option<(String, String)>> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    Ok(Some((method.to_string(), path.to_string())))
+}
+
+/// Work out what a request is for, or the status to reject it with
+fn parse_request(method: &str, path: &str) -> Result<DebugRequest, &'static str> {
+    let path = path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (method, segments.as_slice()) {
+        ("GET", []) => Ok(DebugRequest::Introspect),
+        ("GET", ["metrics"]) => Ok(DebugRequest::Metrics),
+        ("GET", ["events"]) => Ok(DebugRequest::Events),
+        ("POST", ["groups", group, toggle @ ("enable" | "disable")]) => {
+            Ok(DebugRequest::SetGroupEnabled {
+                group: group.to_string(),
+                enabled: *toggle == "enable",
+            })
+        }
+        (_, [] | ["metrics"] | ["events"] | ["groups", _, "enable" | "disable"]) => {
+            Err("405 Method Not Allowed")
+        }
+        _ => Err("404 Not Found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::{Event, Handler, Publisher};
+
+    #[derive(Clone)]
+    struct Job;
+    impl Event for Job {}
+
+    fn fetch(addr: SocketAddr, method: &str, path: &str) -> Value {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn test_debug_endpoint_describes_publisher_and_toggles_groups() {
+        let mut publisher = Publisher::with_source("service");
+        let handled = Arc::new(Mutex::new(0));
+        let handled_clone = handled.clone();
+        publisher.subscribe_group(
+            "workers",
+            Handler::new(move |_job: Job| *handled_clone.lock().unwrap() += 1),
+        );
+        let server = publisher.serve_debug("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+
+        publisher.publish(Job).unwrap();
+        let described = fetch(addr, "GET", "/");
+        assert_eq!(described["source"], "service");
+        assert_eq!(described["groups"][0]["name"], "workers");
+        assert_eq!(described["groups"][0]["enabled"], true);
+        assert_eq!(fetch(addr, "GET", "/metrics")["published"], 1);
+        let events = fetch(addr, "GET", "/events");
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        assert!(events[0]["type_name"].as_str().unwrap().ends_with("Job"));
+
+        fetch(addr, "POST", "/groups/workers/disable");
+        publisher.publish(Job).unwrap();
+        assert_eq!(fetch(addr, "GET", "/")["groups"][0]["enabled"], false);
+        assert_eq!(*handled.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_requests_are_routed_by_method_and_path() {
+        assert_eq!(parse_request("GET", "/"), Ok(DebugRequest::Introspect));
+        assert_eq!(
+            parse_request("GET", "/metrics?x=1"),
+            Ok(DebugRequest::Metrics)
+        );
+        assert_eq!(
+            parse_request("POST", "/groups/workers/disable"),
+            Ok(DebugRequest::SetGroupEnabled {
+                group: String::from("workers"),
+                enabled: false
+            })
+        );
+        assert_eq!(
+            parse_request("POST", "/events"),
+            Err("405 Method Not Allowed")
+        );
+        assert_eq!(parse_request("GET", "/handlers"), Err("404 Not Found"));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{collections::HashMap, panic::RefUnwindSafe, sync::Mutex};
+
+use crate::{Event, Handle, Partition};
+
+/// Types whose changes can be described field by field, so that subscribers of
+/// `Publisher::subscribe_latest` can apply minimal updates. Derive it with `#[derive(Diffable)]`.
+/// # Examples
+/// ```
+/// use crier::Diffable;
+///
+/// #[derive(Diffable)]
+/// struct Player {
+///     name: String,
+///     score: u32,
+/// }
+///
+/// let before = Player { name: String::from("Ada"), score: 10 };
+/// let after = Player { name: String::from("Ada"), score: 12 };
+/// assert_eq!(after.changed_fields(&before), ["score"]);
+/// ```
+pub trait Diffable {
+    /// Names of the fields that differ from `previous`, in the order they are declared
+    fn changed_fields(&self, previous: &Self) -> Vec<&'static str>;
+}
+
+/// The latest value for a key, delivered by `Publisher::subscribe_latest`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Update<T> {
+    /// The value's partition key
+    pub key: u64,
+    pub value: T,
+    /// The fields that changed since the previous value for the key, or None for the first value
+    pub changed: Option<Vec<&'static str>>,
+}
+
+impl<T> Update<T> {
+    /// Whether `field` needs updating, which every field of the first value for a key does
+    pub fn is_changed(&self, field: &str) -> bool {
+        self.changed
+            .as_ref()
+            .is_none_or(|changed| changed.contains(&field))
+    }
+}
+
+/// Keeps the latest value for each key, and passes each new one on with what changed
+pub(crate) struct Latest<T, F> {
+    latest: Mutex<HashMap<u64, T>>,
+    handler: F,
+}
+
+impl<T, F> RefUnwindSafe for Latest<T, F> {}
+
+impl<T, F> Latest<T, F> {
+    pub(crate) fn new(handler: F) -> Self {
+        Latest {
+            latest: Mutex::default(),
+            handler,
+        }
+    }
+}
+
+impl<T, F> Handle for Latest<T, F>
+where
+    T: Event + Clone + Partition + Diffable,
+    F: Fn(Update<T>),
+{
+    type EventType = T;
+
+    fn handle(&self, value: T) {
+        let key = value.partition_key();
+        let changed = {
+            let mut latest = self.latest.lock().expect("Latest mutex poisoned");
+            let changed = latest
+                .get(&key)
+                .map(|previous| value.changed_fields(previous));
+            if changed.as_ref().is_some_and(Vec::is_empty) {
+                return;
+            }
+            latest.insert(key, value.clone());
+            changed
+        };
+
+        (self.handler)(Update {
+            key,
+            value,
+            changed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::Publisher;
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Sprite {
+        id: u64,
+        x: i32,
+        y: i32,
+    }
+    impl Event for Sprite {}
+    impl Partition for Sprite {
+        fn partition_key(&self) -> u64 {
+            self.id
+        }
+    }
+    impl Diffable for Sprite {
+        fn changed_fields(&self, previous: &Self) -> Vec<&'static str> {
+            let mut changed = Vec::new();
+            if self.x != previous.x {
+                changed.push("x");
+            }
+            if self.y != previous.y {
+                changed.push("y");
+            }
+            changed
+        }
+    }
+
+    #[test]
+    fn test_updates_carry_the_fields_changed_since_the_last_value_for_their_key() {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let updates_clone = updates.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe_latest(move |update: Update<Sprite>| {
+            updates_clone.lock().unwrap().push(update)
+        });
+
+        let sprite = |id, x, y| Sprite { id, x, y };
+        for event in [
+            sprite(1, 0, 0),
+            sprite(2, 5, 5),
+            sprite(1, 3, 0),
+            sprite(1, 3, 0),
+            sprite(2, 6, 7),
+        ] {
+            publisher.publish(event).unwrap();
+        }
+
+        let updates = updates.lock().unwrap();
+        let changed: Vec<(u64, Option<Vec<&str>>)> = updates
+            .iter()
+            .map(|update| (update.key, update.changed.clone()))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                (1, None),
+                (2, None),
+                (1, Some(vec!["x"])),
+                (2, Some(vec!["x", "y"])),
+            ]
+        );
+        assert!(updates[0].is_changed("y"));
+        assert!(!updates[2].is_changed("y"));
+        assert_eq!(updates[3].value, sprite(2, 6, 7));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fmt, panic, sync::Arc, thread};
+
+use crate::platform;
+
+/// A piece of work a Publisher hands to an Executor, such as running one handler over the events
+/// being published
+pub type Job<'a> = Box<dyn FnOnce() + Send + 'a>;
+
+/// Describes the handler a Job runs, for Executors that order or place jobs by handler, e.g. to
+/// keep a renderer on a thread of its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JobInfo {
+    /// Label of the handler, which is its name if it was subscribed with `subscribe_named`
+    pub handler: &'static str,
+    /// How many events the job runs the handler over
+    pub events: usize,
+}
+
+/// Runs the jobs a Publisher hands it, for plugging in a thread pool or some other way of
+/// scheduling handlers
+/// # Examples
+/// ```
+/// use crier::{DispatchStrategy, Executor, Job, Publisher};
+/// use std::sync::Arc;
+///
+/// /// Runs every job on the publishing thread, newest first
+/// struct Backwards;
+///
+/// impl Executor for Backwards {
+///     fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+///         for job in jobs.into_iter().rev() {
+///             job();
+///         }
+///     }
+/// }
+///
+/// let publisher = Publisher::with_strategy(DispatchStrategy::Executor(Arc::new(Backwards)));
+/// ```
+pub trait Executor: Send + Sync {
+    /// Run every job to completion before returning. Jobs can be run in any order, and in
+    /// parallel.
+    fn execute<'a>(&self, jobs: Vec<Job<'a>>);
+
+    /// Like `execute`, but with a description of the handler each job runs. This is what a
+    /// Publisher calls, so Executors that decide where or in what order to run jobs by their
+    /// handlers implement this too. Defaults to `execute`.
+    fn dispatch<'a>(&self, jobs: Vec<(JobInfo, Job<'a>)>) {
+        self.execute(jobs.into_iter().map(|(_, job)| job).collect())
+    }
+}
+
+/// Runs every job on the calling thread, one after another, in the order given. What
+/// `DispatchStrategy::Sequential` runs handlers with.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SequentialExecutor;
+
+impl Executor for SequentialExecutor {
+    fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+        for job in jobs {
+            job();
+        }
+    }
+}
+
+/// Gives each job a scoped thread of its own, with at most `max` running at once, joining the
+/// oldest before starting another. What `DispatchStrategy::ScopedThreads` runs handlers with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScopedThreadExecutor {
+    pub max: usize,
+    /// Stack size in bytes of the threads, or the platform's default if None
+    pub stack_size: Option<usize>,
+}
+
+impl Executor for ScopedThreadExecutor {
+    fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+        thread::scope(|s| {
+            let mut active_handles = Vec::new();
+            for job in jobs {
+                // if we hit the max number of threads, join the oldest before spawning a new one
+                if active_handles.len() >= self.max.max(1) {
+                    join(active_handles.remove(0));
+                }
+
+                let mut builder = thread::Builder::new();
+                if let Some(stack_size) = self.stack_size {
+                    builder = builder.stack_size(stack_size);
+                }
+                active_handles.push(
+                    builder
+                        .spawn_scoped(s, job)
+                        .expect("Failed to spawn handler thread"),
+                );
+            }
+
+            for handle in active_handles {
+                join(handle);
+            }
+        })
+    }
+}
+
+/// Wait for a job's thread to finish, passing on its panic
+fn join(handle: thread::ScopedJoinHandle<()>) {
+    if let Err(payload) = handle.join() {
+        panic::resume_unwind(payload);
+    }
+}
+
+/// How a Publisher runs its handlers when events are published. Set with
+/// `Publisher::with_strategy`. Each strategy is an Executor, so custom ones plug in alongside the
+/// built-ins with `DispatchStrategy::Executor`.
+#[derive(Clone)]
+pub enum DispatchStrategy {
+    /// Run every handler on the publishing thread, one after another
+    Sequential,
+    /// Give each handler a scoped thread of its own, with at most `max` running at once. This is
+    /// the default, with `max` set to the available parallelism. The threads are given stacks of
+    /// `stack_size` bytes if set, or the platform's default otherwise.
+    ScopedThreads {
+        max: usize,
+        stack_size: Option<usize>,
+    },
+    /// Hand the handlers to an Executor, such as a thread pool
+    Executor(Arc<dyn Executor>),
+}
+
+impl Default for DispatchStrategy {
+    fn default() -> Self {
+        if !platform::THREADS {
+            return DispatchStrategy::Sequential;
+        }
+
+        DispatchStrategy::ScopedThreads {
+            max: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            stack_size: None,
+        }
+    }
+}
+
+impl DispatchStrategy {
+    /// Run `jobs` with the strategy's Executor
+    pub(crate) fn dispatch<'a>(&self, jobs: Vec<(JobInfo, Job<'a>)>) {
+        match self {
+            DispatchStrategy::Sequential => SequentialExecutor.dispatch(jobs),
+            DispatchStrategy::ScopedThreads { max, stack_size } => ScopedThreadExecutor {
+                max: *max,
+                stack_size: *stack_size,
+            }
+            .dispatch(jobs),
+            DispatchStrategy::Executor(executor) => executor.dispatch(jobs),
+        }
+    }
+}
+
+impl fmt::Debug for DispatchStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchStrategy::Sequential => write!(f, "Sequential"),
+            DispatchStrategy::ScopedThreads { max, stack_size } => f
+                .debug_struct("ScopedThreads")
+                .field("max", max)
+                .field("stack_size", stack_size)
+                .finish(),
+            DispatchStrategy::Executor(_) => write!(f, "Executor(..)"),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    any,
+    panic::RefUnwindSafe,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use crate::{Delivery, DynEvent, platform};
+
+/// Metadata attached by a Publisher to every event it publishes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    /// When the event was published
+    pub timestamp: SystemTime,
+    /// Position of the event in the sequence of events published by its Publisher
+    pub sequence: u64,
+    /// Identifies a chain of related events. Defaults to the sequence number of the event that
+    /// started the chain.
+    pub correlation_id: u64,
+    /// Name of the Publisher that published the event, if it has one
+    pub source: Option<String>,
+    /// Whether the event was received from a Publisher in another process, e.g. through a
+    /// `crier::net` bridge
+    pub remote: bool,
+    /// Identifies the Publisher a `crier::net` Listener received the event from, if it was
+    /// received that way. Forwarders don't send events back to the Publisher they came from.
+    pub peer: Option<u64>,
+    /// The topic the event was published to with `Publisher::publish_to`, if any
+    pub topic: Option<String>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            timestamp: platform::now(),
+            sequence: 0,
+            correlation_id: 0,
+            source: None,
+            remote: false,
+            peer: None,
+            topic: None,
+        }
+    }
+}
+
+/// Where the events being published came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Origin {
+    Local,
+    /// Received from a Publisher in another process
+    Remote,
+    /// Received through a `crier::net` bridge from the Publisher with this ID
+    Peer(u64),
+}
+
+impl Origin {
+    /// Where an event published with `metadata` came from, for publishing it again elsewhere
+    pub(crate) fn of(metadata: &Metadata) -> Self {
+        match (metadata.remote, metadata.peer) {
+            (_, Some(peer)) => Origin::Peer(peer),
+            (true, None) => Origin::Remote,
+            (false, None) => Origin::Local,
+        }
+    }
+}
+
+/// An event together with the metadata it was published with
+#[derive(Clone, Debug)]
+pub struct Envelope<T> {
+    pub metadata: Metadata,
+    pub payload: T,
+}
+
+/// Everything about a published event except the event itself
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventInfo {
+    /// Name of the event's concrete type
+    pub type_name: &'static str,
+    /// Size in bytes of the event's concrete type, not including anything it owns on the heap
+    pub size: usize,
+    pub metadata: Metadata,
+}
+
+impl EventInfo {
+    pub(crate) fn new(event: &dyn DynEvent, metadata: Metadata) -> Self {
+        EventInfo {
+            type_name: event.type_name(),
+            size: event.size(),
+            metadata,
+        }
+    }
+}
+
+/// An event as it travels through a Publisher, carrying its metadata alongside it. The event
+/// itself is kept behind an `Arc` so that handlers can share it without cloning it.
+pub(crate) struct Published {
+    pub(crate) payload: Arc<dyn any::Any + Send + Sync>,
+    pub(crate) metadata: Metadata,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) partition_key: Option<u64>,
+    pub(crate) delivery: Delivery,
+    pub(crate) type_name: &'static str,
+    pub(crate) size: usize,
+}
+
+impl RefUnwindSafe for Published {}
+
+impl Published {
+    pub(crate) fn new<T: DynEvent>(event: T, metadata: Metadata) -> Self {
+        let deadline = event.dyn_deadline();
+        let partition_key = event.dyn_partition_key();
+        let delivery = event.delivery();
+        let type_name = event.type_name();
+        let size = event.size();
+        Published {
+            payload: Box::new(event).into_shared(),
+            metadata,
+            deadline,
+            partition_key,
+            delivery,
+            type_name,
+            size,
+        }
+    }
+}
+
+impl DynEvent for Published {
+    fn get_data(&self) -> &dyn any::Any {
+        self.payload.as_ref()
+    }
+
+    fn metadata(&self) -> Option<&Metadata> {
+        Some(&self.metadata)
+    }
+
+    fn dyn_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn dyn_partition_key(&self) -> Option<u64> {
+        self.partition_key
+    }
+
+    fn delivery(&self) -> Delivery {
+        self.delivery
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn shared_data(&self) -> Option<Arc<dyn any::Any + Send + Sync>> {
+        Some(self.payload.clone())
+    }
+
+    fn into_shared(self: Box<Self>) -> Arc<dyn any::Any + Send + Sync> {
+        self.payload
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn any::Any + Send> {
+        Box::new(self.payload)
+    }
+}
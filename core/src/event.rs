@@ -1,12 +1,195 @@
-use std::{any, panic::RefUnwindSafe};
+use alloc::{boxed::Box, sync::Arc};
+use core::{any, panic::RefUnwindSafe};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+use crate::Metadata;
 
 /// An object that a Publisher can send to its subscribers
-pub trait Event: Send + Sync + Clone + RefUnwindSafe + 'static {}
+pub trait Event: Send + Sync + RefUnwindSafe + 'static {
+    /// How the event is handed to the handlers subscribed to it. `#[derive(Event)]` sets this to
+    /// `Delivery::Shared` or `Delivery::Exclusive` when the type is marked `#[event(shared)]` or
+    /// `#[event(exclusive)]`.
+    const DELIVERY: Delivery = Delivery::Clone;
+
+    /// Events that implement Deadline should return `Some(self)` here so that Publishers can
+    /// schedule them earliest-deadline-first. `#[derive(Event)]` does this for you when the type is
+    /// marked `#[event(deadline)]`.
+    #[cfg(feature = "std")]
+    fn as_deadline(&self) -> Option<&dyn Deadline> {
+        None
+    }
+
+    /// Events that implement Partition should return `Some(self)` here so that handlers subscribed
+    /// with `Publisher::subscribe_partitioned` can handle them in order by key. `#[derive(Event)]`
+    /// does this for you when the type is marked `#[event(partition)]`.
+    fn as_partition(&self) -> Option<&dyn Partition> {
+        None
+    }
+}
+
+/// Strategies a Publisher can use to hand an event to the handlers subscribed to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Every handler receives its own clone of the event, or an `Arc` pointing to it. The event
+    /// must be `Clone` for handlers to take it by value.
+    Clone,
+    /// Every handler receives an `Arc` pointing to the same event, so the event does not need to
+    /// be `Clone`
+    Shared,
+    /// The event is moved into the first subscribed handler that accepts it, wrapped in `Owned`,
+    /// and no other handlers see it. Useful for resources like file handles and sockets that
+    /// should only ever have one owner.
+    Exclusive,
+}
+
+/// An Event that needs to be handled by a certain instant. Publishers deliver these ahead of
+/// other events published alongside them with `Publisher::publish_all`, earliest deadline first,
+/// and count any that are handled too late. Events already queued or published in separate calls
+/// aren't reordered.
+#[cfg(feature = "std")]
+pub trait Deadline {
+    fn deadline(&self) -> Instant;
+}
+
+/// An Event that belongs to a partition, such as the entity or user it concerns. Handlers
+/// subscribed with `Publisher::subscribe_partitioned` handle events with the same key one at a
+/// time and in order, while events with different keys are handled in parallel.
+pub trait Partition {
+    fn partition_key(&self) -> u64;
+}
+
+/// An event that has been moved into the handler that received it. Handlers of events with
+/// `Delivery::Exclusive` take their events as `Owned<T>`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Owned<T>(pub T);
+
+/// Types that a handler can receive. Implemented for Events that are `Clone`, which handlers
+/// receive as clones, for `Arc<T>` of any Event, which handlers receive as a pointer to the
+/// published event, and for `Owned<T>` of any Event, which handlers receive by moving the event
+/// into them.
+pub trait FromEvent: Sized + 'static {
+    /// The Event this is taken from
+    type Source: Event;
+
+    /// Take a copy of an event that is being delivered to all handlers
+    fn from_event(event: &dyn DynEvent) -> Option<Self>;
+
+    /// Take an event that is being moved into a single handler
+    fn from_owned(event: Box<dyn any::Any + Send>) -> Option<Self>;
+}
+
+impl<T: Event + Clone> FromEvent for T {
+    type Source = T;
+
+    fn from_event(event: &dyn DynEvent) -> Option<Self> {
+        downcast_ref::<T>(event.get_data()).cloned()
+    }
+
+    fn from_owned(event: Box<dyn any::Any + Send>) -> Option<Self> {
+        downcast_box::<T>(event).map(|event| *event)
+    }
+}
+
+impl<T: Event> FromEvent for Arc<T> {
+    type Source = T;
+
+    fn from_event(event: &dyn DynEvent) -> Option<Self> {
+        downcast_arc::<T>(event.shared_data()?)
+    }
+
+    fn from_owned(event: Box<dyn any::Any + Send>) -> Option<Self> {
+        downcast_box::<T>(event).map(Arc::from)
+    }
+}
+
+impl<T: Event> FromEvent for Owned<T> {
+    type Source = T;
+
+    fn from_event(_event: &dyn DynEvent) -> Option<Self> {
+        None
+    }
+
+    fn from_owned(event: Box<dyn any::Any + Send>) -> Option<Self> {
+        downcast_box::<T>(event).map(|event| Owned(*event))
+    }
+}
+
+// With the `dynamic-plugins` feature, events are matched to the types handlers take by TypeKey,
+// so that copies of a type from reloaded libraries count as the same type
+#[cfg(feature = "dynamic-plugins")]
+pub(crate) use crate::type_key::{downcast_arc, downcast_box, downcast_ref, is, is_type};
+
+#[cfg(not(feature = "dynamic-plugins"))]
+pub(crate) fn is<T: 'static>(data: &dyn any::Any) -> bool {
+    data.is::<T>()
+}
+
+#[cfg(not(feature = "dynamic-plugins"))]
+pub(crate) fn is_type<T: 'static>(type_id: any::TypeId) -> bool {
+    type_id == any::TypeId::of::<T>()
+}
+
+#[cfg(not(feature = "dynamic-plugins"))]
+fn downcast_ref<T: 'static>(data: &dyn any::Any) -> Option<&T> {
+    data.downcast_ref()
+}
+
+#[cfg(not(feature = "dynamic-plugins"))]
+fn downcast_arc<T: Send + Sync + 'static>(data: Arc<dyn any::Any + Send + Sync>) -> Option<Arc<T>> {
+    data.downcast().ok()
+}
+
+#[cfg(not(feature = "dynamic-plugins"))]
+fn downcast_box<T: 'static>(data: Box<dyn any::Any + Send>) -> Option<Box<T>> {
+    data.downcast().ok()
+}
 
 /// Dynamically typed event. Used internally to alow Publishers to support Handlers and Events of
 /// multiple different types.
 pub trait DynEvent: Send + Sync + RefUnwindSafe + 'static {
     fn get_data(&self) -> &dyn any::Any;
+
+    /// Metadata the event was published with. Only events that have passed through a Publisher
+    /// carry metadata.
+    #[cfg(feature = "std")]
+    fn metadata(&self) -> Option<&Metadata> {
+        None
+    }
+
+    /// The instant by which the event needs to have been handled, if it has a Deadline
+    #[cfg(feature = "std")]
+    fn dyn_deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// The event's partition key, if it has a Partition
+    fn dyn_partition_key(&self) -> Option<u64> {
+        None
+    }
+
+    /// How the event should be handed to handlers
+    fn delivery(&self) -> Delivery;
+
+    /// Name of the event's concrete type
+    fn type_name(&self) -> &'static str;
+
+    /// Size in bytes of the event's concrete type. Doesn't include anything the event owns on the
+    /// heap.
+    fn size(&self) -> usize;
+
+    /// The event behind an `Arc` that handlers can share. Only events that have passed through a
+    /// Publisher are shared.
+    fn shared_data(&self) -> Option<Arc<dyn any::Any + Send + Sync>> {
+        None
+    }
+
+    /// Move the event behind an `Arc` so that it can be shared between handlers
+    fn into_shared(self: Box<Self>) -> Arc<dyn any::Any + Send + Sync>;
+
+    /// Move the event out so that it can be handed to a single handler
+    fn into_any(self: Box<Self>) -> Box<dyn any::Any + Send>;
 }
 
 // Allow handlers to identify the concrete type of any Event object.
@@ -17,4 +200,79 @@ impl<T: Event> DynEvent for T {
     fn get_data(&self) -> &dyn any::Any {
         self
     }
+
+    #[cfg(feature = "std")]
+    fn dyn_deadline(&self) -> Option<Instant> {
+        self.as_deadline().map(Deadline::deadline)
+    }
+
+    fn dyn_partition_key(&self) -> Option<u64> {
+        self.as_partition().map(Partition::partition_key)
+    }
+
+    fn delivery(&self) -> Delivery {
+        T::DELIVERY
+    }
+
+    fn type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+
+    fn size(&self) -> usize {
+        core::mem::size_of::<T>()
+    }
+
+    fn into_shared(self: Box<Self>) -> Arc<dyn any::Any + Send + Sync> {
+        Arc::<T>::from(self)
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn any::Any + Send> {
+        self
+    }
+}
+
+// Allow type-erased events, such as ones that have been deserialized, to be published. Handlers
+// still see the concrete type of the boxed event.
+impl DynEvent for Box<dyn DynEvent> {
+    fn get_data(&self) -> &dyn any::Any {
+        (**self).get_data()
+    }
+
+    #[cfg(feature = "std")]
+    fn metadata(&self) -> Option<&Metadata> {
+        (**self).metadata()
+    }
+
+    #[cfg(feature = "std")]
+    fn dyn_deadline(&self) -> Option<Instant> {
+        (**self).dyn_deadline()
+    }
+
+    fn dyn_partition_key(&self) -> Option<u64> {
+        (**self).dyn_partition_key()
+    }
+
+    fn delivery(&self) -> Delivery {
+        (**self).delivery()
+    }
+
+    fn type_name(&self) -> &'static str {
+        (**self).type_name()
+    }
+
+    fn size(&self) -> usize {
+        (**self).size()
+    }
+
+    fn shared_data(&self) -> Option<Arc<dyn any::Any + Send + Sync>> {
+        (**self).shared_data()
+    }
+
+    fn into_shared(self: Box<Self>) -> Arc<dyn any::Any + Send + Sync> {
+        (*self).into_shared()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn any::Any + Send> {
+        (*self).into_any()
+    }
 }
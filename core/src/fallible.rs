@@ -0,0 +1,80 @@
+use std::{any::Any, error::Error, fmt};
+
+/// An error returned by a handler subscribed with `Publisher::subscribe_try` or
+/// `subscribe_try_mut`, or a HandlerPoisoned for a mut handler that was skipped. `publish` returns
+/// these after the panics of the same event, untouched by the panic policy and formatter, and
+/// `Publisher::try_publish` keeps the two apart.
+#[derive(Debug)]
+pub struct HandlerError {
+    /// Label of the handler that returned the error
+    pub handler: &'static str,
+    pub error: Box<dyn Error + Send + Sync>,
+}
+
+impl HandlerError {
+    /// The error, if it is of type `E`
+    pub fn downcast_ref<E: Error + 'static>(&self) -> Option<&E> {
+        self.error.downcast_ref()
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.handler, self.error)
+    }
+}
+
+impl Error for HandlerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// The error of a HandlerError returned for a mut handler that panicked on an earlier event, so
+/// was skipped. See `Publisher::is_poisoned`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HandlerPoisoned;
+
+impl fmt::Display for HandlerPoisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "handler panicked earlier and has not been recovered")
+    }
+}
+
+impl Error for HandlerPoisoned {}
+
+/// What went wrong while publishing an event with `Publisher::try_publish`
+#[derive(Debug, Default)]
+pub struct PublishErrors {
+    /// Panics of handlers, as `publish` returns them
+    pub panics: Vec<Box<dyn Any + Send + 'static>>,
+    /// Errors returned by fallible handlers
+    pub failures: Vec<HandlerError>,
+}
+
+impl PublishErrors {
+    /// Sort the errors returned by `publish` into panics and failures
+    pub(crate) fn split(errors: Vec<Box<dyn Any + Send + 'static>>) -> Self {
+        let mut split = PublishErrors::default();
+        for error in errors {
+            match error.downcast::<HandlerError>() {
+                Ok(failure) => split.failures.push(*failure),
+                Err(panic) => split.panics.push(panic),
+            }
+        }
+        split
+    }
+}
+
+impl fmt::Display for PublishErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} handlers panicked and {} returned errors",
+            self.panics.len(),
+            self.failures.len()
+        )
+    }
+}
+
+impl Error for PublishErrors {}
@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+
+use crate::{DynEvent, envelope::Origin};
+
+/// Events published together while the gate was closed, along with how they were published
+pub(crate) struct Held {
+    pub(crate) events: Vec<Box<dyn DynEvent>>,
+    pub(crate) correlation_id: Option<u64>,
+    pub(crate) origin: Origin,
+    pub(crate) topic: Option<String>,
+}
+
+/// Holds back events published before a Publisher's subscribers are ready for them
+#[derive(Default)]
+pub(crate) struct Gate {
+    /// The events held back so far, in the order they were published, or None if the gate is open
+    held: Mutex<Option<Vec<Held>>>,
+}
+
+impl Gate {
+    /// Hold back every event published from now on until the gate is opened
+    pub(crate) fn close(&self) {
+        self.held
+            .lock()
+            .expect("Gate mutex poisoned")
+            .get_or_insert_with(Vec::new);
+    }
+
+    /// Hold `events` back if the gate is closed, or hand them back if it is open
+    pub(crate) fn hold<T, I>(
+        &self,
+        events: I,
+        correlation_id: Option<u64>,
+        origin: Origin,
+        topic: Option<&str>,
+    ) -> Option<I>
+    where
+        T: DynEvent,
+        I: IntoIterator<Item = T>,
+    {
+        let mut held = self.held.lock().expect("Gate mutex poisoned");
+        match held.as_mut() {
+            Some(held) => {
+                held.push(Held {
+                    events: events
+                        .into_iter()
+                        .map(|event| Box::new(event) as Box<dyn DynEvent>)
+                        .collect(),
+                    correlation_id,
+                    origin,
+                    topic: topic.map(String::from),
+                });
+                None
+            }
+            None => Some(events),
+        }
+    }
+
+    /// Open the gate, returning the events that were held back
+    pub(crate) fn open(&self) -> Vec<Held> {
+        self.held
+            .lock()
+            .expect("Gate mutex poisoned")
+            .take()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+
+    #[derive(Clone)]
+    struct Started;
+    impl Event for Started {}
+
+    #[test]
+    fn test_gate_holds_events_until_opened() {
+        let gate = Gate::default();
+        assert!(gate.hold([Started], None, Origin::Local, None).is_some());
+
+        gate.close();
+        assert!(
+            gate.hold([Started, Started], Some(1), Origin::Local, None)
+                .is_none()
+        );
+        assert!(
+            gate.hold([Started], None, Origin::Remote, Some("boot"))
+                .is_none()
+        );
+
+        let held = gate.open();
+        assert_eq!(held.len(), 2);
+        assert_eq!(held[0].events.len(), 2);
+        assert_eq!(held[0].correlation_id, Some(1));
+        assert_eq!(held[1].topic.as_deref(), Some("boot"));
+        assert!(gate.hold([Started], None, Origin::Local, None).is_some());
+        assert!(gate.open().is_empty());
+    }
+}
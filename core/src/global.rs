@@ -0,0 +1,115 @@
+use std::sync::OnceLock;
+
+use crate::Publisher;
+
+static GLOBAL: OnceLock<Publisher> = OnceLock::new();
+
+/// The process-wide Publisher, created the first time it is accessed. Each call returns a handle
+/// sharing the same handlers, so it can be used from anywhere without passing a Publisher around.
+/// The global Publisher is never dropped, so batch handlers only receive partial batches if it is
+/// explicitly flushed.
+/// # Examples
+/// ```
+/// use crier::{Event, Handler};
+///
+/// #[derive(Clone, Event)]
+/// struct GamePaused {}
+///
+/// crier::global().subscribe(Handler::new(|_event: GamePaused| println!("Game paused")));
+/// crier::publish!(GamePaused {});
+/// ```
+pub fn global() -> Publisher {
+    GLOBAL.get_or_init(Publisher::default).handle()
+}
+
+/// Publish an event to the `global` Publisher
+#[macro_export]
+macro_rules! publish {
+    ($event:expr) => {
+        $crate::global().publish($event)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{DynEvent, Event, Flow, Handler, Metadata, Middleware};
+
+    #[derive(Clone)]
+    struct GlobalEvent;
+    impl Event for GlobalEvent {}
+
+    #[test]
+    fn test_global_publisher_is_shared_between_handles() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_clone = count.clone();
+        let id = global().subscribe(Handler::new(move |_event: GlobalEvent| {
+            count_clone.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        global().publish(GlobalEvent).unwrap();
+        crate::publish!(GlobalEvent).unwrap();
+        global().unsubscribe(id).unwrap();
+        crate::publish!(GlobalEvent).unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[derive(Clone)]
+    struct Subscribing;
+    impl Event for Subscribing {}
+
+    #[test]
+    fn test_handlers_can_subscribe_and_unsubscribe_on_the_global_publisher() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_clone = count.clone();
+        let id = global().subscribe(Handler::new(move |_event: Subscribing| {
+            let count = count_clone.clone();
+            let added = global().subscribe(Handler::new(move |_event: Subscribing| {
+                count.fetch_add(1, Ordering::SeqCst);
+            }));
+            global().unsubscribe(added).unwrap();
+        }));
+
+        crate::publish!(Subscribing).unwrap();
+        global().unsubscribe(id).unwrap();
+
+        // the handler subscribed during the publish was unsubscribed again before it could run
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[derive(Clone)]
+    struct AddingMiddleware;
+    impl Event for AddingMiddleware {}
+
+    struct CountingMiddleware(Arc<AtomicUsize>);
+    impl Middleware for CountingMiddleware {
+        fn before(&self, event: &dyn DynEvent, _metadata: &mut Metadata) -> Flow {
+            if event.get_data().is::<AddingMiddleware>() {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+            Flow::Continue
+        }
+    }
+
+    #[test]
+    fn test_handlers_can_add_middleware_to_the_global_publisher() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_clone = count.clone();
+        let id = global().subscribe(Handler::new(move |_event: AddingMiddleware| {
+            global().add_middleware(CountingMiddleware(count_clone.clone()));
+        }));
+
+        crate::publish!(AddingMiddleware).unwrap();
+        global().unsubscribe(id).unwrap();
+        crate::publish!(AddingMiddleware).unwrap();
+
+        // the middleware added during the first publish only sees the second
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::{BuildHasher, RandomState},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::{DynEvent, DynHandle};
+
+/// How the members of a subscription group share out the events they compete for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Balance {
+    /// Members take turns, in the order they subscribed
+    #[default]
+    RoundRobin,
+    /// The member with the fewest events still waiting to be handled receives the event
+    LeastBusy,
+    /// A member is picked at random
+    Random,
+}
+
+/// Pick one of `count` candidates according to `balance`. `next` holds whose turn it is for round
+/// robin, and `busy` gives how many events a candidate has waiting.
+pub(crate) fn choose(
+    balance: Balance,
+    count: usize,
+    next: &mut usize,
+    busy: impl Fn(usize) -> usize,
+) -> usize {
+    match balance {
+        Balance::RoundRobin => {
+            let index = *next % count;
+            *next = index + 1;
+            index
+        }
+        Balance::LeastBusy => (0..count).min_by_key(|index| busy(*index)).unwrap_or(0),
+        Balance::Random => RandomState::new().hash_one(*next) as usize % count,
+    }
+}
+
+/// A handler subscribed to a group with `Publisher::subscribe_group`
+pub(crate) struct Member {
+    pub(crate) group: String,
+    pub(crate) handler: Arc<dyn DynHandle>,
+    /// Number of events assigned to the member that it hasn't finished handling
+    busy: AtomicUsize,
+}
+
+impl Member {
+    pub(crate) fn new(group: String, handler: Arc<dyn DynHandle>) -> Self {
+        Member {
+            group,
+            handler,
+            busy: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record that one of the member's assigned events has been handled
+    pub(crate) fn done(&self) {
+        self.busy.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Default)]
+struct GroupState {
+    balance: Balance,
+    /// Whether the group has been disabled, so that none of its members receive events
+    disabled: bool,
+    /// Index of the member whose turn is next, for round robin
+    next: usize,
+}
+
+/// How each subscription group balances its events, and whose turn it is
+#[derive(Default)]
+pub(crate) struct Groups {
+    state: Mutex<HashMap<String, GroupState>>,
+}
+
+impl Groups {
+    pub(crate) fn set_balance(&self, group: String, balance: Balance) {
+        self.state
+            .lock()
+            .expect("Group mutex poisoned")
+            .entry(group)
+            .or_default()
+            .balance = balance;
+    }
+
+    pub(crate) fn set_enabled(&self, group: String, enabled: bool) {
+        self.state
+            .lock()
+            .expect("Group mutex poisoned")
+            .entry(group)
+            .or_default()
+            .disabled = !enabled;
+    }
+
+    pub(crate) fn is_enabled(&self, group: &str) -> bool {
+        self.state
+            .lock()
+            .expect("Group mutex poisoned")
+            .get(group)
+            .is_none_or(|group| !group.disabled)
+    }
+
+    /// Choose which member of each group receives `event`, among the members that accept it.
+    /// `members` holds every group's members in the order they subscribed. Returns the IDs of the
+    /// chosen members, which are counted as busy until they call `Member::done`.
+    pub(crate) fn assign(
+        &self,
+        members: &BTreeMap<&str, Vec<(usize, &Member)>>,
+        event: &dyn DynEvent,
+    ) -> HashSet<usize> {
+        let mut state = self.state.lock().expect("Group mutex poisoned");
+        let mut chosen = HashSet::new();
+        for (group, members) in members {
+            let candidates: Vec<&(usize, &Member)> = members
+                .iter()
+                .filter(|(_, member)| member.handler.accepts(event))
+                .collect();
+            if candidates.is_empty() {
+                continue;
+            }
+
+            let group = state.entry(group.to_string()).or_default();
+            if group.disabled {
+                continue;
+            }
+            let index = choose(group.balance, candidates.len(), &mut group.next, |index| {
+                candidates[index].1.busy.load(Ordering::SeqCst)
+            });
+            let (id, member) = candidates[index];
+            member.busy.fetch_add(1, Ordering::SeqCst);
+            chosen.insert(*id);
+        }
+
+        chosen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler};
+
+    #[derive(Clone)]
+    struct Job;
+    impl Event for Job {}
+
+    fn members() -> Vec<Member> {
+        (0..3)
+            .map(|_| {
+                Member::new(
+                    String::from("workers"),
+                    Arc::new(Handler::new(|_job: Job| {})),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin_takes_turns() {
+        let groups = Groups::default();
+        let members = members();
+        let by_group = BTreeMap::from([("workers", members.iter().enumerate().collect())]);
+
+        let chosen: Vec<HashSet<usize>> = (0..4).map(|_| groups.assign(&by_group, &Job)).collect();
+        assert_eq!(
+            chosen,
+            vec![
+                HashSet::from([0]),
+                HashSet::from([1]),
+                HashSet::from([2]),
+                HashSet::from([0])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_least_busy_picks_the_member_with_least_waiting() {
+        let groups = Groups::default();
+        groups.set_balance(String::from("workers"), Balance::LeastBusy);
+        let members = members();
+        let by_group = BTreeMap::from([("workers", members.iter().enumerate().collect())]);
+
+        assert_eq!(groups.assign(&by_group, &Job), HashSet::from([0]));
+        assert_eq!(groups.assign(&by_group, &Job), HashSet::from([1]));
+        members[0].done();
+        assert_eq!(groups.assign(&by_group, &Job), HashSet::from([0]));
+        assert_eq!(groups.assign(&by_group, &Job), HashSet::from([2]));
+    }
+
+    #[test]
+    fn test_disabled_groups_receive_nothing_until_enabled() {
+        let groups = Groups::default();
+        let members = members();
+        let by_group = BTreeMap::from([("workers", members.iter().enumerate().collect())]);
+
+        groups.set_enabled(String::from("workers"), false);
+        assert!(!groups.is_enabled("workers"));
+        assert_eq!(groups.assign(&by_group, &Job), HashSet::new());
+        groups.set_enabled(String::from("workers"), true);
+        assert_eq!(groups.assign(&by_group, &Job), HashSet::from([0]));
+    }
+}
@@ -1,23 +1,38 @@
-use std::panic::RefUnwindSafe;
-
-use crate::{DynEvent, Event};
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    any::{self, TypeId},
+    error::Error,
+    panic::RefUnwindSafe,
+};
+
+#[cfg(feature = "std")]
+use crate::Envelope;
+use crate::{DynEvent, FromEvent, event};
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
 
 /// Trait for an object which can subscribe to a Producer for specific events
 pub trait Handle {
-    type EventType: Event;
+    type EventType: FromEvent;
 
     fn handle(&self, event: Self::EventType) -> ();
+
+    /// Whether the handler is ready for more events, e.g. because the queue it hands them to is
+    /// below some depth. Consulted when the subscription has an `Overflow` policy.
+    fn poll_ready(&self) -> bool {
+        true
+    }
 }
 
 /// Wrapper for code that handles Events of a specific type.
-pub struct Handler<T: Event> {
+pub struct Handler<T: FromEvent> {
     // closure that takes T and is thread-safe
     handle: Box<dyn Fn(T) + Send + Sync>,
 }
 
-impl<T: Event> RefUnwindSafe for Handler<T> {}
+impl<T: FromEvent> RefUnwindSafe for Handler<T> {}
 
-impl<T: Event> Handler<T> {
+impl<T: FromEvent> Handler<T> {
     pub fn new<F>(f: F) -> Self
     where
         F: Fn(T) + Send + Sync + 'static,
@@ -32,17 +47,164 @@ impl<T: Event> Handler<T> {
 /// of multiple different types.
 pub trait DynHandle: Send + Sync + RefUnwindSafe {
     fn dyn_handle(&self, event: &dyn DynEvent) -> ();
+
+    /// Handle an event, returning the error a fallible handler failed with. Handlers that can't
+    /// fail handle it with `dyn_handle`.
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.dyn_handle(event);
+        Ok(())
+    }
+
+    /// Handle an event that has been moved into this handler because it has
+    /// `Delivery::Exclusive`. Handlers that can't take ownership of events ignore them.
+    fn dyn_handle_owned(&self, _event: Box<dyn any::Any + Send>) {}
+
+    /// Whether this handler would run for the given event. Handlers that don't know ahead of time
+    /// are assumed to accept every event.
+    fn accepts(&self, _event: &dyn DynEvent) -> bool {
+        true
+    }
+
+    /// Whether this handler could run for any event of the given type. Handlers that don't know
+    /// ahead of time are assumed to accept every type.
+    fn accepts_type(&self, _event_type: TypeId) -> bool {
+        true
+    }
+
+    /// Name of the event type this handler takes, if it only takes one
+    fn event_type_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Describes the handler when inspecting a Publisher. Defaults to the name of its type.
+    fn label(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+
+    /// Whether this handler could run for events received from a remote Publisher. Bridges that
+    /// never send remote events back out return false, so that they don't count as interest in
+    /// the events remote Publishers send.
+    fn accepts_remote(&self) -> bool {
+        true
+    }
+
+    /// Whether the handler is ready for more events. Consulted when the subscription has an
+    /// `Overflow` policy.
+    fn poll_ready(&self) -> bool {
+        true
+    }
 }
 
-// Allow Handler to take any DynEvent object and decide whether to run its handle method.
-// This is what enables the Publisher to take handlers and events of any type — as long as they are
-// all DynHandler and DynEvent, the handler can decide whether to handle the event
-impl<T: Event> DynHandle for Handler<T> {
+/// Anything `Publisher::subscribe_all` can subscribe: a handler, or a boxed or shared one, so that
+/// handlers of different types built from configuration can be subscribed together
+#[cfg(feature = "std")]
+pub trait IntoHandler {
+    fn into_handler(self) -> Arc<dyn DynHandle>;
+}
+
+#[cfg(feature = "std")]
+impl<T: DynHandle + 'static> IntoHandler for T {
+    fn into_handler(self) -> Arc<dyn DynHandle> {
+        Arc::new(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl IntoHandler for Box<dyn DynHandle> {
+    fn into_handler(self) -> Arc<dyn DynHandle> {
+        Arc::from(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl IntoHandler for Arc<dyn DynHandle> {
+    fn into_handler(self) -> Arc<dyn DynHandle> {
+        self
+    }
+}
+
+// Handler is a Handle like any other, which gives it a DynHandle implementation and lets it be
+// used with the combinators in HandleExt
+impl<T: FromEvent> Handle for Handler<T> {
+    type EventType = T;
+
+    fn handle(&self, event: T) {
+        (self.handle)(event)
+    }
+}
+
+/// Wrapper for code that handles Events of a specific type and mutates the state it captures
+pub struct HandlerMut<T: FromEvent> {
+    // closure that takes T and can be sent between threads. It needn't be Sync, since the
+    // Publisher only reaches it through a lock.
+    handle: Box<dyn FnMut(T) + Send>,
+}
+
+impl<T: FromEvent> RefUnwindSafe for HandlerMut<T> {}
+
+impl<T: FromEvent> HandlerMut<T> {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        HandlerMut {
+            handle: Box::new(f),
+        }
+    }
+}
+
+impl<T: FromEvent> HandleMut for HandlerMut<T> {
+    type EventType = T;
+
+    fn handle_mut(&mut self, event: T) {
+        (self.handle)(event)
+    }
+}
+
+/// Wrapper for code that handles Events of a specific type along with the metadata they were
+/// published with.
+#[cfg(feature = "std")]
+pub struct EnvelopeHandler<T: FromEvent> {
+    handle: Box<dyn Fn(Envelope<T>) + Send + Sync>,
+}
+
+#[cfg(feature = "std")]
+impl<T: FromEvent> RefUnwindSafe for EnvelopeHandler<T> {}
+
+#[cfg(feature = "std")]
+impl<T: FromEvent> EnvelopeHandler<T> {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Envelope<T>) + Send + Sync + 'static,
+    {
+        EnvelopeHandler {
+            handle: Box::new(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: FromEvent> DynHandle for EnvelopeHandler<T> {
     fn dyn_handle(&self, event: &dyn DynEvent) {
-        if let Some(event_data) = event.get_data().downcast_ref::<T>() {
-            (self.handle)(event_data.clone())
+        if let Some(payload) = T::from_event(event) {
+            (self.handle)(Envelope {
+                metadata: event.metadata().cloned().unwrap_or_default(),
+                payload,
+            })
         }
     }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<T::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<T::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<T::Source>())
+    }
 }
 
 // Allow any Handle object to take any DynEvent object and decide whether to run its handle method.
@@ -50,28 +212,143 @@ impl<T: Event> DynHandle for Handler<T> {
 // all DynHandler and DynEvent, the handler can decide whether to handle the event
 impl<T, U> DynHandle for U
 where
-    T: Event,
+    T: FromEvent,
     U: Handle<EventType = T> + Send + Sync + RefUnwindSafe,
 {
     fn dyn_handle(&self, event: &dyn DynEvent) {
-        if let Some(event_data) = event.get_data().downcast_ref::<T>() {
-            self.handle(event_data.clone())
+        if let Some(event_data) = T::from_event(event) {
+            self.handle(event_data)
         }
     }
+
+    fn dyn_handle_owned(&self, event: Box<dyn any::Any + Send>) {
+        if let Some(event_data) = T::from_owned(event) {
+            self.handle(event_data)
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<T::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<T::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<T::Source>())
+    }
+
+    fn poll_ready(&self) -> bool {
+        Handle::poll_ready(self)
+    }
+}
+
+/// Create a Handler for an enum event that has to handle every variant of the enum. Arms are
+/// written with the variant's name alone, so catch-all arms like `_ => {}` can't be written and
+/// adding a variant to the enum stops the handler compiling until it handles the new variant too.
+/// # Examples
+/// ```
+/// use crier::{Event, Publisher, exhaustive_handler};
+///
+/// #[derive(Clone, Event)]
+/// enum PlayerEvent {
+///     Joined { name: String },
+///     Left(u32),
+///     Idle,
+/// }
+///
+/// let mut publisher = Publisher::default();
+/// publisher.subscribe(exhaustive_handler!(PlayerEvent {
+///     Joined { name } => println!("{name} joined"),
+///     Left(id) => println!("Player {id} left"),
+///     Idle => {},
+/// }));
+/// ```
+///
+/// Leaving out a variant, or trying to cover it with a catch-all, doesn't compile:
+/// ```compile_fail
+/// # use crier::{Event, exhaustive_handler};
+/// # #[derive(Clone, Event)]
+/// # enum PlayerEvent { Joined { name: String }, Left(u32), Idle }
+/// let handler = exhaustive_handler!(PlayerEvent {
+///     Joined { name } => println!("{name} joined"),
+///     _ => {},
+/// });
+/// ```
+#[macro_export]
+macro_rules! exhaustive_handler {
+    ($enum:ty {
+        $($variant:ident $(( $($tuple:tt)* ))? $({ $($fields:tt)* })? => $body:expr),* $(,)?
+    }) => {
+        $crate::Handler::new(move |event: $enum| {
+            type Variants = $enum;
+            match event {
+                $(Variants::$variant $(( $($tuple)* ))? $({ $($fields)* })? => $body,)*
+            }
+        })
+    };
 }
 
 /// Trait for an object that can subscribe to a producer for specific events and mutate itself in
 /// its handler function.
 pub trait HandleMut {
-    type EventType: Event;
+    type EventType: FromEvent;
 
     fn handle_mut(&mut self, event: Self::EventType) -> ();
+
+    /// Whether the handler is ready for more events. See `Handle::poll_ready`.
+    fn poll_ready(&self) -> bool {
+        true
+    }
 }
 
 /// Dynamically typed HandleMut. Used internally to allow Publishers to support events and handlers
 /// of different types.
-pub trait DynHandleMut {
+pub trait DynHandleMut: Send {
     fn dyn_handle_mut(&mut self, event: &dyn DynEvent) -> ();
+
+    /// Handle an event, returning the error a fallible handler failed with. See
+    /// `DynHandle::dyn_try_handle`.
+    fn dyn_try_handle_mut(
+        &mut self,
+        event: &dyn DynEvent,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.dyn_handle_mut(event);
+        Ok(())
+    }
+
+    /// Handle an event that has been moved into this handler because it has
+    /// `Delivery::Exclusive`. Handlers that can't take ownership of events ignore them.
+    fn dyn_handle_mut_owned(&mut self, _event: Box<dyn any::Any + Send>) {}
+
+    /// Whether this handler would run for the given event. Handlers that don't know ahead of time
+    /// are assumed to accept every event.
+    fn accepts(&self, _event: &dyn DynEvent) -> bool {
+        true
+    }
+
+    /// Whether this handler could run for any event of the given type. Handlers that don't know
+    /// ahead of time are assumed to accept every type.
+    fn accepts_type(&self, _event_type: TypeId) -> bool {
+        true
+    }
+
+    /// Name of the event type this handler takes, if it only takes one
+    fn event_type_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Describes the handler when inspecting a Publisher. Defaults to the name of its type.
+    fn label(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+
+    /// Whether the handler is ready for more events. Consulted when the subscription has an
+    /// `Overflow` policy.
+    fn poll_ready(&self) -> bool {
+        true
+    }
 }
 
 // Allow any HandleMut object to take any DynEvent object and decide whether to run its handle method.
@@ -79,19 +356,235 @@ pub trait DynHandleMut {
 // all DynHandle/DynHandleMut and DynEvent, the handler can decide whether to handle the event
 impl<T, U> DynHandleMut for U
 where
-    T: Event,
-    U: HandleMut<EventType = T> + Send + Sync + RefUnwindSafe,
+    T: FromEvent,
+    U: HandleMut<EventType = T> + Send + RefUnwindSafe,
 {
     fn dyn_handle_mut(&mut self, event: &dyn DynEvent) {
-        if let Some(event_data) = event.get_data().downcast_ref::<T>() {
-            self.handle_mut(event_data.clone())
+        if let Some(event_data) = T::from_event(event) {
+            self.handle_mut(event_data)
+        }
+    }
+
+    fn dyn_handle_mut_owned(&mut self, event: Box<dyn any::Any + Send>) {
+        if let Some(event_data) = T::from_owned(event) {
+            self.handle_mut(event_data)
         }
     }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<T::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<T::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<T::Source>())
+    }
+
+    fn poll_ready(&self) -> bool {
+        HandleMut::poll_ready(self)
+    }
+}
+
+/// Trait for a handler that can fail in ways that are expected, like a write to a full disk.
+/// Subscribed with `Publisher::subscribe_try`, the errors it returns are reported by `publish`
+/// alongside, but apart from, the panics of other handlers.
+pub trait TryHandle {
+    type EventType: FromEvent;
+    type Error: Error + Send + Sync + 'static;
+
+    fn try_handle(&self, event: Self::EventType) -> Result<(), Self::Error>;
+}
+
+/// Trait for a handler that mutates itself and can fail in ways that are expected. See
+/// `TryHandle`.
+pub trait TryHandleMut {
+    type EventType: FromEvent;
+    type Error: Error + Send + Sync + 'static;
+
+    fn try_handle_mut(&mut self, event: Self::EventType) -> Result<(), Self::Error>;
+}
+
+/// Wraps a TryHandle or TryHandleMut so that it can be subscribed like any other handler, with
+/// the errors it returns kept rather than discarded. `Publisher::subscribe_try` and
+/// `subscribe_try_mut` wrap handlers in one.
+pub struct Fallible<H> {
+    handler: H,
+}
+
+impl<H> Fallible<H> {
+    pub fn new(handler: H) -> Self {
+        Fallible { handler }
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+}
+
+impl<T, H> DynHandle for Fallible<H>
+where
+    T: FromEvent,
+    H: TryHandle<EventType = T> + Send + Sync + RefUnwindSafe,
+{
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match T::from_event(event) {
+            Some(event_data) => Ok(self.handler.try_handle(event_data)?),
+            None => Ok(()),
+        }
+    }
+
+    fn dyn_handle_owned(&self, event: Box<dyn any::Any + Send>) {
+        if let Some(event_data) = T::from_owned(event) {
+            let _ = self.handler.try_handle(event_data);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<T::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<T::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<T::Source>())
+    }
+
+    fn label(&self) -> &'static str {
+        any::type_name::<H>()
+    }
+}
+
+impl<T, H> DynHandleMut for Fallible<H>
+where
+    T: FromEvent,
+    H: TryHandleMut<EventType = T> + Send,
+{
+    fn dyn_handle_mut(&mut self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle_mut(event);
+    }
+
+    fn dyn_try_handle_mut(
+        &mut self,
+        event: &dyn DynEvent,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match T::from_event(event) {
+            Some(event_data) => Ok(self.handler.try_handle_mut(event_data)?),
+            None => Ok(()),
+        }
+    }
+
+    fn dyn_handle_mut_owned(&mut self, event: Box<dyn any::Any + Send>) {
+        if let Some(event_data) = T::from_owned(event) {
+            let _ = self.handler.try_handle_mut(event_data);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<T::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<T::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<T::Source>())
+    }
+
+    fn label(&self) -> &'static str {
+        any::type_name::<H>()
+    }
+}
+
+/// Trait for an object that subscribes to a Publisher for specific events and handles them in
+/// batches rather than one at a time.
+pub trait HandleBatch {
+    type EventType: FromEvent;
+
+    fn handle_batch(&self, events: Vec<Self::EventType>) -> ();
+}
+
+/// Trait for an object that subscribes to a Publisher for specific events and returns a value
+/// from each one it handles. The values are gathered up by `Publisher::publish_collect`, which is
+/// useful when each subscriber contributes a verdict, such as a vote or a validation result.
+pub trait HandleCollect {
+    type EventType: FromEvent;
+    type Output: Send + 'static;
+
+    fn handle_collect(&self, event: Self::EventType) -> Self::Output;
+}
+
+/// Dynamically typed HandleBatch. Used internally to allow Publishers to support events and
+/// handlers of different types.
+pub trait DynHandleBatch: Send + Sync + RefUnwindSafe {
+    fn dyn_handle_batch(&self, events: &[&dyn DynEvent]) -> ();
+
+    /// Whether this handler would run for the given event. Handlers that don't know ahead of time
+    /// are assumed to accept every event.
+    fn accepts(&self, _event: &dyn DynEvent) -> bool {
+        true
+    }
+
+    /// Whether this handler could run for any event of the given type. Handlers that don't know
+    /// ahead of time are assumed to accept every type.
+    fn accepts_type(&self, _event_type: TypeId) -> bool {
+        true
+    }
+
+    /// Name of the event type this handler takes, if it only takes one
+    fn event_type_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Describes the handler when inspecting a Publisher. Defaults to the name of its type.
+    fn label(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+}
+
+// Allow any HandleBatch object to take any batch of DynEvent objects and pick out the ones of the
+// type it handles
+impl<T, U> DynHandleBatch for U
+where
+    T: FromEvent,
+    U: HandleBatch<EventType = T> + Send + Sync + RefUnwindSafe,
+{
+    fn dyn_handle_batch(&self, events: &[&dyn DynEvent]) {
+        let batch: Vec<T> = events
+            .iter()
+            .filter_map(|event| T::from_event(*event))
+            .collect();
+        if !batch.is_empty() {
+            self.handle_batch(batch)
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<T::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<T::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<T::Source>())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Event;
     use std::sync::{Arc, Mutex};
 
     #[derive(Clone, Debug, PartialEq)]
@@ -134,6 +627,31 @@ mod tests {
         }
     }
 
+    #[derive(Clone)]
+    enum Signal {
+        Start,
+        Move(i32, i32),
+        Stop { reason: &'static str },
+    }
+
+    impl Event for Signal {}
+
+    #[test]
+    fn test_exhaustive_handler_matches_every_variant() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let handler = crate::exhaustive_handler!(Signal {
+            Start => received_clone.lock().unwrap().push(String::from("start")),
+            Move(x, y) => received_clone.lock().unwrap().push(format!("{x},{y}")),
+            Stop { reason } => received_clone.lock().unwrap().push(reason.to_string()),
+        });
+
+        handler.dyn_handle(&Signal::Start);
+        handler.dyn_handle(&Signal::Move(1, 2));
+        handler.dyn_handle(&Signal::Stop { reason: "done" });
+        assert_eq!(*received.lock().unwrap(), vec!["start", "1,2", "done"]);
+    }
+
     #[test]
     fn test_dyn_handle_calls_handle_on_matching_type() {
         let called = Arc::new(Mutex::new(false));
@@ -206,6 +724,23 @@ mod tests {
         assert_eq!(*last_value.lock().unwrap(), Some(123));
     }
 
+    #[test]
+    fn test_handler_mut_keeps_the_state_its_closure_captures() {
+        let totals = Arc::new(Mutex::new(Vec::new()));
+        let totals_clone = totals.clone();
+        let mut total = 0;
+        let mut handler = HandlerMut::new(move |event: MutEvent| {
+            total += event.0;
+            totals_clone.lock().unwrap().push(total);
+        });
+
+        handler.dyn_handle_mut(&MutEvent(1));
+        handler.dyn_handle_mut(&OtherEvent);
+        handler.dyn_handle_mut(&MutEvent(2));
+
+        assert_eq!(*totals.lock().unwrap(), vec![1, 3]);
+    }
+
     #[test]
     fn test_dyn_handle_mut_does_not_call_handle_mut_on_non_matching_type() {
         let called = Arc::new(Mutex::new(false));
@@ -221,4 +756,30 @@ mod tests {
         assert!(!*called.lock().unwrap());
         assert_eq!(*last_value.lock().unwrap(), None);
     }
+
+    struct TestHandleBatch {
+        batches: Arc<Mutex<Vec<Vec<i32>>>>,
+    }
+
+    impl HandleBatch for TestHandleBatch {
+        type EventType = MyEvent;
+        fn handle_batch(&self, events: Vec<MyEvent>) {
+            let values = events.into_iter().map(|event| event.0).collect();
+            self.batches.lock().unwrap().push(values);
+        }
+    }
+
+    #[test]
+    fn test_dyn_handle_batch_only_receives_matching_events() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let handler = TestHandleBatch {
+            batches: batches.clone(),
+        };
+
+        let events: [&dyn DynEvent; 3] = [&MyEvent(1), &OtherEvent, &MyEvent(2)];
+        handler.dyn_handle_batch(&events);
+        handler.dyn_handle_batch(&[&OtherEvent]);
+
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1, 2]]);
+    }
 }
@@ -0,0 +1,431 @@
+use std::{
+    any::TypeId,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    marker::PhantomData,
+    panic::RefUnwindSafe,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use crate::{DynEvent, DynHandle, FromEvent, platform};
+
+/// A set of event types that a join subscription waits to have received all of. Implemented for
+/// tuples of up to six types that handlers can receive.
+pub trait Join: Sized + Send + 'static {
+    /// The latest event of each type received so far
+    #[doc(hidden)]
+    type Slots: Default + Send;
+
+    /// Store the event in the slot for its type, replacing whatever was there
+    #[doc(hidden)]
+    fn store(slots: &mut Self::Slots, event: &dyn DynEvent);
+
+    /// Take the events out of the slots if every slot is filled, leaving them all empty
+    #[doc(hidden)]
+    fn take(slots: &mut Self::Slots) -> Option<Self>;
+
+    /// Whether the event is of one of the types in the set
+    #[doc(hidden)]
+    fn accepts(event: &dyn DynEvent) -> bool;
+}
+
+/// A closure that can be called with the events of a join subscription as separate arguments
+pub trait JoinFn<J: Join>: Send + Sync + 'static {
+    fn call(&self, joined: J);
+}
+
+macro_rules! impl_join {
+    ($($T:ident $t:ident $i:tt),+) => {
+        impl<$($T: FromEvent + Send),+> Join for ($($T,)+) {
+            type Slots = ($(Option<$T>,)+);
+
+            fn store(slots: &mut Self::Slots, event: &dyn DynEvent) {
+                let type_id = event.get_data().type_id();
+                $(
+                    if type_id == TypeId::of::<$T::Source>() {
+                        if let Some(event) = $T::from_event(event) {
+                            slots.$i = Some(event);
+                        }
+                    }
+                )+
+            }
+
+            fn take(slots: &mut Self::Slots) -> Option<Self> {
+                if $(slots.$i.is_none())||+ {
+                    return None;
+                }
+
+                Some(($(slots.$i.take()?,)+))
+            }
+
+            fn accepts(event: &dyn DynEvent) -> bool {
+                let type_id = event.get_data().type_id();
+                $(type_id == TypeId::of::<$T::Source>())||+
+            }
+        }
+
+        impl<F, $($T: FromEvent + Send),+> JoinFn<($($T,)+)> for F
+        where
+            F: Fn($($T),+) + Send + Sync + 'static,
+        {
+            fn call(&self, ($($t,)+): ($($T,)+)) {
+                self($($t),+)
+            }
+        }
+    };
+}
+
+impl_join!(A a 0, B b 1);
+impl_join!(A a 0, B b 1, C c 2);
+impl_join!(A a 0, B b 1, C c 2, D d 3);
+impl_join!(A a 0, B b 1, C c 2, D d 3, E e 4);
+impl_join!(A a 0, B b 1, C c 2, D d 3, E e 4, G g 5);
+
+/// Handler that waits until it has received an event of every type in `J`, then runs with the
+/// latest of each and starts waiting again. Created by `Publisher::subscribe_join` and
+/// `Publisher::subscribe_join_correlated`.
+pub(crate) struct JoinHandler<J: Join, F> {
+    handler: F,
+    /// Slots for each correlation ID, or just for 0 if the join isn't correlated
+    slots: Mutex<HashMap<u64, J::Slots>>,
+    correlated: bool,
+    joined: PhantomData<fn(J)>,
+}
+
+impl<J: Join, F> RefUnwindSafe for JoinHandler<J, F> {}
+
+impl<J: Join, F: JoinFn<J>> JoinHandler<J, F> {
+    pub(crate) fn new(handler: F, correlated: bool) -> Self {
+        JoinHandler {
+            handler,
+            slots: Mutex::default(),
+            correlated,
+            joined: PhantomData,
+        }
+    }
+}
+
+impl<J: Join, F: JoinFn<J>> DynHandle for JoinHandler<J, F> {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        if !J::accepts(event) {
+            return;
+        }
+        let key = match (self.correlated, event.metadata()) {
+            (true, Some(metadata)) => metadata.correlation_id,
+            _ => 0,
+        };
+
+        let joined = {
+            let mut slots = self.slots.lock().expect("Join mutex poisoned");
+            let entry = slots.entry(key).or_default();
+            J::store(entry, event);
+            let joined = J::take(entry);
+            if joined.is_some() {
+                slots.remove(&key);
+            }
+            joined
+        };
+
+        if let Some(joined) = joined {
+            self.handler.call(joined);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        J::accepts(event)
+    }
+}
+
+/// An event from one side of a `WindowJoin` that was never paired, either because it arrived
+/// after its window had already passed or because nothing matched it before its window closed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unpaired<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Handler that pairs events of two types that share a key and were published within a window of
+/// each other, such as a click and the purchase it led to. Each event is paired at most once,
+/// with the oldest unpaired event on the other side. Events that are never paired are passed to
+/// the handler given to `on_unpaired`, if any.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use crier::{Event, Publisher, WindowJoin};
+///
+/// #[derive(Clone, Event)]
+/// struct Click { user: u32 }
+///
+/// #[derive(Clone, Event)]
+/// struct Purchase { user: u32 }
+///
+/// let mut publisher = Publisher::default();
+/// let join = WindowJoin::within(
+///     Duration::from_secs(30),
+///     |click: &Click| click.user,
+///     |purchase: &Purchase| purchase.user,
+///     |click, _purchase| println!("User {} bought after clicking", click.user),
+/// );
+/// publisher.subscribe(join);
+/// ```
+pub struct WindowJoin<A: FromEvent, B: FromEvent, K> {
+    window: Duration,
+    left_key: Box<dyn Fn(&A) -> K + Send + Sync>,
+    right_key: Box<dyn Fn(&B) -> K + Send + Sync>,
+    handle: Box<dyn Fn(A, B) + Send + Sync>,
+    unpaired: Option<Box<UnpairedHandle<A, B>>>,
+    buffers: Mutex<WindowBuffers<A, B, K>>,
+}
+
+type UnpairedHandle<A, B> = dyn Fn(Unpaired<A, B>) + Send + Sync;
+
+impl<A: FromEvent, B: FromEvent, K> RefUnwindSafe for WindowJoin<A, B, K> {}
+
+/// Unpaired events from each side, by key, in the order they arrived
+struct WindowBuffers<A, B, K> {
+    left: HashMap<K, VecDeque<(SystemTime, A)>>,
+    right: HashMap<K, VecDeque<(SystemTime, B)>>,
+    /// The latest publish time seen. Events published more than a window before it can no longer
+    /// be paired.
+    latest: SystemTime,
+}
+
+impl<A, B, K> WindowBuffers<A, B, K> {
+    /// Remove every buffered event published before `cutoff`
+    fn expire(&mut self, cutoff: SystemTime, expired: &mut Vec<Unpaired<A, B>>) {
+        expire_side(&mut self.left, cutoff, expired, Unpaired::Left);
+        expire_side(&mut self.right, cutoff, expired, Unpaired::Right);
+    }
+}
+
+fn expire_side<K, T, A, B>(
+    side: &mut HashMap<K, VecDeque<(SystemTime, T)>>,
+    cutoff: SystemTime,
+    expired: &mut Vec<Unpaired<A, B>>,
+    unpaired: fn(T) -> Unpaired<A, B>,
+) {
+    side.retain(|_, events| {
+        while events.front().is_some_and(|(time, _)| *time < cutoff) {
+            if let Some((_, event)) = events.pop_front() {
+                expired.push(unpaired(event));
+            }
+        }
+        !events.is_empty()
+    });
+}
+
+/// Pair `event` with the oldest unpaired event for its key on the other side, or buffer it
+fn pair_or_buffer<K: Eq + Hash, T, U>(
+    key: K,
+    time: SystemTime,
+    event: T,
+    own: &mut HashMap<K, VecDeque<(SystemTime, T)>>,
+    other: &mut HashMap<K, VecDeque<(SystemTime, U)>>,
+) -> Option<(T, U)> {
+    match other.get_mut(&key).and_then(VecDeque::pop_front) {
+        Some((_, matched)) => {
+            if other.get(&key).is_some_and(VecDeque::is_empty) {
+                other.remove(&key);
+            }
+            Some((event, matched))
+        }
+        None => {
+            own.entry(key).or_default().push_back((time, event));
+            None
+        }
+    }
+}
+
+impl<A, B, K> WindowJoin<A, B, K>
+where
+    A: FromEvent,
+    B: FromEvent,
+    K: Eq + Hash + Send + 'static,
+{
+    /// Pair events published within `window` of each other whose keys, taken with `left_key` and
+    /// `right_key`, are equal
+    pub fn within<L, R, F>(window: Duration, left_key: L, right_key: R, f: F) -> Self
+    where
+        L: Fn(&A) -> K + Send + Sync + 'static,
+        R: Fn(&B) -> K + Send + Sync + 'static,
+        F: Fn(A, B) + Send + Sync + 'static,
+    {
+        WindowJoin {
+            window,
+            left_key: Box::new(left_key),
+            right_key: Box::new(right_key),
+            handle: Box::new(f),
+            unpaired: None,
+            buffers: Mutex::new(WindowBuffers {
+                left: HashMap::new(),
+                right: HashMap::new(),
+                latest: SystemTime::UNIX_EPOCH,
+            }),
+        }
+    }
+
+    /// Pass events that are never paired to `f` rather than dropping them
+    pub fn on_unpaired<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Unpaired<A, B>) + Send + Sync + 'static,
+    {
+        self.unpaired = Some(Box::new(f));
+        self
+    }
+}
+
+impl<A, B, K> DynHandle for WindowJoin<A, B, K>
+where
+    A: FromEvent + Send,
+    B: FromEvent + Send,
+    K: Eq + Hash + Send + 'static,
+{
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let time = event
+            .metadata()
+            .map_or_else(platform::now, |metadata| metadata.timestamp);
+        let type_id = event.get_data().type_id();
+
+        let mut unpaired = Vec::new();
+        let paired = {
+            let mut buffers = self.buffers.lock().expect("Window join mutex poisoned");
+            buffers.latest = buffers.latest.max(time);
+            let cutoff = buffers
+                .latest
+                .checked_sub(self.window)
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            buffers.expire(cutoff, &mut unpaired);
+            let buffers = &mut *buffers;
+
+            if type_id == TypeId::of::<A::Source>()
+                && let Some(left) = A::from_event(event)
+            {
+                if time < cutoff {
+                    unpaired.push(Unpaired::Left(left));
+                    None
+                } else {
+                    let key = (self.left_key)(&left);
+                    pair_or_buffer(key, time, left, &mut buffers.left, &mut buffers.right)
+                }
+            } else if type_id == TypeId::of::<B::Source>()
+                && let Some(right) = B::from_event(event)
+            {
+                if time < cutoff {
+                    unpaired.push(Unpaired::Right(right));
+                    None
+                } else {
+                    let key = (self.right_key)(&right);
+                    pair_or_buffer(key, time, right, &mut buffers.right, &mut buffers.left)
+                        .map(|(right, left)| (left, right))
+                }
+            } else {
+                None
+            }
+        };
+
+        if let Some(unpaired_handle) = &self.unpaired {
+            for event in unpaired {
+                unpaired_handle(event);
+            }
+        }
+        if let Some((left, right)) = paired {
+            (self.handle)(left, right);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        let type_id = event.get_data().type_id();
+        type_id == TypeId::of::<A::Source>() || type_id == TypeId::of::<B::Source>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Metadata, envelope::Published};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Left(i32);
+    impl Event for Left {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Right(i32);
+    impl Event for Right {}
+
+    #[derive(Clone)]
+    struct Other;
+    impl Event for Other {}
+
+    #[test]
+    fn test_runs_with_the_latest_of_each_once_all_have_arrived() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let handler = JoinHandler::new(
+            move |left: Left, right: Right| received_clone.lock().unwrap().push((left.0, right.0)),
+            false,
+        );
+
+        handler.dyn_handle(&Left(1));
+        handler.dyn_handle(&Left(2));
+        assert!(received.lock().unwrap().is_empty());
+        handler.dyn_handle(&Right(3));
+        assert_eq!(*received.lock().unwrap(), vec![(2, 3)]);
+
+        // the join resets after running
+        handler.dyn_handle(&Right(4));
+        assert_eq!(*received.lock().unwrap(), vec![(2, 3)]);
+        handler.dyn_handle(&Left(5));
+        assert_eq!(*received.lock().unwrap(), vec![(2, 3), (5, 4)]);
+    }
+
+    #[test]
+    fn test_accepts_only_the_joined_types() {
+        let handler = JoinHandler::new(|_left: Left, _right: Arc<Right>| {}, false);
+        assert!(handler.accepts(&Left(1)));
+        assert!(handler.accepts(&Right(1)));
+        assert!(!handler.accepts(&Other));
+    }
+
+    fn at(event: impl Event, secs: u64) -> Published {
+        let metadata = Metadata {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+            ..Default::default()
+        };
+        Published::new(event, metadata)
+    }
+
+    #[test]
+    fn test_window_join_pairs_events_within_the_window() {
+        let paired = Arc::new(Mutex::new(Vec::new()));
+        let paired_clone = paired.clone();
+        let unpaired = Arc::new(Mutex::new(Vec::new()));
+        let unpaired_clone = unpaired.clone();
+        let join = WindowJoin::within(
+            Duration::from_secs(10),
+            |left: &Left| left.0 % 10,
+            |right: &Right| right.0 % 10,
+            move |left: Left, right: Right| paired_clone.lock().unwrap().push((left.0, right.0)),
+        )
+        .on_unpaired(move |event| unpaired_clone.lock().unwrap().push(event));
+
+        join.dyn_handle(&at(Left(1), 100));
+        join.dyn_handle(&at(Left(2), 101));
+        join.dyn_handle(&at(Right(11), 105));
+        assert_eq!(*paired.lock().unwrap(), vec![(1, 11)]);
+
+        // the unpaired Left(2) expires once time moves more than a window past it, and anything
+        // published before then is too late to pair
+        join.dyn_handle(&at(Right(3), 120));
+        join.dyn_handle(&at(Left(3), 105));
+        assert_eq!(*paired.lock().unwrap(), vec![(1, 11)]);
+        assert_eq!(
+            *unpaired.lock().unwrap(),
+            vec![Unpaired::Left(Left(2)), Unpaired::Left(Left(3))]
+        );
+
+        join.dyn_handle(&at(Left(13), 125));
+        assert_eq!(*paired.lock().unwrap(), vec![(1, 11), (13, 3)]);
+    }
+}
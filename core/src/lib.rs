@@ -1,9 +1,219 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(feature = "std")]
+mod bootstrap;
+#[cfg(feature = "std")]
+mod cancellable;
+#[cfg(feature = "std")]
+mod collect;
+mod combinator;
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "serde")]
+mod compress;
+#[cfg(feature = "std")]
+mod dead_letter;
+#[cfg(feature = "debug-http")]
+pub mod debug;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "std")]
+mod dispatch;
+#[cfg(feature = "std")]
+mod envelope;
 mod event;
+#[cfg(feature = "std")]
+mod fallible;
+#[cfg(feature = "std")]
+mod gate;
+#[cfg(feature = "global")]
+mod global;
+#[cfg(feature = "std")]
+mod group;
 mod handler;
+#[cfg(feature = "std")]
+mod join;
+#[cfg(feature = "std")]
+mod load;
+#[cfg(feature = "std")]
+pub mod loadgen;
+#[cfg(feature = "std")]
+mod mailbox;
+#[cfg(feature = "std")]
+mod middleware;
+#[cfg(feature = "std")]
+mod mirror;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "std")]
+mod named;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "std")]
+mod overflow;
+#[cfg(feature = "std")]
+mod panic;
+#[cfg(feature = "std")]
+mod partition;
+#[cfg(feature = "std")]
+mod pause;
+#[cfg(feature = "std")]
+mod platform;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "std")]
+mod projection;
+#[cfg(feature = "std")]
+mod propagation;
+#[cfg(feature = "std")]
 mod publisher;
+#[cfg(feature = "std")]
+mod race;
+#[cfg(feature = "std")]
+mod rate_limit;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "serde")]
+mod replay;
+#[cfg(feature = "std")]
+mod request;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+mod routing;
+#[cfg(feature = "std")]
+mod sampling;
+#[cfg(feature = "std")]
+mod scheduler;
+#[cfg(feature = "std")]
+mod scope;
+mod sequential;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+mod subscription;
+#[cfg(feature = "tokio")]
+mod task;
+#[cfg(feature = "std")]
+mod telemetry;
+#[cfg(feature = "proptest")]
+pub mod testing;
+mod timer;
+#[cfg(feature = "std")]
+mod topic;
+#[cfg(feature = "std")]
+mod topology;
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "std")]
+mod transaction;
+#[cfg(feature = "std")]
+mod transform;
+#[cfg(feature = "dynamic-plugins")]
+mod type_key;
+#[cfg(feature = "std")]
+mod typed;
+#[cfg(feature = "std")]
+mod wait;
+#[cfg(feature = "std")]
+mod watchdog;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
-pub use event::{DynEvent, Event};
-pub use handler::{DynHandle, DynHandleMut, Handle, HandleMut, Handler};
+#[cfg(feature = "std")]
+pub use bootstrap::{Bootstrap, BootstrapError, InitStage};
+#[cfg(feature = "std")]
+pub use cancellable::Cancellable;
+pub use combinator::{Filter, FilterMapEvent, HandleExt, Inspect, MapEvent};
+#[cfg(feature = "serde")]
+pub use compress::{Compressor, DICTIONARY_TAG, DictionaryCompression};
+#[cfg(feature = "std")]
+pub use dead_letter::DeadLetter;
+#[cfg(feature = "std")]
+pub use diff::{Diffable, Update};
+#[cfg(feature = "std")]
+pub use dispatch::{
+    DispatchStrategy, Executor, Job, JobInfo, ScopedThreadExecutor, SequentialExecutor,
+};
+#[cfg(feature = "std")]
+pub use envelope::{Envelope, EventInfo, Metadata};
+#[cfg(feature = "std")]
+pub use event::Deadline;
+pub use event::{Delivery, DynEvent, Event, FromEvent, Owned, Partition};
+#[cfg(feature = "std")]
+pub use fallible::{HandlerError, HandlerPoisoned, PublishErrors};
+#[cfg(feature = "global")]
+pub use global::global;
+#[cfg(feature = "std")]
+pub use group::Balance;
+pub use handler::{
+    DynHandle, DynHandleBatch, DynHandleMut, Fallible, Handle, HandleBatch, HandleCollect,
+    HandleMut, Handler, HandlerMut, TryHandle, TryHandleMut,
+};
+#[cfg(feature = "std")]
+pub use handler::{EnvelopeHandler, IntoHandler};
+#[cfg(feature = "std")]
+pub use join::{Join, JoinFn, Unpaired, WindowJoin};
+#[cfg(feature = "std")]
+pub use load::{LoadThresholds, LoadTier};
+#[cfg(feature = "std")]
+pub use middleware::{Flow, Middleware};
+#[cfg(feature = "std")]
+pub use named::Named;
+#[cfg(feature = "std")]
+pub use overflow::Overflow;
+#[cfg(feature = "std")]
+pub use panic::{HandlerPanic, PanicFormatter, PanicMessage, PanicPolicy, panic_message};
+#[cfg(feature = "std")]
+pub use pool::{BufferPool, BuffersExhausted, PooledBuffer, WorkerPool};
+#[cfg(feature = "std")]
+pub use projection::{Projected, Projection};
+#[cfg(feature = "std")]
 pub use publisher::Publisher;
+#[cfg(feature = "std")]
+pub use race::{Race, Race2, Race3, Race4, RaceTimedOut};
+#[cfg(feature = "std")]
+pub use rate_limit::RateLimit;
+#[cfg(feature = "serde")]
+pub use replay::{Compaction, Positions, RecordedEvent, Recorder, ReplayError, ReplaySpeed};
+#[cfg(feature = "std")]
+pub use request::{Replies, Request};
+#[cfg(feature = "std")]
+pub use retry::{Backoff, RetryPolicy};
+#[cfg(feature = "std")]
+pub use routing::Routing;
+#[cfg(feature = "std")]
+pub use sampling::Sampling;
+#[cfg(feature = "std")]
+pub use scheduler::{ScheduleHandle, ScheduleId, ScheduledInfo};
+#[cfg(feature = "std")]
+pub use scope::Link;
+pub use sequential::SequentialPublisher;
+#[cfg(feature = "serde")]
+pub use serialize::{
+    Codec, CorruptRecordSkipped, EventRegistry, EventTooLarge, PooledSerializingHandler,
+    SerializableEvent, SerializeError, SerializedEvent, SerializingHandler,
+};
+#[cfg(feature = "std")]
+pub use subscription::{HandlerInfo, SubscriptionId, UnsubscribeError, Unsubscribed};
+pub use timer::TickSource;
+#[cfg(feature = "std")]
+pub use trace::ChromeTrace;
+#[cfg(feature = "std")]
+pub use transaction::{Transaction, TransactionError};
+#[cfg(feature = "dynamic-plugins")]
+pub use type_key::TypeKey;
+#[cfg(feature = "std")]
+pub use typed::TypedPublisher;
+#[cfg(feature = "std")]
+pub use wait::PublishReport;
+#[cfg(feature = "std")]
+pub use watchdog::{SlowHandler, Watchdog};
 
-pub use crier_derive::Event;
+pub use crier_derive::{Diffable, Event, define_bus};
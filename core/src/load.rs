@@ -0,0 +1,150 @@
+use std::{
+    sync::{
+        RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::platform;
+
+/// How heavily loaded a Publisher is. Subscriptions marked with `Publisher::shed_above` stop
+/// receiving events while the load is above their tier, so optional work is shed under pressure.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoadTier {
+    #[default]
+    Normal,
+    Elevated,
+    Critical,
+}
+
+/// The queue depth and latency at which a Publisher's load moves up a tier. The load is at the
+/// highest tier reached by either measure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadThresholds {
+    /// Number of events being published or waiting in the background at which load is Elevated
+    pub elevated_depth: usize,
+    /// Number of events being published or waiting in the background at which load is Critical
+    pub critical_depth: usize,
+    /// Average time taken to publish an event at which load is Elevated
+    pub elevated_latency: Duration,
+    /// Average time taken to publish an event at which load is Critical
+    pub critical_latency: Duration,
+}
+
+impl Default for LoadThresholds {
+    fn default() -> Self {
+        LoadThresholds {
+            elevated_depth: 64,
+            critical_depth: 256,
+            elevated_latency: Duration::from_millis(50),
+            critical_latency: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Tracks how many events a Publisher is publishing at once and how long publishing takes
+#[derive(Default)]
+pub(crate) struct Load {
+    depth: AtomicUsize,
+    /// Moving average of the time taken to publish, in microseconds
+    latency: AtomicU64,
+    thresholds: RwLock<LoadThresholds>,
+}
+
+impl Load {
+    /// Record the start of a publish, which ends when the guard is dropped
+    pub(crate) fn start(&self) -> LoadGuard<'_> {
+        self.depth.fetch_add(1, Ordering::SeqCst);
+        LoadGuard {
+            load: self,
+            started: platform::instant(),
+        }
+    }
+
+    pub(crate) fn set_thresholds(&self, thresholds: LoadThresholds) {
+        *self.thresholds.write().expect("Load lock poisoned") = thresholds;
+    }
+
+    /// The current tier, counting `waiting` pieces of background work towards the queue depth
+    pub(crate) fn tier(&self, waiting: usize) -> LoadTier {
+        let thresholds = *self.thresholds.read().expect("Load lock poisoned");
+        let depth = self.depth.load(Ordering::SeqCst) + waiting;
+        let latency = Duration::from_micros(self.latency.load(Ordering::SeqCst));
+
+        let by_depth = if depth >= thresholds.critical_depth {
+            LoadTier::Critical
+        } else if depth >= thresholds.elevated_depth {
+            LoadTier::Elevated
+        } else {
+            LoadTier::Normal
+        };
+        let by_latency = if latency >= thresholds.critical_latency {
+            LoadTier::Critical
+        } else if latency >= thresholds.elevated_latency {
+            LoadTier::Elevated
+        } else {
+            LoadTier::Normal
+        };
+
+        by_depth.max(by_latency)
+    }
+}
+
+/// Marks a publish as in progress until dropped
+pub(crate) struct LoadGuard<'a> {
+    load: &'a Load,
+    started: Option<Instant>,
+}
+
+impl Drop for LoadGuard<'_> {
+    fn drop(&mut self) {
+        self.load.depth.fetch_sub(1, Ordering::SeqCst);
+        let Some(started) = self.started else {
+            return;
+        };
+
+        // an exponential moving average, so that the latency recovers once the pressure is off.
+        // Racing publishes can lose each other's samples, which doesn't matter for an average.
+        let sample = started.elapsed().as_micros() as i64;
+        let average = self.load.latency.load(Ordering::SeqCst) as i64;
+        let average = average + (sample - average) / 8;
+        self.load.latency.store(average as u64, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tier_follows_depth() {
+        let load = Load::default();
+        load.set_thresholds(LoadThresholds {
+            elevated_depth: 2,
+            critical_depth: 3,
+            ..Default::default()
+        });
+
+        let _first = load.start();
+        assert_eq!(load.tier(0), LoadTier::Normal);
+        let _second = load.start();
+        assert_eq!(load.tier(0), LoadTier::Elevated);
+        assert_eq!(load.tier(1), LoadTier::Critical);
+    }
+
+    #[test]
+    fn test_tier_follows_latency() {
+        let load = Load::default();
+        load.set_thresholds(LoadThresholds {
+            elevated_latency: Duration::ZERO,
+            critical_latency: Duration::from_secs(60),
+            ..Default::default()
+        });
+        assert_eq!(load.tier(0), LoadTier::Elevated);
+
+        load.latency
+            .store(Duration::from_secs(60).as_micros() as u64, Ordering::SeqCst);
+        assert_eq!(load.tier(0), LoadTier::Critical);
+    }
+}
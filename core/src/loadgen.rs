@@ -0,0 +1,221 @@
+//! Synthetic load for capacity-testing a Publisher's handlers.
+//!
+//! A `LoadGenerator` publishes a mix of event streams, each at its own target rate and with its
+//! own jitter, for a fixed length of time, then reports how much it published and how far it fell
+//! behind schedule. Use it to check that handlers keep up, and that rate limits and load shedding
+//! kick in where expected, before real traffic arrives.
+//!
+//! # Examples
+//! ```
+//! use std::time::Duration;
+//! use crier::{Event, Publisher, loadgen::{Jitter, LoadGenerator}};
+//!
+//! #[derive(Clone, Event)]
+//! struct Click(u64);
+//!
+//! #[derive(Clone, Event)]
+//! struct Scroll(u64);
+//!
+//! let mut publisher = Publisher::default();
+//! publisher.subscribe_with(|_click: Click| {});
+//!
+//! let report = LoadGenerator::default()
+//!     .stream(500.0, Jitter::None, Click)
+//!     .stream(200.0, Jitter::Poisson, Scroll)
+//!     .run(&mut publisher, Duration::from_millis(20));
+//!
+//! assert_eq!(report.published["Click"], 10);
+//! assert!(report.errors.is_empty());
+//! ```
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Event, Publisher};
+
+/// How the gaps between the events of a stream vary around the gap its rate calls for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Jitter {
+    /// Evenly spaced events
+    None,
+    /// Each gap is stretched or shrunk by a random fraction of up to the given amount, e.g. 0.2
+    /// for gaps within 20% of the target
+    Uniform(f64),
+    /// Gaps are exponentially distributed, as for independent arrivals, so events sometimes come
+    /// in bursts
+    Poisson,
+}
+
+type Publish = Box<dyn FnMut(&mut Publisher, u64) -> Result<(), Vec<Box<dyn Any + Send>>>>;
+
+struct Stream {
+    name: &'static str,
+    /// Target gap between events, in nanoseconds
+    gap: f64,
+    jitter: Jitter,
+    publish: Publish,
+    /// When the next event is due, in nanoseconds since the run started
+    due: f64,
+    sent: u64,
+}
+
+/// What a `LoadGenerator` run did
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Number of events published, by the short name of their type
+    pub published: BTreeMap<&'static str, u64>,
+    /// Errors returned by handlers
+    pub errors: Vec<Box<dyn Any + Send>>,
+    /// How late the most delayed event was published, because publishing couldn't keep up
+    pub max_lag: Duration,
+    /// How long the run took, including publishing events that fell behind
+    pub elapsed: Duration,
+}
+
+/// Publishes configurable mixes of events at target rates. Each stream's events are built by a
+/// closure from their number in the stream, starting at 0.
+pub struct LoadGenerator {
+    streams: Vec<Stream>,
+    seed: u64,
+}
+
+impl Default for LoadGenerator {
+    fn default() -> Self {
+        LoadGenerator {
+            streams: Vec::new(),
+            seed: 0x853C_49E6_748F_EA9B,
+        }
+    }
+}
+
+impl LoadGenerator {
+    /// Add a stream of `rate` events per second, built by `make`
+    pub fn stream<E, F>(mut self, rate: f64, jitter: Jitter, mut make: F) -> Self
+    where
+        E: Event,
+        F: FnMut(u64) -> E + 'static,
+    {
+        let name = std::any::type_name::<E>();
+        self.streams.push(Stream {
+            name: name.rsplit("::").next().unwrap_or(name),
+            gap: 1e9 / rate.max(f64::MIN_POSITIVE),
+            jitter,
+            publish: Box::new(move |publisher, n| publisher.publish(make(n))),
+            due: 0.0,
+            sent: 0,
+        });
+        self
+    }
+
+    /// Seed the jitter, so that runs with the same seed space their events the same way
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed.max(1);
+        self
+    }
+
+    /// Publish every stream's events to `publisher` on the current thread until `duration` has
+    /// passed. Events that fall behind schedule are published as soon as possible, so every event
+    /// due within `duration` is published.
+    pub fn run(mut self, publisher: &mut Publisher, duration: Duration) -> LoadReport {
+        let end = duration.as_nanos() as f64;
+        let mut random = XorShift(self.seed);
+        let mut report = LoadReport::default();
+        let start = Instant::now();
+
+        while let Some(stream) = self
+            .streams
+            .iter_mut()
+            .filter(|stream| stream.due < end)
+            .min_by(|a, b| a.due.total_cmp(&b.due))
+        {
+            let due = Duration::from_nanos(stream.due as u64);
+            match due.checked_sub(start.elapsed()) {
+                Some(wait) => thread::sleep(wait),
+                None => report.max_lag = report.max_lag.max(start.elapsed() - due),
+            }
+            if let Err(errors) = (stream.publish)(publisher, stream.sent) {
+                report.errors.extend(errors);
+            }
+            *report.published.entry(stream.name).or_default() += 1;
+            stream.sent += 1;
+            stream.due += stream.gap * random.jitter(stream.jitter);
+        }
+
+        report.elapsed = start.elapsed();
+        report
+    }
+}
+
+/// Small, fast pseudo-random numbers, good enough for spacing events
+struct XorShift(u64);
+
+impl XorShift {
+    /// A number in (0, 1]
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        ((self.0 >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// What to multiply a stream's target gap by for its next event
+    fn jitter(&mut self, jitter: Jitter) -> f64 {
+        match jitter {
+            Jitter::None => 1.0,
+            Jitter::Uniform(amount) => 1.0 + amount * (2.0 * self.next() - 1.0),
+            Jitter::Poisson => -self.next().ln(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::Handler;
+
+    #[derive(Clone)]
+    struct Order(u64);
+    impl Event for Order {}
+
+    #[derive(Clone)]
+    struct Refund;
+    impl Event for Refund {}
+
+    #[test]
+    fn test_streams_publish_every_event_due_in_order() {
+        let mut publisher = Publisher::default();
+        let orders = Arc::new(Mutex::new(Vec::new()));
+        let orders_clone = orders.clone();
+        publisher.subscribe(Handler::new(move |order: Order| {
+            orders_clone.lock().unwrap().push(order.0)
+        }));
+        publisher.subscribe(Handler::new(|_refund: Refund| panic!("refunds are broken")));
+
+        let report = LoadGenerator::default()
+            .stream(1000.0, Jitter::Uniform(0.5), Order)
+            .stream(250.0, Jitter::None, |_| Refund)
+            .run(&mut publisher, Duration::from_millis(40));
+
+        assert_eq!(report.published["Refund"], 10);
+        assert_eq!(report.errors.len(), 10);
+        let orders = orders.lock().unwrap();
+        assert_eq!(report.published["Order"], orders.len() as u64);
+        assert!((20..=80).contains(&orders.len()));
+        assert!(orders.iter().copied().eq(0..orders.len() as u64));
+    }
+
+    #[test]
+    fn test_jitter_keeps_the_average_rate() {
+        let mut random = XorShift(7);
+        for jitter in [Jitter::None, Jitter::Uniform(0.3), Jitter::Poisson] {
+            let mean = (0..10_000).map(|_| random.jitter(jitter)).sum::<f64>() / 10_000.0;
+            assert!((mean - 1.0).abs() < 0.05, "{jitter:?} averaged {mean}");
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    any::{self, TypeId},
+    panic::{AssertUnwindSafe, RefUnwindSafe},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use crate::{
+    DynEvent, DynHandle, FromEvent, HandleMut, HandlerError, HandlerPoisoned, event, platform,
+    wait::{InFlight, InFlightGuard},
+};
+
+/// Handler that queues the events a HandleMut takes for a thread of its own, which handles them
+/// one at a time in the order they were published. Publishing only waits for the event to be
+/// queued. The thread stops once the Mailbox is dropped and it has handled what was queued. On
+/// targets without threads, the events are handled on the publishing thread instead.
+pub(crate) struct Mailbox<T: HandleMut> {
+    route: Route<T>,
+    in_flight: Arc<InFlight>,
+}
+
+enum Route<T: HandleMut> {
+    Thread(mpsc::Sender<(T::EventType, InFlightGuard)>),
+    Inline(Mutex<Actor<T>>),
+}
+
+impl<T: HandleMut> RefUnwindSafe for Mailbox<T> {}
+
+impl<T> Mailbox<T>
+where
+    T: HandleMut + Send + 'static,
+    T::EventType: Send,
+{
+    pub(crate) fn new(handler: T, in_flight: Arc<InFlight>, stack_size: Option<usize>) -> Self {
+        let mut actor = Actor {
+            handler,
+            poisoned: false,
+        };
+        if !platform::THREADS {
+            return Mailbox {
+                route: Route::Inline(Mutex::new(actor)),
+                in_flight,
+            };
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut builder = thread::Builder::new().name(String::from("crier-actor"));
+        if let Some(stack_size) = stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        builder
+            .spawn(move || {
+                for (event, guard) in receiver {
+                    actor.receive(event, &guard);
+                }
+            })
+            .expect("Failed to spawn actor thread");
+
+        Mailbox {
+            route: Route::Thread(sender),
+            in_flight,
+        }
+    }
+}
+
+/// A HandleMut and whether it has panicked
+struct Actor<T> {
+    handler: T,
+    poisoned: bool,
+}
+
+impl<T: HandleMut> Actor<T> {
+    /// Handle an event, reporting a panic through `guard`. A handler that panics may have been
+    /// left half way through changing its state, so isn't given any more events, and each is
+    /// reported as a HandlerError wrapping HandlerPoisoned instead.
+    fn receive(&mut self, event: T::EventType, guard: &InFlightGuard) {
+        if self.poisoned {
+            guard.fail(Box::new(HandlerError {
+                handler: any::type_name::<T>(),
+                error: Box::new(HandlerPoisoned),
+            }));
+            return;
+        }
+        let handler = &mut self.handler;
+        if let Err(e) = std::panic::catch_unwind(AssertUnwindSafe(|| handler.handle_mut(event))) {
+            self.poisoned = true;
+            guard.fail(e);
+        }
+    }
+}
+
+impl<T> DynHandle for Mailbox<T>
+where
+    T: HandleMut + Send + 'static,
+    T::EventType: Send,
+{
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let Some(event) = T::EventType::from_event(event) else {
+            return;
+        };
+        let guard = self.in_flight.start();
+        match &self.route {
+            // the thread only stops once this is dropped, so it is still there to receive
+            Route::Thread(sender) => {
+                let _ = sender.send((event, guard));
+            }
+            Route::Inline(actor) => actor
+                .lock()
+                .expect("Actor mutex poisoned")
+                .receive(event, &guard),
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<<T::EventType as FromEvent>::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<<T::EventType as FromEvent>::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<<T::EventType as FromEvent>::Source>())
+    }
+
+    fn label(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Event, HandleMut, HandlerError, HandlerPoisoned, Publisher};
+    use std::{
+        sync::{Arc, Mutex, mpsc},
+        thread::{self, ThreadId},
+        time::Duration,
+    };
+
+    #[derive(Clone)]
+    struct Deposit(i64);
+    impl Event for Deposit {}
+
+    struct Account {
+        balance: i64,
+        seen: Arc<Mutex<Vec<(i64, ThreadId)>>>,
+        release: mpsc::Receiver<()>,
+    }
+
+    impl HandleMut for Account {
+        type EventType = Deposit;
+
+        fn handle_mut(&mut self, deposit: Deposit) {
+            // the first deposit is held up until the test has finished publishing
+            if self.seen.lock().unwrap().is_empty() {
+                self.release.recv().unwrap();
+            }
+            assert!(deposit.0 >= 0, "negative deposit");
+            self.balance += deposit.0;
+            self.seen
+                .lock()
+                .unwrap()
+                .push((self.balance, thread::current().id()));
+        }
+    }
+
+    #[test]
+    fn test_actors_handle_events_in_order_on_a_thread_of_their_own() {
+        let mut publisher = Publisher::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let (release, receiver) = mpsc::channel();
+        publisher.subscribe_actor(Account {
+            balance: 0,
+            seen: seen.clone(),
+            release: receiver,
+        });
+
+        // publishing doesn't wait for the blocked handler
+        publisher.publish(Deposit(1)).unwrap();
+        publisher.publish(Deposit(2)).unwrap();
+        assert!(seen.lock().unwrap().is_empty());
+        release.send(()).unwrap();
+        let report = publisher.publish_and_wait(Deposit(3), Duration::from_secs(5));
+        assert!(report.is_ok());
+
+        let seen = seen.lock().unwrap();
+        let balances: Vec<i64> = seen.iter().map(|(balance, _)| *balance).collect();
+        assert_eq!(balances, [1, 3, 6]);
+        assert!(seen.iter().all(|(_, id)| *id == seen[0].1));
+        assert_ne!(seen[0].1, thread::current().id());
+    }
+
+    #[test]
+    fn test_actors_that_panic_are_reported_as_poisoned_afterwards() {
+        let mut publisher = Publisher::default();
+        let (release, receiver) = mpsc::channel();
+        release.send(()).unwrap();
+        publisher.subscribe_actor(Account {
+            balance: 0,
+            seen: Arc::default(),
+            release: receiver,
+        });
+
+        let report = publisher.publish_and_wait(Deposit(-1), Duration::from_secs(5));
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].downcast_ref::<HandlerError>().is_none());
+        let report = publisher.publish_and_wait(Deposit(1), Duration::from_secs(5));
+        let failure = report.errors[0].downcast_ref::<HandlerError>().unwrap();
+        assert_eq!(failure.downcast_ref(), Some(&HandlerPoisoned));
+    }
+}
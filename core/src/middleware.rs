@@ -0,0 +1,22 @@
+use crate::{DynEvent, Metadata};
+
+/// Whether a Publisher should carry on dispatching an event after a Middleware has seen it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Trait for an object that sees every event published by a Publisher before and after its
+/// handlers run. Useful for cross-cutting concerns like logging, auth or rate limiting.
+pub trait Middleware: Send + Sync {
+    /// Called before any handlers run, with the metadata the event will be delivered with.
+    /// Returning `Flow::Stop` prevents the event from reaching any handlers or later middleware.
+    fn before(&self, _event: &dyn DynEvent, _metadata: &mut Metadata) -> Flow {
+        Flow::Continue
+    }
+
+    /// Called after all handlers have run. Not called for events with `Delivery::Exclusive`, which
+    /// have been moved into their handler by then.
+    fn after(&self, _event: &dyn DynEvent, _metadata: &Metadata) {}
+}
@@ -0,0 +1,136 @@
+use std::{
+    any::{self, TypeId},
+    marker::PhantomData,
+    panic::{AssertUnwindSafe, RefUnwindSafe},
+    sync::Arc,
+    time::Instant,
+};
+
+use crate::{DynEvent, DynHandle, FromEvent, event, scheduler::Timer, wait::InFlight};
+
+/// Publishes a mirrored event on the Publisher it is mirrored to, if that is still around
+pub(crate) type Forward<B> = dyn Fn(B) + Send + Sync;
+
+/// Handler that hands the events of type `A` it receives to the Publisher's scheduler thread,
+/// where they are transformed and published on another Publisher, so that neither the transform
+/// nor the other Publisher's handlers hold up the one they were published on
+pub(crate) struct Mirror<A, B, F> {
+    transform: Arc<F>,
+    forward: Arc<Forward<B>>,
+    timer: Timer,
+    in_flight: Arc<InFlight>,
+    _types: PhantomData<fn(A) -> B>,
+}
+
+impl<A, B, F> RefUnwindSafe for Mirror<A, B, F> {}
+
+impl<A, B, F> Mirror<A, B, F> {
+    pub(crate) fn new(
+        transform: F,
+        forward: Arc<Forward<B>>,
+        timer: Timer,
+        in_flight: Arc<InFlight>,
+    ) -> Self {
+        Mirror {
+            transform: Arc::new(transform),
+            forward,
+            timer,
+            in_flight,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<A, B, F> DynHandle for Mirror<A, B, F>
+where
+    A: FromEvent + Send,
+    B: 'static,
+    F: Fn(A) -> Option<B> + Send + Sync + 'static,
+{
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let Some(event) = A::from_event(event) else {
+            return;
+        };
+
+        let guard = self.in_flight.start();
+        let transform = self.transform.clone();
+        let forward = self.forward.clone();
+        self.timer.schedule(Instant::now(), move || {
+            match std::panic::catch_unwind(AssertUnwindSafe(|| transform(event))) {
+                Ok(Some(mirrored)) => forward(mirrored),
+                Ok(None) => {}
+                Err(e) => guard.fail(e),
+            }
+        });
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event::is::<A::Source>(event.get_data())
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        event::is_type::<A::Source>(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        Some(any::type_name::<A::Source>())
+    }
+
+    fn label(&self) -> &'static str {
+        any::type_name::<F>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Event, Publisher};
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    #[derive(Clone)]
+    struct Login {
+        user: String,
+        attempt: u32,
+    }
+    impl Event for Login {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct AnonymousLogin(u32);
+    impl Event for AnonymousLogin {}
+
+    #[test]
+    fn test_mirrored_events_are_transformed_and_published_off_the_publishing_thread() {
+        let mut production = Publisher::default();
+        let mut diagnostics = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        diagnostics.subscribe_with(move |login: AnonymousLogin| {
+            received_clone
+                .lock()
+                .unwrap()
+                .push((login, thread::current().id()))
+        });
+        production.mirror_to(&diagnostics, |login: Login| {
+            // the user's name is left behind, and only every other attempt is kept
+            (!login.user.is_empty() && login.attempt % 2 == 1)
+                .then_some(AnonymousLogin(login.attempt))
+        });
+
+        for attempt in 1..=4 {
+            let login = Login {
+                user: String::from("alice"),
+                attempt,
+            };
+            let report = production.publish_and_wait(login, Duration::from_secs(5));
+            assert!(report.is_ok());
+        }
+
+        let received = received.lock().unwrap();
+        let logins: Vec<&AnonymousLogin> = received.iter().map(|(login, _)| login).collect();
+        assert_eq!(logins, [&AnonymousLogin(1), &AnonymousLogin(3)]);
+        assert!(received.iter().all(|(_, id)| *id != thread::current().id()));
+    }
+}
@@ -0,0 +1,564 @@
+//! A bridge between a Publisher and an MQTT broker, built on the `rumqttc` client.
+//!
+//! Each event type registered with the bridge's EventRegistry is mapped to the topic
+//! `<prefix>/<type tag>`. Events published locally are sent to their topic as JSON, and messages
+//! arriving on those topics are published locally as events. Devices that speak MQTT but not
+//! crier only need to send and receive the JSON form of the events.
+//!
+//! The bridge speaks MQTT 5 at QoS 0, and asks the broker not to send it back the messages it
+//! published itself. If the connection to the broker is lost, the bridge reconnects and
+//! subscribes again. Events published while it is disconnected are dropped once its queue for
+//! the broker is full.
+
+use std::{
+    error::Error,
+    io,
+    net::ToSocketAddrs,
+    panic::RefUnwindSafe,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rumqttc::v5::{
+    Client, Connection, ConnectionError, Event, Incoming, MqttOptions as ClientOptions,
+    mqttbytes::{QoS, v5::Filter},
+};
+
+use crate::{DynEvent, DynHandle, EventRegistry, EventTooLarge, SerializedEvent};
+
+/// Largest packet, in bytes, that will be sent or accepted, and the largest event the bridge sends
+/// unless set lower with `MqttOptions::max_event_size`. Larger events are dropped, and the
+/// connection is closed if the broker sends a larger packet.
+pub const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+
+/// Number of events that can be waiting to be sent to the broker, beyond which events are
+/// dropped, such as while the bridge is reconnecting
+const QUEUE_CAPACITY: usize = 1024;
+
+/// How long to wait between attempts to reconnect to the broker
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Where and how to connect to an MQTT broker
+#[derive(Clone, Debug)]
+pub struct MqttOptions {
+    broker: String,
+    client_id: String,
+    topic_prefix: String,
+    keep_alive: Duration,
+    max_event_size: usize,
+}
+
+impl MqttOptions {
+    /// Connect to the broker at `broker`, e.g. `"localhost:1883"`, as `client_id`. Topics are
+    /// prefixed with `crier` and the connection is kept alive every 30 seconds unless configured
+    /// otherwise.
+    pub fn new(broker: impl Into<String>, client_id: impl Into<String>) -> Self {
+        MqttOptions {
+            broker: broker.into(),
+            client_id: client_id.into(),
+            topic_prefix: String::from("crier"),
+            keep_alive: Duration::from_secs(30),
+            max_event_size: MAX_PACKET_SIZE,
+        }
+    }
+
+    /// Map each event type to the topic `<prefix>/<type tag>`
+    pub fn topic_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.topic_prefix = prefix.into();
+        self
+    }
+
+    /// How often to ping the broker when nothing else has been received. Rounded down to whole
+    /// seconds, and must be at least five.
+    pub fn keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Drop events that serialize to more than `bytes`, rather than sending them, failing with
+    /// EventTooLarge. Larger messages from the broker are skipped. Can't be set higher than
+    /// MAX_PACKET_SIZE.
+    pub fn max_event_size(mut self, bytes: usize) -> Self {
+        self.max_event_size = bytes.min(MAX_PACKET_SIZE);
+        self
+    }
+
+    fn topic(&self, tag: &str) -> String {
+        format!("{}/{tag}", self.topic_prefix)
+    }
+
+    fn tag<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        topic.strip_prefix(&self.topic_prefix)?.strip_prefix('/')
+    }
+
+    /// The options the client is created with
+    fn client_options(&self) -> io::Result<ClientOptions> {
+        let keep_alive = Duration::from_secs(self.keep_alive.as_secs());
+        if keep_alive < Duration::from_secs(5) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid keep alive interval",
+            ));
+        }
+        let addr = self.broker.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing")
+        })?;
+
+        let mut options = ClientOptions::new(&self.client_id, addr.ip().to_string(), addr.port());
+        options
+            .set_keep_alive(keep_alive)
+            .set_clean_start(true)
+            .set_max_packet_size(Some(MAX_PACKET_SIZE as u32));
+        Ok(options)
+    }
+}
+
+/// Handler that sends events to the broker. Subscribed to the Publisher by
+/// `Publisher::bridge_mqtt`.
+pub(crate) struct MqttForwarder {
+    options: Arc<MqttOptions>,
+    registry: Arc<EventRegistry>,
+    client: Client,
+}
+
+impl RefUnwindSafe for MqttForwarder {}
+
+impl DynHandle for MqttForwarder {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.accepts(event) {
+            return Ok(());
+        }
+        let Ok(serialized) = self.registry.serialize(event) else {
+            return Ok(());
+        };
+        EventTooLarge::check("mqtt", &serialized, self.options.max_event_size)?;
+        let SerializedEvent { tag, payload } = serialized;
+        // queued rather than waited for, so that a broker that is down doesn't hold up publishing
+        let _ = self
+            .client
+            .try_publish(self.options.topic(&tag), QoS::AtMostOnce, false, payload);
+        Ok(())
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        // events that came from the broker are not sent back to it
+        let remote = event.metadata().is_some_and(|metadata| metadata.remote);
+        !remote && self.registry.is_registered(event)
+    }
+
+    fn accepts_remote(&self) -> bool {
+        false
+    }
+}
+
+/// A connection to an MQTT broker that relays events in both directions. Created by
+/// `Publisher::bridge_mqtt`. Disconnects and unsubscribes from the Publisher when dropped.
+pub struct MqttBridge {
+    client: Client,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    unsubscribe: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl MqttBridge {
+    /// Connect to the broker and subscribe to the topic of every registered event type, passing
+    /// every event received to `publish` until it returns false. Returns the bridge along with the
+    /// handler that sends events to the broker.
+    pub(crate) fn connect<F>(
+        options: MqttOptions,
+        registry: Arc<EventRegistry>,
+        publish: F,
+    ) -> io::Result<(Self, MqttForwarder)>
+    where
+        F: Fn(Box<dyn DynEvent>) -> bool + Send + 'static,
+    {
+        let (client, connection) = Client::new(options.client_options()?, QUEUE_CAPACITY);
+        let mut tags: Vec<&str> = registry.tags().collect();
+        tags.sort_unstable();
+        let filters: Vec<Filter> = tags
+            .into_iter()
+            .map(|tag| Filter {
+                nolocal: true,
+                ..Filter::new(options.topic(tag), QoS::AtMostOnce)
+            })
+            .collect();
+
+        let options = Arc::new(options);
+        let stopped = Arc::new(AtomicBool::new(false));
+        // the client drives its connection on a runtime of its own, which can't be started from
+        // within another, so even the first connection is made on the bridge's thread
+        let (connected, first) = mpsc::channel();
+        let thread = {
+            let receiver = Receiver {
+                client: client.clone(),
+                filters,
+                stopped: stopped.clone(),
+                options: options.clone(),
+                registry: registry.clone(),
+            };
+            thread::Builder::new()
+                .name(String::from("crier-mqtt"))
+                .spawn(move || receiver.run(connection, connected, &publish))?
+        };
+        match first.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(io::Error::other("MQTT thread stopped")),
+        }
+
+        let bridge = MqttBridge {
+            client: client.clone(),
+            stopped,
+            thread: Some(thread),
+            unsubscribe: None,
+        };
+        let forwarder = MqttForwarder {
+            options,
+            registry,
+            client,
+        };
+
+        Ok((bridge, forwarder))
+    }
+
+    /// Run `unsubscribe` when the bridge is dropped, to remove its forwarder from the Publisher
+    pub(crate) fn on_drop(&mut self, unsubscribe: impl FnOnce() + Send + 'static) {
+        self.unsubscribe = Some(Box::new(unsubscribe));
+    }
+}
+
+impl Drop for MqttBridge {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+        self.stopped.store(true, Ordering::SeqCst);
+        let _ = self.client.try_disconnect();
+
+        if let Some(thread) = self.thread.take() {
+            // cut short any wait to reconnect
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Drives the connection to the broker on the bridge's thread
+struct Receiver {
+    client: Client,
+    filters: Vec<Filter>,
+    stopped: Arc<AtomicBool>,
+    options: Arc<MqttOptions>,
+    registry: Arc<EventRegistry>,
+}
+
+impl Receiver {
+    /// Publish the messages the broker sends until the bridge is dropped or the Publisher is
+    /// gone, reconnecting whenever the connection is lost. Whether the first connection succeeds
+    /// is sent through `connected`, and the thread stops if it doesn't.
+    fn run(
+        self,
+        mut connection: Connection,
+        connected: mpsc::Sender<io::Result<()>>,
+        publish: &dyn Fn(Box<dyn DynEvent>) -> bool,
+    ) {
+        let mut connected = Some(connected);
+        for notification in connection.iter() {
+            if self.stopped.load(Ordering::SeqCst) {
+                return;
+            }
+            match notification {
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                    // subscriptions end with the session, so they're made afresh on every
+                    // connection
+                    if !self.filters.is_empty() {
+                        let _ = self.client.try_subscribe_many(self.filters.clone());
+                    }
+                    if let Some(connected) = connected.take() {
+                        let _ = connected.send(Ok(()));
+                    }
+                }
+                Ok(Event::Incoming(Incoming::Publish(message))) => {
+                    let Some(tag) = std::str::from_utf8(&message.topic)
+                        .ok()
+                        .and_then(|topic| self.options.tag(topic))
+                    else {
+                        continue;
+                    };
+                    let serialized = SerializedEvent {
+                        tag: String::from(tag),
+                        payload: message.payload.to_vec(),
+                    };
+                    if EventTooLarge::check("mqtt", &serialized, self.options.max_event_size)
+                        .is_err()
+                    {
+                        continue;
+                    }
+                    // messages that don't deserialize, e.g. from devices sending some other
+                    // format, are skipped rather than ending the connection
+                    if let Ok(event) = self.registry.deserialize(&serialized)
+                        && !publish(event)
+                    {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    if let Some(connected) = connected.take() {
+                        let _ = connected.send(Err(io_error(e)));
+                        return;
+                    }
+                    // the client reconnects when it is next polled
+                    thread::park_timeout(RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+}
+
+fn io_error(error: ConnectionError) -> io::Error {
+    match error {
+        ConnectionError::Io(e) => e,
+        ConnectionError::ConnectionRefused(code) => io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("broker refused connection: {code:?}"),
+        ),
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Publisher, SerializableEvent};
+    use serde::{Deserialize, Serialize};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+    };
+
+    const CONNECT: u8 = 0x10;
+    const CONNACK: u8 = 0x20;
+    const PUBLISH: u8 = 0x30;
+    const SUBSCRIBE: u8 = 0x82;
+
+    /// Subscription option asking the broker not to forward our own messages back to us
+    const NO_LOCAL: u8 = 0x04;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Temperature(f32);
+    impl Event for Temperature {}
+    impl SerializableEvent for Temperature {
+        const TYPE_TAG: &'static str = "temperature";
+    }
+
+    fn registry() -> Arc<EventRegistry> {
+        let mut registry = EventRegistry::default();
+        registry.register::<Temperature>();
+        Arc::new(registry)
+    }
+
+    fn put_str(buffer: &mut Vec<u8>, value: &str) {
+        buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(value.as_bytes());
+    }
+
+    fn take_str(buffer: &[u8]) -> Option<(&str, &[u8])> {
+        let (len, rest) = buffer.split_first_chunk::<2>()?;
+        let len = usize::from(u16::from_be_bytes(*len));
+        let value = std::str::from_utf8(rest.get(..len)?).ok()?;
+
+        Some((value, &rest[len..]))
+    }
+
+    /// MQTT's variable length integers hold 7 bits per byte, with the high bit set on every byte
+    /// but the last
+    fn put_varint(buffer: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value % 128) as u8;
+            value /= 128;
+            if value == 0 {
+                buffer.push(byte);
+                return;
+            }
+            buffer.push(byte | 0x80);
+        }
+    }
+
+    fn take_varint(buffer: &[u8]) -> Option<(usize, &[u8])> {
+        let mut value = 0;
+        for (i, byte) in buffer.iter().take(4).enumerate() {
+            value += usize::from(byte & 0x7F) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Some((value, &buffer[i + 1..]));
+            }
+        }
+
+        None
+    }
+
+    /// Read the next packet, returning its first byte, which holds its type and flags, and its
+    /// body
+    fn read_packet(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+        let mut packet_type = [0];
+        reader.read_exact(&mut packet_type)?;
+        let mut len = 0;
+        for i in 0..4 {
+            let mut byte = [0];
+            reader.read_exact(&mut byte)?;
+            len += usize::from(byte[0] & 0x7F) << (7 * i);
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+
+        Ok((packet_type[0], body))
+    }
+
+    fn write_packet(writer: &mut impl Write, packet_type: u8, body: &[u8]) {
+        let mut packet = vec![packet_type];
+        put_varint(&mut packet, body.len());
+        packet.extend_from_slice(body);
+        writer.write_all(&packet).unwrap();
+    }
+
+    /// Split the body of a QoS 0 PUBLISH packet into its topic and payload
+    fn parse_publish(body: &[u8]) -> Option<(&str, &[u8])> {
+        let (topic, rest) = take_str(body)?;
+        let (properties_len, rest) = take_varint(rest)?;
+
+        Some((topic, rest.get(properties_len..)?))
+    }
+
+    /// Accept a single client, returning its socket once it has connected and the topic filters
+    /// it subscribed to
+    fn broker(listener: &TcpListener) -> (TcpStream, Vec<(String, u8)>) {
+        let (mut socket, _) = listener.accept().unwrap();
+        let (packet_type, _) = read_packet(&mut socket).unwrap();
+        assert_eq!(packet_type, CONNECT);
+        write_packet(&mut socket, CONNACK, &[0, 0, 0]);
+
+        let (packet_type, body) = read_packet(&mut socket).unwrap();
+        assert_eq!(packet_type, SUBSCRIBE);
+        let (_, mut rest) = take_varint(&body[2..]).unwrap();
+        let mut filters = Vec::new();
+        while let Some((topic, options)) = take_str(rest) {
+            filters.push((String::from(topic), options[0]));
+            rest = &options[1..];
+        }
+
+        (socket, filters)
+    }
+
+    fn receiving_publisher() -> (Publisher, mpsc::Receiver<f32>) {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe(Handler::new(move |event: Temperature| {
+            sender.lock().unwrap().send(event.0).unwrap()
+        }));
+        (publisher, receiver)
+    }
+
+    #[test]
+    fn test_bridge_relays_events_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let broker = thread::spawn(move || broker(&listener));
+
+        let (mut publisher, receiver) = receiving_publisher();
+        let options = MqttOptions::new(addr.to_string(), "test").topic_prefix("home/kitchen");
+        let _bridge = publisher.bridge_mqtt(options, registry()).unwrap();
+        let (mut socket, filters) = broker.join().unwrap();
+        assert_eq!(
+            filters,
+            vec![(String::from("home/kitchen/temperature"), NO_LOCAL)]
+        );
+
+        // local events go to the broker
+        let _ = publisher.publish(Temperature(21.5));
+        assert_eq!(receiver.recv().unwrap(), 21.5);
+        let (packet_type, body) = read_packet(&mut socket).unwrap();
+        assert_eq!(packet_type, PUBLISH);
+        assert_eq!(
+            parse_publish(&body),
+            Some(("home/kitchen/temperature", &b"21.5"[..]))
+        );
+
+        // messages from the broker are published locally, and not sent back
+        let mut body = Vec::new();
+        put_str(&mut body, "home/kitchen/temperature");
+        put_varint(&mut body, 0);
+        body.extend_from_slice(b"19.0");
+        write_packet(&mut socket, PUBLISH, &body);
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), 19.0);
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        assert!(read_packet(&mut socket).is_err());
+    }
+
+    #[test]
+    fn test_bridge_reconnects_and_subscribes_again() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (mut publisher, receiver) = receiving_publisher();
+        let broker = thread::spawn(move || {
+            let (socket, _) = broker(&listener);
+            drop(socket);
+            broker(&listener)
+        });
+
+        let _bridge = publisher
+            .bridge_mqtt(MqttOptions::new(addr.to_string(), "test"), registry())
+            .unwrap();
+        let (mut socket, filters) = broker.join().unwrap();
+        assert_eq!(filters, vec![(String::from("crier/temperature"), NO_LOCAL)]);
+
+        let mut body = Vec::new();
+        put_str(&mut body, "crier/temperature");
+        put_varint(&mut body, 0);
+        body.extend_from_slice(b"4.5");
+        write_packet(&mut socket, PUBLISH, &body);
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), 4.5);
+    }
+
+    #[test]
+    fn test_refused_connections_are_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let broker = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_packet(&mut socket).unwrap();
+            // not authorized
+            write_packet(&mut socket, CONNACK, &[0, 0x87, 0]);
+        });
+
+        let mut publisher = Publisher::default();
+        let error = publisher
+            .bridge_mqtt(MqttOptions::new(addr.to_string(), "test"), registry())
+            .err()
+            .unwrap();
+        broker.join().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn test_short_keep_alives_are_rejected() {
+        let options = MqttOptions::new("127.0.0.1:1883", "test").keep_alive(Duration::from_secs(1));
+        let error = options.client_options().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    any::{Any, TypeId},
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{DynEvent, DynHandle, DynHandleMut, HandlerPanic};
+
+/// A handler with a human-readable name, such as "audio::on_pause", which is used as its label
+/// when inspecting the Publisher, and attached to its panics as a `HandlerPanic` so that they can
+/// be traced back to it. Subscribe one with `Publisher::subscribe_named`, or wrap a handler in
+/// one before subscribing it some other way.
+pub struct Named<H> {
+    name: &'static str,
+    handler: H,
+}
+
+impl<H> Named<H> {
+    pub fn new(name: &'static str, handler: H) -> Self {
+        Named { name, handler }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+}
+
+/// Run `handle`, attaching `name` to any panic it raises
+fn attribute<R>(name: &'static str, handle: impl FnOnce() -> R) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(handle)) {
+        Ok(result) => result,
+        Err(payload) => panic::resume_unwind(Box::new(HandlerPanic {
+            handler: name,
+            payload,
+        })),
+    }
+}
+
+impl<H: DynHandle> DynHandle for Named<H> {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        attribute(self.name, || self.handler.dyn_handle(event))
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        attribute(self.name, || self.handler.dyn_try_handle(event))
+    }
+
+    fn dyn_handle_owned(&self, event: Box<dyn Any + Send>) {
+        attribute(self.name, || self.handler.dyn_handle_owned(event))
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        self.handler.accepts(event)
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        self.handler.accepts_type(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        self.handler.event_type_name()
+    }
+
+    fn label(&self) -> &'static str {
+        self.name
+    }
+
+    fn accepts_remote(&self) -> bool {
+        self.handler.accepts_remote()
+    }
+
+    fn poll_ready(&self) -> bool {
+        self.handler.poll_ready()
+    }
+}
+
+impl<H: DynHandleMut> DynHandleMut for Named<H> {
+    fn dyn_handle_mut(&mut self, event: &dyn DynEvent) {
+        attribute(self.name, || self.handler.dyn_handle_mut(event))
+    }
+
+    fn dyn_try_handle_mut(
+        &mut self,
+        event: &dyn DynEvent,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        attribute(self.name, || self.handler.dyn_try_handle_mut(event))
+    }
+
+    fn dyn_handle_mut_owned(&mut self, event: Box<dyn Any + Send>) {
+        attribute(self.name, || self.handler.dyn_handle_mut_owned(event))
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        self.handler.accepts(event)
+    }
+
+    fn accepts_type(&self, event_type: TypeId) -> bool {
+        self.handler.accepts_type(event_type)
+    }
+
+    fn event_type_name(&self) -> Option<&'static str> {
+        self.handler.event_type_name()
+    }
+
+    fn label(&self) -> &'static str {
+        self.name
+    }
+
+    fn poll_ready(&self) -> bool {
+        self.handler.poll_ready()
+    }
+}
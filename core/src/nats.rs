@@ -0,0 +1,480 @@
+//! A bridge between a Publisher and a NATS server, built on the `async-nats` client.
+//!
+//! Each event type registered with the bridge's EventRegistry is mapped to the subject
+//! `<prefix>.<type tag>`. Events published locally are sent to their subject as JSON, and messages
+//! arriving on those subjects are published locally as events. Bridges that join the same queue
+//! group share the messages on each subject between them, rather than each receiving them all.
+//!
+//! The bridge asks the server not to send it back the messages it published itself. If the
+//! connection is lost, the client reconnects and subscribes again. Events published while the
+//! server is unreachable are queued, up to a limit, then dropped.
+
+use std::{
+    error::Error,
+    io,
+    panic::RefUnwindSafe,
+    sync::{Arc, mpsc},
+    thread,
+    time::Duration,
+};
+
+use async_nats::{Client, ConnectError, ConnectErrorKind, ConnectOptions, Message, Subscriber};
+use futures_util::{StreamExt, stream};
+use tokio::sync::{mpsc as queue, oneshot};
+
+use crate::{DynEvent, DynHandle, EventRegistry, EventTooLarge, SerializedEvent};
+
+/// Largest message payload, in bytes, that will be sent or accepted, and the largest event the
+/// bridge sends unless set lower with `NatsOptions::max_event_size`. Larger events are dropped.
+pub const MAX_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+/// How many events can wait to be sent to the server before more are dropped
+const QUEUE_CAPACITY: usize = 1024;
+
+/// How long to wait between attempts to reconnect
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// How long to wait for queued events to be sent when the bridge is dropped
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Where and how to connect to a NATS server
+#[derive(Clone, Debug)]
+pub struct NatsOptions {
+    addr: String,
+    subject_prefix: String,
+    queue_group: Option<String>,
+    max_event_size: usize,
+}
+
+impl NatsOptions {
+    /// Connect to the NATS server at `addr`, e.g. `"localhost:4222"`. Subjects are prefixed with
+    /// `crier` unless configured otherwise.
+    pub fn new(addr: impl Into<String>) -> Self {
+        NatsOptions {
+            addr: addr.into(),
+            subject_prefix: String::from("crier"),
+            queue_group: None,
+            max_event_size: MAX_PAYLOAD_SIZE,
+        }
+    }
+
+    /// Map each event type to the subject `<prefix>.<type tag>`
+    pub fn subject_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.subject_prefix = prefix.into();
+        self
+    }
+
+    /// Join the queue group `group`, so that each message is only received by one of the bridges
+    /// in the group
+    pub fn queue_group(mut self, group: impl Into<String>) -> Self {
+        self.queue_group = Some(group.into());
+        self
+    }
+
+    /// Drop events that serialize to more than `bytes`, rather than sending them, failing with
+    /// EventTooLarge. Larger messages from the server are skipped. Can't be set higher than
+    /// MAX_PAYLOAD_SIZE.
+    pub fn max_event_size(mut self, bytes: usize) -> Self {
+        self.max_event_size = bytes.min(MAX_PAYLOAD_SIZE);
+        self
+    }
+
+    fn subject(&self, tag: &str) -> String {
+        format!("{}.{tag}", self.subject_prefix)
+    }
+
+    fn tag<'a>(&self, subject: &'a str) -> Option<&'a str> {
+        subject
+            .strip_prefix(&self.subject_prefix)?
+            .strip_prefix('.')
+    }
+}
+
+/// Handler that sends events to the NATS server. Subscribed to the Publisher by
+/// `Publisher::bridge_nats`.
+pub(crate) struct NatsForwarder {
+    options: Arc<NatsOptions>,
+    registry: Arc<EventRegistry>,
+    queue: queue::Sender<(String, Vec<u8>)>,
+}
+
+impl RefUnwindSafe for NatsForwarder {}
+
+impl DynHandle for NatsForwarder {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.accepts(event) {
+            return Ok(());
+        }
+        let Ok(serialized) = self.registry.serialize(event) else {
+            return Ok(());
+        };
+        EventTooLarge::check("nats", &serialized, self.options.max_event_size)?;
+        let SerializedEvent { tag, payload } = serialized;
+        // the client sends events from the bridge's thread, so publishing never waits for the
+        // server
+        let _ = self.queue.try_send((self.options.subject(&tag), payload));
+        Ok(())
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        // events that came from NATS are not sent back to it
+        let remote = event.metadata().is_some_and(|metadata| metadata.remote);
+        !remote && self.registry.is_registered(event)
+    }
+
+    fn accepts_remote(&self) -> bool {
+        false
+    }
+}
+
+/// A connection to a NATS server that relays events in both directions. Created by
+/// `Publisher::bridge_nats`. Disconnects and unsubscribes from the Publisher when dropped.
+pub struct NatsBridge {
+    stop: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+    unsubscribe: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl NatsBridge {
+    /// Connect to the server and subscribe to the subject of every registered event type, passing
+    /// every event received to `publish` until it returns false. Returns the bridge along with the
+    /// handler that sends events to the server.
+    pub(crate) fn connect<F>(
+        options: NatsOptions,
+        registry: Arc<EventRegistry>,
+        publish: F,
+    ) -> io::Result<(Self, NatsForwarder)>
+    where
+        F: Fn(Box<dyn DynEvent>) -> bool + Send + 'static,
+    {
+        let options = Arc::new(options);
+        let (sender, outgoing) = queue::channel(QUEUE_CAPACITY);
+        let (stop, stopped) = oneshot::channel();
+        let (connected, first) = mpsc::channel();
+        // the client runs on a runtime of its own, which can't be started from within another
+        let thread = {
+            let relay = Relay {
+                options: options.clone(),
+                registry: registry.clone(),
+            };
+            thread::Builder::new()
+                .name(String::from("crier-nats"))
+                .spawn(move || {
+                    let runtime = match tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                    {
+                        Ok(runtime) => runtime,
+                        Err(e) => {
+                            let _ = connected.send(Err(e));
+                            return;
+                        }
+                    };
+                    runtime.block_on(relay.run(connected, outgoing, stopped, &publish));
+                })?
+        };
+        match first.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(io::Error::other("NATS thread stopped")),
+        }
+
+        let bridge = NatsBridge {
+            stop: Some(stop),
+            thread: Some(thread),
+            unsubscribe: None,
+        };
+        let forwarder = NatsForwarder {
+            options,
+            registry,
+            queue: sender,
+        };
+
+        Ok((bridge, forwarder))
+    }
+
+    /// Run `unsubscribe` when the bridge is dropped, to remove its forwarder from the Publisher
+    pub(crate) fn on_drop(&mut self, unsubscribe: impl FnOnce() + Send + 'static) {
+        self.unsubscribe = Some(Box::new(unsubscribe));
+    }
+}
+
+impl Drop for NatsBridge {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Relays events between the server and the Publisher on the bridge's thread
+struct Relay {
+    options: Arc<NatsOptions>,
+    registry: Arc<EventRegistry>,
+}
+
+impl Relay {
+    /// Connect and subscribe, then send queued events to the server and publish the messages it
+    /// sends until the bridge is dropped or the Publisher is gone. Whether connecting succeeds is
+    /// sent through `connected`; after that the client reconnects and subscribes again by itself.
+    async fn run(
+        self,
+        connected: mpsc::Sender<io::Result<()>>,
+        mut outgoing: queue::Receiver<(String, Vec<u8>)>,
+        mut stopped: oneshot::Receiver<()>,
+        publish: &dyn Fn(Box<dyn DynEvent>) -> bool,
+    ) {
+        let (client, subscribers) = match self.connect().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                let _ = connected.send(Err(e));
+                return;
+            }
+        };
+        let _ = connected.send(Ok(()));
+
+        // events are sent from a task of their own, so that waiting for the server to come back
+        // doesn't hold up stopping the bridge
+        let sending = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                while let Some((subject, payload)) = outgoing.recv().await {
+                    let _ = client.publish(subject, payload.into()).await;
+                }
+            })
+        };
+        let mut incoming = stream::select_all(subscribers);
+        loop {
+            tokio::select! {
+                _ = &mut stopped => break,
+                Some(message) = incoming.next() => {
+                    if !self.receive(message, publish) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let _ = tokio::time::timeout(FLUSH_TIMEOUT, client.flush()).await;
+        sending.abort();
+    }
+
+    async fn connect(&self) -> io::Result<(Client, Vec<Subscriber>)> {
+        let client = ConnectOptions::new()
+            .name("crier")
+            .no_echo()
+            .reconnect_delay_callback(|_| RECONNECT_DELAY)
+            .connect(self.options.addr.as_str())
+            .await
+            .map_err(io_error)?;
+
+        let mut tags: Vec<&str> = self.registry.tags().collect();
+        tags.sort_unstable();
+        let mut subscribers = Vec::new();
+        for tag in tags {
+            let subject = self.options.subject(tag);
+            let subscriber = match &self.options.queue_group {
+                Some(group) => client.queue_subscribe(subject, group.clone()).await,
+                None => client.subscribe(subject).await,
+            };
+            subscribers.push(subscriber.map_err(io::Error::other)?);
+        }
+
+        Ok((client, subscribers))
+    }
+
+    /// Publish a message from the server, returning false if the Publisher is gone
+    fn receive(&self, message: Message, publish: &dyn Fn(Box<dyn DynEvent>) -> bool) -> bool {
+        let Some(tag) = self.options.tag(message.subject.as_str()) else {
+            return true;
+        };
+        let serialized = SerializedEvent {
+            tag: String::from(tag),
+            payload: message.payload.to_vec(),
+        };
+        if EventTooLarge::check("nats", &serialized, self.options.max_event_size).is_err() {
+            return true;
+        }
+        // messages that don't deserialize, e.g. from services sending some other format, are
+        // skipped
+        match self.registry.deserialize(&serialized) {
+            Ok(event) => publish(event),
+            Err(_) => true,
+        }
+    }
+}
+
+fn io_error(error: ConnectError) -> io::Error {
+    let kind = match error.kind() {
+        ConnectErrorKind::ServerParse | ConnectErrorKind::Dns => io::ErrorKind::InvalidInput,
+        ConnectErrorKind::Authentication | ConnectErrorKind::AuthorizationViolation => {
+            io::ErrorKind::PermissionDenied
+        }
+        ConnectErrorKind::TimedOut => io::ErrorKind::TimedOut,
+        ConnectErrorKind::Io => error
+            .source()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .map_or(io::ErrorKind::Other, io::Error::kind),
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Publisher, SerializableEvent};
+    use serde::{Deserialize, Serialize};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct OrderPlaced {
+        id: u32,
+    }
+    impl Event for OrderPlaced {}
+    impl SerializableEvent for OrderPlaced {
+        const TYPE_TAG: &'static str = "order_placed";
+    }
+
+    fn registry() -> Arc<EventRegistry> {
+        let mut registry = EventRegistry::default();
+        registry.register::<OrderPlaced>();
+        Arc::new(registry)
+    }
+
+    /// Read a protocol line, without its trailing CRLF
+    fn read_line(reader: &mut impl BufRead) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        String::from(line.trim_end_matches("\r\n"))
+    }
+
+    /// Read the next protocol line from the client other than a PING, which is answered
+    fn read_op(reader: &mut BufReader<TcpStream>) -> String {
+        loop {
+            let line = read_line(reader);
+            if line != "PING" {
+                return line;
+            }
+            reader.get_mut().write_all(b"PONG\r\n").unwrap();
+        }
+    }
+
+    /// Accept a client, returning its socket once it has connected along with its CONNECT options
+    /// and subscription
+    fn accept(listener: &TcpListener) -> (BufReader<TcpStream>, serde_json::Value, String) {
+        let (mut socket, _) = listener.accept().unwrap();
+        socket
+            .write_all(b"INFO {\"server_id\":\"test\",\"max_payload\":1048576}\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(socket.try_clone().unwrap());
+
+        let connect = read_line(&mut reader);
+        let connect = serde_json::from_str(connect.strip_prefix("CONNECT ").unwrap()).unwrap();
+        assert_eq!(read_line(&mut reader), "PING");
+        socket.write_all(b"PONG\r\n").unwrap();
+        let subscription = read_op(&mut reader);
+
+        (reader, connect, subscription)
+    }
+
+    fn receiving_publisher() -> (Publisher, mpsc::Receiver<u32>) {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe(Handler::new(move |event: OrderPlaced| {
+            sender.lock().unwrap().send(event.id).unwrap()
+        }));
+        (publisher, receiver)
+    }
+
+    #[test]
+    fn test_bridge_relays_events_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (mut publisher, receiver) = receiving_publisher();
+        let server = thread::spawn(move || accept(&listener));
+        let options = NatsOptions::new(addr.to_string()).subject_prefix("shop");
+        let _bridge = publisher.bridge_nats(options, registry()).unwrap();
+        let (mut socket, connect, subscription) = server.join().unwrap();
+        assert_eq!(connect["echo"], false);
+        assert_eq!(connect["name"], "crier");
+        assert_eq!(subscription, "SUB shop.order_placed 1");
+
+        // local events go to the server
+        let _ = publisher.publish(OrderPlaced { id: 1 });
+        assert_eq!(receiver.recv().unwrap(), 1);
+        assert_eq!(read_op(&mut socket), "PUB shop.order_placed 8");
+        assert_eq!(read_line(&mut socket), "{\"id\":1}");
+
+        // messages from the server are published locally
+        socket
+            .get_mut()
+            .write_all(b"MSG shop.order_placed 1 8\r\n{\"id\":2}\r\n")
+            .unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_queue_groups_are_joined() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut publisher = Publisher::default();
+        let server = thread::spawn(move || accept(&listener));
+        let options = NatsOptions::new(addr.to_string()).queue_group("workers");
+        let _bridge = publisher.bridge_nats(options, registry()).unwrap();
+        let (_, _, subscription) = server.join().unwrap();
+        assert_eq!(subscription, "SUB crier.order_placed workers 1");
+    }
+
+    #[test]
+    fn test_bridge_reconnects_and_subscribes_again() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (mut publisher, receiver) = receiving_publisher();
+        let server = thread::spawn(move || {
+            let (socket, _, _) = accept(&listener);
+            drop(socket);
+            accept(&listener)
+        });
+        let _bridge = publisher
+            .bridge_nats(NatsOptions::new(addr.to_string()), registry())
+            .unwrap();
+        let (mut socket, _, subscription) = server.join().unwrap();
+        assert_eq!(subscription, "SUB crier.order_placed 1");
+
+        socket
+            .get_mut()
+            .write_all(b"MSG crier.order_placed 1 8\r\n{\"id\":3}\r\n")
+            .unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_connecting_to_nothing_fails() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut publisher = Publisher::default();
+        let Err(e) = publisher.bridge_nats(NatsOptions::new(addr.to_string()), registry()) else {
+            panic!("connected to a closed port");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+    }
+}
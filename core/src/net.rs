@@ -0,0 +1,2109 @@
+//! Bridges that carry events between Publishers in different processes over TCP.
+//!
+//! Subscribe a `Forwarder` to a Publisher to send its events to a remote process, and call
+//! `Publisher::listen` in that process to publish the events it receives. Doing both in each
+//! process shares one logical event bus between them. Only event types registered with the
+//! EventRegistry on both ends cross the bridge.
+//!
+//! Listeners tell connected Forwarders which event types their Publisher has handlers for, and
+//! send them the changes whenever that set changes, so Forwarders only serialize and send events
+//! that the remote side wants. Until a Forwarder hears from its Listener it sends every
+//! registered event.
+//!
+//! Listeners also grant their Forwarders credit for a window of events, and grant more as they
+//! publish them, so a Forwarder whose Listener is falling behind pauses rather than filling the
+//! connection's buffers. The window is set with `Listener::set_window`.
+//!
+//! A Forwarder created `with_snapshot` keeps the latest event of each registered type, or of each
+//! partition key for partitioned events, and streams them over every new connection before any
+//! live events, so that the remote side starts from the current state without replaying history.
+//!
+//! A Forwarder created `with_batching` holds the events it sends back and writes them in one go,
+//! once enough have built up or the first of them has waited for the linger time, trading a
+//! little latency for fewer writes when events are small and frequent.
+//!
+//! A Forwarder can be given a secondary remote to connect to when its own can't be reached, and
+//! an outage buffer to keep the events it fails to send in, which are sent ahead of the next event
+//! once it's connected again. Subscribed with `Publisher::forward`, it also publishes BridgeDown
+//! when it loses its connection or fails to connect, and BridgeRestored once it's connected again.
+//!
+//! Each end of a connection says which Publisher it belongs to, and Listeners mark the events they
+//! publish with the Publisher they came from. A Forwarder subscribed with `Publisher::forward`
+//! relays events received from other processes, but not to the Publisher they came from, and a
+//! Listener drops any that are sent back to its own Publisher anyway, so events published on one
+//! side of a bidirectional bridge aren't echoed back to it. Forwarders subscribed with `subscribe`
+//! don't forward received events at all.
+//!
+//! Every event a Forwarder sends is numbered. A Listener given a hold time with
+//! `Listener::set_reordering` holds events that arrive ahead of an earlier one from the same
+//! Forwarder, such as when a reconnecting Forwarder's new connection overtakes its old one, and
+//! publishes them in order once the gap is filled. Events are held for at most the hold time, after
+//! which the missing ones are given up on.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque, hash_map::RandomState},
+    error::Error,
+    hash::BuildHasher,
+    io::{self, Read, Write},
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+    },
+    panic::RefUnwindSafe,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CorruptRecordSkipped, DynEvent, DynHandle, Event, EventRegistry, EventTooLarge,
+    SerializedEvent, serialize::checksum,
+};
+
+/// Largest frame, in bytes, that will be sent or accepted, and the largest event a Forwarder sends
+/// unless set lower with `Forwarder::with_max_event_size`. Larger events are dropped by the
+/// Forwarder, and a connection that announces a larger frame is closed.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long a Forwarder waits to connect to its remote before giving up on an event
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a Forwarder waits for credit from its Listener before dropping an event
+const CREDIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of events a Listener grants each connection credit for, unless set with
+/// `Listener::set_window`
+pub const DEFAULT_WINDOW: usize = 1024;
+
+/// How often a Listener checks whether the event types its Publisher wants have changed
+const INTEREST_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Type tag of the frames a Listener sends its Forwarders to update which event types it wants.
+/// It starts with a NUL so that it can't clash with the tag of a real event type.
+const INTEREST_TAG: &str = "\0interest";
+
+/// Type tag of the frames a Listener sends its Forwarders to grant them credit for more events.
+/// The payload is the number of events granted, as a big-endian u32.
+const CREDIT_TAG: &str = "\0credit";
+
+/// Type tag of the frame a Forwarder sends ahead of each event, carrying the ID of the Forwarder
+/// and the event's number, as big-endian u64s, followed by the ID of the Publisher the event was
+/// received from, if it was received from one over a bridge
+const SEQUENCE_TAG: &str = "\0sequence";
+
+/// Type tag of the frame each end of a connection sends first, carrying the ID of the Publisher it
+/// belongs to as a big-endian u64. Forwarders only send it once subscribed with
+/// `Publisher::forward`, since until then they don't know their Publisher.
+const PEER_TAG: &str = "\0peer";
+
+/// Identifies a Publisher to the Publishers it's bridged to, so that events aren't sent back to
+/// the Publisher they came from. Random, so that Publishers in different processes don't share one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PeerId(pub(crate) u64);
+
+impl Default for PeerId {
+    fn default() -> Self {
+        PeerId(RandomState::new().hash_one((std::process::id(), SystemTime::now())))
+    }
+}
+
+/// Published by a Forwarder subscribed with `Publisher::forward` when it loses its connection to
+/// its remote, or fails to connect to it, and hasn't already said so since it was last connected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeDown {
+    /// Why the connection was lost or couldn't be made
+    pub error: String,
+}
+
+impl Event for BridgeDown {}
+
+/// Published by a Forwarder subscribed with `Publisher::forward` once it has connected again
+/// after a BridgeDown
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeRestored {
+    /// The address connected to, which is the secondary remote's if its own couldn't be reached
+    pub addr: SocketAddr,
+}
+
+impl Event for BridgeRestored {}
+
+/// Publishes the events a Forwarder reports its connection's health with
+type HealthHook = dyn Fn(Box<dyn DynEvent>) + Send + Sync;
+
+/// Change to the set of event types a Listener wants, sent as the payload of an interest frame
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct InterestUpdate {
+    add: Vec<String>,
+    remove: Vec<String>,
+}
+
+/// Latest frame sent for each type tag and partition key, streamed to new connections
+type Snapshot = BTreeMap<(String, Option<u64>), Vec<u8>>;
+
+/// An event's frame, along with the Publisher the event was received from, if any
+type Outgoing = (Option<u64>, Vec<u8>);
+
+/// The event types a Forwarder's Listener wants, and the events it has room for, as last heard
+/// over its current connection
+#[derive(Default)]
+struct Interest {
+    /// Incremented on every new connection, so that updates from old ones are ignored
+    connection: u64,
+    /// None until the Listener has said what it wants
+    wanted: Option<HashSet<String>>,
+    /// Number of events the Listener has granted credit for. None until it grants any.
+    credit: Option<u64>,
+    /// The Publisher the Listener belongs to. None until it says.
+    peer: Option<u64>,
+}
+
+/// Events a batching Forwarder has numbered but not yet written
+#[derive(Default)]
+struct Pending {
+    /// The sequence and event frames, in the order they were sent
+    bytes: Vec<u8>,
+    /// The events in `bytes`, without their sequence frames, to keep if the batch can't be written
+    events: Vec<Outgoing>,
+    /// When the first event was added. None while the batch is empty.
+    since: Option<Instant>,
+    /// Events in batches that lingered but couldn't be written, oldest first, and why, for the
+    /// next send to buffer and report
+    failed: Option<(io::Error, Vec<Outgoing>)>,
+    /// Set when the Forwarder is dropped, to stop the thread that flushes the batch
+    stopped: bool,
+}
+
+/// How a Forwarder batches its events, and the batch being built
+struct Batch {
+    /// Size, in bytes, at which the batch is written straight away
+    max_bytes: usize,
+    /// How long the first event in the batch waits before it's written anyway
+    linger: Duration,
+    pending: Mutex<Pending>,
+    /// Notified when an event is added to an empty batch, or the Forwarder is dropped
+    added: Condvar,
+}
+
+impl Batch {
+    /// Write each batch once its first event has waited for the linger time, until the Forwarder
+    /// is dropped. Batches are only ever written to a connection the Forwarder already made, and
+    /// are left for the next send to deal with if it has since been lost.
+    fn flush_after_linger(&self, stream: &Mutex<Option<TcpStream>>) {
+        loop {
+            let mut pending = self.pending.lock().expect("Batch mutex poisoned");
+            loop {
+                if pending.stopped {
+                    return;
+                }
+                let Some(since) = pending.since else {
+                    pending = self.added.wait(pending).expect("Batch mutex poisoned");
+                    continue;
+                };
+                let wait = (since + self.linger).saturating_duration_since(Instant::now());
+                if wait.is_zero() {
+                    break;
+                }
+                (pending, _) = self
+                    .added
+                    .wait_timeout(pending, wait)
+                    .expect("Batch mutex poisoned");
+            }
+            drop(pending);
+
+            // the connection is locked before the batch, like when sending, so that a batch
+            // written by a sender in the meantime can't be overtaken
+            let mut stream = stream.lock().expect("Forwarder mutex poisoned");
+            let (bytes, events) = self.take();
+            if bytes.is_empty() {
+                continue;
+            }
+            let written = match stream.as_mut() {
+                Some(connected) => connected.write_all(&bytes),
+                None => Err(io::Error::from(io::ErrorKind::NotConnected)),
+            };
+            if let Err(e) = written {
+                *stream = None;
+                let mut pending = self.pending.lock().expect("Batch mutex poisoned");
+                match pending.failed.as_mut() {
+                    Some((_, failed)) => failed.extend(events),
+                    None => pending.failed = Some((e, events)),
+                }
+            }
+        }
+    }
+
+    /// Add sequenced events to the batch, returning the whole batch to be written if it has grown
+    /// large enough
+    fn add(
+        &self,
+        sequenced: &[u8],
+        events: impl IntoIterator<Item = Outgoing>,
+    ) -> Option<(Vec<u8>, Vec<Outgoing>)> {
+        let mut pending = self.pending.lock().expect("Batch mutex poisoned");
+        pending.bytes.extend_from_slice(sequenced);
+        pending.events.extend(events);
+        if pending.bytes.len() < self.max_bytes {
+            if pending.since.is_none() {
+                pending.since = Some(Instant::now());
+                self.added.notify_all();
+            }
+            return None;
+        }
+        drop(pending);
+        Some(self.take())
+    }
+
+    /// Empty the batch, returning what was in it
+    fn take(&self) -> (Vec<u8>, Vec<Outgoing>) {
+        let mut pending = self.pending.lock().expect("Batch mutex poisoned");
+        pending.since = None;
+        (
+            std::mem::take(&mut pending.bytes),
+            std::mem::take(&mut pending.events),
+        )
+    }
+
+    /// Events in batches that lingered but couldn't be written, and why
+    fn take_failed(&self) -> Option<(io::Error, Vec<Outgoing>)> {
+        let mut pending = self.pending.lock().expect("Batch mutex poisoned");
+        pending.failed.take()
+    }
+}
+
+/// Handler that sends every event whose type is registered with its EventRegistry to a remote
+/// `Listener`. Connects on the first event and reconnects whenever the connection is lost.
+/// Events that can't be sent while the remote is unreachable are dropped, unless kept in an
+/// outage buffer.
+///
+/// Events that were themselves received from a remote are only forwarded by Forwarders subscribed
+/// with `Publisher::forward`, and never back to the Publisher they were received from, so two
+/// processes can forward to each other without events bouncing back and forth.
+pub struct Forwarder {
+    addrs: Vec<SocketAddr>,
+    /// Tried in turn when none of `addrs` can be connected to
+    secondary: Vec<SocketAddr>,
+    registry: Arc<EventRegistry>,
+    stream: Arc<Mutex<Option<TcpStream>>>,
+    interest: Arc<Mutex<Interest>>,
+    /// Notified whenever the Listener grants more credit
+    granted: Arc<Condvar>,
+    snapshot: Option<Mutex<Snapshot>>,
+    /// Tells this Forwarder's events apart from those of other Forwarders to the same Listener
+    origin: u64,
+    /// The Publisher the Forwarder was subscribed to with `Publisher::forward`, if it was
+    peer: Option<PeerId>,
+    /// Number of the next event to send. Only advanced once an event has been written.
+    sequence: Mutex<u64>,
+    /// Largest serialized event, in bytes, that will be sent
+    max_event_size: usize,
+    batch: Option<Arc<Batch>>,
+    /// Writes batches that have waited for the linger time
+    flusher: Option<thread::JoinHandle<()>>,
+    /// Frames of the events that couldn't be sent, oldest first, to send ahead of the next event,
+    /// along with the Publisher each was received from
+    outage: Mutex<VecDeque<Outgoing>>,
+    /// Most events kept in the outage buffer. Zero drops events that can't be sent.
+    outage_capacity: usize,
+    /// Whether BridgeDown has been reported since the Forwarder was last connected
+    down: AtomicBool,
+    health: Option<Box<HealthHook>>,
+}
+
+impl RefUnwindSafe for Forwarder {}
+
+impl Forwarder {
+    /// Create a Forwarder that sends events to `addr`. The address is resolved straight away but
+    /// no connection is made until the first event is handled.
+    pub fn new(addr: impl ToSocketAddrs, registry: Arc<EventRegistry>) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "address resolved to nothing",
+            ));
+        }
+
+        Ok(Forwarder {
+            addrs,
+            secondary: Vec::new(),
+            registry,
+            stream: Arc::default(),
+            interest: Arc::default(),
+            granted: Arc::default(),
+            snapshot: None,
+            origin: RandomState::new().hash_one((std::process::id(), SystemTime::now())),
+            peer: None,
+            sequence: Mutex::new(0),
+            max_event_size: MAX_FRAME_SIZE,
+            batch: None,
+            flusher: None,
+            outage: Mutex::default(),
+            outage_capacity: 0,
+            down: AtomicBool::new(false),
+            health: None,
+        })
+    }
+
+    /// Keep the latest event of each registered type, or of each partition key for partitioned
+    /// events, including those that couldn't be sent, and send them over every new connection
+    /// before any live events
+    pub fn with_snapshot(mut self) -> Self {
+        self.snapshot = Some(Mutex::default());
+        self
+    }
+
+    /// Drop events that serialize to more than `bytes`, rather than sending them, failing with
+    /// EventTooLarge. Can't be set higher than MAX_FRAME_SIZE.
+    pub fn with_max_event_size(mut self, bytes: usize) -> Self {
+        self.max_event_size = bytes.min(MAX_FRAME_SIZE);
+        self
+    }
+
+    /// Connect to `addr` when the remote the Forwarder was created with can't be reached. Each new
+    /// connection tries the Forwarder's own remote first.
+    pub fn with_secondary(mut self, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        self.secondary = addr.to_socket_addrs()?.collect();
+        Ok(self)
+    }
+
+    /// Keep up to `events` of the events that can't be sent, dropping the oldest once it's full,
+    /// and send them ahead of the next event once the Forwarder is connected again, as many at a
+    /// time as the Listener has granted credit for
+    pub fn with_outage_buffer(mut self, events: usize) -> Self {
+        self.outage_capacity = events;
+        self
+    }
+
+    /// Report the connection's health by passing BridgeDown and BridgeRestored events to
+    /// `publish`. Called by `Publisher::forward`.
+    pub(crate) fn on_health(
+        &mut self,
+        publish: impl Fn(Box<dyn DynEvent>) + Send + Sync + 'static,
+    ) {
+        self.health = Some(Box::new(publish));
+    }
+
+    /// Send events received from other Publishers on, and tell the Listener which Publisher the
+    /// events come from. Called by `Publisher::forward`.
+    pub(crate) fn set_peer(&mut self, peer: PeerId) {
+        self.peer = Some(peer);
+    }
+
+    /// Hold events back and write them together, once they add up to `max_bytes` or the first of
+    /// them has waited for `linger`. Events in a batch that can't be written are handled like any
+    /// other event that can't be sent, although the outage is only noticed by the next event sent
+    /// if the batch was written after lingering.
+    pub fn with_batching(mut self, max_bytes: usize, linger: Duration) -> Self {
+        let batch = Arc::new(Batch {
+            max_bytes,
+            linger,
+            pending: Mutex::default(),
+            added: Condvar::new(),
+        });
+        let flusher = {
+            let batch = batch.clone();
+            let stream = self.stream.clone();
+            thread::Builder::new()
+                .name(String::from("crier-batch"))
+                .spawn(move || batch.flush_after_linger(&stream))
+                .expect("Failed to spawn batching thread")
+        };
+        self.batch = Some(batch);
+        self.flusher = Some(flusher);
+        self
+    }
+
+    /// Whether the Listener belongs to the Publisher `peer`, as far as the Forwarder knows. The
+    /// Listener also drops any events sent back to it before the Forwarder has heard.
+    fn is_remote(&self, peer: u64) -> bool {
+        let interest = self.interest.lock().expect("Interest mutex poisoned");
+        interest.peer == Some(peer)
+    }
+
+    /// Whether the Listener wants events with the given type tag, as far as the Forwarder knows
+    fn wants(&self, tag: &str) -> bool {
+        let interest = self.interest.lock().expect("Interest mutex poisoned");
+        interest
+            .wanted
+            .as_ref()
+            .is_none_or(|wanted| wanted.contains(tag))
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in self.addrs.iter().chain(&self.secondary) {
+            match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+                Ok(mut stream) => {
+                    stream.set_nodelay(true)?;
+                    if let Some(peer) = self.peer {
+                        stream.write_all(&peer_frame(peer))?;
+                    }
+                    self.watch_interest(stream.try_clone()?);
+                    return Ok(stream);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.expect("Forwarder has at least one address"))
+    }
+
+    /// Send an event's frame, along with the Publisher the event was received from, if any
+    fn send(&self, from: Option<u64>, frame: &[u8]) -> io::Result<()> {
+        // credit is taken before the connection is locked, so that waiting for it doesn't hold up
+        // other threads sending on this Forwarder, and handed back if the event isn't sent
+        let Some(credit) = self.take_credit() else {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no credit from the Listener",
+            ));
+        };
+        let mut health = Vec::new();
+        let sent = self.write_sequenced(from, frame, &mut health);
+        if sent.is_err() {
+            self.return_credit(credit, 1);
+        }
+        // published once the connection is unlocked, since their handlers may publish events
+        // that are forwarded in turn
+        if let Some(publish) = &self.health {
+            health.into_iter().for_each(publish);
+        }
+        sent
+    }
+
+    /// Number the event and write it, along with any events kept in the outage buffer, noting
+    /// changes to the connection's health in `health`. Buffered events need credit of their own,
+    /// so if there isn't enough for all of them, the event is kept in the buffer behind the ones
+    /// that are left.
+    fn write_sequenced(
+        &self,
+        from: Option<u64>,
+        frame: &[u8],
+        health: &mut Vec<Box<dyn DynEvent>>,
+    ) -> io::Result<()> {
+        let mut stream = self.stream.lock().expect("Forwarder mutex poisoned");
+        let mut sequence = self.sequence.lock().expect("Sequence mutex poisoned");
+        let mut outage = self.outage.lock().expect("Outage mutex poisoned");
+        if let Some((error, failed)) = self.batch.as_ref().and_then(|batch| batch.take_failed()) {
+            self.report_down(&error, health);
+            self.requeue(failed, &mut sequence, &mut outage);
+        }
+        // the credit taken for the event goes to the oldest buffered event instead when it can't
+        // be sent ahead of all of them
+        let (connection, spare) = self.take_spare_credit(outage.len());
+        let caught_up = spare == outage.len();
+        let replayed = if caught_up { spare } else { spare + 1 };
+        let mut sequenced = Vec::new();
+        let frames = outage
+            .iter()
+            .take(replayed)
+            .map(|(from, frame)| (*from, frame.as_slice()))
+            .chain(caught_up.then_some((from, frame)));
+        for (number, (from, frame)) in (*sequence..).zip(frames) {
+            let mut payload = self.origin.to_be_bytes().to_vec();
+            payload.extend_from_slice(&number.to_be_bytes());
+            if let Some(from) = from {
+                payload.extend_from_slice(&from.to_be_bytes());
+            }
+            sequenced.extend(
+                encode_frame(&SerializedEvent {
+                    tag: String::from(SEQUENCE_TAG),
+                    payload,
+                })
+                .expect("sequence frames are small"),
+            );
+            sequenced.extend_from_slice(frame);
+        }
+
+        let written = match &self.batch {
+            Some(_) => self.connect_for_batch(&mut stream, health),
+            None => self.write(&mut stream, &sequenced, health),
+        };
+        match written {
+            Ok(()) => {
+                *sequence += replayed as u64 + u64::from(caught_up);
+                let replayed: Vec<Outgoing> = outage.drain(..replayed).collect();
+                if !caught_up {
+                    self.keep(&mut outage, from, frame);
+                }
+                let Some(batch) = &self.batch else {
+                    return Ok(());
+                };
+                let events = replayed
+                    .into_iter()
+                    .chain(caught_up.then(|| (from, frame.to_vec())));
+                let Some((bytes, events)) = batch.add(&sequenced, events) else {
+                    return Ok(());
+                };
+                self.write(&mut stream, &bytes, health)
+                    .inspect_err(|_| self.requeue(events, &mut sequence, &mut outage))
+            }
+            Err(e) => {
+                self.return_credit(connection, spare as u64);
+                self.keep(&mut outage, from, frame);
+                Err(e)
+            }
+        }
+    }
+
+    /// Keep an event's frame in the outage buffer, if there is one, making room by dropping the
+    /// oldest event
+    fn keep(&self, outage: &mut VecDeque<Outgoing>, from: Option<u64>, frame: &[u8]) {
+        if self.outage_capacity > 0 {
+            if outage.len() >= self.outage_capacity {
+                outage.pop_front();
+            }
+            outage.push_back((from, frame.to_vec()));
+        }
+    }
+
+    /// Put the events of a batch that couldn't be written back in the outage buffer, ahead of the
+    /// events already in it, and take back their sequence numbers so that the Listener doesn't
+    /// see a gap. The oldest events are dropped if there isn't room for them all.
+    fn requeue(&self, failed: Vec<Outgoing>, sequence: &mut u64, outage: &mut VecDeque<Outgoing>) {
+        // a failed batch holds the latest numbered events, since the connection is locked from
+        // the time they are numbered until the batch is written
+        *sequence -= failed.len() as u64;
+        for event in failed.into_iter().rev() {
+            outage.push_front(event);
+        }
+        while outage.len() > self.outage_capacity {
+            outage.pop_front();
+        }
+    }
+
+    /// Connect, if the Forwarder isn't already, before events are added to the batch, so that the
+    /// thread writing batches that have lingered never has to connect
+    fn connect_for_batch(
+        &self,
+        stream: &mut Option<TcpStream>,
+        health: &mut Vec<Box<dyn DynEvent>>,
+    ) -> io::Result<()> {
+        if stream.is_none() {
+            let mut connected = self.reconnect(health)?;
+            if let Err(e) = self.send_snapshot(&mut connected) {
+                self.report_down(&e, health);
+                return Err(e);
+            }
+            *stream = Some(connected);
+        }
+        Ok(())
+    }
+
+    /// Write to the connection, connecting first if there isn't one
+    fn write(
+        &self,
+        stream: &mut Option<TcpStream>,
+        bytes: &[u8],
+        health: &mut Vec<Box<dyn DynEvent>>,
+    ) -> io::Result<()> {
+        // a connection the remote has closed is usually only noticed when writing to it, so retry
+        // once on a fresh connection
+        let mut last_error = None;
+        for _ in 0..2 {
+            let fresh = stream.is_none();
+            let connected = match stream.as_mut() {
+                Some(connected) => connected,
+                None => stream.insert(self.reconnect(health)?),
+            };
+            let written = if fresh {
+                self.send_snapshot(connected)
+            } else {
+                Ok(())
+            };
+            match written.and_then(|()| connected.write_all(bytes)) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    *stream = None;
+                    self.report_down(&e, health);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("send was attempted"))
+    }
+
+    /// Connect, noting in `health` whether the connection was restored or couldn't be made
+    fn reconnect(&self, health: &mut Vec<Box<dyn DynEvent>>) -> io::Result<TcpStream> {
+        match self.connect() {
+            Ok(stream) => {
+                if self.down.swap(false, Ordering::SeqCst)
+                    && let Ok(addr) = stream.peer_addr()
+                {
+                    health.push(Box::new(BridgeRestored { addr }));
+                }
+                Ok(stream)
+            }
+            Err(e) => {
+                self.report_down(&e, health);
+                Err(e)
+            }
+        }
+    }
+
+    /// Note in `health` that the connection is down, unless that has already been reported
+    fn report_down(&self, error: &io::Error, health: &mut Vec<Box<dyn DynEvent>>) {
+        if !self.down.swap(true, Ordering::SeqCst) {
+            health.push(Box::new(BridgeDown {
+                error: error.to_string(),
+            }));
+        }
+    }
+
+    /// Use up credit for one event, waiting for the Listener to grant more if it has run out.
+    /// Returns the connection the credit was granted over, or None if none was granted in time.
+    fn take_credit(&self) -> Option<u64> {
+        let interest = self.interest.lock().expect("Interest mutex poisoned");
+        let (mut interest, _) = self
+            .granted
+            .wait_timeout_while(interest, CREDIT_TIMEOUT, |interest| {
+                interest.credit == Some(0)
+            })
+            .expect("Interest mutex poisoned");
+        match interest.credit.as_mut() {
+            Some(0) => return None,
+            Some(credit) => *credit -= 1,
+            None => {}
+        }
+        Some(interest.connection)
+    }
+
+    /// Use up whatever credit there is for as many as `events` events without waiting for more.
+    /// Returns the connection the credit was granted over and the number of events it covers.
+    fn take_spare_credit(&self, events: usize) -> (u64, usize) {
+        let mut interest = self.interest.lock().expect("Interest mutex poisoned");
+        let taken = match interest.credit.as_mut() {
+            Some(credit) => {
+                let taken = (*credit).min(events as u64);
+                *credit -= taken;
+                taken as usize
+            }
+            None => events,
+        };
+        (interest.connection, taken)
+    }
+
+    /// Hand back credit taken for `events` events that weren't sent, unless it was granted over a
+    /// connection that has since been replaced, whose credit no longer counts
+    fn return_credit(&self, connection: u64, events: u64) {
+        let mut interest = self.interest.lock().expect("Interest mutex poisoned");
+        if interest.connection != connection || events == 0 {
+            return;
+        }
+        if let Some(credit) = interest.credit.as_mut() {
+            *credit += events;
+            self.granted.notify_all();
+        }
+    }
+
+    /// Send the latest frames kept for the snapshot, if there is one, over a new connection
+    fn send_snapshot(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let Some(snapshot) = &self.snapshot else {
+            return Ok(());
+        };
+        let snapshot = snapshot.lock().expect("Snapshot mutex poisoned");
+        snapshot
+            .values()
+            .try_for_each(|frame| stream.write_all(frame))
+    }
+
+    /// Apply the interest updates and credit the Listener sends over a new connection until it
+    /// closes. Corrupt frames are skipped, while anything else that can't be read closes the
+    /// connection, so that the next send reconnects rather than sending without credit.
+    fn watch_interest(&self, mut stream: TcpStream) {
+        let connection = {
+            let mut interest = self.interest.lock().expect("Interest mutex poisoned");
+            interest.connection += 1;
+            interest.wanted = None;
+            interest.credit = None;
+            interest.peer = None;
+            interest.connection
+        };
+        let interest = self.interest.clone();
+        let granted = self.granted.clone();
+        let _ = thread::Builder::new()
+            .name(String::from("crier-interest"))
+            .spawn(move || {
+                loop {
+                    let frame = match read_frame(&mut stream) {
+                        Ok(Some(Ok(frame))) => frame,
+                        Ok(Some(Err(_))) => continue,
+                        Ok(None) | Err(_) => break,
+                    };
+                    let mut interest = interest.lock().expect("Interest mutex poisoned");
+                    if interest.connection != connection {
+                        return;
+                    }
+                    match frame.tag.as_str() {
+                        INTEREST_TAG => {
+                            let Ok(update) =
+                                serde_json::from_slice::<InterestUpdate>(&frame.payload)
+                            else {
+                                continue;
+                            };
+                            let wanted = interest.wanted.get_or_insert_default();
+                            for tag in update.remove {
+                                wanted.remove(&tag);
+                            }
+                            wanted.extend(update.add);
+                        }
+                        CREDIT_TAG => {
+                            let Ok(more) = <[u8; 4]>::try_from(frame.payload.as_slice()) else {
+                                continue;
+                            };
+                            let credit = interest.credit.get_or_insert_default();
+                            *credit += u64::from(u32::from_be_bytes(more));
+                            granted.notify_all();
+                        }
+                        PEER_TAG => {
+                            if let Ok(peer) = <[u8; 8]>::try_from(frame.payload.as_slice()) {
+                                interest.peer = Some(u64::from_be_bytes(peer));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                // stop waiting for credit that won't come, so that the next send finds out the
+                // connection has gone and reconnects
+                let _ = stream.shutdown(Shutdown::Both);
+                let mut interest = interest.lock().expect("Interest mutex poisoned");
+                if interest.connection == connection {
+                    interest.credit = None;
+                    granted.notify_all();
+                }
+            });
+    }
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        if let Some(batch) = &self.batch {
+            if let Ok(mut pending) = batch.pending.lock() {
+                pending.stopped = true;
+                batch.added.notify_all();
+            }
+            if let Some(flusher) = self.flusher.take() {
+                let _ = flusher.join();
+            }
+        }
+
+        if let Ok(Some(stream)) = self.stream.lock().as_deref_mut() {
+            // what's left of the batch is still sent
+            if let Some(batch) = &self.batch {
+                let _ = stream.write_all(&batch.take().0);
+            }
+            // also ends the thread watching for interest updates
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl DynHandle for Forwarder {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.accepts(event) {
+            return Ok(());
+        }
+        let Ok(serialized) = self.registry.serialize(event) else {
+            return Ok(());
+        };
+        EventTooLarge::check("net", &serialized, self.max_event_size)?;
+        if let Some(frame) = encode_frame(&serialized) {
+            if self.wants(&serialized.tag) {
+                let from = event.metadata().and_then(|metadata| metadata.peer);
+                let _ = self.send(from, &frame);
+            }
+            // kept after sending, so that a fresh connection doesn't receive the event twice
+            if let Some(snapshot) = &self.snapshot {
+                snapshot
+                    .lock()
+                    .expect("Snapshot mutex poisoned")
+                    .insert((serialized.tag, event.dyn_partition_key()), frame);
+            }
+        }
+        Ok(())
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        if let Some(metadata) = event.metadata()
+            && metadata.remote
+            && (self.peer.is_none() || metadata.peer.is_some_and(|from| self.is_remote(from)))
+        {
+            return false;
+        }
+        // events the Listener doesn't want are still kept for the snapshot
+        self.registry
+            .tag_of(event)
+            .is_some_and(|tag| self.snapshot.is_some() || self.wants(tag))
+    }
+
+    fn accepts_remote(&self) -> bool {
+        self.peer.is_some()
+    }
+}
+
+/// Accepts connections from remote Forwarders and publishes the events they send. Created by
+/// `Publisher::listen`. Stops listening and closes its connections when dropped.
+pub struct Listener {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    connections: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    window: Arc<AtomicUsize>,
+    reorder: Arc<Reorder>,
+    thread: Option<thread::JoinHandle<()>>,
+    interest_thread: Option<thread::JoinHandle<()>>,
+    reorder_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Listener {
+    /// Listen on `addr` for the Publisher `peer`, passing every event received to `publish`
+    /// along with the Publisher it came from, if known, until it returns false. Connections are
+    /// sent updates to the tags `interest` returns until it returns None.
+    pub(crate) fn bind<F, I>(
+        addr: impl ToSocketAddrs,
+        registry: Arc<EventRegistry>,
+        peer: PeerId,
+        publish: F,
+        interest: I,
+    ) -> io::Result<Self>
+    where
+        F: Fn(Box<dyn DynEvent>, Option<PeerId>) -> bool + Send + Sync + 'static,
+        I: Fn() -> Option<HashSet<String>> + Send + 'static,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let window = Arc::new(AtomicUsize::new(DEFAULT_WINDOW));
+        let reorder = Arc::new(Reorder::default());
+        // events sent back to the Listener's own Publisher are dropped here, after reordering,
+        // so that they still take their place in their Forwarder's sequence
+        let publish = Arc::new(move |received: Received| {
+            received.from == Some(peer.0) || publish(received.event, received.sender)
+        });
+
+        let thread = {
+            let stopped = stopped.clone();
+            let connections = connections.clone();
+            let window = window.clone();
+            let reorder = reorder.clone();
+            let publish = publish.clone();
+            thread::Builder::new()
+                .name(String::from("crier-listener"))
+                .spawn(move || {
+                    accept(
+                        listener,
+                        &stopped,
+                        &connections,
+                        &window,
+                        &reorder,
+                        &registry,
+                        &publish,
+                    )
+                })?
+        };
+        let reorder_thread = {
+            let stopped = stopped.clone();
+            let reorder = reorder.clone();
+            thread::Builder::new()
+                .name(String::from("crier-reorder"))
+                .spawn(move || reorder.release_expired(&stopped, publish.as_ref()))?
+        };
+        let interest_thread = {
+            let stopped = stopped.clone();
+            let connections = connections.clone();
+            thread::Builder::new()
+                .name(String::from("crier-interest"))
+                .spawn(move || send_interest(peer, &stopped, &connections, interest))?
+        };
+
+        Ok(Listener {
+            local_addr,
+            stopped,
+            connections,
+            window,
+            reorder,
+            thread: Some(thread),
+            interest_thread: Some(interest_thread),
+            reorder_thread: Some(reorder_thread),
+        })
+    }
+
+    /// The address the Listener is bound to. Useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Set how many events each Forwarder may send before it has to wait for the Listener to
+    /// publish some of them. Takes effect as connections are next granted credit.
+    pub fn set_window(&self, events: usize) {
+        self.window.store(events.max(1), Ordering::SeqCst);
+    }
+
+    /// Hold events that arrive ahead of earlier events from the same Forwarder for up to `hold`,
+    /// publishing them in order once the earlier ones arrive. Zero, the default, publishes events
+    /// as they arrive.
+    pub fn set_reordering(&self, hold: Duration) {
+        let mut held = self.reorder.held.lock().expect("Reorder mutex poisoned");
+        held.set_hold(hold);
+        self.reorder.changed.notify_all();
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Ok(connections) = self.connections.lock() {
+            for connection in connections.values() {
+                let _ = connection.shutdown(Shutdown::Both);
+            }
+        }
+
+        // wake the accept loop so that it sees it has been stopped
+        let mut wake = self.local_addr;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(match wake.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake, CONNECT_TIMEOUT);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if let Some(thread) = self.interest_thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+        if let Some(thread) = self.reorder_thread.take() {
+            // notified under the lock, so that the thread can't miss it between checking whether
+            // it has been stopped and waiting
+            if let Ok(_held) = self.reorder.held.lock() {
+                self.reorder.changed.notify_all();
+            }
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Send each connection the changes to the event types the Publisher `peer` wants, until the
+/// Listener is stopped or the Publisher is gone. New connections are first told the Publisher's
+/// ID.
+fn send_interest<I>(
+    peer: PeerId,
+    stopped: &AtomicBool,
+    connections: &Mutex<HashMap<u64, TcpStream>>,
+    interest: I,
+) where
+    I: Fn() -> Option<HashSet<String>>,
+{
+    // what each connection has been told so far
+    let mut sent: HashMap<u64, HashSet<String>> = HashMap::new();
+    while !stopped.load(Ordering::SeqCst) {
+        let Some(wanted) = interest() else {
+            break;
+        };
+        let Ok(connections) = connections.lock() else {
+            break;
+        };
+        sent.retain(|id, _| connections.contains_key(id));
+        for (id, mut connection) in connections.iter() {
+            let told = sent.get(id);
+            if told == Some(&wanted) {
+                continue;
+            }
+            if told.is_none() && connection.write_all(&peer_frame(peer)).is_err() {
+                continue;
+            }
+            let told = told.cloned().unwrap_or_default();
+            let update = InterestUpdate {
+                add: wanted.difference(&told).cloned().collect(),
+                remove: told.difference(&wanted).cloned().collect(),
+            };
+            let frame = serde_json::to_vec(&update).ok().and_then(|payload| {
+                encode_frame(&SerializedEvent {
+                    tag: String::from(INTEREST_TAG),
+                    payload,
+                })
+            });
+            if let Some(frame) = frame
+                && connection.write_all(&frame).is_ok()
+            {
+                sent.insert(*id, wanted.clone());
+            }
+        }
+        drop(connections);
+
+        thread::park_timeout(INTEREST_INTERVAL);
+    }
+}
+
+fn accept<F>(
+    listener: TcpListener,
+    stopped: &AtomicBool,
+    connections: &Arc<Mutex<HashMap<u64, TcpStream>>>,
+    window: &Arc<AtomicUsize>,
+    reorder: &Arc<Reorder>,
+    registry: &Arc<EventRegistry>,
+    publish: &Arc<F>,
+) where
+    F: Fn(Received) -> bool + Send + Sync + 'static,
+{
+    let mut next_id = 0;
+    for stream in listener.incoming() {
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let Ok(clone) = stream.try_clone() else {
+            continue;
+        };
+
+        let id = next_id;
+        next_id += 1;
+        connections
+            .lock()
+            .expect("Listener mutex poisoned")
+            .insert(id, clone);
+
+        let spawned = {
+            let connections = connections.clone();
+            let window = window.clone();
+            let reorder = reorder.clone();
+            let registry = registry.clone();
+            let publish = publish.clone();
+            thread::Builder::new()
+                .name(String::from("crier-connection"))
+                .spawn(move || {
+                    let credit = Credit {
+                        connections: &connections,
+                        id,
+                        window: &window,
+                    };
+                    receive(stream, &registry, publish.as_ref(), &credit, &reorder);
+                    if let Ok(mut connections) = connections.lock() {
+                        connections.remove(&id);
+                    }
+                })
+        };
+        if spawned.is_err()
+            && let Ok(mut connections) = connections.lock()
+        {
+            connections.remove(&id);
+        }
+    }
+}
+
+/// Grants a single connection credit for the events it sends
+struct Credit<'a> {
+    connections: &'a Mutex<HashMap<u64, TcpStream>>,
+    id: u64,
+    window: &'a AtomicUsize,
+}
+
+impl Credit<'_> {
+    fn window(&self) -> usize {
+        self.window.load(Ordering::SeqCst)
+    }
+
+    /// Grant credit for `events` more events. Written under the connections lock, so that it
+    /// can't be interleaved with an interest update.
+    fn grant(&self, events: usize) {
+        let events = u32::try_from(events).unwrap_or(u32::MAX);
+        let Some(frame) = encode_frame(&SerializedEvent {
+            tag: String::from(CREDIT_TAG),
+            payload: events.to_be_bytes().to_vec(),
+        }) else {
+            return;
+        };
+        if let Ok(connections) = self.connections.lock()
+            && let Some(mut connection) = connections.get(&self.id)
+        {
+            let _ = connection.write_all(&frame);
+        }
+    }
+}
+
+/// An event received by a Listener
+struct Received {
+    event: Box<dyn DynEvent>,
+    /// The Publisher the Forwarder belongs to, if it has said
+    sender: Option<PeerId>,
+    /// The Publisher the Forwarder received the event from, if any
+    from: Option<u64>,
+}
+
+/// Publish events from a single connection until it closes, sends something invalid, or the
+/// Publisher is gone. More credit is granted each time half a window of events has been handled.
+fn receive(
+    mut stream: TcpStream,
+    registry: &EventRegistry,
+    publish: &dyn Fn(Received) -> bool,
+    credit: &Credit,
+    reorder: &Reorder,
+) {
+    credit.grant(credit.window());
+    let mut handled = 0;
+    let mut sender = None;
+    // origin and number of the next event, and the Publisher it was received from, sent ahead of
+    // it
+    let mut position = None;
+    while let Ok(Some(frame)) = read_frame(&mut stream) {
+        if let Ok(frame) = &frame {
+            match frame.tag.as_str() {
+                SEQUENCE_TAG => {
+                    position = read_sequence(&frame.payload);
+                    continue;
+                }
+                PEER_TAG => {
+                    if let Ok(peer) = <[u8; 8]>::try_from(frame.payload.as_slice()) {
+                        sender = Some(PeerId(u64::from_be_bytes(peer)));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        let position = position.take();
+        let event = match frame {
+            // events of types this side hasn't registered are skipped rather than ending the
+            // connection, so that both sides don't have to be upgraded at the same time
+            Ok(serialized) => registry.deserialize(&serialized).ok(),
+            // the length was intact, so the connection can carry on with the next frame
+            Err(corrupt) => Some(Box::new(corrupt) as Box<dyn DynEvent>),
+        };
+        let published = match (event, position) {
+            (Some(event), Some((origin, sequence, from))) => {
+                let received = Received {
+                    event,
+                    sender,
+                    from,
+                };
+                reorder.publish(origin, sequence, received, publish)
+            }
+            (Some(event), None) => publish(Received {
+                event,
+                sender,
+                from: None,
+            }),
+            (None, _) => true,
+        };
+        if !published {
+            break;
+        }
+
+        handled += 1;
+        if handled >= credit.window().div_ceil(2) {
+            credit.grant(handled);
+            handled = 0;
+        }
+    }
+}
+
+/// Events held by a Listener until the events before them from the same Forwarder arrive
+#[derive(Default)]
+struct Reorder {
+    held: Mutex<ReorderBuffer<Received>>,
+    /// Notified whenever events are held, the hold time changes, or the Listener is stopped
+    changed: Condvar,
+}
+
+impl Reorder {
+    /// Publish an event numbered `sequence` by the Forwarder `origin`, along with any held events
+    /// it releases, or hold it if events before it are missing. Returns false once the Publisher
+    /// is gone.
+    fn publish(
+        &self,
+        origin: u64,
+        sequence: u64,
+        received: Received,
+        publish: &dyn Fn(Received) -> bool,
+    ) -> bool {
+        let mut held = self.held.lock().expect("Reorder mutex poisoned");
+        if held.hold.is_zero() {
+            drop(held);
+            return publish(received);
+        }
+
+        let ready = held.push(origin, sequence, received, Instant::now());
+        self.changed.notify_all();
+        // published under the lock, so that events released from different connections can't
+        // overtake each other
+        ready.into_iter().all(publish)
+    }
+
+    /// Publish events that have been held for the hold time, giving up on the events missing
+    /// before them, until the Listener is stopped or the Publisher is gone
+    fn release_expired(&self, stopped: &AtomicBool, publish: &dyn Fn(Received) -> bool) {
+        let mut held = self.held.lock().expect("Reorder mutex poisoned");
+        while !stopped.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            if !held.expire(now).into_iter().all(publish) {
+                return;
+            }
+            held = match held.deadline() {
+                Some(deadline) => {
+                    let wait = deadline.saturating_duration_since(now);
+                    let (held, _) = self
+                        .changed
+                        .wait_timeout(held, wait)
+                        .expect("Reorder mutex poisoned");
+                    held
+                }
+                None => self.changed.wait(held).expect("Reorder mutex poisoned"),
+            };
+        }
+    }
+}
+
+/// Events held back until the events numbered before them from the same origin arrive, or until
+/// they have been held for the hold time
+struct ReorderBuffer<T> {
+    /// Zero turns reordering off
+    hold: Duration,
+    origins: HashMap<u64, Sequenced<T>>,
+}
+
+/// Events held for a single origin
+struct Sequenced<T> {
+    /// Number of the next event to release
+    next: u64,
+    /// When each held event arrived, by its number
+    held: BTreeMap<u64, (Instant, T)>,
+}
+
+impl<T> Default for ReorderBuffer<T> {
+    fn default() -> Self {
+        ReorderBuffer {
+            hold: Duration::ZERO,
+            origins: HashMap::new(),
+        }
+    }
+}
+
+impl<T> ReorderBuffer<T> {
+    fn set_hold(&mut self, hold: Duration) {
+        // while reordering was off events weren't counted, so start counting afresh
+        if self.hold.is_zero() {
+            self.origins
+                .retain(|_, sequenced| !sequenced.held.is_empty());
+        }
+        self.hold = hold;
+    }
+
+    /// Hold an event, returning the events it releases in order. The first event from an origin
+    /// is released straight away, as are events that arrive after the events following them have
+    /// been released, since they can no longer be put in order.
+    fn push(&mut self, origin: u64, sequence: u64, item: T, now: Instant) -> Vec<T> {
+        let sequenced = self.origins.entry(origin).or_insert_with(|| Sequenced {
+            next: sequence,
+            held: BTreeMap::new(),
+        });
+        if sequence < sequenced.next {
+            return vec![item];
+        }
+
+        sequenced.held.insert(sequence, (now, item));
+        let mut ready = Vec::new();
+        sequenced.release(&mut ready);
+        ready
+    }
+
+    /// Release events that have been held for the hold time, along with the events before and
+    /// after them that are no longer waiting on a missing event
+    fn expire(&mut self, now: Instant) -> Vec<T> {
+        let mut ready = Vec::new();
+        for sequenced in self.origins.values_mut() {
+            let expired = sequenced
+                .held
+                .iter()
+                .filter(|(_, (arrived, _))| now.saturating_duration_since(*arrived) >= self.hold)
+                .map(|(sequence, _)| *sequence)
+                .max();
+            if let Some(last) = expired {
+                sequenced.next = last;
+                while let Some(entry) = sequenced.held.first_entry()
+                    && *entry.key() < last
+                {
+                    ready.push(entry.remove().1);
+                }
+                sequenced.release(&mut ready);
+            }
+        }
+        ready
+    }
+
+    /// When the next held event expires
+    fn deadline(&self) -> Option<Instant> {
+        self.origins
+            .values()
+            .flat_map(|sequenced| sequenced.held.values())
+            .map(|(arrived, _)| *arrived + self.hold)
+            .min()
+    }
+}
+
+impl<T> Sequenced<T> {
+    /// Release held events for as long as they follow on from the last one released
+    fn release(&mut self, ready: &mut Vec<T>) {
+        while let Some(entry) = self.held.first_entry()
+            && *entry.key() == self.next
+        {
+            ready.push(entry.remove().1);
+            self.next += 1;
+        }
+    }
+}
+
+/// Read the payload of a sequence frame
+fn read_sequence(payload: &[u8]) -> Option<(u64, u64, Option<u64>)> {
+    let (origin, rest) = payload.split_first_chunk::<8>()?;
+    let (sequence, from) = rest.split_first_chunk::<8>()?;
+    let from = match from {
+        [] => None,
+        from => Some(u64::from_be_bytes(<[u8; 8]>::try_from(from).ok()?)),
+    };
+    Some((
+        u64::from_be_bytes(*origin),
+        u64::from_be_bytes(*sequence),
+        from,
+    ))
+}
+
+/// Frame announcing the Publisher one end of a connection belongs to
+fn peer_frame(peer: PeerId) -> Vec<u8> {
+    encode_frame(&SerializedEvent {
+        tag: String::from(PEER_TAG),
+        payload: peer.0.to_be_bytes().to_vec(),
+    })
+    .expect("peer frames are small")
+}
+
+/// Frames are the length of the rest of the frame as a big-endian u32, followed by the CRC-32 of
+/// the rest of the frame as a big-endian u32, the length of the type tag as a big-endian u16, the
+/// tag, and then the payload. Returns None if the event is too large to send.
+fn encode_frame(event: &SerializedEvent) -> Option<Vec<u8>> {
+    let tag_len = u16::try_from(event.tag.len()).ok()?;
+    let len = 4 + 2 + event.tag.len() + event.payload.len();
+    if len > MAX_FRAME_SIZE {
+        return None;
+    }
+
+    let mut frame = Vec::with_capacity(4 + len);
+    frame.extend_from_slice(&(len as u32).to_be_bytes());
+    frame.extend_from_slice(&[0; 4]);
+    frame.extend_from_slice(&tag_len.to_be_bytes());
+    frame.extend_from_slice(event.tag.as_bytes());
+    frame.extend_from_slice(&event.payload);
+    let crc = checksum(&frame[8..]);
+    frame[4..8].copy_from_slice(&crc.to_be_bytes());
+
+    Some(frame)
+}
+
+/// Read the next frame. Returns None if the connection was closed cleanly between frames, or
+/// CorruptRecordSkipped if the frame's contents don't match its checksum.
+fn read_frame(
+    reader: &mut impl Read,
+) -> io::Result<Option<Result<SerializedEvent, CorruptRecordSkipped>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame exceeds MAX_FRAME_SIZE",
+        ));
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed frame");
+    let (expected, rest) = frame.split_first_chunk::<4>().ok_or_else(invalid)?;
+    let expected = u32::from_be_bytes(*expected);
+    let actual = checksum(rest);
+    if actual != expected {
+        return Ok(Some(Err(CorruptRecordSkipped { expected, actual })));
+    }
+
+    let (tag_len, rest) = rest.split_first_chunk::<2>().ok_or_else(invalid)?;
+    let tag_len = u16::from_be_bytes(*tag_len) as usize;
+    if tag_len > rest.len() {
+        return Err(invalid());
+    }
+    let (tag, payload) = rest.split_at(tag_len);
+    let tag = String::from_utf8(tag.to_vec()).map_err(|_| invalid())?;
+
+    Ok(Some(Ok(SerializedEvent {
+        tag,
+        payload: payload.to_vec(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Metadata, Publisher, SerializableEvent, envelope::Published};
+    use serde::{Deserialize, Serialize};
+    use std::{
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat(String);
+    impl Event for Chat {}
+    impl SerializableEvent for Chat {
+        const TYPE_TAG: &'static str = "chat";
+    }
+
+    /// Subscribes a Forwarder while keeping hold of it, so that its state can be checked
+    struct Shared(Arc<Forwarder>);
+    impl DynHandle for Shared {
+        fn dyn_handle(&self, event: &dyn DynEvent) {
+            self.0.dyn_handle(event)
+        }
+    }
+
+    fn registry() -> Arc<EventRegistry> {
+        let mut registry = EventRegistry::default();
+        registry.register::<Chat>();
+        Arc::new(registry)
+    }
+
+    fn receiving_publisher() -> (Publisher, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let mut publisher = Publisher::default();
+        publisher.subscribe(Handler::new(move |event: Chat| {
+            sender.lock().unwrap().send(event.0).unwrap()
+        }));
+        (publisher, receiver)
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let event = SerializedEvent {
+            tag: String::from("chat"),
+            payload: b"\"hello\"".to_vec(),
+        };
+        let frame = encode_frame(&event).unwrap();
+
+        let mut reader = frame.as_slice();
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(Ok(event)));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_corrupt_frames_are_skipped() {
+        let event = SerializedEvent {
+            tag: String::from("chat"),
+            payload: b"\"hello\"".to_vec(),
+        };
+        let mut frames = encode_frame(&event).unwrap();
+        let last = frames.len() - 1;
+        frames[last] ^= 1;
+        frames.extend(encode_frame(&event).unwrap());
+
+        let mut reader = frames.as_slice();
+        assert!(matches!(
+            read_frame(&mut reader).unwrap(),
+            Some(Err(CorruptRecordSkipped { expected, actual })) if expected != actual
+        ));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(Ok(event)));
+    }
+
+    #[test]
+    fn test_oversized_frames_are_rejected() {
+        let event = SerializedEvent {
+            tag: String::from("chat"),
+            payload: vec![0; MAX_FRAME_SIZE],
+        };
+        assert_eq!(encode_frame(&event), None);
+
+        let header = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes();
+        let error = read_frame(&mut header.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Ping;
+    impl Event for Ping {}
+    impl SerializableEvent for Ping {
+        const TYPE_TAG: &'static str = "ping";
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_forwarders_only_send_what_the_listener_wants() {
+        let mut registry = EventRegistry::default();
+        registry.register::<Chat>().register::<Ping>();
+        let registry = Arc::new(registry);
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry.clone()).unwrap();
+        // a forwarder on the listening side doesn't count as interest in remote events
+        remote.subscribe(Forwarder::new("127.0.0.1:1", registry.clone()).unwrap());
+
+        let forwarder = Arc::new(Forwarder::new(listener.local_addr(), registry).unwrap());
+        let mut local = Publisher::default();
+        local.subscribe(Shared(forwarder.clone()));
+        let _ = local.publish(Chat(String::from("hello")));
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "hello"
+        );
+        wait_until(|| !forwarder.wants("ping"));
+        assert!(forwarder.wants("chat"));
+
+        let pings = remote.subscribe(Handler::new(|_ping: Ping| {}));
+        wait_until(|| forwarder.wants("ping"));
+        remote.unsubscribe(pings).unwrap();
+        wait_until(|| !forwarder.wants("ping"));
+    }
+
+    #[test]
+    fn test_new_connections_start_with_the_snapshot() {
+        // reserve an address for the remote, which isn't listening yet
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut local = Publisher::default();
+        local.subscribe(Forwarder::new(addr, registry()).unwrap().with_snapshot());
+        let _ = local.publish(Chat(String::from("stale")));
+        let _ = local.publish(Chat(String::from("current")));
+
+        let (mut remote, receiver) = receiving_publisher();
+        let _listener = remote.listen(addr, registry()).unwrap();
+        let _ = local.publish(Chat(String::from("live")));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "current");
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "live");
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_forwarders_wait_for_credit_from_slow_listeners() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let gate = Arc::new(Mutex::new(()));
+        let held = gate.lock().unwrap();
+        let mut remote = Publisher::default();
+        let gate_clone = gate.clone();
+        remote.subscribe(Handler::new(move |event: Chat| {
+            let _open = gate_clone.lock().unwrap();
+            sender.lock().unwrap().send(event.0).unwrap()
+        }));
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+        listener.set_window(2);
+
+        let forwarder = Arc::new(Forwarder::new(listener.local_addr(), registry()).unwrap());
+        let mut local = Publisher::default();
+        local.subscribe(Shared(forwarder.clone()));
+        let publishing = thread::spawn(move || {
+            for n in 0..6 {
+                let _ = local.publish(Chat(n.to_string()));
+            }
+        });
+
+        // the first event is stuck in the handler, and the second uses up the window
+        wait_until(|| forwarder.interest.lock().unwrap().credit == Some(0));
+        assert!(!publishing.is_finished());
+
+        drop(held);
+        publishing.join().unwrap();
+        let received: Vec<String> = (0..6)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(received, ["0", "1", "2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn test_corrupt_frames_from_the_listener_are_skipped() {
+        let remote = TcpListener::bind("127.0.0.1:0").unwrap();
+        let forwarder = Forwarder::new(remote.local_addr().unwrap(), registry()).unwrap();
+        forwarder.send(None, b"connect").unwrap();
+        let (mut socket, _) = remote.accept().unwrap();
+
+        let credit = |events: u32| {
+            encode_frame(&SerializedEvent {
+                tag: String::from(CREDIT_TAG),
+                payload: events.to_be_bytes().to_vec(),
+            })
+            .unwrap()
+        };
+        let mut frames = credit(1000);
+        let last = frames.len() - 1;
+        frames[last] ^= 1;
+        frames.extend(credit(1));
+        socket.write_all(&frames).unwrap();
+
+        wait_until(|| forwarder.interest.lock().unwrap().credit == Some(1));
+        forwarder.send(None, b"frame").unwrap();
+        assert_eq!(forwarder.interest.lock().unwrap().credit, Some(0));
+    }
+
+    #[test]
+    fn test_credit_is_handed_back_when_an_event_is_not_sent() {
+        // reserve an address that nothing is listening on
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let forwarder = Forwarder::new(addr, registry()).unwrap();
+        forwarder.interest.lock().unwrap().credit = Some(1);
+
+        assert!(forwarder.send(None, b"frame").is_err());
+        assert_eq!(forwarder.interest.lock().unwrap().credit, Some(1));
+    }
+
+    #[test]
+    fn test_buffered_events_are_only_replayed_with_credit_for_each() {
+        let remote = TcpListener::bind("127.0.0.1:0").unwrap();
+        let forwarder = Forwarder::new(remote.local_addr().unwrap(), registry())
+            .unwrap()
+            .with_outage_buffer(4);
+        forwarder.send(None, b"connect").unwrap();
+        forwarder.outage.lock().unwrap().extend([
+            (None, b"a".to_vec()),
+            (None, b"b".to_vec()),
+            (None, b"c".to_vec()),
+        ]);
+        let buffered = || -> Vec<Vec<u8>> {
+            let outage = forwarder.outage.lock().unwrap();
+            outage.iter().map(|(_, frame)| frame.clone()).collect()
+        };
+
+        // credit for one event only covers the oldest buffered one
+        forwarder.interest.lock().unwrap().credit = Some(1);
+        forwarder.send(None, b"d").unwrap();
+        assert_eq!(buffered(), [b"b", b"c", b"d"]);
+        assert_eq!(forwarder.interest.lock().unwrap().credit, Some(0));
+
+        forwarder.interest.lock().unwrap().credit = Some(4);
+        forwarder.send(None, b"e").unwrap();
+        assert!(buffered().is_empty());
+        assert_eq!(forwarder.interest.lock().unwrap().credit, Some(0));
+        assert_eq!(*forwarder.sequence.lock().unwrap(), 6);
+    }
+
+    #[test]
+    fn test_held_events_are_released_in_order_or_when_they_expire() {
+        let start = Instant::now();
+        let later = |millis| start + Duration::from_millis(millis);
+        let mut buffer = ReorderBuffer::default();
+        buffer.set_hold(Duration::from_millis(100));
+
+        assert_eq!(buffer.push(1, 5, "a", start), ["a"]);
+        assert!(buffer.push(1, 7, "c", start).is_empty());
+        assert_eq!(buffer.push(2, 0, "x", start), ["x"]);
+        assert_eq!(buffer.push(1, 6, "b", later(10)), ["b", "c"]);
+        // too late to be put in order
+        assert_eq!(buffer.push(1, 4, "late", later(10)), ["late"]);
+
+        assert!(buffer.push(1, 10, "f", later(20)).is_empty());
+        assert!(buffer.push(1, 9, "e", later(50)).is_empty());
+        assert!(buffer.push(1, 12, "h", later(50)).is_empty());
+        assert_eq!(buffer.deadline(), Some(later(120)));
+        assert!(buffer.expire(later(119)).is_empty());
+        assert_eq!(buffer.expire(later(120)), ["e", "f"]);
+        assert_eq!(buffer.push(1, 11, "g", later(130)), ["g", "h"]);
+        assert_eq!(buffer.deadline(), None);
+    }
+
+    #[test]
+    fn test_listeners_reorder_events_that_arrive_out_of_order() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+        listener.set_reordering(Duration::from_millis(200));
+
+        let frame = |sequence: u64, text: &str| {
+            let mut payload = 7u64.to_be_bytes().to_vec();
+            payload.extend_from_slice(&sequence.to_be_bytes());
+            let mut frame = encode_frame(&SerializedEvent {
+                tag: String::from(SEQUENCE_TAG),
+                payload,
+            })
+            .unwrap();
+            frame.extend(encode_frame(&registry().serialize(&Chat(text.into())).unwrap()).unwrap());
+            frame
+        };
+        let mut first = TcpStream::connect(listener.local_addr()).unwrap();
+        let mut second = TcpStream::connect(listener.local_addr()).unwrap();
+        first.write_all(&frame(0, "zero")).unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "zero");
+
+        // the second connection overtakes the first, and four never arrives
+        second.write_all(&frame(2, "two")).unwrap();
+        second.write_all(&frame(3, "three")).unwrap();
+        second.write_all(&frame(5, "five")).unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+        first.write_all(&frame(1, "one")).unwrap();
+
+        let received: Vec<String> = (0..4)
+            .map(|_| receiver.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(received, ["one", "two", "three", "five"]);
+    }
+
+    #[test]
+    fn test_events_are_published_remotely() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+
+        let mut local = Publisher::default();
+        local.subscribe(Forwarder::new(listener.local_addr(), registry()).unwrap());
+        let _ = local.publish(Chat(String::from("hello")));
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, "hello");
+    }
+
+    #[test]
+    fn test_batches_are_written_once_they_are_large_enough() {
+        let remote = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut local = Publisher::default();
+        let forwarder = Forwarder::new(remote.local_addr().unwrap(), registry())
+            .unwrap()
+            .with_batching(100, Duration::from_secs(3600));
+        local.subscribe(forwarder);
+
+        // each event takes 52 bytes along with its sequence frame
+        let _ = local.publish(Chat(String::from("a")));
+        let (mut socket, _) = remote.accept().unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        assert!(socket.read(&mut [0; 1]).is_err());
+
+        let _ = local.publish(Chat(String::from("b")));
+        socket.set_read_timeout(None).unwrap();
+        let mut batch = [0; 104];
+        socket.read_exact(&mut batch).unwrap();
+        let mut reader = batch.as_slice();
+        let chats: Vec<String> = std::iter::from_fn(|| read_frame(&mut reader).unwrap())
+            .map(|frame| frame.unwrap())
+            .filter(|frame| frame.tag == "chat")
+            .map(|frame| serde_json::from_slice(&frame.payload).unwrap())
+            .collect();
+        assert_eq!(chats, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_batches_are_written_once_they_have_lingered() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+
+        let mut local = Publisher::default();
+        let forwarder = Forwarder::new(listener.local_addr(), registry())
+            .unwrap()
+            .with_batching(1 << 20, Duration::from_millis(20));
+        local.subscribe(forwarder);
+        let _ = local.publish(Chat(String::from("first")));
+        let _ = local.publish(Chat(String::from("second")));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "first");
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_batches_that_cant_be_written_are_buffered_and_replayed() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+        let addr = listener.local_addr();
+        let (sender, health) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        // each event takes 52 bytes along with its sequence frame, so two make a batch
+        let mut forwarder = Forwarder::new(addr, registry())
+            .unwrap()
+            .with_batching(100, Duration::from_secs(3600))
+            .with_outage_buffer(4);
+        forwarder.on_health(move |event| {
+            let down = event.get_data().is::<BridgeDown>();
+            sender.lock().unwrap().send(down).unwrap()
+        });
+        let chat = |text: &str| {
+            encode_frame(&registry().serialize(&Chat(String::from(text))).unwrap()).unwrap()
+        };
+
+        forwarder.send(None, &chat("first")).unwrap();
+        // drop the connection with the first event still in the batch. The Forwarder may already
+        // have shut it down on seeing the Listener go.
+        drop(listener);
+        let stream = forwarder.stream.lock().unwrap();
+        let _ = stream.as_ref().unwrap().shutdown(Shutdown::Write);
+        drop(stream);
+        assert!(forwarder.send(None, &chat("second")).is_err());
+        assert_eq!(health.try_recv(), Ok(true));
+        assert_eq!(forwarder.outage.lock().unwrap().len(), 2);
+        assert_eq!(*forwarder.sequence.lock().unwrap(), 0);
+
+        let _listener = remote.listen(addr, registry()).unwrap();
+        forwarder.send(None, &chat("third")).unwrap();
+        assert_eq!(health.try_recv(), Ok(false));
+        let timeout = Duration::from_secs(5);
+        let received: Vec<String> = (0..3)
+            .map(|_| receiver.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(received, ["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_events_over_the_max_event_size_fail_and_are_dropped() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+
+        let mut local = Publisher::default();
+        let forwarder = Forwarder::new(listener.local_addr(), registry())
+            .unwrap()
+            .with_max_event_size(16);
+        local.subscribe(forwarder);
+        let errors = local.try_publish(Chat("x".repeat(32))).unwrap_err();
+        assert_eq!(
+            errors.failures[0].downcast_ref(),
+            Some(&EventTooLarge {
+                bridge: "net",
+                tag: String::from("chat"),
+                size: 34,
+                limit: 16,
+            })
+        );
+        local.try_publish(Chat(String::from("small"))).unwrap();
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, "small");
+    }
+
+    #[test]
+    fn test_forwarders_fall_back_to_their_secondary_remote() {
+        // reserve an address that nothing is listening on
+        let primary = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+
+        let mut local = Publisher::default();
+        let forwarder = Forwarder::new(primary, registry())
+            .unwrap()
+            .with_secondary(listener.local_addr())
+            .unwrap();
+        local.subscribe(forwarder);
+        let _ = local.publish(Chat(String::from("hello")));
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, "hello");
+    }
+
+    #[test]
+    fn test_outages_are_reported_and_buffered_events_replayed() {
+        // reserve an address for the remote, which isn't listening yet
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut local = Publisher::default();
+        let (sender, health) = mpsc::channel();
+        let down = Mutex::new(sender.clone());
+        local.subscribe(Handler::new(move |_: BridgeDown| {
+            down.lock().unwrap().send(None).unwrap()
+        }));
+        let restored = Mutex::new(sender);
+        local.subscribe(Handler::new(move |event: BridgeRestored| {
+            restored.lock().unwrap().send(Some(event.addr)).unwrap()
+        }));
+        local.forward(
+            Forwarder::new(addr, registry())
+                .unwrap()
+                .with_outage_buffer(2),
+        );
+
+        for text in ["dropped", "kept", "also kept"] {
+            let _ = local.publish(Chat(String::from(text)));
+        }
+        assert_eq!(health.try_recv(), Ok(None));
+        assert!(health.try_recv().is_err());
+
+        let (mut remote, receiver) = receiving_publisher();
+        let _listener = remote.listen(addr, registry()).unwrap();
+        let _ = local.publish(Chat(String::from("live")));
+        assert_eq!(health.try_recv(), Ok(Some(addr)));
+
+        let timeout = Duration::from_secs(5);
+        let received: Vec<String> = (0..3)
+            .map(|_| receiver.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(received, ["kept", "also kept", "live"]);
+    }
+
+    #[test]
+    fn test_received_events_are_not_forwarded_again() {
+        let (mut first, first_receiver) = receiving_publisher();
+        let (mut second, second_receiver) = receiving_publisher();
+        let first_listener = first.listen("127.0.0.1:0", registry()).unwrap();
+        let second_listener = second.listen("127.0.0.1:0", registry()).unwrap();
+        first.subscribe(Forwarder::new(second_listener.local_addr(), registry()).unwrap());
+        second.subscribe(Forwarder::new(first_listener.local_addr(), registry()).unwrap());
+
+        let _ = first.publish(Chat(String::from("hello")));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(first_receiver.recv_timeout(timeout).unwrap(), "hello");
+        assert_eq!(second_receiver.recv_timeout(timeout).unwrap(), "hello");
+        let timeout = Duration::from_millis(100);
+        assert!(first_receiver.recv_timeout(timeout).is_err());
+        assert!(second_receiver.recv_timeout(timeout).is_err());
+    }
+
+    #[test]
+    fn test_events_are_relayed_but_not_echoed_back_to_the_peer_they_came_from() {
+        let (mut first, first_receiver) = receiving_publisher();
+        let (mut second, second_receiver) = receiving_publisher();
+        let (mut third, third_receiver) = receiving_publisher();
+        let first_listener = first.listen("127.0.0.1:0", registry()).unwrap();
+        let second_listener = second.listen("127.0.0.1:0", registry()).unwrap();
+        let third_listener = third.listen("127.0.0.1:0", registry()).unwrap();
+        first.forward(Forwarder::new(second_listener.local_addr(), registry()).unwrap());
+        second.forward(Forwarder::new(first_listener.local_addr(), registry()).unwrap());
+        second.forward(Forwarder::new(third_listener.local_addr(), registry()).unwrap());
+
+        let _ = first.publish(Chat(String::from("hello")));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(first_receiver.recv_timeout(timeout).unwrap(), "hello");
+        assert_eq!(second_receiver.recv_timeout(timeout).unwrap(), "hello");
+        assert_eq!(third_receiver.recv_timeout(timeout).unwrap(), "hello");
+        let timeout = Duration::from_millis(100);
+        assert!(first_receiver.recv_timeout(timeout).is_err());
+        assert!(second_receiver.recv_timeout(timeout).is_err());
+        assert!(third_receiver.recv_timeout(timeout).is_err());
+    }
+
+    #[test]
+    fn test_forwarders_skip_events_received_from_their_listeners_publisher() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+
+        let mut forwarder = Forwarder::new(listener.local_addr(), registry()).unwrap();
+        forwarder.set_peer(PeerId(1));
+        let forwarder = Arc::new(forwarder);
+        let mut local = Publisher::default();
+        local.subscribe(Shared(forwarder.clone()));
+        let _ = local.publish(Chat(String::from("hello")));
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "hello"
+        );
+        wait_until(|| forwarder.interest.lock().unwrap().peer.is_some());
+        let peer = forwarder.interest.lock().unwrap().peer.unwrap();
+
+        let received_from = |peer| {
+            let metadata = Metadata {
+                remote: true,
+                peer: Some(peer),
+                ..Default::default()
+            };
+            Published::new(Chat(String::from("received")), metadata)
+        };
+        assert!(!forwarder.accepts(&received_from(peer)));
+        assert!(forwarder.accepts(&received_from(peer.wrapping_add(1))));
+    }
+
+    #[test]
+    fn test_listeners_drop_events_sent_back_to_their_own_publisher() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+        listener.set_reordering(Duration::from_secs(5));
+
+        let mut stream = TcpStream::connect(listener.local_addr()).unwrap();
+        let announced = loop {
+            let Ok(Some(Ok(frame))) = read_frame(&mut stream) else {
+                panic!("Listener did not announce its Publisher");
+            };
+            if frame.tag == PEER_TAG {
+                break frame;
+            }
+        };
+
+        let frame = |sequence: u64, from: &[u8], text: &str| {
+            let mut payload = 7u64.to_be_bytes().to_vec();
+            payload.extend_from_slice(&sequence.to_be_bytes());
+            payload.extend_from_slice(from);
+            let mut frame = encode_frame(&SerializedEvent {
+                tag: String::from(SEQUENCE_TAG),
+                payload,
+            })
+            .unwrap();
+            frame.extend(encode_frame(&registry().serialize(&Chat(text.into())).unwrap()).unwrap());
+            frame
+        };
+        stream.write_all(&frame(0, &[], "first")).unwrap();
+        stream
+            .write_all(&frame(1, &announced.payload, "echo"))
+            .unwrap();
+        stream.write_all(&frame(2, &[], "second")).unwrap();
+
+        // the dropped echo doesn't leave a gap for the event after it to be held back by
+        let timeout = Duration::from_secs(1);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "first");
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), "second");
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_forwarder_reconnects() {
+        let (mut remote, receiver) = receiving_publisher();
+        let listener = remote.listen("127.0.0.1:0", registry()).unwrap();
+        let addr = listener.local_addr();
+
+        let mut local = Publisher::default();
+        local.subscribe(Forwarder::new(addr, registry()).unwrap());
+        let _ = local.publish(Chat(String::from("first")));
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "first"
+        );
+
+        drop(listener);
+        let _listener = remote.listen(addr, registry()).unwrap();
+
+        // events sent before the Forwarder notices the old connection has gone are lost
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let _ = local.publish(Chat(String::from("second")));
+            if let Ok(received) = receiver.recv_timeout(Duration::from_millis(50)) {
+                assert_eq!(received, "second");
+                break;
+            }
+            assert!(Instant::now() < deadline, "Forwarder did not reconnect");
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::DynEvent;
+
+/// How often a blocked publish checks whether a handler has become ready
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// What happens to the events for a subscription whose handler's `poll_ready` says it isn't ready
+/// for them. Set with `Publisher::on_overflow`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the events
+    Drop,
+    /// Keep the events, and deliver them in order before any later ones once the handler is
+    /// ready, at the next publish or flush
+    Buffer,
+    /// Hold up the publish for up to the given time until the handler is ready, then drop the
+    /// events if it still isn't
+    Block(Duration),
+}
+
+/// The overflow policy of each subscription that has one, and the events buffered under them
+#[derive(Default)]
+pub(crate) struct Overflows {
+    policies: RwLock<HashMap<usize, Overflow>>,
+    backlogs: Mutex<HashMap<usize, Vec<Arc<dyn DynEvent>>>>,
+}
+
+impl Overflows {
+    pub(crate) fn set(&self, id: usize, overflow: Overflow) {
+        self.policies
+            .write()
+            .expect("Overflow lock poisoned")
+            .insert(id, overflow);
+    }
+
+    /// Forget a subscription's policy along with any events buffered for it
+    pub(crate) fn remove(&self, id: usize) {
+        self.policies
+            .write()
+            .expect("Overflow lock poisoned")
+            .remove(&id);
+        self.backlogs
+            .lock()
+            .expect("Backlog mutex poisoned")
+            .remove(&id);
+    }
+
+    /// Take the events buffered for each subscription whose handler is ready for them again
+    pub(crate) fn release(
+        &self,
+        ready: impl Fn(usize) -> bool,
+    ) -> Vec<(usize, Vec<Arc<dyn DynEvent>>)> {
+        let mut backlogs = self.backlogs.lock().expect("Backlog mutex poisoned");
+        let ids: Vec<usize> = backlogs.keys().copied().filter(|id| ready(*id)).collect();
+        let mut released: Vec<(usize, Vec<Arc<dyn DynEvent>>)> = ids
+            .into_iter()
+            .filter_map(|id| Some((id, backlogs.remove(&id)?)))
+            .collect();
+        released.sort_by_key(|(id, _)| *id);
+        released
+    }
+
+    /// The subscriptions that shouldn't be given `events` because their handlers aren't ready,
+    /// after waiting for those that block and buffering the events accepted by those that buffer.
+    /// Subscriptions that still have a backlog keep buffering, so that their events stay in order.
+    pub(crate) fn overflowing(
+        &self,
+        events: &[Arc<dyn DynEvent>],
+        ready: impl Fn(usize) -> bool,
+        accepts: impl Fn(usize, &dyn DynEvent) -> bool,
+    ) -> HashSet<usize> {
+        let policies: Vec<(usize, Overflow)> = {
+            let policies = self.policies.read().expect("Overflow lock poisoned");
+            policies.iter().map(|(id, policy)| (*id, *policy)).collect()
+        };
+        let mut overflowing = HashSet::new();
+        for (id, policy) in policies {
+            match policy {
+                Overflow::Drop if !ready(id) => {
+                    overflowing.insert(id);
+                }
+                Overflow::Buffer => {
+                    let mut backlogs = self.backlogs.lock().expect("Backlog mutex poisoned");
+                    if backlogs.contains_key(&id) || !ready(id) {
+                        backlogs.entry(id).or_default().extend(
+                            events
+                                .iter()
+                                .filter(|event| accepts(id, event.as_ref()))
+                                .cloned(),
+                        );
+                        overflowing.insert(id);
+                    }
+                }
+                Overflow::Block(timeout) => {
+                    let deadline = Instant::now() + timeout;
+                    while !ready(id) {
+                        if Instant::now() >= deadline {
+                            overflowing.insert(id);
+                            break;
+                        }
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+                Overflow::Drop => {}
+            }
+        }
+        overflowing
+    }
+}
@@ -0,0 +1,177 @@
+use std::{any::Any, fmt};
+
+/// A handler's panic, converted to a string by a PanicFormatter. Publishers with a formatter
+/// return these in place of the raw panic payloads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanicMessage {
+    pub message: String,
+    /// Whether the message was cut short to fit the formatter's length limit
+    pub truncated: bool,
+    /// Name of the handler that panicked, if it was subscribed with one
+    pub handler: Option<&'static str>,
+}
+
+impl fmt::Display for PanicMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(handler) = self.handler {
+            write!(f, "{handler}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if self.truncated {
+            write!(f, "…")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The panic of a handler subscribed with a name, returned in place of its raw payload by
+/// `publish` and the other methods that report errors. `panic_message` sees through it.
+#[derive(Debug)]
+pub struct HandlerPanic {
+    /// Name the handler was subscribed with
+    pub handler: &'static str,
+    pub payload: Box<dyn Any + Send>,
+}
+
+/// What a Publisher does when one of its handlers panics. Set with `Publisher::set_panic_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Catch the panic and return it from `publish` and the other methods that report errors,
+    /// alongside the panics of any other handlers
+    #[default]
+    Collect,
+    /// Log the panic and carry on, without returning it, for applications that have nothing
+    /// better to do with a handler's panics than log them. Panics go to the hook set with
+    /// `Publisher::on_panic`, or without one to an error event with the `tracing` feature, or to
+    /// stderr otherwise.
+    LogAndContinue,
+    /// Unsubscribe the handler that panicked, so that a handler that panics on every event isn't
+    /// called forever, then return the panic as `Collect` does. Only handlers that run on the
+    /// Publisher's threads are unsubscribed, not mut or ordered handlers.
+    UnsubscribeOffender,
+    /// Abort the process as soon as a panic is reported, for applications that would rather
+    /// crash fast than carry on in a bad state
+    Abort,
+    /// Resume the panic on the thread that published the event, as though the handler had been
+    /// called directly. If several handlers panicked, the first one reported is resumed.
+    ResumeUnwindOnCaller,
+}
+
+type Redact = dyn Fn(&str) -> String + Send + Sync;
+
+/// Converts the payloads of handler panics into PanicMessages, optionally redacting and
+/// truncating them, since panic messages can contain sensitive formatted data
+/// # Examples
+/// ```
+/// use crier::{PanicFormatter, Publisher};
+///
+/// let mut publisher = Publisher::default();
+/// publisher.set_panic_formatter(
+///     PanicFormatter::default()
+///         .redact(|message| message.replace("hunter2", "[redacted]"))
+///         .max_len(200),
+/// );
+/// ```
+#[derive(Default)]
+pub struct PanicFormatter {
+    redact: Option<Box<Redact>>,
+    max_len: Option<usize>,
+}
+
+impl PanicFormatter {
+    /// Pass every message through `redact` before it is returned
+    pub fn redact<F>(mut self, redact: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.redact = Some(Box::new(redact));
+        self
+    }
+
+    /// Cut messages down to at most `max_len` bytes, after redacting them
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Convert a panic payload into a PanicMessage
+    pub fn format(&self, payload: &(dyn Any + Send)) -> PanicMessage {
+        let message = panic_message(payload).unwrap_or("non-string panic payload");
+        let mut message = match &self.redact {
+            Some(redact) => redact(message),
+            None => String::from(message),
+        };
+
+        let mut truncated = false;
+        if let Some(max_len) = self.max_len
+            && message.len() > max_len
+        {
+            let mut end = max_len;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+            truncated = true;
+        }
+
+        PanicMessage {
+            message,
+            truncated,
+            handler: payload
+                .downcast_ref::<HandlerPanic>()
+                .map(|panic| panic.handler),
+        }
+    }
+}
+
+/// The message a handler panicked with, if it panicked with a string. Also unwraps
+/// PanicMessages and HandlerPanics, so works on the errors returned by Publishers with or without
+/// a formatter.
+pub fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        Some(message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        Some(message)
+    } else if let Some(panic) = payload.downcast_ref::<HandlerPanic>() {
+        panic_message(panic.payload.as_ref())
+    } else {
+        payload
+            .downcast_ref::<PanicMessage>()
+            .map(|message| message.message.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extracts_string_payloads() {
+        let formatter = PanicFormatter::default();
+        let static_str: Box<dyn Any + Send> = Box::new("boom");
+        let string: Box<dyn Any + Send> = Box::new(format!("boom {}", 1));
+        let other: Box<dyn Any + Send> = Box::new(1);
+
+        assert_eq!(formatter.format(static_str.as_ref()).message, "boom");
+        assert_eq!(formatter.format(string.as_ref()).message, "boom 1");
+        assert_eq!(
+            formatter.format(other.as_ref()).message,
+            "non-string panic payload"
+        );
+    }
+
+    #[test]
+    fn test_redacts_then_truncates_on_char_boundaries() {
+        let formatter = PanicFormatter::default()
+            .redact(|message| message.replace("secret", "█"))
+            .max_len(10);
+        let payload: Box<dyn Any + Send> = Box::new("secret key: secret");
+
+        let formatted = formatter.format(payload.as_ref());
+        // "█" is 3 bytes, so the second one doesn't fit in the last byte
+        assert_eq!(formatted.message, "█ key: ");
+        assert!(formatted.truncated);
+        assert_eq!(formatted.to_string(), "█ key: …");
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{DynEvent, DynHandle};
+
+/// Number of locks a partitioned handler spreads its keys across. Events whose keys share a lock
+/// are handled one at a time even though their keys differ, so this bounds the parallelism.
+const STRIPES: usize = 64;
+
+type PartitionKey = dyn Fn(&dyn DynEvent) -> Option<u64> + Send + Sync;
+
+/// A handler subscribed with `Publisher::subscribe_partitioned`, along with a lock for each
+/// stripe of keys so that events with the same key are never handled at the same time, even when
+/// they are published from different threads
+pub(crate) struct Partitioned {
+    pub(crate) handler: Arc<dyn DynHandle>,
+    key: Box<PartitionKey>,
+    stripes: Vec<Mutex<()>>,
+}
+
+impl Partitioned {
+    pub(crate) fn new(handler: Arc<dyn DynHandle>, key: Box<PartitionKey>) -> Self {
+        Partitioned {
+            handler,
+            key,
+            stripes: (0..STRIPES).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    /// Split the events the handler accepts into runs that must be handled in order, one for each
+    /// stripe of keys. Events without a key all go in the same run.
+    pub(crate) fn split(
+        &self,
+        events: &[Arc<dyn DynEvent>],
+    ) -> Vec<(usize, Vec<Arc<dyn DynEvent>>)> {
+        let mut runs: Vec<(usize, Vec<Arc<dyn DynEvent>>)> = Vec::new();
+        for event in events
+            .iter()
+            .filter(|event| self.handler.accepts(event.as_ref()))
+        {
+            let stripe = self.stripe_of(event.as_ref());
+            match runs
+                .iter_mut()
+                .find(|(run_stripe, _)| *run_stripe == stripe)
+            {
+                Some((_, run)) => run.push(event.clone()),
+                None => runs.push((stripe, vec![event.clone()])),
+            }
+        }
+
+        runs
+    }
+
+    /// The stripe an event's key falls in
+    pub(crate) fn stripe_of(&self, event: &dyn DynEvent) -> usize {
+        stripe((self.key)(event).unwrap_or(0))
+    }
+
+    /// Hold the lock for a stripe while its run of events is handled
+    pub(crate) fn lock(&self, stripe: usize) -> MutexGuard<'_, ()> {
+        self.stripes[stripe]
+            .lock()
+            .expect("Partition mutex poisoned")
+    }
+}
+
+/// Spread keys evenly across the stripes, even when they follow a pattern like all being even
+fn stripe(key: u64) -> usize {
+    (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - STRIPES.trailing_zeros())) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler};
+    use std::collections::HashMap;
+
+    #[derive(Clone)]
+    struct Moved {
+        entity: u64,
+    }
+    impl Event for Moved {}
+
+    #[test]
+    fn test_split_keeps_events_with_the_same_key_together_in_order() {
+        let partitioned = Partitioned::new(
+            Arc::new(Handler::new(|_event: Moved| {})),
+            Box::new(|event| {
+                event
+                    .get_data()
+                    .downcast_ref::<Moved>()
+                    .map(|moved| moved.entity)
+            }),
+        );
+        let events: Vec<Arc<dyn DynEvent>> = (0..100)
+            .map(|i| Arc::new(Moved { entity: i % 10 }) as Arc<dyn DynEvent>)
+            .collect();
+
+        let runs = partitioned.split(&events);
+        assert!(runs.len() > 1);
+        let mut stripes = HashMap::new();
+        let mut seen = 0;
+        for (stripe, run) in &runs {
+            for event in run {
+                let entity = event.get_data().downcast_ref::<Moved>().unwrap().entity;
+                assert_eq!(*stripes.entry(entity).or_insert(*stripe), *stripe);
+                seen += 1;
+            }
+        }
+        assert_eq!(stripes.len(), 10);
+        assert_eq!(seen, 100);
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use crate::DynEvent;
+
+/// Events kept for a paused subscription, or None if they are dropped
+type Buffer = Option<Vec<Arc<dyn DynEvent>>>;
+
+/// Subscriptions paused with `Publisher::pause` or `Publisher::pause_buffered`, along with the
+/// events kept for those that buffer
+#[derive(Default)]
+pub(crate) struct Paused {
+    subscriptions: RwLock<HashMap<usize, Buffer>>,
+}
+
+impl Paused {
+    pub(crate) fn pause(&self, id: usize, buffer: bool) {
+        self.subscriptions
+            .write()
+            .expect("Paused lock poisoned")
+            .entry(id)
+            .or_insert_with(|| buffer.then(Vec::new));
+    }
+
+    /// Unpause a subscription, returning the events buffered for it
+    pub(crate) fn resume(&self, id: usize) -> Vec<Arc<dyn DynEvent>> {
+        self.subscriptions
+            .write()
+            .expect("Paused lock poisoned")
+            .remove(&id)
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn ids(&self) -> HashSet<usize> {
+        let subscriptions = self.subscriptions.read().expect("Paused lock poisoned");
+        subscriptions.keys().copied().collect()
+    }
+
+    /// Keep the events each buffering subscription accepts until it is resumed
+    pub(crate) fn buffer(
+        &self,
+        events: &[Arc<dyn DynEvent>],
+        accepts: impl Fn(usize, &dyn DynEvent) -> bool,
+    ) {
+        let mut subscriptions = self.subscriptions.write().expect("Paused lock poisoned");
+        for (id, buffer) in subscriptions.iter_mut() {
+            if let Some(buffer) = buffer {
+                buffer.extend(
+                    events
+                        .iter()
+                        .filter(|event| accepts(*id, event.as_ref()))
+                        .cloned(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::time::{Instant, SystemTime};
+
+/// Whether the target can run handlers on threads of their own. Browsers running
+/// `wasm32-unknown-unknown` can't, so Publishers dispatch sequentially there by default.
+/// Scheduling events for later, and debouncing handlers, still need threads.
+pub(crate) const THREADS: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// The current time. Browsers don't give `std` access to the clock, so on `wasm32-unknown-unknown`
+/// it is read from JavaScript's `Date.now()` instead.
+pub(crate) fn now() -> SystemTime {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        SystemTime::now()
+    }
+}
+
+/// The current instant, for measuring how long things take, or None on targets that can't tell,
+/// like `wasm32-unknown-unknown`, where `Instant` isn't available. Deadlines aren't checked and
+/// publish latency isn't measured there.
+pub(crate) fn instant() -> Option<Instant> {
+    THREADS.then(Instant::now)
+}
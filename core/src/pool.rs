@@ -0,0 +1,396 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt, hint, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use crate::{Executor, Job};
+
+type StaticJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// Lets threads sleep until something happens without missing a notification that arrives
+/// between checking for work and going to sleep. A waiter takes the current count as a ticket,
+/// checks for work, then sleeps only while the count hasn't moved on from its ticket. Sleeping
+/// threads are parked, which is a futex wait on Linux.
+#[derive(Default)]
+struct EventCount {
+    count: AtomicU32,
+    sleepers: AtomicUsize,
+    waiters: Mutex<Vec<Thread>>,
+}
+
+impl EventCount {
+    fn ticket(&self) -> u32 {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    fn notify(&self) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            for waiter in self.waiters.lock().expect("Waiter lock poisoned").iter() {
+                waiter.unpark();
+            }
+        }
+    }
+
+    /// Wait until the count moves on from `ticket`, spinning for up to `spin` before parking
+    fn wait(&self, ticket: u32, spin: Duration) {
+        if !spin.is_zero() {
+            let started = Instant::now();
+            while started.elapsed() < spin {
+                if self.ticket() != ticket {
+                    return;
+                }
+                hint::spin_loop();
+            }
+        }
+
+        let current = thread::current();
+        self.waiters
+            .lock()
+            .expect("Waiter lock poisoned")
+            .push(current.clone());
+        self.sleepers.fetch_add(1, Ordering::SeqCst);
+        while self.ticket() == ticket {
+            thread::park();
+        }
+        self.sleepers.fetch_sub(1, Ordering::SeqCst);
+        self.waiters
+            .lock()
+            .expect("Waiter lock poisoned")
+            .retain(|waiter| waiter.id() != current.id());
+    }
+}
+
+/// State shared between a WorkerPool and its threads
+#[derive(Default)]
+struct Intake {
+    queue: Mutex<VecDeque<StaticJob>>,
+    events: EventCount,
+    /// How long idle threads spin before parking, in nanoseconds
+    spin: AtomicU64,
+    shutdown: AtomicBool,
+}
+
+impl Intake {
+    fn pop(&self) -> Option<StaticJob> {
+        self.queue.lock().expect("Intake lock poisoned").pop_front()
+    }
+
+    fn spin(&self) -> Duration {
+        Duration::from_nanos(self.spin.load(Ordering::Relaxed))
+    }
+
+    fn work(&self) {
+        loop {
+            let ticket = self.events.ticket();
+            if let Some(job) = self.pop() {
+                job();
+                continue;
+            }
+            if self.shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+            self.events.wait(ticket, self.spin());
+        }
+    }
+}
+
+/// Counts down the jobs of one `execute` call as they finish
+struct Latch {
+    remaining: AtomicUsize,
+    panic: Mutex<Option<Box<dyn std::any::Any + Send>>>,
+}
+
+/// An Executor that keeps its threads running between publishes, rather than starting new ones
+/// for each, for use with `DispatchStrategy::Executor`. The publishing thread runs queued jobs
+/// too while it waits for its own, so handlers can publish on the same Publisher.
+///
+/// By default, idle threads park straight away, which costs nothing while there is no work but
+/// takes a few microseconds to wake from. For sparse events that need handling with as little
+/// latency as possible, `spin_then_park` has idle threads spin first, picking up new work within
+/// nanoseconds, at the cost of keeping a core busy per thread for as long as they spin.
+/// # Examples
+/// ```
+/// use std::{sync::Arc, time::Duration};
+/// use crier::{DispatchStrategy, Publisher, WorkerPool};
+///
+/// let pool = WorkerPool::new(4).spin_then_park(Duration::from_micros(50));
+/// let publisher = Publisher::with_strategy(DispatchStrategy::Executor(Arc::new(pool)));
+/// ```
+pub struct WorkerPool {
+    intake: Arc<Intake>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Start a pool of `threads` threads, or one if `threads` is 0
+    pub fn new(threads: usize) -> Self {
+        let intake = Arc::new(Intake::default());
+        let workers = (0..threads.max(1))
+            .filter_map(|index| {
+                let intake = intake.clone();
+                thread::Builder::new()
+                    .name(format!("crier-worker-{index}"))
+                    .spawn(move || intake.work())
+                    .ok()
+            })
+            .collect();
+
+        WorkerPool { intake, workers }
+    }
+
+    /// Have idle threads, and publishing threads waiting on them, spin for up to `spin` before
+    /// parking. Each spinning thread keeps a core busy, so keep `spin` short and only use this
+    /// where wakeup latency matters more than CPU time.
+    pub fn spin_then_park(self, spin: Duration) -> Self {
+        self.intake
+            .spin
+            .store(spin.as_nanos() as u64, Ordering::Relaxed);
+        self
+    }
+
+    /// Number of threads in the pool
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl Executor for WorkerPool {
+    fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+        let latch = Arc::new(Latch {
+            remaining: AtomicUsize::new(jobs.len()),
+            panic: Mutex::new(None),
+        });
+        {
+            let mut queue = self.intake.queue.lock().expect("Intake lock poisoned");
+            for job in jobs {
+                let latch = latch.clone();
+                let intake = self.intake.clone();
+                let job: Job<'a> = Box::new(move || {
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                        latch
+                            .panic
+                            .lock()
+                            .expect("Latch lock poisoned")
+                            .get_or_insert(payload);
+                    }
+                    latch.remaining.fetch_sub(1, Ordering::SeqCst);
+                    intake.events.notify();
+                });
+                // SAFETY: this doesn't return until the latch says every job has run, so nothing
+                // a job borrows goes out of scope while it could still be running
+                let job = unsafe { mem::transmute::<Job<'a>, StaticJob>(job) };
+                queue.push_back(job);
+            }
+        }
+        self.intake.events.notify();
+
+        let spin = self.intake.spin();
+        loop {
+            let ticket = self.intake.events.ticket();
+            if latch.remaining.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            match self.intake.pop() {
+                Some(job) => job(),
+                None => self.intake.events.wait(ticket, spin),
+            }
+        }
+
+        if let Some(payload) = latch.panic.lock().expect("Latch lock poisoned").take() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.intake.shutdown.store(true, Ordering::SeqCst);
+        self.intake.events.notify();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("threads", &self.workers.len())
+            .field("spin", &self.intake.spin())
+            .finish()
+    }
+}
+
+/// Buffers allocated by the caller, such as with the alignment or pinning a GPU or DMA engine
+/// needs, which are lent out one at a time and go back to the pool when the borrower drops them.
+/// A `PooledSerializingHandler` encodes events straight into them, so their payloads can be
+/// handed on without being copied.
+pub struct BufferPool<B> {
+    free: Mutex<Vec<B>>,
+    /// Notified whenever a buffer goes back to the pool
+    returned: Condvar,
+    /// How long to wait for a buffer to go back to the pool when none are free
+    wait: Duration,
+}
+
+impl<B: AsMut<[u8]>> BufferPool<B> {
+    pub fn new(buffers: impl IntoIterator<Item = B>) -> Self {
+        BufferPool {
+            free: Mutex::new(buffers.into_iter().collect()),
+            returned: Condvar::new(),
+            wait: Duration::ZERO,
+        }
+    }
+
+    /// Wait up to `wait` for a buffer to go back to the pool when none are free, rather than
+    /// failing straight away
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Number of buffers that aren't lent out
+    pub fn available(&self) -> usize {
+        self.free.lock().expect("Buffer pool mutex poisoned").len()
+    }
+
+    /// Lend out a buffer, waiting for one to go back to the pool if none are free
+    pub fn take(self: &Arc<Self>) -> Result<PooledBuffer<B>, BuffersExhausted> {
+        let free = self.free.lock().expect("Buffer pool mutex poisoned");
+        let (mut free, _) = self
+            .returned
+            .wait_timeout_while(free, self.wait, |free| free.is_empty())
+            .expect("Buffer pool mutex poisoned");
+        let buffer = free.pop().ok_or(BuffersExhausted)?;
+        Ok(PooledBuffer {
+            buffer: Some(buffer),
+            len: 0,
+            pool: self.clone(),
+        })
+    }
+}
+
+/// A buffer lent out by a BufferPool, along with how much of it has been written. Goes back to
+/// the pool when dropped.
+pub struct PooledBuffer<B> {
+    /// Only None once the buffer has gone back to the pool
+    buffer: Option<B>,
+    len: usize,
+    pool: Arc<BufferPool<B>>,
+}
+
+impl<B: AsMut<[u8]> + AsRef<[u8]>> PooledBuffer<B> {
+    /// The bytes written to the buffer
+    pub fn bytes(&self) -> &[u8] {
+        &self.buffer().as_ref()[..self.len]
+    }
+
+    /// The whole buffer, such as to find the address it was pinned at
+    pub fn buffer(&self) -> &B {
+        self.buffer.as_ref().expect("buffer is only taken on drop")
+    }
+
+    /// Write to the buffer with `write`, which returns how many bytes it wrote
+    #[cfg(feature = "serde")]
+    pub(crate) fn fill<E>(
+        &mut self,
+        write: impl FnOnce(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<(), E> {
+        let buffer = self.buffer.as_mut().expect("buffer is only taken on drop");
+        self.len = write(buffer.as_mut())?;
+        Ok(())
+    }
+}
+
+impl<B> Drop for PooledBuffer<B> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take()
+            && let Ok(mut free) = self.pool.free.lock()
+        {
+            free.push(buffer);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+/// Returned when a BufferPool has no buffer to lend out, since every one is still in use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuffersExhausted;
+
+impl fmt::Display for BuffersExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "every buffer in the pool is in use")
+    }
+}
+
+impl Error for BuffersExhausted {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DispatchStrategy, Event, Link, Publisher};
+
+    #[derive(Clone)]
+    struct Tick(u32);
+    impl Event for Tick {}
+
+    fn publisher_on(pool: WorkerPool) -> (Publisher, Arc<Mutex<Vec<u32>>>) {
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Executor(Arc::new(pool)));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..4 {
+            let received = received.clone();
+            publisher.subscribe_with(move |tick: Tick| received.lock().unwrap().push(tick.0));
+        }
+        (publisher, received)
+    }
+
+    #[test]
+    fn test_pool_runs_every_handler_whether_spinning_or_parking() {
+        for pool in [
+            WorkerPool::new(2),
+            WorkerPool::new(2).spin_then_park(Duration::from_micros(200)),
+        ] {
+            let (mut publisher, received) = publisher_on(pool);
+            for tick in 0..50 {
+                publisher.publish(Tick(tick)).unwrap();
+                thread::sleep(Duration::from_micros(100));
+            }
+
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 200);
+            assert_eq!(received.iter().sum::<u32>(), 4 * (0..50).sum::<u32>());
+        }
+    }
+
+    #[derive(Clone)]
+    struct Tock(u32);
+    impl Event for Tock {}
+
+    #[test]
+    fn test_handlers_can_publish_on_the_pool_they_run_on() {
+        let mut publisher =
+            Publisher::with_strategy(DispatchStrategy::Executor(Arc::new(WorkerPool::new(1))));
+        // events published on the child bubble up to the handlers below, from within them
+        let child = Mutex::new(publisher.child(Link::default().bubble_up()));
+        publisher.subscribe_with(move |tick: Tick| {
+            child.lock().unwrap().publish(Tock(tick.0)).unwrap();
+        });
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |tock: Tock| received_clone.lock().unwrap().push(tock.0));
+
+        for tick in 0..3 {
+            publisher.publish(Tick(tick)).unwrap();
+        }
+        assert_eq!(*received.lock().unwrap(), [0, 1, 2]);
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{FromEvent, SubscriptionId};
+
+/// A read model built up from events, such as a table of account balances kept up to date from
+/// deposits and withdrawals. Keep one continuously updated from a Publisher's events with
+/// `Publisher::project`.
+pub trait Projection: Send + Sync + 'static {
+    /// The events the read model is built from. Use an enum to build it from several kinds of
+    /// event.
+    type Event: FromEvent;
+    /// A copy of the read model that can be stored and restored later, so it doesn't have to be
+    /// rebuilt from every event since the beginning
+    type Snapshot;
+
+    fn apply(&mut self, event: Self::Event);
+
+    fn snapshot(&self) -> Self::Snapshot;
+
+    fn restore(snapshot: Self::Snapshot) -> Self;
+}
+
+/// Handle to a read model kept up to date by `Publisher::project`
+pub struct Projected<P> {
+    projection: Arc<RwLock<P>>,
+    id: SubscriptionId,
+}
+
+impl<P: Projection> Projected<P> {
+    pub(crate) fn new(projection: Arc<RwLock<P>>, id: SubscriptionId) -> Self {
+        Projected { projection, id }
+    }
+
+    /// The ID of the subscription that keeps the read model up to date, which can be passed to
+    /// `Publisher::unsubscribe` to stop updating it
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Run `f` with the current state of the read model
+    pub fn read<R>(&self, f: impl FnOnce(&P) -> R) -> R {
+        f(&self.projection.read().expect("Projection lock poisoned"))
+    }
+
+    pub fn snapshot(&self) -> P::Snapshot {
+        self.read(P::snapshot)
+    }
+
+    /// Replace the read model with one restored from `snapshot`. Events published from now on are
+    /// applied on top of it.
+    pub fn restore(&self, snapshot: P::Snapshot) {
+        *self.projection.write().expect("Projection lock poisoned") = P::restore(snapshot);
+    }
+
+    /// Rebuild the read model from scratch by applying `events`, such as ones replayed from
+    /// storage, to a fresh default. Events published while it is rebuilt wait until it is done.
+    pub fn rebuild<I>(&self, events: I)
+    where
+        P: Default,
+        I: IntoIterator<Item = P::Event>,
+    {
+        let mut projection = self.projection.write().expect("Projection lock poisoned");
+        *projection = P::default();
+        for event in events {
+            projection.apply(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Publisher};
+
+    #[derive(Clone)]
+    struct Deposited(u32);
+    impl Event for Deposited {}
+
+    #[derive(Default)]
+    struct Balance(u32);
+
+    impl Projection for Balance {
+        type Event = Deposited;
+        type Snapshot = u32;
+
+        fn apply(&mut self, event: Deposited) {
+            self.0 += event.0;
+        }
+
+        fn snapshot(&self) -> u32 {
+            self.0
+        }
+
+        fn restore(snapshot: u32) -> Self {
+            Balance(snapshot)
+        }
+    }
+
+    #[test]
+    fn test_projection_follows_published_events() {
+        let mut publisher = Publisher::default();
+        let balance = publisher.project::<Balance>();
+
+        let _ = publisher.publish(Deposited(5));
+        let _ = publisher.publish(Deposited(10));
+        assert_eq!(balance.snapshot(), 15);
+
+        balance.restore(100);
+        let _ = publisher.publish(Deposited(1));
+        assert_eq!(balance.read(|balance| balance.0), 101);
+
+        balance.rebuild([Deposited(2), Deposited(3)]);
+        assert_eq!(balance.snapshot(), 5);
+
+        publisher.unsubscribe(balance.id()).unwrap();
+        let _ = publisher.publish(Deposited(1));
+        assert_eq!(balance.snapshot(), 5);
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    any::{self, TypeId},
+    cmp::Reverse,
+    ops::ControlFlow,
+    panic::{AssertUnwindSafe, catch_unwind},
+};
+
+use crate::{DynEvent, FromEvent};
+
+type Propagate = dyn Fn(&dyn DynEvent) -> ControlFlow<()> + Send + Sync;
+
+/// A handler subscribed with `Publisher::subscribe_ordered`, which runs before any ordered
+/// handler with a lower priority and can stop the event from reaching them
+pub(crate) struct Ordered {
+    pub(crate) priority: i32,
+    pub(crate) event_type: TypeId,
+    pub(crate) event_type_name: &'static str,
+    pub(crate) label: &'static str,
+    handler: Box<Propagate>,
+}
+
+impl Ordered {
+    pub(crate) fn new<T, F>(priority: i32, handler: F) -> Self
+    where
+        T: FromEvent,
+        F: Fn(T) -> ControlFlow<()> + Send + Sync + 'static,
+    {
+        Ordered {
+            priority,
+            event_type: TypeId::of::<T::Source>(),
+            event_type_name: any::type_name::<T::Source>(),
+            label: any::type_name::<F>(),
+            handler: Box::new(move |event| match T::from_event(event) {
+                Some(event) => handler(event),
+                None => ControlFlow::Continue(()),
+            }),
+        }
+    }
+}
+
+/// Pass an event down a chain of ordered handlers, highest priority first and in the order they
+/// subscribed among equal priorities, until one of them breaks. Handlers that panic let the event
+/// carry on down the chain. Returns the errors from any that panicked.
+pub(crate) fn propagate(
+    chain: &mut [(usize, &Ordered)],
+    event: &dyn DynEvent,
+) -> Vec<Box<dyn std::any::Any + Send + 'static>> {
+    chain.sort_by_key(|(id, ordered)| (Reverse(ordered.priority), *id));
+    let mut errors = Vec::new();
+    for (_, ordered) in chain.iter() {
+        match catch_unwind(AssertUnwindSafe(|| (ordered.handler)(event))) {
+            Ok(ControlFlow::Break(())) => break,
+            Ok(ControlFlow::Continue(())) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct Click;
+    impl Event for Click {}
+
+    #[test]
+    fn test_propagate_stops_at_break() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str, flow: ControlFlow<()>| {
+            let seen = seen.clone();
+            move |_click: Click| {
+                seen.lock().unwrap().push(name);
+                flow
+            }
+        };
+        let low = Ordered::new(0, record("low", ControlFlow::Continue(())));
+        let high = Ordered::new(10, record("high", ControlFlow::Continue(())));
+        let modal = Ordered::new(5, record("modal", ControlFlow::Break(())));
+        let also_modal = Ordered::new(5, record("also modal", ControlFlow::Continue(())));
+
+        let mut chain = vec![(4, &also_modal), (1, &low), (2, &high), (3, &modal)];
+        assert!(propagate(&mut chain, &Click).is_empty());
+        assert_eq!(*seen.lock().unwrap(), vec!["high", "modal"]);
+    }
+}
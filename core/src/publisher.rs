@@ -1,10 +1,71 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    any::{Any, TypeId},
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::ControlFlow,
+    panic::{AssertUnwindSafe, RefUnwindSafe},
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock, Weak,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
     thread,
+    time::{Duration, Instant},
 };
 
-use crate::{DynEvent, DynHandle, DynHandleMut, Event, Handler};
+use crate::{
+    Balance, Cancellable, ChromeTrace, DeadLetter, Delivery, Diffable, DispatchStrategy, DynEvent,
+    DynHandle, DynHandleBatch, DynHandleMut, EnvelopeHandler, Event, EventInfo, Fallible, Flow,
+    FromEvent, HandleCollect, HandleMut, Handler, HandlerError, HandlerMut, HandlerPanic,
+    HandlerPoisoned, IntoHandler, Job, JobInfo, Join, JoinFn, Link, LoadThresholds, LoadTier,
+    Metadata, Middleware, Named, Overflow, PanicFormatter, PanicMessage, PanicPolicy, Partition,
+    Projected, Projection, PublishErrors, PublishReport, Race, RaceTimedOut, RateLimit, Replies,
+    Request, RetryPolicy, Routing, Sampling, ScheduleHandle, ScheduleId, ScheduledInfo,
+    SubscriptionId, Transaction, TransactionError, TryHandle, TryHandleMut, UnsubscribeError,
+    Unsubscribed, Update, Watchdog,
+    batch::Batched,
+    collect::{CollectFn, Collecting, Collector},
+    dead_letter::{DeadLetterHandler, Fallback},
+    diff::Latest,
+    envelope::{Envelope, Origin, Published},
+    gate::Gate,
+    group::{Groups, Member},
+    join::JoinHandler,
+    load::Load,
+    mailbox::Mailbox,
+    mirror::{Forward, Mirror},
+    overflow::Overflows,
+    panic_message,
+    partition::Partitioned,
+    pause::Paused,
+    platform,
+    propagation::{Ordered, propagate},
+    race::{RaceHandler, Timeout},
+    rate_limit::Limited,
+    request::{Requested, Responder},
+    retry::{Retried, Retries, Retry},
+    routing::Routes,
+    sampling::Samplers,
+    scheduler::{Pending, Scheduler},
+    scope::Relayed,
+    subscription::HandlerInfo,
+    subscription::{PublisherId, Removed},
+    telemetry::{HandlerTelemetry, Outcome, PublishTelemetry},
+    topic::TopicTree,
+    topology,
+    transaction::Commit,
+    transform::Transform,
+    wait::InFlight,
+};
+
+thread_local! {
+    /// Number of events delivered on this thread after their deadlines had passed, so that
+    /// `publish_and_wait` can tell whether its own event missed one while other threads publish
+    static MISSED_DEADLINES: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+type PanicHook = dyn Fn(&str, &str) + Send + Sync;
 
 /// Publishes all Events to all subscribed Handlers that accept Events of that type
 /// # Examples
@@ -26,38 +87,458 @@ use crate::{DynEvent, DynHandle, DynHandleMut, Event, Handler};
 /// ```
 #[derive(Default)]
 pub struct Publisher {
-    handler_count: usize,
-    handlers: HashMap<usize, HandlerType>,
+    shared: Arc<Shared>,
+    /// Whether this is a handle to a Publisher owned elsewhere, which shouldn't flush on drop
+    handle: bool,
+}
+
+/// State of a Publisher that is shared with its scheduler thread so that delayed events can be
+/// published in the background
+#[derive(Default)]
+struct Shared {
+    handler_count: AtomicUsize,
+    handlers: RwLock<HashMap<usize, Arc<HandlerType>>>,
+    /// Handlers subscribed with `subscribe` or `subscribe_mut`, as their own types, so that they
+    /// can be downcast once unsubscribed
+    typed: RwLock<HashMap<usize, Arc<dyn Any + Send + Sync>>>,
+    middleware: RwLock<Vec<Arc<dyn Middleware>>>,
+    source: Option<String>,
+    sequence: AtomicU64,
+    missed_deadlines: AtomicU64,
+    scheduler: OnceLock<Scheduler>,
+    /// Events scheduled with `publish_after` or `publish_every` that haven't been published yet
+    pending: Pending,
+    /// Stack size in bytes of the Publisher's dedicated threads, or 0 for the platform's default
+    stack_size: AtomicUsize,
+    in_flight: Arc<InFlight>,
+    panic_formatter: RwLock<Option<PanicFormatter>>,
+    panic_policy: RwLock<PanicPolicy>,
+    /// Told about the panics PanicPolicy::LogAndContinue logs, if set with `on_panic`
+    panic_hook: RwLock<Option<Box<PanicHook>>>,
+    trace: RwLock<Option<Arc<ChromeTrace>>>,
+    watchdog: RwLock<Option<Arc<Watchdog>>>,
+    /// Retry policies set with `set_retry_policy`, shared with the retries still to run
+    retries: Arc<Retries>,
+    strategy: DispatchStrategy,
+    load: Load,
+    /// The highest load at which each subscription marked with `shed_above` receives events
+    shed: RwLock<HashMap<usize, LoadTier>>,
+    publisher: PublisherId,
+    paused: Paused,
+    overflows: Overflows,
+    /// Subscriptions made with `subscribe_default` or `subscribe_dead_letter`, which only receive
+    /// the events that no handler ranked ahead of them takes
+    fallbacks: RwLock<HashMap<usize, Fallback>>,
+    /// Subscriptions added to each group with `add_to_group`
+    members: RwLock<HashMap<String, HashSet<usize>>>,
+    topics: RwLock<TopicTree>,
+    groups: Groups,
+    routes: Routes,
+    samplers: Samplers,
+    gate: Gate,
+    /// The Publisher this one is a child of, if any, and how events cross between them
+    parent: Option<(Weak<Shared>, Link)>,
+    transforms: RwLock<BTreeMap<usize, Transform>>,
+    /// Children this Publisher pushes events down to, and Publishers it is bridged to
+    downstream: RwLock<Vec<(Weak<Shared>, Link)>>,
+    /// Identifies this Publisher to the Publishers it's bridged to with `crier::net`
+    #[cfg(feature = "net")]
+    peer: crate::net::PeerId,
+}
+
+/// Lock a mut handler even if it panicked while holding the lock, to ask it about itself. Only
+/// handlers that haven't panicked are given events.
+fn lock_handler<'a>(
+    mutex: &'a Mutex<dyn DynHandleMut + 'static>,
+) -> MutexGuard<'a, dyn DynHandleMut + 'static> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 /// Represents the different types of handler and how they are stored in the `handlers` map on the
 /// Publisher
-enum HandlerType {
+pub(crate) enum HandlerType {
     Sync(Arc<dyn DynHandle>),
     SyncMut(Arc<Mutex<dyn DynHandleMut>>),
+    Limited(Limited),
+    Batch(Batched),
+    Metadata(Box<dyn Fn(EventInfo) + Send + Sync>),
+    Partitioned(Partitioned),
+    /// A handler that competes with the other members of its group for events
+    Grouped(Member),
+    /// A handler that only receives events published to topics matching `filter`
+    Topic {
+        filter: String,
+        handler: Arc<dyn DynHandle>,
+    },
+    /// A handler that takes its turn with an event in order of priority, and can stop it from
+    /// reaching those with lower priority
+    Ordered(Ordered),
+}
+
+impl HandlerType {
+    /// The handler, unless it is a kind that isn't a DynHandle, like mutable or batch handlers
+    pub(crate) fn dyn_handle(&self) -> Option<&Arc<dyn DynHandle>> {
+        match self {
+            HandlerType::Sync(handler) | HandlerType::Topic { handler, .. } => Some(handler),
+            HandlerType::Limited(limited) => Some(&limited.handler),
+            HandlerType::Partitioned(partitioned) => Some(&partitioned.handler),
+            HandlerType::Grouped(member) => Some(&member.handler),
+            _ => None,
+        }
+    }
+
+    /// Whether the handler might receive `event`
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        match self {
+            HandlerType::Sync(dyn_handle) => dyn_handle.accepts(event),
+            HandlerType::SyncMut(mutex) => lock_handler(mutex).accepts(event),
+            HandlerType::Limited(limited) => limited.handler.accepts(event),
+            HandlerType::Batch(batched) => batched.handler.accepts(event),
+            HandlerType::Partitioned(partitioned) => partitioned.handler.accepts(event),
+            HandlerType::Grouped(member) => member.handler.accepts(event),
+            HandlerType::Topic { handler, .. } => handler.accepts(event),
+            HandlerType::Ordered(ordered) => ordered.event_type == event.get_data().type_id(),
+            HandlerType::Metadata(_) => false,
+        }
+    }
+
+    /// Whether the handler is ready for more events. Only handlers that take events through a
+    /// DynHandle or DynHandleMut can say they aren't.
+    fn poll_ready(&self) -> bool {
+        match self {
+            HandlerType::Sync(dyn_handle) => dyn_handle.poll_ready(),
+            HandlerType::SyncMut(mutex) => lock_handler(mutex).poll_ready(),
+            HandlerType::Limited(limited) => limited.handler.poll_ready(),
+            HandlerType::Partitioned(partitioned) => partitioned.handler.poll_ready(),
+            HandlerType::Grouped(member) => member.handler.poll_ready(),
+            HandlerType::Topic { handler, .. } => handler.poll_ready(),
+            HandlerType::Batch(_) | HandlerType::Metadata(_) | HandlerType::Ordered(_) => true,
+        }
+    }
+
+    /// Whether the handler could receive any event of the given type. If `remote`, only handlers
+    /// that accept events received from remote Publishers count.
+    fn accepts_type(&self, event_type: TypeId, remote: bool) -> bool {
+        let accepts = |handler: &dyn DynHandle| {
+            handler.accepts_type(event_type) && (!remote || handler.accepts_remote())
+        };
+        match self {
+            HandlerType::Sync(dyn_handle) => accepts(dyn_handle.as_ref()),
+            HandlerType::SyncMut(mutex) => lock_handler(mutex).accepts_type(event_type),
+            HandlerType::Limited(limited) => accepts(limited.handler.as_ref()),
+            HandlerType::Batch(batched) => batched.handler.accepts_type(event_type),
+            HandlerType::Partitioned(partitioned) => accepts(partitioned.handler.as_ref()),
+            HandlerType::Grouped(member) => accepts(member.handler.as_ref()),
+            HandlerType::Topic { handler, .. } => accepts(handler.as_ref()),
+            HandlerType::Ordered(ordered) => ordered.event_type == event_type,
+            // metadata handlers are told about every event
+            HandlerType::Metadata(_) => true,
+        }
+    }
+
+    /// How the handler was subscribed, the event type it takes if it only takes one, and its label
+    fn describe(&self) -> (&'static str, Option<&'static str>, &'static str) {
+        let describe =
+            |kind, handler: &dyn DynHandle| (kind, handler.event_type_name(), handler.label());
+        match self {
+            HandlerType::Sync(dyn_handle) => describe("sync", dyn_handle.as_ref()),
+            HandlerType::SyncMut(mutex) => {
+                let handler = lock_handler(mutex);
+                ("mut", handler.event_type_name(), handler.label())
+            }
+            HandlerType::Limited(limited) => describe("limited", limited.handler.as_ref()),
+            HandlerType::Batch(batched) => (
+                "batch",
+                batched.handler.event_type_name(),
+                batched.handler.label(),
+            ),
+            HandlerType::Metadata(_) => ("metadata", None, "metadata"),
+            HandlerType::Partitioned(partitioned) => {
+                describe("partitioned", partitioned.handler.as_ref())
+            }
+            HandlerType::Grouped(member) => describe("grouped", member.handler.as_ref()),
+            HandlerType::Topic { handler, .. } => describe("topic", handler.as_ref()),
+            HandlerType::Ordered(ordered) => {
+                ("ordered", Some(ordered.event_type_name), ordered.label)
+            }
+        }
+    }
 }
 
 impl Publisher {
+    /// Create a Publisher whose events carry `source` in their metadata
+    pub fn with_source(source: impl Into<String>) -> Self {
+        Publisher {
+            shared: Arc::new(Shared {
+                source: Some(source.into()),
+                ..Default::default()
+            }),
+            handle: false,
+        }
+    }
+
+    /// Create a Publisher that runs its handlers according to `strategy`, rather than giving each
+    /// one a thread of its own
+    pub fn with_strategy(strategy: DispatchStrategy) -> Self {
+        Publisher {
+            shared: Arc::new(Shared {
+                strategy,
+                ..Default::default()
+            }),
+            handle: false,
+        }
+    }
+
+    /// Another Publisher sharing this one's handlers and state, which doesn't flush batches when
+    /// it is dropped
+    #[cfg(feature = "global")]
+    pub(crate) fn handle(&self) -> Self {
+        Publisher {
+            shared: self.shared.clone(),
+            handle: true,
+        }
+    }
+
+    /// Create a Publisher scoped within this one, such as for a UI scene or a plugin, with
+    /// handlers of its own. Depending on `link`, events published on the child also reach this
+    /// Publisher's handlers, and events published here also reach the child's. Events with
+    /// Exclusive delivery are only ever handled by the Publisher they are published on.
+    pub fn child(&self, link: Link) -> Publisher {
+        let shared = Arc::new(Shared {
+            strategy: self.shared.strategy.clone(),
+            parent: Some((Arc::downgrade(&self.shared), link.clone())),
+            ..Default::default()
+        });
+        if link.push_down {
+            self.shared
+                .downstream
+                .write()
+                .expect("Downstream lock poisoned")
+                .push((Arc::downgrade(&shared), link));
+        }
+
+        Publisher {
+            shared,
+            handle: false,
+        }
+    }
+
+    /// Also publish events published here for which `filter` returns true on `other`, until
+    /// `other` is dropped. Events forwarded from another Publisher aren't forwarded again, so
+    /// two Publishers can be bridged both ways.
+    /// # Examples
+    /// ```
+    /// use crier::{Event, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Saved;
+    ///
+    /// let mut storage = Publisher::default();
+    /// let mut ui = Publisher::default();
+    /// storage.bridge_to(&ui, |event| event.get_data().is::<Saved>());
+    /// ```
+    pub fn bridge_to<F>(&mut self, other: &Publisher, filter: F)
+    where
+        F: Fn(&dyn DynEvent) -> bool + Send + Sync + 'static,
+    {
+        self.shared
+            .downstream
+            .write()
+            .expect("Downstream lock poisoned")
+            .push((Arc::downgrade(&other.shared), Link::bridge(filter)));
+    }
+
+    /// Also publish a copy of each event of type `A` published here on `other`, as transformed by
+    /// `transform`, until `other` is dropped. Events for which `transform` returns None aren't
+    /// mirrored, so it can anonymize or sample them. The transform runs on the Publisher's
+    /// scheduler thread, as does publishing on `other`, so a production bus can feed a diagnostics
+    /// bus without either slowing its own handlers. `publish_and_wait` waits for events to be
+    /// mirrored, and returns the panics of `transform`.
+    /// Returns the ID needed to stop mirroring with `unsubscribe`.
+    /// # Examples
+    /// ```
+    /// use crier::{Event, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Purchase {
+    ///     card_number: String,
+    ///     cents: u64,
+    /// }
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Sale(u64);
+    ///
+    /// let mut production = Publisher::default();
+    /// let diagnostics = Publisher::default();
+    /// production.mirror_to(&diagnostics, |purchase: Purchase| Some(Sale(purchase.cents)));
+    /// ```
+    pub fn mirror_to<A, B, F>(&mut self, other: &Publisher, transform: F) -> SubscriptionId
+    where
+        A: FromEvent + Send,
+        B: Event,
+        F: Fn(A) -> Option<B> + Send + Sync + 'static,
+    {
+        let other = Arc::downgrade(&other.shared);
+        let forward: Arc<Forward<B>> = Arc::new(move |event: B| {
+            if let Some(other) = other.upgrade() {
+                let _ = other.dispatch(event, None);
+            }
+        });
+        self.subscribe(Mirror::new(
+            transform,
+            forward,
+            self.shared.scheduler().timer(),
+            self.shared.in_flight.clone(),
+        ))
+    }
+
+    /// Create a Publisher that also receives every event published on each of `publishers`, so
+    /// that a handler subscribed to it once hears from all of them, e.g. both a network bridge and
+    /// the local bus. Events published on the merged Publisher itself only reach its own handlers.
+    /// Each of `publishers` stops forwarding to it once it is dropped.
+    pub fn merge<'a>(publishers: impl IntoIterator<Item = &'a mut Publisher>) -> Publisher {
+        let merged = Publisher::default();
+        for publisher in publishers {
+            publisher.bridge_to(&merged, |_event| true);
+        }
+
+        merged
+    }
+
     /// Subscribe a handler to the publisher so that the handler receives all published events.
     /// Returns the ID needed to `unsubscribe` the handler.
-    pub fn subscribe<T>(&mut self, handler: T) -> usize
+    pub fn subscribe<T>(&mut self, handler: T) -> SubscriptionId
     where
         T: DynHandle + 'static,
     {
-        let handler: Arc<dyn DynHandle> = Arc::new(handler);
-        let id = self.handler_count + 1;
-        // self.handlers.insert(id, handler);
-        self.handlers.insert(id, HandlerType::Sync(handler));
-        self.handler_count = id;
+        let handler = Arc::new(handler);
+        let id = self.shared.insert(HandlerType::Sync(handler.clone()));
+        self.shared.keep_typed(id, handler);
+        self.shared.subscription(id)
+    }
 
-        id
+    /// Subscribe every handler in `handlers` at once, taking the Publisher's locks once rather than
+    /// once per handler, for setups that build hundreds of handlers from configuration. Handlers
+    /// of different types can be subscribed together as `Box<dyn DynHandle>`. Unlike with
+    /// `subscribe`, they can't be downcast once unsubscribed.
+    /// Returns the IDs needed to `unsubscribe` the handlers, in the same order.
+    /// # Examples
+    /// ```
+    /// use crier::{DynHandle, Event, Handler, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Tick;
+    ///
+    /// let names = ["physics", "audio", "ui"];
+    /// let handlers = names.map(|name| {
+    ///     Box::new(Handler::new(move |_tick: Tick| println!("{name} ticked"))) as Box<dyn DynHandle>
+    /// });
+    ///
+    /// let mut publisher = Publisher::default();
+    /// let ids = publisher.subscribe_all(handlers);
+    /// assert_eq!(ids.len(), 3);
+    /// assert_eq!(publisher.handler_count(), 3);
+    /// ```
+    pub fn subscribe_all<I>(&mut self, handlers: I) -> Vec<SubscriptionId>
+    where
+        I: IntoIterator,
+        I::Item: IntoHandler,
+    {
+        let handlers = handlers
+            .into_iter()
+            .map(|handler| HandlerType::Sync(handler.into_handler()))
+            .collect();
+        self.shared
+            .insert_all(handlers)
+            .into_iter()
+            .map(|id| self.shared.subscription(id))
+            .collect()
+    }
+
+    /// Like `subscribe_all`, but add every handler to `group` as `add_to_group` does, so that they
+    /// can be managed together.
+    /// Returns the IDs needed to `unsubscribe` the handlers, in the same order.
+    pub fn subscribe_all_to_group<I>(
+        &mut self,
+        group: impl Into<String>,
+        handlers: I,
+    ) -> Vec<SubscriptionId>
+    where
+        I: IntoIterator,
+        I::Item: IntoHandler,
+    {
+        let handlers = handlers
+            .into_iter()
+            .map(|handler| HandlerType::Sync(handler.into_handler()))
+            .collect();
+        let ids = self.shared.insert_all(handlers);
+        self.shared
+            .members
+            .write()
+            .expect("Members lock poisoned")
+            .entry(group.into())
+            .or_default()
+            .extend(ids.iter().copied());
+        ids.into_iter()
+            .map(|id| self.shared.subscription(id))
+            .collect()
+    }
+
+    /// Subscribe a handler with a name, such as "audio::on_pause", that identifies it when
+    /// inspecting the Publisher and in the errors returned when it panics. See `Named`.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_named<T>(&mut self, name: &'static str, handler: T) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+    {
+        self.subscribe(Named::new(name, handler))
+    }
+
+    /// Subscribe a fallback handler, which only receives the events that no other handler takes.
+    /// This lets a library handle its own events sensibly, such as by logging its warnings, until
+    /// the application subscribes a handler of its own for them. Handlers that take every type of
+    /// event count as handlers of their own.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_default<T>(&mut self, handler: T) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+    {
+        let handler = Arc::new(handler);
+        let id = self.shared.insert(HandlerType::Sync(handler.clone()));
+        self.shared.keep_typed(id, handler);
+        self.shared
+            .fallbacks
+            .write()
+            .expect("Fallbacks lock poisoned")
+            .insert(id, Fallback::Default);
+        self.shared.subscription(id)
+    }
+
+    /// Subscribe a closure to every event that no other handler takes, including handlers
+    /// subscribed with `subscribe_default`, along with the name of its type, so that events
+    /// nobody is listening for don't go missing silently. Handlers that take every type of event,
+    /// like bridges, count as taking them.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_dead_letter<F>(&mut self, handler: F) -> SubscriptionId
+    where
+        F: Fn(DeadLetter) + Send + Sync + 'static,
+    {
+        let id = self
+            .shared
+            .insert(HandlerType::Sync(Arc::new(DeadLetterHandler::new(handler))));
+        self.shared
+            .fallbacks
+            .write()
+            .expect("Fallbacks lock poisoned")
+            .insert(id, Fallback::DeadLetter);
+        self.shared.subscription(id)
     }
 
     // Subscribe a closure to events of its input type.
     // Returns the ID needed to `unsubscribe` the handler.
-    pub fn subscribe_with<T, F>(&mut self, handler: F) -> usize
+    pub fn subscribe_with<T, F>(&mut self, handler: F) -> SubscriptionId
     where
-        T: Event,
+        T: FromEvent,
         F: Fn(T) + Send + Sync + 'static,
     {
         let wrapped = Handler::new(handler);
@@ -65,226 +546,4968 @@ impl Publisher {
         self.subscribe(wrapped)
     }
 
-    pub fn subscribe_mut<T>(&mut self, handler: T) -> usize
+    /// Subscribe a closure that mutates what it captures to events of its input type, as
+    /// `subscribe_mut` would a HandleMut, so that it is never called on two threads at once.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    /// # Examples
+    /// ```
+    /// use crier::{Event, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Tick;
+    ///
+    /// let mut publisher = Publisher::default();
+    /// let mut ticks = 0;
+    /// publisher.subscribe_with_mut(move |_tick: Tick| {
+    ///     ticks += 1;
+    ///     println!("{ticks} ticks");
+    /// });
+    /// publisher.publish(Tick).unwrap();
+    /// ```
+    pub fn subscribe_with_mut<T, F>(&mut self, handler: F) -> SubscriptionId
     where
-        T: DynHandleMut + 'static,
+        T: FromEvent,
+        F: FnMut(T) + Send + 'static,
     {
-        let handler: Arc<Mutex<dyn DynHandleMut>> = Arc::new(Mutex::new(handler));
-        let id = self.handler_count + 1;
-        self.handlers.insert(id, HandlerType::SyncMut(handler));
-        self.handler_count = id;
+        self.subscribe_mut(HandlerMut::new(handler))
+    }
 
-        id
+    /// Subscribe a closure to events of its input type, wrapped in an Envelope with the metadata
+    /// they were published with.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_envelope<T, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        T: FromEvent,
+        F: Fn(Envelope<T>) + Send + Sync + 'static,
+    {
+        self.subscribe(EnvelopeHandler::new(handler))
     }
 
-    /// Remove a handler from the publisher so that it stops receiving events
-    pub fn unsubscribe(&mut self, id: usize) {
-        self.handlers.remove_entry(&id);
+    pub fn subscribe_mut<T>(&mut self, handler: T) -> SubscriptionId
+    where
+        T: DynHandleMut + 'static,
+    {
+        let handler = Arc::new(Mutex::new(handler));
+        let id = self.shared.insert(HandlerType::SyncMut(handler.clone()));
+        self.shared.keep_typed(id, handler);
+        self.shared.subscription(id)
     }
-    /// Remove a mut handler from the publisher so that it stops receiving events
-    pub fn unsubscribe_mut(&mut self, id: usize) {
-        self.handlers.remove_entry(&id);
+
+    /// Subscribe a mut handler as an actor, with a thread of its own that it handles its events
+    /// on, one at a time and in the order they were published. `publish` only queues the events,
+    /// so a slow handler doesn't hold up publishing, and the handler never runs on two threads at
+    /// once. `publish_and_wait` waits for the queue to empty and returns the panics of the handler.
+    /// Once it has panicked, it is given no more events, and each is reported as a HandlerError
+    /// wrapping HandlerPoisoned. Its thread stops once it is unsubscribed and has handled what was
+    /// queued. On targets without threads, it handles its events as they are published instead.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use crier::{Event, HandleMut, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Frame(Vec<u8>);
+    ///
+    /// struct Recorder {
+    ///     written: usize,
+    /// }
+    ///
+    /// impl HandleMut for Recorder {
+    ///     type EventType = Frame;
+    ///
+    ///     fn handle_mut(&mut self, frame: Frame) {
+    ///         // compressing and writing the frame to disk takes a while
+    ///         self.written += frame.0.len();
+    ///     }
+    /// }
+    ///
+    /// let mut publisher = Publisher::default();
+    /// publisher.subscribe_actor(Recorder { written: 0 });
+    /// let report = publisher.publish_and_wait(Frame(vec![0; 1024]), Duration::from_secs(1));
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn subscribe_actor<T>(&mut self, handler: T) -> SubscriptionId
+    where
+        T: HandleMut + Send + 'static,
+        T::EventType: Send,
+    {
+        let mailbox = Mailbox::new(
+            handler,
+            self.shared.in_flight.clone(),
+            self.shared.stack_size(),
+        );
+        self.subscribe(mailbox)
     }
 
-    /// Publish an event to all subscribed handlers, utilizing as many threads as possible to run
-    /// handlers in parallel
-    pub fn publish<T>(
-        &mut self,
-        event: T,
-    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    /// Subscribe a handler that returns a Result, for failures that are expected rather than
+    /// bugs. The errors it returns are reported by `publish` as `HandlerError`s after any panics,
+    /// and `try_publish` keeps the two apart.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    /// # Examples
+    /// ```
+    /// use std::fmt;
+    /// use crier::{Event, Publisher, TryHandle};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Save(u32);
+    ///
+    /// #[derive(Debug)]
+    /// struct DiskFull;
+    ///
+    /// impl fmt::Display for DiskFull {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         write!(f, "disk full")
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for DiskFull {}
+    ///
+    /// struct SaveHandler;
+    ///
+    /// impl TryHandle for SaveHandler {
+    ///     type EventType = Save;
+    ///     type Error = DiskFull;
+    ///
+    ///     fn try_handle(&self, save: Save) -> Result<(), DiskFull> {
+    ///         if save.0 > 100 { Err(DiskFull) } else { Ok(()) }
+    ///     }
+    /// }
+    ///
+    /// let mut publisher = Publisher::default();
+    /// publisher.subscribe_try(SaveHandler);
+    ///
+    /// assert!(publisher.try_publish(Save(1)).is_ok());
+    /// let errors = publisher.try_publish(Save(101)).unwrap_err();
+    /// assert!(errors.panics.is_empty());
+    /// assert!(errors.failures[0].downcast_ref::<DiskFull>().is_some());
+    /// ```
+    pub fn subscribe_try<T>(&mut self, handler: T) -> SubscriptionId
     where
-        T: DynEvent,
+        T: TryHandle + Send + Sync + RefUnwindSafe + 'static,
     {
-        let event: Arc<dyn DynEvent> = Arc::new(event);
-        let max_threads = thread::available_parallelism()
-            .map(|n| n.get())
-            .unwrap_or(1);
+        self.subscribe(Fallible::new(handler))
+    }
 
-        let mut errors = Vec::new();
+    /// Subscribe a handler that mutates itself and returns a Result. See `subscribe_try`.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_try_mut<T>(&mut self, handler: T) -> SubscriptionId
+    where
+        T: TryHandleMut + Send + 'static,
+    {
+        self.subscribe_mut(Fallible::new(handler))
+    }
 
-        thread::scope(|s| {
-            let mut active_handles: Vec<
-                thread::ScopedJoinHandle<Result<(), Box<dyn std::any::Any + Send + 'static>>>,
-            > = Vec::new();
+    /// Subscribe a handler whose events are throttled or debounced according to `limit`.
+    /// Debounced handlers are run from the Publisher's scheduler thread, so any errors they return
+    /// are discarded unless the event was published with `publish_and_wait`.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_limited<T>(&mut self, handler: T, limit: RateLimit) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+    {
+        let handler: Arc<dyn DynHandle> = Arc::new(handler);
+        let id = self
+            .shared
+            .insert(HandlerType::Limited(Limited::new(handler, limit)));
+        self.shared.subscription(id)
+    }
 
-            for handler in self.handlers.values() {
-                match handler {
-                    HandlerType::Sync(dyn_handle) => {
-                        // if we hit the max number of threads, join the oldest before spawning a new one
-                        if active_handles.len() >= max_threads {
-                            let handle = active_handles.remove(0);
-                            if let Err(e) = handle.join().unwrap_or_else(Err) {
-                                errors.push(e);
-                            }
-                        }
+    /// Subscribe a handler that receives events in batches of up to `max_size` rather than one at
+    /// a time. A batch is delivered as soon as it is full, and partial batches are delivered when
+    /// the Publisher is flushed or dropped.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_batch<T>(&mut self, handler: T, max_size: usize) -> SubscriptionId
+    where
+        T: DynHandleBatch + 'static,
+    {
+        let handler: Arc<dyn DynHandleBatch> = Arc::new(handler);
+        let id = self
+            .shared
+            .insert(HandlerType::Batch(Batched::new(handler, max_size)));
+        self.shared.subscription(id)
+    }
 
-                        let handler_clone = Arc::clone(dyn_handle);
-                        let cloned_event = event.clone();
-                        active_handles.push(s.spawn(move || {
-                            std::panic::catch_unwind(|| {
-                                handler_clone.dyn_handle(cloned_event.as_ref())
-                            })
-                        }));
-                    }
-                    HandlerType::SyncMut(mutex) => {
-                        // mutable handlers are called in series to prevent problems caused by simultaneous
-                        // mutation of the same object
-                        let handler_mut_clone = Arc::clone(mutex);
-                        let cloned_event = event.clone();
-
-                        let mut handler_guard =
-                            handler_mut_clone.lock().expect("Handler mutex poisoned");
-                        handler_guard.dyn_handle_mut(cloned_event.as_ref());
-                    }
-                }
-                // if we hit the max number of threads, join the oldest before spawning a new one
-                if active_handles.len() >= max_threads {
-                    let handle = active_handles.remove(0);
-                    if let Err(e) = handle.join().unwrap_or_else(Err) {
-                        errors.push(e);
-                    }
-                }
-            }
+    /// Subscribe a closure to information about every published event, including its type name,
+    /// size and metadata, without receiving the event itself. Useful for cheap auditing and
+    /// metrics, since events are never cloned for it. The closure runs on the publishing thread.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_metadata<F>(&mut self, handler: F) -> SubscriptionId
+    where
+        F: Fn(EventInfo) + Send + Sync + 'static,
+    {
+        self.shared
+            .subscription(self.shared.insert(HandlerType::Metadata(Box::new(handler))))
+    }
 
-            for handle in active_handles {
-                if let Err(e) = handle.join().unwrap_or_else(Err) {
-                    errors.push(e);
-                }
-            }
+    /// Subscribe a handler to the events published with `publish_to` to topics matching `filter`.
+    /// Topics are split into `/`-separated levels, such as `"sensors/kitchen/temperature"`, and a
+    /// filter can use `+` to match any single level, as in `"ui/+/click"`, or end with `#` to
+    /// match a whole subtree, as in `"sensors/#"`. Events with `Delivery::Exclusive` are never
+    /// delivered to topic subscriptions.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_topic<T>(&mut self, filter: impl Into<String>, handler: T) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+    {
+        let filter = filter.into();
+        let handler: Arc<dyn DynHandle> = Arc::new(handler);
+        let id = self.shared.insert(HandlerType::Topic {
+            filter: filter.clone(),
+            handler,
         });
+        self.shared
+            .topics
+            .write()
+            .expect("Topic lock poisoned")
+            .insert(&filter, id);
 
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
-        }
+        self.shared.subscription(id)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::Event;
+    /// Subscribe a handler that handles events with the same partition key one at a time and in
+    /// the order they were published, while events with different keys are handled in parallel.
+    /// Keys are taken from events that implement `Partition`, and events without one are handled
+    /// in order with each other.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_partitioned<T>(&mut self, handler: T) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+    {
+        let handler: Arc<dyn DynHandle> = Arc::new(handler);
+        let key = Box::new(|event: &dyn DynEvent| event.dyn_partition_key());
+        let id = self
+            .shared
+            .insert(HandlerType::Partitioned(Partitioned::new(handler, key)));
+        self.shared.subscription(id)
+    }
 
-    use super::*;
-    use std::sync::{Arc, Mutex};
+    /// Subscribe a closure to the latest value of each key of some keyed state, such as the state
+    /// of each entity shown in a UI. Values are keyed by their `Partition`, and each is delivered
+    /// as an `Update` listing the fields that changed since the previous value for its key, so
+    /// that the closure can apply a minimal update rather than redrawing the whole value. Values
+    /// that are identical to the previous one for their key aren't delivered.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_latest<T, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        T: Event + Clone + Partition + Diffable,
+        F: Fn(Update<T>) + Send + Sync + 'static,
+    {
+        self.subscribe(Latest::new(handler))
+    }
 
-    #[derive(Clone)]
-    struct TestEvent;
-    impl Event for TestEvent {}
+    /// Like `subscribe_partitioned`, but takes the partition key of events of type `E` with `key`
+    /// rather than from their `Partition` implementation
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_partitioned_by<T, E, K, F>(&mut self, handler: T, key: F) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+        E: Event,
+        K: Hash,
+        F: Fn(&E) -> K + Send + Sync + 'static,
+    {
+        let handler: Arc<dyn DynHandle> = Arc::new(handler);
+        let key = Box::new(move |event: &dyn DynEvent| {
+            let event = event.get_data().downcast_ref::<E>()?;
+            let mut hasher = DefaultHasher::new();
+            key(event).hash(&mut hasher);
+            Some(hasher.finish())
+        });
+        let id = self
+            .shared
+            .insert(HandlerType::Partitioned(Partitioned::new(handler, key)));
+        self.shared.subscription(id)
+    }
 
-    struct TestHandler {
-        called: Arc<Mutex<bool>>,
+    /// Subscribe a closure that takes its turn with events of its input type in order of
+    /// `priority`, highest first. Returning `ControlFlow::Break` stops the event from reaching
+    /// ordered handlers with a lower priority, the way a dialog can keep clicks from the window
+    /// behind it. Ordered handlers run one after another on the publishing thread, before the
+    /// event is handed to other handlers, which always receive it. Handlers with the same priority
+    /// run in the order they subscribed.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_ordered<T, F>(&mut self, priority: i32, handler: F) -> SubscriptionId
+    where
+        T: FromEvent,
+        F: Fn(T) -> ControlFlow<()> + Send + Sync + 'static,
+    {
+        let id = self
+            .shared
+            .insert(HandlerType::Ordered(Ordered::new(priority, handler)));
+        self.shared.subscription(id)
     }
-    impl DynHandle for TestHandler {
-        fn dyn_handle(&self, _event: &dyn DynEvent) {
-            let mut called = self.called.lock().unwrap();
-            *called = true;
-        }
+
+    /// Subscribe a handler as a member of `group`. Members of a group compete for events rather
+    /// than all receiving them: each event goes to exactly one of the members that accept it,
+    /// chosen according to the group's `Balance`, which is round robin unless set with
+    /// `set_group_balance`. Useful for sharing work out between handlers.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_group<T>(&mut self, group: impl Into<String>, handler: T) -> SubscriptionId
+    where
+        T: DynHandle + 'static,
+    {
+        let handler: Arc<dyn DynHandle> = Arc::new(handler);
+        let id = self
+            .shared
+            .insert(HandlerType::Grouped(Member::new(group.into(), handler)));
+        self.shared.subscription(id)
     }
 
-    struct TestHandlerMut {
-        called: Arc<Mutex<bool>>,
+    /// Choose how the members of `group` share out events
+    pub fn set_group_balance(&mut self, group: impl Into<String>, balance: Balance) {
+        self.shared.groups.set_balance(group.into(), balance);
     }
-    impl DynHandleMut for TestHandlerMut {
-        fn dyn_handle_mut(&mut self, _event: &dyn DynEvent) {
-            let mut called = self.called.lock().unwrap();
-            *called = true;
+
+    /// Stop or resume delivering events to the members of `group`. Groups are enabled by default.
+    pub fn set_group_enabled(&mut self, group: impl Into<String>, enabled: bool) {
+        self.shared.groups.set_enabled(group.into(), enabled);
+    }
+
+    /// Whether events are being delivered to the members of `group`
+    pub fn group_enabled(&self, group: &str) -> bool {
+        self.shared.groups.is_enabled(group)
+    }
+
+    /// Add the subscription with the given ID to `group`, so that it can be managed along with
+    /// the rest of the group by `unsubscribe_group`, `pause_group` and `resume_group`. Unlike
+    /// members subscribed with `subscribe_group`, they don't compete for events. A subscription
+    /// can be in any number of groups.
+    pub fn add_to_group(
+        &mut self,
+        id: SubscriptionId,
+        group: impl Into<String>,
+    ) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared
+            .members
+            .write()
+            .expect("Members lock poisoned")
+            .entry(group.into())
+            .or_default()
+            .insert(id);
+        Ok(())
+    }
+
+    /// Unsubscribe every subscription in `group`, whether it was added with `add_to_group` or
+    /// subscribed with `subscribe_group`
+    pub fn unsubscribe_group(&mut self, group: &str) {
+        for id in self.shared.group_ids(group) {
+            self.shared.remove(id);
         }
     }
 
-    struct PanicHandler;
-    impl DynHandle for PanicHandler {
-        fn dyn_handle(&self, _event: &dyn DynEvent) {
-            panic!("handler panic");
+    /// Pause every subscription in `group`, as `pause` does, until `resume_group` is called
+    pub fn pause_group(&mut self, group: &str) {
+        for id in self.shared.group_ids(group) {
+            self.shared.paused.pause(id, false);
         }
     }
 
-    #[test]
-    fn test_subscribe_with_and_publish() {
-        let mut publisher = Publisher::default();
-        let called = Arc::new(Mutex::new(false));
-        let handler = TestHandler {
-            called: called.clone(),
-        };
-        publisher.subscribe(handler);
-        let _ = publisher.publish(TestEvent);
-        assert!(*called.lock().unwrap());
+    /// Resume every subscription in `group`, delivering any events buffered for those paused
+    /// with `pause_buffered`
+    pub fn resume_group(
+        &mut self,
+        group: &str,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>> {
+        let mut ids: Vec<usize> = self.shared.group_ids(group).into_iter().collect();
+        ids.sort();
+        let mut errors = Vec::new();
+        for id in ids {
+            if let Err(resumed) = self.shared.resume(id) {
+                errors.extend(resumed);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 
-    #[test]
-    fn test_subscribe_and_publish() {
-        let mut publisher = Publisher::default();
-        let called = Arc::new(Mutex::new(false));
-        let handler = TestHandler {
-            called: called.clone(),
-        };
-        publisher.subscribe(handler);
-        let _ = publisher.publish(TestEvent);
-        assert!(*called.lock().unwrap());
+    /// Choose how events of type `T` are routed to the handlers subscribed to them. By default
+    /// they are broadcast to every handler, but they can instead be shared out so that each event
+    /// goes to exactly one handler, turning the Publisher into a way to distribute work. Only
+    /// handlers subscribed with `subscribe`, or the helpers built on it, and `subscribe_mut` are
+    /// load-balanced; other subscriptions receive events as they otherwise would.
+    pub fn set_routing<T: Event>(&mut self, routing: Routing) {
+        self.shared.routes.set(TypeId::of::<T>(), routing);
     }
 
-    #[test]
-    fn test_unsubscribe() {
-        let mut publisher = Publisher::default();
-        let called = Arc::new(Mutex::new(false));
-        let handler = TestHandler {
-            called: called.clone(),
-        };
-        let id = publisher.subscribe(handler);
-        publisher.unsubscribe(id);
-        let _ = publisher.publish(TestEvent);
-        assert!(!*called.lock().unwrap());
+    /// Choose how many events of type `T` are delivered. Events that are sampled out or muted are
+    /// dropped before they reach middleware or any handler, so diagnostic events can be dialled
+    /// down while the Publisher is running and back up when they are needed.
+    pub fn set_sampling<T: Event>(&mut self, sampling: Sampling) {
+        self.shared.samplers.set(TypeId::of::<T>(), sampling);
     }
 
-    #[test]
-    fn test_publish_error() {
-        let mut publisher = Publisher::default();
-        publisher.subscribe(PanicHandler);
-        let result = publisher.publish(TestEvent);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err().len(), 1);
+    /// How many events of type `T` are currently delivered
+    pub fn sampling<T: Event>(&self) -> Sampling {
+        self.shared.samplers.get(TypeId::of::<T>())
+    }
+
+    /// Keep a read model of type `P` up to date with the events published from now on, starting
+    /// from its default. Returns a handle for reading the model and taking snapshots of it.
+    pub fn project<P>(&mut self) -> Projected<P>
+    where
+        P: Projection + Default,
+    {
+        let projection = Arc::new(RwLock::new(P::default()));
+        let handler_projection = projection.clone();
+        let id = self.subscribe_with(move |event: P::Event| {
+            handler_projection
+                .write()
+                .expect("Projection lock poisoned")
+                .apply(event)
+        });
+
+        Projected::new(projection, id)
+    }
+
+    /// Subscribe a closure that replies to requests of type `R` published with `request` or
+    /// `request_async`
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn respond<R, F>(&mut self, respond: F) -> SubscriptionId
+    where
+        R: Request,
+        F: Fn(R) -> R::Response + Send + Sync + 'static,
+    {
+        self.subscribe(Responder::new(respond))
+    }
+
+    /// Subscribe a handler whose return values are gathered up by `publish_collect`
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_collect<H>(&mut self, handler: H) -> SubscriptionId
+    where
+        H: HandleCollect + Send + Sync + 'static,
+    {
+        self.subscribe(Collector(handler))
+    }
+
+    /// Subscribe a closure whose return values are gathered up by `publish_collect`.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_collect_with<T, R, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        T: FromEvent,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.subscribe_collect(CollectFn::new(handler))
+    }
+
+    /// Subscribe a closure that takes one argument for each of a set of event types, such as
+    /// `|a: A, b: B, c: C|`. The latest event of each type is kept until all of them have been
+    /// published, then the closure runs with them and starts waiting for a full set again.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_join<J, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        J: Join,
+        F: JoinFn<J>,
+    {
+        self.subscribe(JoinHandler::new(handler, false))
+    }
+
+    /// Like `subscribe_join`, but keeps a separate set of events for each correlation ID, so the
+    /// closure only runs with events that were published with `publish_correlated` as part of the
+    /// same chain.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_join_correlated<J, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        J: Join,
+        F: JoinFn<J>,
+    {
+        self.subscribe(JoinHandler::new(handler, true))
+    }
+
+    /// Subscribe a closure to whichever of a set of event types is published first in each chain
+    /// of correlated events, such as `|outcome: Race2<Success, Failure>|`. Events of the other
+    /// types that arrive later in the same chain are ignored.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_race<R, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        R: Race,
+        F: Fn(R) + Send + Sync + 'static,
+    {
+        self.subscribe(RaceHandler::new(handler, None))
+    }
+
+    /// Like `subscribe_race`, but if none of the event types arrive within `timeout` of the first
+    /// event in a chain, a `RaceTimedOut` event is published in the chain instead and decides the
+    /// race. Include `RaceTimedOut` in the race to handle it.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_race_timeout<R, F>(&mut self, handler: F, timeout: Duration) -> SubscriptionId
+    where
+        R: Race,
+        F: Fn(R) + Send + Sync + 'static,
+    {
+        let schedule_shared = Arc::downgrade(&self.shared);
+        let publish_shared = Arc::downgrade(&self.shared);
+        let timeout = Timeout {
+            after: timeout,
+            schedule: Box::new(move |due, job| match Weak::upgrade(&schedule_shared) {
+                Some(shared) => shared.scheduler().schedule(due, job),
+                None => ScheduleHandle::new(),
+            }),
+            publish: Arc::new(move |timed_out: RaceTimedOut| {
+                if let Some(shared) = Weak::upgrade(&publish_shared) {
+                    let correlation_id = timed_out.correlation_id;
+                    let _ = shared.dispatch(timed_out, Some(correlation_id));
+                }
+            }),
+        };
+
+        self.subscribe(RaceHandler::new(handler, Some(timeout)))
+    }
+
+    /// Remove a handler from the publisher so that it stops receiving events. Returns the
+    /// handler, so that its state can be inspected or it can be subscribed again with
+    /// `resubscribe`. Fails if it has already been unsubscribed, or was subscribed to a different
+    /// Publisher.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> Result<Unsubscribed, UnsubscribeError> {
+        self.shared.unsubscribe(id)
+    }
+
+    /// Subscribe a handler returned by `unsubscribe`, on this or any other Publisher, the same
+    /// way it was subscribed before. It keeps any state it built up, but not its pauses, groups
+    /// or other settings.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn resubscribe(&mut self, unsubscribed: Unsubscribed) -> SubscriptionId {
+        let id = match unsubscribed.removed {
+            Removed::Handler(handler) => {
+                let filter = match handler.as_ref() {
+                    HandlerType::Topic { filter, .. } => Some(filter.clone()),
+                    _ => None,
+                };
+                let id = self.shared.insert(handler);
+                if let Some(filter) = filter {
+                    self.shared
+                        .topics
+                        .write()
+                        .expect("Topic lock poisoned")
+                        .insert(&filter, id);
+                }
+                id
+            }
+            Removed::Transform(transform) => self.shared.insert_transform(transform),
+        };
+        if let Some(typed) = unsubscribed.typed {
+            self.shared.keep_typed(id, typed);
+        }
+
+        self.shared.subscription(id)
+    }
+
+    /// Number of subscriptions, including transforms
+    pub fn handler_count(&self) -> usize {
+        let handlers = self.shared.handlers.read().expect("Handler lock poisoned");
+        let transforms = self
+            .shared
+            .transforms
+            .read()
+            .expect("Transform lock poisoned");
+        handlers.len() + transforms.len()
+    }
+
+    /// Describe every subscription, including transforms, in the order they were made
+    pub fn handlers(&self) -> Vec<HandlerInfo> {
+        self.shared.handler_infos()
+    }
+
+    /// IDs of the subscriptions that could receive events of type `T`, in the order they were
+    /// made. Handlers that don't say which types they take, like bridges, and metadata handlers
+    /// are included, but whether they are paused, shed or filtered out isn't taken into account.
+    pub fn handlers_for<T: Event>(&self) -> Vec<SubscriptionId> {
+        let event_type = TypeId::of::<T>();
+        let handlers = self.shared.handlers.read().expect("Handler lock poisoned");
+        let transforms = self
+            .shared
+            .transforms
+            .read()
+            .expect("Transform lock poisoned");
+        let mut ids: Vec<usize> = handlers
+            .iter()
+            .filter(|(_, handler)| handler.accepts_type(event_type, false))
+            .map(|(id, _)| *id)
+            .chain(
+                transforms
+                    .iter()
+                    .filter(|(_, transform)| transform.event_type == event_type)
+                    .map(|(id, _)| *id),
+            )
+            .collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| self.shared.subscription(id))
+            .collect()
+    }
+
+    /// Names of the event types that handlers and transforms are subscribed to. Handlers that
+    /// don't say which types they take aren't counted.
+    pub fn subscribed_event_types(&self) -> BTreeSet<&'static str> {
+        self.handlers()
+            .into_iter()
+            .filter_map(|info| info.event_type)
+            .collect()
+    }
+
+    /// Describe which handlers take which event types, and which event types transforms derive
+    /// from which, as a Graphviz DOT graph, so that the flow of events through a large
+    /// application can be drawn with `dot -Tsvg`. Handlers are labelled with their names, so
+    /// subscribe them with `subscribe_named` for a readable graph.
+    pub fn topology_dot(&self) -> String {
+        let derived: BTreeMap<SubscriptionId, &'static str> = self
+            .shared
+            .transforms
+            .read()
+            .expect("Transform lock poisoned")
+            .iter()
+            .map(|(id, transform)| (self.shared.subscription(*id), transform.derived_type_name))
+            .collect();
+
+        topology::dot(&self.handlers(), &derived)
+    }
+
+    /// Stop the subscription with the given ID from receiving events while the Publisher's load is
+    /// above `tier`, so that optional work, like cosmetic updates, is shed under pressure
+    pub fn shed_above(
+        &mut self,
+        id: SubscriptionId,
+        tier: LoadTier,
+    ) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared
+            .shed
+            .write()
+            .expect("Shed lock poisoned")
+            .insert(id, tier);
+        Ok(())
+    }
+
+    /// Decide what happens to the events for the subscription with the given ID while its
+    /// handler's `poll_ready` says it isn't ready for them, so that a slow handler manages its own
+    /// backlog without the others being affected. Without a policy, handlers are given events
+    /// whether they are ready or not.
+    pub fn on_overflow(
+        &mut self,
+        id: SubscriptionId,
+        overflow: Overflow,
+    ) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared.overflows.set(id, overflow);
+        Ok(())
+    }
+
+    /// Give the handler of the subscription with the given ID another go at each event it panics
+    /// on or returns an error for, in the background, according to `policy`. Events it still
+    /// fails on once the policy gives up are passed to the handlers subscribed with
+    /// `subscribe_dead_letter`. The first failure is still reported by `publish`, and
+    /// `publish_and_wait` waits for the retries too. Handlers subscribed with `subscribe_batch`
+    /// aren't retried, and those subscribed with `subscribe_mut` are only retried on errors, since
+    /// a panic leaves them poisoned.
+    /// # Examples
+    /// ```
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    /// use std::time::Duration;
+    /// use crier::{Backoff, Event, Publisher, RetryPolicy};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Upload;
+    ///
+    /// static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+    ///
+    /// let mut publisher = Publisher::default();
+    /// let id = publisher.subscribe_with(|_upload: Upload| {
+    ///     if ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+    ///         panic!("connection reset");
+    ///     }
+    /// });
+    /// publisher.set_retry_policy(id, RetryPolicy::new(5, Backoff::Immediate)).unwrap();
+    ///
+    /// let report = publisher.publish_and_wait(Upload, Duration::from_secs(1));
+    /// assert_eq!(report.errors.len(), 1);
+    /// assert!(!report.timed_out);
+    /// assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 3);
+    /// ```
+    pub fn set_retry_policy(
+        &mut self,
+        id: SubscriptionId,
+        policy: RetryPolicy,
+    ) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared.retries.set(id, policy);
+        Ok(())
+    }
+
+    /// Stop the subscription with the given ID from receiving events until `resume` is called,
+    /// without giving up its ID. Events published while it is paused are dropped.
+    pub fn pause(&mut self, id: SubscriptionId) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared.paused.pause(id, false);
+        Ok(())
+    }
+
+    /// Like `pause`, but keep the events the subscription would have received while paused, and
+    /// deliver them to it when it is resumed
+    pub fn pause_buffered(&mut self, id: SubscriptionId) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared.paused.pause(id, true);
+        Ok(())
+    }
+
+    /// Let a paused subscription receive events again, first delivering any events buffered for
+    /// it in the order they were published. Fails with an UnsubscribeError, rather than the
+    /// handlers' errors, if it has been unsubscribed or was subscribed to a different Publisher.
+    pub fn resume(
+        &mut self,
+        id: SubscriptionId,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>> {
+        let id = self
+            .shared
+            .live(id)
+            .map_err(|e| vec![Box::new(e) as Box<dyn std::any::Any + Send + 'static>])?;
+        self.shared.resume(id)
+    }
+
+    /// Whether the mut handler subscribed with the given ID panicked while handling an event. It
+    /// is skipped from then on, since it may have been left half way through changing its state,
+    /// and `publish` returns a HandlerError wrapping HandlerPoisoned for each event it would have
+    /// received, until `recover` is called. Fails if it has been unsubscribed, or was subscribed
+    /// to a different Publisher.
+    pub fn is_poisoned(&self, id: SubscriptionId) -> Result<bool, UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        Ok(self.shared.mut_handler(id, |mutex| mutex.is_poisoned()) == Some(true))
+    }
+
+    /// Let a poisoned mut handler receive events again, once whatever state it was left in after
+    /// panicking is fine to carry on from
+    pub fn recover(&mut self, id: SubscriptionId) -> Result<(), UnsubscribeError> {
+        let id = self.shared.live(id)?;
+        self.shared.mut_handler(id, |mutex| mutex.clear_poison());
+        Ok(())
+    }
+
+    /// How heavily loaded the Publisher is, judged by how many events it is publishing at once or
+    /// has waiting in the background, and how long publishing has recently taken
+    pub fn load_tier(&self) -> LoadTier {
+        self.shared.load.tier(self.shared.in_flight.count())
+    }
+
+    /// Set the queue depth and latency at which the Publisher's load moves up a tier
+    pub fn set_load_thresholds(&mut self, thresholds: LoadThresholds) {
+        self.shared.load.set_thresholds(thresholds);
+    }
+
+    /// Remove a mut handler from the publisher so that it stops receiving events. Returns the
+    /// handler, as `unsubscribe` does. Fails if it has already been unsubscribed, or was
+    /// subscribed to a different Publisher.
+    pub fn unsubscribe_mut(
+        &mut self,
+        id: SubscriptionId,
+    ) -> Result<Unsubscribed, UnsubscribeError> {
+        self.shared.unsubscribe(id)
+    }
+
+    /// Whenever an event of type `A` is published, also publish the event of type `B` that
+    /// `transform` derives from it, if any. Derived events are published once the source event
+    /// has been handled, with the same correlation ID, and can be transformed in turn, so
+    /// transforms must not form a cycle.
+    /// Returns the ID needed to `unsubscribe` the transform.
+    /// # Examples
+    /// ```
+    /// use crier::{Event, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct KeyPressed(char);
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Jumped;
+    ///
+    /// let mut publisher = Publisher::default();
+    /// publisher.transform(|key: KeyPressed| (key.0 == ' ').then_some(Jumped));
+    /// ```
+    pub fn transform<A, B, F>(&mut self, transform: F) -> SubscriptionId
+    where
+        A: FromEvent,
+        B: Event,
+        F: Fn(A) -> Option<B> + Send + Sync + 'static,
+    {
+        let id = self.shared.insert_transform(Transform::new(transform));
+        self.shared.subscription(id)
+    }
+
+    /// Add a middleware that sees every event before and after it is dispatched to handlers.
+    /// Middleware runs in the order it was added before dispatch, and in reverse order after.
+    pub fn add_middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware + 'static,
+    {
+        self.shared
+            .middleware
+            .write()
+            .expect("Middleware lock poisoned")
+            .push(Arc::new(middleware));
+    }
+
+    /// Convert the panics of handlers into PanicMessages with `formatter` before they are returned
+    /// from `publish` and the other methods that report errors, e.g. to redact sensitive data
+    pub fn set_panic_formatter(&mut self, formatter: PanicFormatter) {
+        *self
+            .shared
+            .panic_formatter
+            .write()
+            .expect("Panic formatter lock poisoned") = Some(formatter);
+    }
+
+    /// Choose what happens when a handler panics. By default panics are caught and returned from
+    /// `publish` and the other methods that report errors.
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        *self
+            .shared
+            .panic_policy
+            .write()
+            .expect("Panic policy lock poisoned") = policy;
+    }
+
+    /// Call `hook` with the name of each handler that panics, or its type name if it wasn't
+    /// subscribed with one, and its panic message, in place of writing them to stderr when the
+    /// PanicPolicy is LogAndContinue. Lets applications send handler panics to their own logging.
+    /// # Examples
+    /// ```
+    /// use crier::{PanicPolicy, Publisher};
+    ///
+    /// let mut publisher = Publisher::default();
+    /// publisher.set_panic_policy(PanicPolicy::LogAndContinue);
+    /// publisher.on_panic(|handler, message| println!("{handler} panicked: {message}"));
+    /// ```
+    pub fn on_panic<F>(&mut self, hook: F)
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        *self
+            .shared
+            .panic_hook
+            .write()
+            .expect("Panic hook lock poisoned") = Some(Box::new(hook));
+    }
+
+    /// Record when each handler starts and finishes running in `trace`, until it is replaced or
+    /// set to None. Mut handlers are recorded too, but ordered and metadata handlers aren't.
+    pub fn set_trace(&mut self, trace: Option<Arc<ChromeTrace>>) {
+        *self.shared.trace.write().expect("Trace lock poisoned") = trace;
+    }
+
+    /// Time each handler as it handles each event with `watchdog`, calling back about the ones
+    /// that are slow, until it is replaced or set to None
+    pub fn set_watchdog(&mut self, watchdog: Option<Watchdog>) {
+        *self
+            .shared
+            .watchdog
+            .write()
+            .expect("Watchdog lock poisoned") = watchdog.map(Arc::new);
+    }
+
+    /// Give the threads the Publisher spawns for itself, such as its scheduler thread, stacks of
+    /// `stack_size` bytes. Only applies to threads started afterwards. The stack size of handler
+    /// threads is set with `DispatchStrategy::ScopedThreads` instead.
+    pub fn set_stack_size(&mut self, stack_size: usize) {
+        self.shared.stack_size.store(stack_size, Ordering::SeqCst);
+    }
+
+    /// Publish an event to all subscribed handlers, utilizing as many threads as possible to run
+    /// handlers in parallel
+    pub fn publish<T>(
+        &mut self,
+        event: T,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+    {
+        self.shared.dispatch(event, None)
+    }
+
+    /// Publish an event like `publish`, but with the errors returned by fallible handlers
+    /// subscribed with `subscribe_try` kept apart from the panics of other handlers
+    pub fn try_publish<T>(&mut self, event: T) -> Result<(), PublishErrors>
+    where
+        T: DynEvent,
+    {
+        self.shared
+            .dispatch(event, None)
+            .map_err(PublishErrors::split)
+    }
+
+    /// Publish an event wrapped in a `Cancellable`, which handlers subscribed to
+    /// `Arc<Cancellable<T>>` can cancel. Returns whether any of them did, alongside the errors of
+    /// the handlers if any of them panicked.
+    pub fn publish_cancellable<T>(
+        &mut self,
+        event: T,
+    ) -> Result<bool, (bool, Vec<Box<dyn std::any::Any + Send + 'static>>)>
+    where
+        T: Event,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let result = self
+            .shared
+            .dispatch(Cancellable::new(event, cancelled.clone()), None);
+        let cancelled = cancelled.load(Ordering::SeqCst);
+
+        result
+            .map(|()| cancelled)
+            .map_err(|errors| (cancelled, errors))
+    }
+
+    /// Publish the event built by `event`, but only build it if a handler might receive it, so
+    /// that large diagnostic events nobody is listening for cost nothing. Events of a type that is
+    /// muted with `set_sampling` are never built.
+    pub fn publish_with<T, F>(
+        &mut self,
+        event: F,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: Event,
+        F: FnOnce() -> T,
+    {
+        if !self.shared.has_subscribers(TypeId::of::<T>(), false) {
+            return Ok(());
+        }
+
+        self.shared.dispatch(event(), None)
+    }
+
+    /// Hold back every event published from now on, rather than delivering it, until `seal` is
+    /// called. Call this before modules start subscribing so that events published while some of
+    /// them are still starting up aren't lost. Requests and collecting publishes made while events
+    /// are held back get no replies.
+    pub fn hold(&mut self) {
+        self.shared.gate.close();
+    }
+
+    /// Declare that every expected subscriber has registered, and deliver the events held back
+    /// since `hold` in the order they were published. Events are delivered as they are published
+    /// from then on.
+    pub fn seal(&mut self) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>> {
+        let mut errors = Vec::new();
+        for held in self.shared.gate.open() {
+            if let Err(e) = self.shared.dispatch_all(
+                held.events,
+                held.correlation_id,
+                held.origin,
+                held.topic.as_deref(),
+            ) {
+                errors.extend(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Publish many events at once. Each handler receives the events in order on a single thread,
+    /// which avoids setting up threads for every event when publishing large numbers of them.
+    /// Events with a `Deadline` are delivered ahead of the rest, earliest deadline first, though
+    /// only among the events passed in the same call.
+    pub fn publish_all<T, I>(
+        &mut self,
+        events: I,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+        I: IntoIterator<Item = T>,
+    {
+        self.shared.dispatch_all(events, None, Origin::Local, None)
+    }
+
+    /// Run `transaction`, queueing the events it publishes rather than publishing them straight
+    /// away. If it returns Ok, every queued event is published, each Publisher receiving its
+    /// events in order as with `publish_all`. If it returns an error or panics, none are, so a
+    /// state change made up of several events is never half announced.
+    /// # Examples
+    /// ```
+    /// use crier::{Event, Publisher};
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Withdrawn(u32);
+    ///
+    /// #[derive(Clone, Event)]
+    /// struct Deposited(u32);
+    ///
+    /// let mut publisher = Publisher::default();
+    /// let result = publisher.transaction(|tx| {
+    ///     tx.publish(Withdrawn(10));
+    ///     tx.publish(Deposited(10));
+    ///     Ok::<(), String>(())
+    /// });
+    /// assert!(result.is_ok());
+    /// ```
+    pub fn transaction<E, F>(&mut self, transaction: F) -> Result<(), TransactionError<E>>
+    where
+        F: FnOnce(&mut Transaction) -> Result<(), E>,
+    {
+        let mut tx = Transaction::new(self);
+        transaction(&mut tx).map_err(TransactionError::Aborted)?;
+
+        tx.commit().map_err(TransactionError::Handler)
+    }
+
+    /// Identifies the Publisher, with a way of publishing a committed Transaction's events on it
+    /// for as long as it is around
+    pub(crate) fn committer(&self) -> (PublisherId, Commit) {
+        let shared = Arc::downgrade(&self.shared);
+        let commit: Commit = Box::new(move |events| match Weak::upgrade(&shared) {
+            Some(shared) => shared.dispatch_all(events, None, Origin::Local, None),
+            None => Ok(()),
+        });
+
+        (self.shared.publisher, commit)
+    }
+
+    /// Publish an event as part of an existing chain of events identified by `correlation_id`,
+    /// which is usually taken from the metadata of the event that caused this one.
+    pub fn publish_correlated<T>(
+        &mut self,
+        event: T,
+        correlation_id: u64,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+    {
+        self.shared.dispatch(event, Some(correlation_id))
+    }
+
+    /// Publish an event to a named topic, such as `"physics/collision"`, so that the same type of
+    /// event can be routed to different logical channels. Handlers subscribed with
+    /// `subscribe_topic` only receive events published to topics matching their filter, while
+    /// handlers subscribed any other way receive the event as usual.
+    pub fn publish_to<T>(
+        &mut self,
+        topic: &str,
+        event: T,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+    {
+        self.shared
+            .dispatch_all(std::iter::once(event), None, Origin::Local, Some(topic))
+    }
+
+    /// Publish a request to every responder subscribed with `respond`, and collect their replies.
+    /// Responders that panic don't reply. Handlers subscribed to `R` any other way don't receive
+    /// requests.
+    pub fn request<R: Request>(&mut self, request: R) -> Vec<R::Response> {
+        self.shared.request(request)
+    }
+
+    /// Like `request`, but publishes the request on a background thread and returns a future that
+    /// resolves to the replies, so that async code isn't blocked while responders run
+    pub fn request_async<R: Request>(&mut self, request: R) -> Replies<R::Response> {
+        let (replies, complete) = Replies::new();
+        if !platform::THREADS {
+            complete(self.shared.request(request));
+            return replies;
+        }
+
+        let shared = self.shared.clone();
+        let mut builder = thread::Builder::new().name(String::from("crier-request"));
+        if let Some(stack_size) = shared.stack_size() {
+            builder = builder.stack_size(stack_size);
+        }
+        builder
+            .spawn(move || complete(shared.request(request)))
+            .expect("Failed to spawn request thread");
+
+        replies
+    }
+
+    /// Publish an event to every handler subscribed with `subscribe_collect` that returns `R`, and
+    /// collect what they return. Handlers that panic don't contribute. Handlers subscribed to `T`
+    /// any other way don't receive the event.
+    pub fn publish_collect<T, R>(&mut self, event: T) -> Vec<R>
+    where
+        T: DynEvent,
+        R: Send + 'static,
+    {
+        let outputs = Arc::new(Mutex::new(Vec::new()));
+        let collecting = Collecting {
+            event: Box::new(event),
+            outputs: outputs.clone(),
+        };
+        let _ = self.shared.dispatch(collecting, None);
+
+        std::mem::take(&mut *outputs.lock().expect("Collect mutex poisoned"))
+    }
+
+    /// Like `publish_collect`, but combines what the handlers return with `fold`, starting from
+    /// `init`, such as to count votes or check that every validator passed
+    pub fn publish_fold<T, R, A, F>(&mut self, event: T, init: A, fold: F) -> A
+    where
+        T: DynEvent,
+        R: Send + 'static,
+        F: FnMut(A, R) -> A,
+    {
+        self.publish_collect(event).into_iter().fold(init, fold)
+    }
+
+    /// Publish an event, then block until every handler it reaches has finished or `timeout` has
+    /// elapsed. This includes debounced handlers, which run in the background once things go
+    /// quiet, and batch handlers, whose buffered events are delivered straight away. Useful in
+    /// tests, which would otherwise have to sleep while background handlers catch up.
+    pub fn publish_and_wait<T>(&mut self, event: T, timeout: Duration) -> PublishReport
+    where
+        T: DynEvent,
+    {
+        let deadline = Instant::now() + timeout;
+        let missed_before = MISSED_DEADLINES.with(|missed| missed.get());
+        let mut errors = self.shared.dispatch(event, None).err().unwrap_or_default();
+        errors.extend(
+            self.shared
+                .format_errors(self.shared.deliver(&[], true, None)),
+        );
+        let missed_deadline = MISSED_DEADLINES.with(|missed| missed.get()) > missed_before;
+        let (background_errors, timed_out) = self.shared.in_flight.wait(deadline);
+        errors.extend(self.shared.format_errors(background_errors));
+
+        PublishReport {
+            errors,
+            timed_out,
+            missed_deadline,
+        }
+    }
+
+    /// Deliver any events buffered for batch handlers, however many there are
+    pub fn flush(&mut self) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>> {
+        let errors = self
+            .shared
+            .format_errors(self.shared.deliver(&[], true, None));
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Number of events with a Deadline that were still being handled after their deadline had
+    /// passed
+    pub fn missed_deadlines(&self) -> u64 {
+        self.shared.missed_deadlines.load(Ordering::SeqCst)
+    }
+
+    /// Subscribe a handler built against any crier version with the same `compat::ABI_VERSION`,
+    /// e.g. one from a plugin. It receives every published event whose type is registered with
+    /// `registry`, serialized. Returns the ID needed to `unsubscribe` the handler.
+    #[cfg(feature = "compat")]
+    pub fn subscribe_raw(
+        &mut self,
+        registry: Arc<crate::EventRegistry>,
+        handler: crate::compat::RawHandler,
+    ) -> Result<SubscriptionId, crate::compat::AbiMismatch> {
+        if handler.abi_version != crate::compat::ABI_VERSION {
+            return Err(crate::compat::AbiMismatch {
+                expected: crate::compat::ABI_VERSION,
+                found: handler.abi_version,
+            });
+        }
+
+        Ok(
+            self.subscribe(crate::SerializingHandler::new(registry, move |event| {
+                handler.call(&event.tag, &event.payload)
+            })),
+        )
+    }
+
+    /// Subscribe a `net::Forwarder`, publishing a `net::BridgeDown` here whenever it loses its
+    /// connection or fails to connect, and a `net::BridgeRestored` once it's connected again. The
+    /// events are published from whichever thread the Forwarder was sending on, so any errors
+    /// returned by their handlers are discarded.
+    ///
+    /// Unlike a Forwarder subscribed with `subscribe`, it also relays events this Publisher
+    /// received from other processes, except to the Publisher it received them from, so that
+    /// events published on one side of a bidirectional bridge aren't echoed back to it.
+    /// Returns the ID needed to `unsubscribe` the Forwarder.
+    #[cfg(feature = "net")]
+    pub fn forward(&mut self, mut forwarder: crate::net::Forwarder) -> SubscriptionId {
+        forwarder.set_peer(self.shared.peer);
+        let shared = Arc::downgrade(&self.shared);
+        forwarder.on_health(move |event| {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                let _ = shared.dispatch_all(std::iter::once(event), None, Origin::Local, None);
+            }
+        });
+        self.subscribe(forwarder)
+    }
+
+    /// Publish events sent by remote `net::Forwarder`s to `addr`. Only events whose types are
+    /// registered with `registry` are published, and they are marked as remote in their metadata,
+    /// along with the Publisher they came from if the Forwarder was subscribed with `forward`.
+    /// Events are published from the Listener's threads, so any errors returned by their handlers
+    /// are discarded. Connected Forwarders are kept up to date with which of those types this
+    /// Publisher has handlers for, so that they only send those. Stops listening when the returned
+    /// Listener is dropped.
+    #[cfg(feature = "net")]
+    pub fn listen(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        registry: Arc<crate::EventRegistry>,
+    ) -> std::io::Result<crate::net::Listener> {
+        let shared = Arc::downgrade(&self.shared);
+        let interest = {
+            let shared = shared.clone();
+            let registry = registry.clone();
+            move || {
+                let shared = Weak::upgrade(&shared)?;
+                Some(
+                    registry
+                        .tagged_types()
+                        .filter(|(_, event_type)| shared.has_subscribers(*event_type, true))
+                        .map(|(tag, _)| String::from(tag))
+                        .collect(),
+                )
+            }
+        };
+        crate::net::Listener::bind(
+            addr,
+            registry,
+            self.shared.peer,
+            move |event, peer| match Weak::upgrade(&shared) {
+                Some(shared) => {
+                    let origin = peer.map_or(Origin::Remote, |peer| Origin::Peer(peer.0));
+                    let _ = shared.dispatch_all(std::iter::once(event), None, origin, None);
+                    true
+                }
+                None => false,
+            },
+            interest,
+        )
+    }
+
+    /// Like `publish`, but runs the publish on tokio's blocking thread pool and waits for it
+    /// asynchronously, so that handlers don't block the async runtime. Must be called from
+    /// within a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn publish_async<T>(
+        &mut self,
+        event: T,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+    {
+        let shared = self.shared.clone();
+        match tokio::task::spawn_blocking(move || shared.dispatch(event, None)).await {
+            Ok(result) => result,
+            // only reachable when the PanicPolicy resumes handler panics on the caller
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+
+    /// Subscribe an async closure, such as `|event: T| async move { .. }`, to events of its input
+    /// type. Each event's future runs as a task on the tokio runtime the closure is subscribed
+    /// from, which must be called from within. The tasks run in the background, so
+    /// `publish_and_wait` waits for them and reports their panics.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    #[cfg(feature = "tokio")]
+    pub fn subscribe_async<T, F, Fut>(&mut self, handler: F) -> SubscriptionId
+    where
+        T: FromEvent,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe(crate::task::AsyncHandler::new(
+            handler,
+            tokio::runtime::Handle::current(),
+            self.shared.in_flight.clone(),
+        ))
+    }
+
+    /// Publish recorded events, such as ones written by a `Recorder`, in order, spacing them out
+    /// according to `speed` so that a replay of a debugging session can look like the original
+    /// run rather than a burst. Blocks until every event has been published. Stops at the first
+    /// record that can't be deserialized with `registry`.
+    #[cfg(feature = "serde")]
+    pub fn replay<I>(
+        &mut self,
+        registry: &crate::EventRegistry,
+        records: I,
+        speed: crate::ReplaySpeed,
+    ) -> Result<(), crate::ReplayError>
+    where
+        I: IntoIterator<Item = crate::RecordedEvent>,
+    {
+        crate::replay::replay(self, registry, records, speed)
+    }
+
+    /// Serve WebSocket peers on `addr`, sending them the events they ask for and publishing the
+    /// events they send. Only events whose types are registered with `registry` cross the bridge,
+    /// and events received from peers are marked as remote in their metadata. See
+    /// `crier::websocket` for the protocol. Stops serving when the returned server is dropped.
+    #[cfg(feature = "websocket")]
+    pub fn serve_websocket(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        registry: Arc<crate::EventRegistry>,
+    ) -> std::io::Result<crate::websocket::WebSocketServer> {
+        let shared = Arc::downgrade(&self.shared);
+        let (mut server, broadcaster) = crate::websocket::WebSocketServer::bind(
+            addr,
+            registry,
+            move |event| match Weak::upgrade(&shared) {
+                Some(shared) => {
+                    let _ = shared.dispatch_all(std::iter::once(event), None, Origin::Remote, None);
+                    true
+                }
+                None => false,
+            },
+        )?;
+        let id = self.subscribe(broadcaster);
+        let shared = Arc::downgrade(&self.shared);
+        server.on_drop(move || {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                let _ = shared.unsubscribe(id);
+            }
+        });
+
+        Ok(server)
+    }
+
+    /// Serve a JSON debug endpoint over HTTP on `addr`, for inspecting the Publisher's handlers,
+    /// metrics and recently published events, and toggling its subscription groups, while it
+    /// runs. See `crier::debug` for the endpoints. Stops serving when the returned server is
+    /// dropped.
+    #[cfg(feature = "debug-http")]
+    pub fn serve_debug(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+    ) -> std::io::Result<crate::debug::DebugServer> {
+        use crate::debug::{DebugRequest, RecentEvents};
+
+        let recent = Arc::new(RecentEvents::default());
+        let shared = Arc::downgrade(&self.shared);
+        let events = recent.clone();
+        let mut server = crate::debug::DebugServer::bind(addr, move |request| {
+            let shared = Weak::upgrade(&shared)?;
+            Some(match request {
+                DebugRequest::Introspect => shared.introspect(),
+                DebugRequest::Metrics => serde_json::json!({
+                    "published": shared.sequence.load(Ordering::SeqCst),
+                    "missed_deadlines": shared.missed_deadlines.load(Ordering::SeqCst),
+                    "in_flight": shared.in_flight.count(),
+                    "load_tier": format!("{:?}", shared.load.tier(shared.in_flight.count())),
+                }),
+                DebugRequest::Events => events.to_json(),
+                DebugRequest::SetGroupEnabled { group, enabled } => {
+                    shared.groups.set_enabled(group.clone(), enabled);
+                    serde_json::json!({ "group": group, "enabled": enabled })
+                }
+            })
+        })?;
+        let id = self.subscribe_metadata(move |info| recent.record(info));
+        let shared = Arc::downgrade(&self.shared);
+        server.on_drop(move || {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                let _ = shared.unsubscribe(id);
+            }
+        });
+
+        Ok(server)
+    }
+
+    /// Connect to an MQTT broker, sending events to the topics their types are mapped to and
+    /// publishing the messages that arrive on those topics. Only events whose types are registered
+    /// with `registry` cross the bridge, and events received from the broker are marked as remote
+    /// in their metadata. See `crier::mqtt` for how types are mapped to topics. Disconnects when
+    /// the returned bridge is dropped.
+    #[cfg(feature = "mqtt")]
+    pub fn bridge_mqtt(
+        &mut self,
+        options: crate::mqtt::MqttOptions,
+        registry: Arc<crate::EventRegistry>,
+    ) -> std::io::Result<crate::mqtt::MqttBridge> {
+        let shared = Arc::downgrade(&self.shared);
+        let (mut bridge, forwarder) =
+            crate::mqtt::MqttBridge::connect(options, registry, move |event| match Weak::upgrade(
+                &shared,
+            ) {
+                Some(shared) => {
+                    let _ = shared.dispatch_all(std::iter::once(event), None, Origin::Remote, None);
+                    true
+                }
+                None => false,
+            })?;
+        let id = self.subscribe(forwarder);
+        let shared = Arc::downgrade(&self.shared);
+        bridge.on_drop(move || {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                let _ = shared.unsubscribe(id);
+            }
+        });
+
+        Ok(bridge)
+    }
+
+    /// Connect to a NATS server, sending events to the subjects their types are mapped to and
+    /// publishing the messages that arrive on those subjects. Only events whose types are
+    /// registered with `registry` cross the bridge, and events received from NATS are marked as
+    /// remote in their metadata. See `crier::nats` for how types are mapped to subjects.
+    /// Disconnects when the returned bridge is dropped.
+    #[cfg(feature = "nats")]
+    pub fn bridge_nats(
+        &mut self,
+        options: crate::nats::NatsOptions,
+        registry: Arc<crate::EventRegistry>,
+    ) -> std::io::Result<crate::nats::NatsBridge> {
+        let shared = Arc::downgrade(&self.shared);
+        let (mut bridge, forwarder) =
+            crate::nats::NatsBridge::connect(options, registry, move |event| match Weak::upgrade(
+                &shared,
+            ) {
+                Some(shared) => {
+                    let _ = shared.dispatch_all(std::iter::once(event), None, Origin::Remote, None);
+                    true
+                }
+                None => false,
+            })?;
+        let id = self.subscribe(forwarder);
+        let shared = Arc::downgrade(&self.shared);
+        bridge.on_drop(move || {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                let _ = shared.unsubscribe(id);
+            }
+        });
+
+        Ok(bridge)
+    }
+
+    /// Connect to Redis, publishing events to the channels their types are mapped to and
+    /// publishing the messages that arrive on those channels. Only events whose types are
+    /// registered with `registry` cross the bridge, and events received from Redis are marked as
+    /// remote in their metadata. See `crier::redis` for how types are mapped to channels.
+    /// Disconnects when the returned bridge is dropped.
+    #[cfg(feature = "redis")]
+    pub fn bridge_redis(
+        &mut self,
+        options: crate::redis::RedisOptions,
+        registry: Arc<crate::EventRegistry>,
+    ) -> std::io::Result<crate::redis::RedisBridge> {
+        let shared = Arc::downgrade(&self.shared);
+        let (mut bridge, forwarder) = crate::redis::RedisBridge::connect(
+            options,
+            registry,
+            move |event| match Weak::upgrade(&shared) {
+                Some(shared) => {
+                    let _ = shared.dispatch_all(std::iter::once(event), None, Origin::Remote, None);
+                    true
+                }
+                None => false,
+            },
+        )?;
+        let id = self.subscribe(forwarder);
+        let shared = Arc::downgrade(&self.shared);
+        bridge.on_drop(move || {
+            if let Some(shared) = Weak::upgrade(&shared) {
+                let _ = shared.unsubscribe(id);
+            }
+        });
+
+        Ok(bridge)
+    }
+
+    /// Publish an event once `delay` has passed. The event is published from the Publisher's
+    /// scheduler thread, so any errors returned by its handlers are discarded.
+    /// Returns a handle that can be used to cancel the event before it is published.
+    pub fn publish_after<T>(&mut self, event: T, delay: Duration) -> ScheduleHandle
+    where
+        T: DynEvent,
+    {
+        let due = Instant::now() + delay;
+        let handle = ScheduleHandle::new();
+        let id = handle.id();
+        self.shared.pending.insert(
+            &handle,
+            (event.get_data().type_id(), event.type_name()),
+            due,
+            None,
+        );
+        let shared = Arc::downgrade(&self.shared);
+        self.shared
+            .scheduler()
+            .schedule_with_handle(due, handle.clone(), move || {
+                if let Some(shared) = Weak::upgrade(&shared) {
+                    shared.pending.remove(id);
+                    let _ = shared.dispatch(event, None);
+                }
+            });
+
+        handle
+    }
+
+    /// Publish an event created by `event_factory` every `period`, starting one period from now,
+    /// until the returned handle is cancelled. Events are published from the Publisher's scheduler
+    /// thread, so any errors returned by their handlers are discarded.
+    pub fn publish_every<T, F>(&mut self, event_factory: F, period: Duration) -> ScheduleHandle
+    where
+        T: DynEvent,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        let due = Instant::now() + period;
+        let handle = ScheduleHandle::new();
+        self.shared.pending.insert(
+            &handle,
+            (TypeId::of::<T>(), std::any::type_name::<T>()),
+            due,
+            Some(period),
+        );
+        schedule_recurring(
+            Arc::downgrade(&self.shared),
+            Arc::new(event_factory),
+            period,
+            due,
+            handle.clone(),
+        );
+
+        handle
+    }
+
+    /// The events scheduled with `publish_after` or `publish_every` that are still waiting to be
+    /// published, soonest first
+    pub fn scheduled(&self) -> Vec<ScheduledInfo> {
+        self.shared.pending.list()
+    }
+
+    /// Cancel a scheduled event, returning whether it was still waiting to be published
+    pub fn cancel_scheduled(&mut self, id: ScheduleId) -> bool {
+        self.shared.pending.cancel(|scheduled, _| scheduled == id) == 1
+    }
+
+    /// Cancel every scheduled event of type T, such as when a game scene unloads, returning how
+    /// many were still waiting to be published
+    pub fn cancel_all_scheduled<T: Event>(&mut self) -> usize {
+        self.shared
+            .pending
+            .cancel(|_, event_type| event_type == TypeId::of::<T>())
+    }
+}
+
+/// Schedule the next run of a recurring event. Each run schedules the one after it, measured from
+/// when it was due rather than when it ran so that the interval does not drift.
+fn schedule_recurring<T, F>(
+    shared: Weak<Shared>,
+    event_factory: Arc<F>,
+    period: Duration,
+    due: Instant,
+    handle: ScheduleHandle,
+) where
+    T: DynEvent,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let Some(strong) = Weak::upgrade(&shared) else {
+        return;
+    };
+    let next_handle = handle.clone();
+    strong
+        .scheduler()
+        .schedule_with_handle(due, handle, move || {
+            if let Some(strong) = Weak::upgrade(&shared) {
+                strong.pending.reschedule(next_handle.id(), due + period);
+                let _ = strong.dispatch(event_factory(), None);
+                drop(strong);
+                schedule_recurring(shared, event_factory, period, due + period, next_handle);
+            }
+        });
+}
+
+/// Lists the Publisher's subscriptions, with the event types they take and their labels
+impl fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Publisher")
+            .field("source", &self.shared.source)
+            .field("handlers", &self.handlers())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        // don't lose events buffered for batch handlers
+        if !self.handle {
+            let _ = self.flush();
+        }
+    }
+}
+
+impl Shared {
+    fn insert(&self, handler: impl Into<Arc<HandlerType>>) -> usize {
+        let id = self.handler_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.handlers
+            .write()
+            .expect("Handler lock poisoned")
+            .insert(id, handler.into());
+
+        id
+    }
+
+    /// Insert handlers under a single acquisition of the handler lock, returning their IDs in the
+    /// same order
+    fn insert_all(&self, handlers: Vec<HandlerType>) -> Vec<usize> {
+        let first = self
+            .handler_count
+            .fetch_add(handlers.len(), Ordering::SeqCst)
+            + 1;
+        let mut map = self.handlers.write().expect("Handler lock poisoned");
+        map.reserve(handlers.len());
+        (first..)
+            .zip(handlers)
+            .map(|(id, handler)| {
+                map.insert(id, Arc::new(handler));
+                id
+            })
+            .collect()
+    }
+
+    fn insert_transform(&self, transform: Transform) -> usize {
+        let id = self.handler_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.transforms
+            .write()
+            .expect("Transform lock poisoned")
+            .insert(id, transform);
+
+        id
+    }
+
+    /// Keep a handler as its own type, so that it can be downcast once unsubscribed
+    fn keep_typed(&self, id: usize, handler: Arc<dyn Any + Send + Sync>) {
+        self.typed
+            .write()
+            .expect("Typed lock poisoned")
+            .insert(id, handler);
+    }
+
+    /// The SubscriptionId handed out for the subscription with the given ID
+    fn subscription(&self, id: usize) -> SubscriptionId {
+        SubscriptionId::new(self.publisher, id)
+    }
+
+    /// The ID of a subscription within this Publisher, if it was made with this Publisher
+    fn local(&self, id: SubscriptionId) -> Result<usize, UnsubscribeError> {
+        id.local(self.publisher)
+    }
+
+    /// The local ID of a subscription that was made with this Publisher and is still subscribed,
+    /// for the methods that change how it receives events
+    fn live(&self, id: SubscriptionId) -> Result<usize, UnsubscribeError> {
+        let id = self.local(id)?;
+        let subscribed = self
+            .handlers
+            .read()
+            .expect("Handler lock poisoned")
+            .contains_key(&id)
+            || self
+                .transforms
+                .read()
+                .expect("Transform lock poisoned")
+                .contains_key(&id);
+        if subscribed {
+            Ok(id)
+        } else {
+            Err(UnsubscribeError::Stale)
+        }
+    }
+
+    /// Call `f` with the mutex of the handler subscribed with the given ID, if it is a mut handler
+    fn mut_handler<R>(
+        &self,
+        id: usize,
+        f: impl FnOnce(&Mutex<dyn DynHandleMut>) -> R,
+    ) -> Option<R> {
+        match self
+            .handlers
+            .read()
+            .expect("Handler lock poisoned")
+            .get(&id)?
+            .as_ref()
+        {
+            HandlerType::SyncMut(mutex) => Some(f(mutex)),
+            _ => None,
+        }
+    }
+
+    fn unsubscribe(&self, id: SubscriptionId) -> Result<Unsubscribed, UnsubscribeError> {
+        self.remove(self.local(id)?).ok_or(UnsubscribeError::Stale)
+    }
+
+    /// Let a paused subscription receive events again, delivering the events buffered for it
+    fn resume(&self, id: usize) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>> {
+        let buffered = self.paused.resume(id);
+        if buffered.is_empty() {
+            return Ok(());
+        }
+
+        let errors = self.format_errors(self.deliver(&buffered, false, Some(id)));
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// IDs of the subscriptions added to `group` or subscribed as its members
+    fn group_ids(&self, group: &str) -> HashSet<usize> {
+        let mut ids = self
+            .members
+            .read()
+            .expect("Members lock poisoned")
+            .get(group)
+            .cloned()
+            .unwrap_or_default();
+        let handlers = self.handlers.read().expect("Handler lock poisoned");
+        ids.extend(
+            handlers
+                .iter()
+                .filter_map(|(id, handler)| match handler.as_ref() {
+                    HandlerType::Grouped(member) if member.group == group => Some(*id),
+                    _ => None,
+                }),
+        );
+        ids
+    }
+
+    /// Remove a subscription, returning it if there was one with the given ID
+    fn remove(&self, id: usize) -> Option<Unsubscribed> {
+        let removed = self
+            .handlers
+            .write()
+            .expect("Handler lock poisoned")
+            .remove(&id);
+        self.shed.write().expect("Shed lock poisoned").remove(&id);
+        self.paused.resume(id);
+        self.overflows.remove(id);
+        self.retries.remove(id);
+        self.fallbacks
+            .write()
+            .expect("Fallbacks lock poisoned")
+            .remove(&id);
+        let mut members = self.members.write().expect("Members lock poisoned");
+        for ids in members.values_mut() {
+            ids.remove(&id);
+        }
+        members.retain(|_, ids| !ids.is_empty());
+        drop(members);
+        let transform = self
+            .transforms
+            .write()
+            .expect("Transform lock poisoned")
+            .remove(&id);
+        match removed.as_deref() {
+            Some(HandlerType::Limited(limited)) => limited.cancel_pending(),
+            Some(HandlerType::Topic { filter, .. }) => self
+                .topics
+                .write()
+                .expect("Topic lock poisoned")
+                .remove(filter, id),
+            _ => {}
+        }
+
+        let typed = self.typed.write().expect("Typed lock poisoned").remove(&id);
+        let removed = match (removed, transform) {
+            (Some(handler), _) => Removed::Handler(handler),
+            (None, Some(transform)) => Removed::Transform(transform),
+            (None, None) => return None,
+        };
+
+        Some(Unsubscribed { removed, typed })
+    }
+
+    /// Describe the Publisher's handlers, groups and settings for the debug endpoint
+    #[cfg(feature = "debug-http")]
+    fn introspect(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let handlers = self.handlers.read().expect("Handler lock poisoned");
+        let mut ids: Vec<&usize> = handlers.keys().collect();
+        ids.sort();
+        let mut groups: BTreeMap<&str, usize> = BTreeMap::new();
+        let handlers: Vec<serde_json::Value> = ids
+            .into_iter()
+            .map(|id| {
+                let handler = &handlers[id];
+                let (kind, event_type, label) = handler.describe();
+                let mut described = json!({
+                    "id": id,
+                    "kind": kind,
+                    "event_type": event_type,
+                    "label": label,
+                });
+                match handler.as_ref() {
+                    HandlerType::Grouped(member) => {
+                        *groups.entry(&member.group).or_default() += 1;
+                        described["group"] = json!(member.group);
+                    }
+                    HandlerType::Topic { filter, .. } => described["filter"] = json!(filter),
+                    HandlerType::Ordered(ordered) => {
+                        described["priority"] = json!(ordered.priority)
+                    }
+                    _ => {}
+                }
+                described
+            })
+            .collect();
+        let groups: Vec<serde_json::Value> = groups
+            .into_iter()
+            .map(|(group, members)| {
+                json!({
+                    "name": group,
+                    "members": members,
+                    "enabled": self.groups.is_enabled(group),
+                })
+            })
+            .collect();
+
+        json!({
+            "source": self.source,
+            "handlers": handlers,
+            "groups": groups,
+            "middleware": self.middleware.read().expect("Middleware lock poisoned").len(),
+            "strategy": format!("{:?}", self.strategy),
+            "panic_policy": format!(
+                "{:?}",
+                self.panic_policy.read().expect("Panic policy lock poisoned")
+            ),
+        })
+    }
+
+    /// The scheduler thread is only started the first time an event is scheduled
+    fn scheduler(&self) -> &Scheduler {
+        self.scheduler
+            .get_or_init(|| Scheduler::new(self.stack_size()))
+    }
+
+    fn stack_size(&self) -> Option<usize> {
+        match self.stack_size.load(Ordering::SeqCst) {
+            0 => None,
+            stack_size => Some(stack_size),
+        }
+    }
+
+    fn next_metadata(
+        &self,
+        correlation_id: Option<u64>,
+        origin: Origin,
+        topic: Option<&str>,
+    ) -> Metadata {
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        Metadata {
+            timestamp: platform::now(),
+            sequence,
+            correlation_id: correlation_id.unwrap_or(sequence),
+            source: self.source.clone(),
+            remote: origin != Origin::Local,
+            peer: match origin {
+                Origin::Peer(peer) => Some(peer),
+                Origin::Local | Origin::Remote => None,
+            },
+            topic: topic.map(String::from),
+        }
+    }
+
+    fn request<R: Request>(&self, request: R) -> Vec<R::Response> {
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let requested = Requested {
+            request,
+            responses: responses.clone(),
+        };
+        let _ = self.dispatch(requested, None);
+
+        std::mem::take(&mut *responses.lock().expect("Response mutex poisoned"))
+    }
+
+    fn dispatch<T>(
+        &self,
+        event: T,
+        correlation_id: Option<u64>,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+    {
+        self.dispatch_all(std::iter::once(event), correlation_id, Origin::Local, None)
+    }
+
+    /// Run each event past the middleware, then deliver all the events that make it through to
+    /// the handlers in one go
+    fn dispatch_all<T, I>(
+        &self,
+        events: I,
+        correlation_id: Option<u64>,
+        origin: Origin,
+        topic: Option<&str>,
+    ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>>
+    where
+        T: DynEvent,
+        I: IntoIterator<Item = T>,
+    {
+        let Some(events) = self.gate.hold(events, correlation_id, origin, topic) else {
+            return Ok(());
+        };
+        let _load = self.load.start();
+        let mut telemetry = PublishTelemetry::start();
+        // copied out of the lock, so that middleware and handlers can add middleware themselves
+        let middleware = self
+            .middleware
+            .read()
+            .expect("Middleware lock poisoned")
+            .clone();
+        let mut errors = Vec::new();
+        let mut published: Vec<Arc<Published>> = events
+            .into_iter()
+            .filter(|event| self.samplers.admit(event))
+            .filter_map(|event| {
+                telemetry.event(event.type_name());
+                #[cfg(feature = "dynamic-plugins")]
+                crate::type_key::register(event.get_data().type_id(), event.type_name());
+                let mut metadata = self.next_metadata(correlation_id, origin, topic);
+                for middleware in middleware.iter() {
+                    if middleware.before(&event, &mut metadata) == Flow::Stop {
+                        return None;
+                    }
+                }
+
+                errors.extend(self.tap(&event, &metadata));
+
+                if event.delivery() == Delivery::Exclusive {
+                    errors.extend(self.deliver_owned(event));
+                    return None;
+                }
+
+                Some(Arc::new(Published::new(event, metadata)))
+            })
+            .collect();
+        // events with deadlines go first, earliest deadline first, followed by everything else in
+        // the order it was published
+        published.sort_by_key(|published| {
+            let deadline = published.dyn_deadline();
+            (deadline.is_none(), deadline)
+        });
+
+        let events: Vec<Arc<dyn DynEvent>> = published
+            .iter()
+            .map(|published| published.clone() as Arc<dyn DynEvent>)
+            .collect();
+        if !events.is_empty() {
+            errors.extend(self.deliver(&events, false, None));
+        }
+
+        for published in &published {
+            for middleware in middleware.iter().rev() {
+                middleware.after(published.as_ref(), &published.metadata);
+            }
+        }
+
+        let mut errors = self.format_errors(errors);
+        // events that crossed a link aren't sent back the way they came
+        let from = TypeId::of::<T>();
+        errors.extend(self.relay(
+            &published,
+            from != TypeId::of::<Relayed<false>>(),
+            from != TypeId::of::<Relayed<true>>(),
+        ));
+        errors.extend(self.transform(&published));
+        telemetry.finish(&errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Publish the events derived from `published` by the Publisher's transforms, returning their
+    /// handlers' errors
+    fn transform(
+        &self,
+        published: &[Arc<Published>],
+    ) -> Vec<Box<dyn std::any::Any + Send + 'static>> {
+        let mut panics = Vec::new();
+        let mut derived = Vec::new();
+        {
+            let transforms = self.transforms.read().expect("Transform lock poisoned");
+            if transforms.is_empty() {
+                return Vec::new();
+            }
+            for published in published {
+                let events: Vec<Box<dyn DynEvent>> = transforms
+                    .values()
+                    .filter_map(|transform| {
+                        std::panic::catch_unwind(AssertUnwindSafe(|| {
+                            transform.apply(published.as_ref())
+                        }))
+                        .unwrap_or_else(|e| {
+                            panics.push(e);
+                            None
+                        })
+                    })
+                    .collect();
+                if !events.is_empty() {
+                    derived.push((events, &published.metadata));
+                }
+            }
+        }
+
+        let mut errors = self.format_errors(panics);
+        // the transforms lock is released first, so that handlers of derived events can add more
+        for (events, metadata) in derived {
+            if let Err(e) = self.dispatch_all(
+                events,
+                Some(metadata.correlation_id),
+                Origin::of(metadata),
+                None,
+            ) {
+                errors.extend(e);
+            }
+        }
+
+        errors
+    }
+
+    /// Publish events on the parent, children and bridged Publishers they are linked to,
+    /// returning their handlers' errors
+    fn relay(
+        &self,
+        published: &[Arc<Published>],
+        bubble_up: bool,
+        push_down: bool,
+    ) -> Vec<Box<dyn std::any::Any + Send + 'static>> {
+        let mut errors = Vec::new();
+        if bubble_up
+            && let Some((parent, link)) = &self.parent
+            && link.bubble_up
+            && let Some(parent) = Weak::upgrade(parent)
+        {
+            for published in published.iter().filter(|event| link.admits(event.as_ref())) {
+                let metadata = &published.metadata;
+                if let Err(e) = parent.dispatch_all(
+                    std::iter::once(Relayed::<true>(published.clone())),
+                    Some(metadata.correlation_id),
+                    Origin::of(metadata),
+                    metadata.topic.as_deref(),
+                ) {
+                    errors.extend(e);
+                }
+            }
+        }
+
+        if push_down {
+            let downstream: Vec<(Arc<Shared>, Link)> = {
+                let mut downstream = self.downstream.write().expect("Downstream lock poisoned");
+                downstream.retain(|(child, _)| child.strong_count() > 0);
+                downstream
+                    .iter()
+                    .filter_map(|(child, link)| Some((Weak::upgrade(child)?, link.clone())))
+                    .collect()
+            };
+            for (child, link) in downstream {
+                for published in published.iter().filter(|event| link.admits(event.as_ref())) {
+                    let metadata = &published.metadata;
+                    let correlation_id = Some(metadata.correlation_id);
+                    let origin = Origin::of(metadata);
+                    let topic = metadata.topic.as_deref();
+                    let result = if link.bridge {
+                        let relayed = Relayed::<true>(published.clone());
+                        child.dispatch_all([relayed], correlation_id, origin, topic)
+                    } else {
+                        let relayed = Relayed::<false>(published.clone());
+                        child.dispatch_all([relayed], correlation_id, origin, topic)
+                    };
+                    if let Err(e) = result {
+                        errors.extend(e);
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Replace the panic payloads of handler errors with PanicMessages, if a PanicFormatter has
+    /// been set. Logs and drops the panics, or aborts or resumes the first of them, instead if the
+    /// PanicPolicy says to.
+    fn format_errors(
+        &self,
+        errors: Vec<Box<dyn std::any::Any + Send + 'static>>,
+    ) -> Vec<Box<dyn std::any::Any + Send + 'static>> {
+        // errors returned by fallible handlers aren't panics, so the policy and formatter leave
+        // them be
+        let (failures, mut errors): (Vec<_>, Vec<_>) = errors
+            .into_iter()
+            .partition(|error| error.is::<HandlerError>());
+        if !errors.is_empty() {
+            match *self
+                .panic_policy
+                .read()
+                .expect("Panic policy lock poisoned")
+            {
+                PanicPolicy::Collect | PanicPolicy::UnsubscribeOffender => {}
+                PanicPolicy::LogAndContinue => {
+                    let hook = self.panic_hook.read().expect("Panic hook lock poisoned");
+                    for error in errors {
+                        let handler = error
+                            .downcast_ref::<HandlerPanic>()
+                            .map_or("handler", |panic| panic.handler);
+                        let message =
+                            panic_message(error.as_ref()).unwrap_or("non-string panic payload");
+                        match hook.as_ref() {
+                            Some(hook) => hook(handler, message),
+                            #[cfg(feature = "tracing")]
+                            None => tracing::error!(handler, message, "handler panicked"),
+                            #[cfg(not(feature = "tracing"))]
+                            None => eprintln!("crier: {handler} panicked: {message}"),
+                        }
+                    }
+                    return failures;
+                }
+                PanicPolicy::Abort => std::process::abort(),
+                PanicPolicy::ResumeUnwindOnCaller => std::panic::resume_unwind(errors.remove(0)),
+            }
+        }
+
+        let formatter = self
+            .panic_formatter
+            .read()
+            .expect("Panic formatter lock poisoned");
+        let mut errors = match formatter.as_ref() {
+            Some(formatter) => errors
+                .into_iter()
+                .map(|error| {
+                    let message: PanicMessage = formatter.format(error.as_ref());
+                    Box::new(message) as Box<dyn std::any::Any + Send + 'static>
+                })
+                .collect(),
+            None => errors,
+        };
+        errors.extend(failures);
+        errors
+    }
+
+    /// Whether any handler might receive an event of the given type. If `remote`, only handlers
+    /// that accept events received from remote Publishers count.
+    fn has_subscribers(&self, event_type: TypeId, remote: bool) -> bool {
+        if self.samplers.get(event_type) == Sampling::Muted {
+            return false;
+        }
+
+        let handlers = self.handlers.read().expect("Handler lock poisoned");
+        handlers
+            .values()
+            .any(|handler| handler.accepts_type(event_type, remote))
+    }
+
+    /// Every subscription, including transforms, in the order they were made
+    fn handler_infos(&self) -> Vec<HandlerInfo> {
+        let handlers = self.handlers.read().expect("Handler lock poisoned");
+        let transforms = self.transforms.read().expect("Transform lock poisoned");
+        let mut infos: Vec<HandlerInfo> = handlers
+            .iter()
+            .map(|(id, handler)| {
+                let (kind, event_type, label) = handler.describe();
+                HandlerInfo {
+                    id: self.subscription(*id),
+                    kind,
+                    event_type,
+                    label,
+                }
+            })
+            .chain(transforms.iter().map(|(id, transform)| HandlerInfo {
+                id: self.subscription(*id),
+                kind: "transform",
+                event_type: Some(transform.event_type_name),
+                label: transform.label,
+            }))
+            .collect();
+        infos.sort_by_key(|info| info.id);
+        infos
+    }
+
+    /// IDs of the subscriptions that are shed at the current load
+    fn shed_ids(&self) -> HashSet<usize> {
+        let shed = self.shed.read().expect("Shed lock poisoned");
+        if shed.is_empty() {
+            return HashSet::new();
+        }
+
+        let tier = self.load.tier(self.in_flight.count());
+        shed.iter()
+            .filter(|(_, max)| tier > **max)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// IDs of the subscriptions that are shed at the current load or paused
+    fn skipped_ids(&self) -> HashSet<usize> {
+        let mut skipped = self.shed_ids();
+        skipped.extend(self.paused.ids());
+        skipped
+    }
+
+    /// Pass information about an event to every handler subscribed with `subscribe_metadata`
+    fn tap(
+        &self,
+        event: &dyn DynEvent,
+        metadata: &Metadata,
+    ) -> Vec<Box<dyn std::any::Any + Send + 'static>> {
+        let skipped = self.skipped_ids();
+        self.handlers()
+            .iter()
+            .filter(|(id, _)| !skipped.contains(id))
+            .filter_map(|(_, handler)| match handler.as_ref() {
+                HandlerType::Metadata(tap) => std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    tap(EventInfo::new(event, metadata.clone()))
+                }))
+                .err(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Move an event with `Delivery::Exclusive` into the first subscribed handler that accepts it.
+    /// Returns the error if the handler panics.
+    fn deliver_owned<T>(&self, event: T) -> Option<Box<dyn std::any::Any + Send + 'static>>
+    where
+        T: DynEvent,
+    {
+        let skipped = self.skipped_ids();
+        let handlers = self.handlers();
+        let mut ids: Vec<&usize> = handlers.keys().filter(|id| !skipped.contains(id)).collect();
+        // fallback handlers only take the event if nothing ranked ahead of them does
+        let fallbacks = self.fallbacks.read().expect("Fallbacks lock poisoned");
+        ids.sort_by_key(|id| (fallbacks.get(id).copied(), **id));
+        drop(fallbacks);
+
+        let handler =
+            ids.into_iter()
+                .map(|id| &handlers[id])
+                .find(|handler| match handler.as_ref() {
+                    HandlerType::Sync(dyn_handle) => dyn_handle.accepts(&event),
+                    HandlerType::Limited(limited) => limited.handler.accepts(&event),
+                    HandlerType::Partitioned(partitioned) => partitioned.handler.accepts(&event),
+                    HandlerType::Grouped(member) => member.handler.accepts(&event),
+                    HandlerType::SyncMut(mutex) => lock_handler(mutex).accepts(&event),
+                    // moved events don't carry their metadata, so their topic is unknown, and they
+                    // can't be passed down a chain of ordered handlers
+                    HandlerType::Batch(_)
+                    | HandlerType::Metadata(_)
+                    | HandlerType::Topic { .. }
+                    | HandlerType::Ordered(_) => false,
+                })?;
+
+        let stripe = match handler.as_ref() {
+            HandlerType::Partitioned(partitioned) => Some(partitioned.stripe_of(&event)),
+            _ => None,
+        };
+        let event = Box::new(event).into_any();
+        let result = match handler.as_ref() {
+            HandlerType::Sync(dyn_handle) => {
+                std::panic::catch_unwind(AssertUnwindSafe(|| dyn_handle.dyn_handle_owned(event)))
+            }
+            HandlerType::Limited(limited) => std::panic::catch_unwind(AssertUnwindSafe(|| {
+                limited.handler.dyn_handle_owned(event)
+            })),
+            HandlerType::Grouped(member) => std::panic::catch_unwind(AssertUnwindSafe(|| {
+                member.handler.dyn_handle_owned(event)
+            })),
+            HandlerType::Partitioned(partitioned) => {
+                let _lock = partitioned.lock(stripe.unwrap_or_default());
+                std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    partitioned.handler.dyn_handle_owned(event)
+                }))
+            }
+            HandlerType::SyncMut(mutex) => match mutex.lock() {
+                // the guard is dropped inside the catch_unwind, so a panic poisons the mutex
+                Ok(mut handler_guard) => std::panic::catch_unwind(AssertUnwindSafe(move || {
+                    handler_guard.dyn_handle_mut_owned(event)
+                })),
+                Err(poisoned) => Err(Box::new(HandlerError {
+                    handler: poisoned.into_inner().label(),
+                    error: Box::new(HandlerPoisoned),
+                }) as Box<dyn Any + Send>),
+            },
+            HandlerType::Batch(_)
+            | HandlerType::Metadata(_)
+            | HandlerType::Topic { .. }
+            | HandlerType::Ordered(_) => Ok(()),
+        };
+
+        result.err()
+    }
+
+    /// Deliver events to every handler, running them according to the DispatchStrategy. Each
+    /// handler is a single piece of work that receives the events in order, so publishing many
+    /// events at once only pays for thread setup once per handler. Partitioned handlers get a
+    /// piece of work for each stripe of keys instead.
+    /// Batch handlers only receive their buffered events once a batch fills up, or if `flush` is
+    /// true. If `only` is given, no other handler receives the events.
+    fn deliver(
+        &self,
+        events: &[Arc<dyn DynEvent>],
+        flush: bool,
+        only: Option<usize>,
+    ) -> Vec<Box<dyn std::any::Any + Send + 'static>> {
+        let mut run = HandlerRun::default();
+        // events buffered for handlers that weren't ready go ahead of the new ones
+        let mut errors = Vec::new();
+        if only.is_none() {
+            for (id, backlog) in self.overflows.release(|id| self.handler_ready(id)) {
+                errors.extend(self.deliver(&backlog, flush, Some(id)));
+            }
+        }
+        // buffered batches are still flushed to shed handlers, since they were accepted earlier
+        let mut skipped = if events.is_empty() {
+            HashSet::new()
+        } else {
+            self.shed_ids()
+        };
+        skipped.extend(self.paused.ids());
+        if only.is_none() && !events.is_empty() {
+            let overflowing = self.overflows.overflowing(
+                events,
+                // skipped subscriptions don't receive the events either way
+                |id| skipped.contains(&id) || self.handler_ready(id),
+                |id, event| {
+                    let handlers = self.handlers.read().expect("Handler lock poisoned");
+                    handlers
+                        .get(&id)
+                        .is_some_and(|handler| handler.accepts(event))
+                },
+            );
+            skipped.extend(overflowing);
+        }
+        let skip = |id: &usize| skipped.contains(id) || only.is_some_and(|only| only != *id);
+        let handlers = self.handlers();
+        if only.is_none() && !events.is_empty() {
+            self.paused.buffer(events, |id, event| {
+                handlers
+                    .get(&id)
+                    .is_some_and(|handler| handler.accepts(event))
+            });
+        }
+        // the topic subscriptions each event reaches are looked up once, rather than by every
+        // handler
+        let topics = self.topics.read().expect("Topic lock poisoned");
+        let topic_matches: Vec<HashSet<usize>> = events
+            .iter()
+            .map(|event| {
+                event
+                    .metadata()
+                    .and_then(|metadata| metadata.topic.as_deref())
+                    .map(|topic| topics.matches(topic))
+                    .unwrap_or_default()
+            })
+            .collect();
+        drop(topics);
+        // fallback handlers only receive the events that no handler ranked ahead of them takes
+        let fallbacks = self
+            .fallbacks
+            .read()
+            .expect("Fallbacks lock poisoned")
+            .clone();
+        let lowest: Vec<Option<Fallback>> = if fallbacks.is_empty() {
+            vec![None; events.len()]
+        } else {
+            events
+                .iter()
+                .map(|event| {
+                    handlers
+                        .iter()
+                        .filter(|(_, handler)| handler.accepts(event.as_ref()))
+                        .map(|(id, _)| fallbacks.get(id).copied())
+                        .min()
+                        .flatten()
+                })
+                .collect()
+        };
+        let defaulted = |index: usize, id: usize| fallbacks.get(&id).copied() <= lowest[index];
+        // likewise, the handler that receives each load-balanced event, and the member of each
+        // group that receives each event, are chosen up front
+        let routed: Vec<Option<usize>> = if self.routes.is_empty() {
+            vec![None; events.len()]
+        } else {
+            let mut ids: Vec<usize> = handlers
+                .iter()
+                .filter(|(id, handler)| {
+                    !skip(id)
+                        && matches!(
+                            handler.as_ref(),
+                            HandlerType::Sync(_) | HandlerType::SyncMut(_)
+                        )
+                })
+                .map(|(id, _)| *id)
+                .collect();
+            ids.sort();
+            events
+                .iter()
+                .enumerate()
+                .map(|(index, event)| {
+                    let candidates: Vec<usize> = ids
+                        .iter()
+                        .copied()
+                        .filter(|id| defaulted(index, *id))
+                        .filter(|id| match handlers[id].as_ref() {
+                            HandlerType::Sync(dyn_handle) => dyn_handle.accepts(event.as_ref()),
+                            HandlerType::SyncMut(mutex) => {
+                                lock_handler(mutex).accepts(event.as_ref())
+                            }
+                            _ => false,
+                        })
+                        .collect();
+                    self.routes.route(event.as_ref(), &candidates)
+                })
+                .collect()
+        };
+        let reaches = |index: usize, id: usize| {
+            defaulted(index, id) && routed[index].is_none_or(|chosen| chosen == id)
+        };
+
+        let mut members: BTreeMap<&str, Vec<(usize, &Member)>> = BTreeMap::new();
+        for (id, handler) in handlers.iter().filter(|(id, _)| !skip(id)) {
+            if let HandlerType::Grouped(member) = handler.as_ref() {
+                members
+                    .entry(member.group.as_str())
+                    .or_default()
+                    .push((*id, member));
+            }
+        }
+        let group_assignments: Vec<HashSet<usize>> = if members.is_empty() {
+            vec![HashSet::new(); events.len()]
+        } else {
+            for members in members.values_mut() {
+                members.sort_by_key(|(id, _)| *id);
+            }
+            events
+                .iter()
+                .map(|event| self.groups.assign(&members, event.as_ref()))
+                .collect()
+        };
+
+        let mut chain: Vec<(usize, &Ordered)> = handlers
+            .iter()
+            .filter(|(id, _)| !skip(id))
+            .filter_map(|(id, handler)| match handler.as_ref() {
+                HandlerType::Ordered(ordered) => Some((*id, ordered)),
+                _ => None,
+            })
+            .collect();
+        if !chain.is_empty() {
+            for event in events {
+                run.errors.extend(propagate(&mut chain, event.as_ref()));
+                run.check_deadline(event.as_ref());
+            }
+        }
+
+        let trace = self.trace.read().expect("Trace lock poisoned").clone();
+        let watchdog = self
+            .watchdog
+            .read()
+            .expect("Watchdog lock poisoned")
+            .clone();
+        let mut queued = Vec::new();
+        for (id, handler) in handlers.iter().filter(|(id, _)| !skip(id)) {
+            let work =
+                match handler.as_ref() {
+                    HandlerType::Sync(dyn_handle)
+                        if !fallbacks.contains_key(id) && routed.iter().all(Option::is_none) =>
+                    {
+                        (!events.is_empty())
+                            .then_some(Work::Each(dyn_handle, Cow::Borrowed(events)))
+                    }
+                    HandlerType::Sync(dyn_handle) => {
+                        let reached: Vec<Arc<dyn DynEvent>> = events
+                            .iter()
+                            .enumerate()
+                            .filter(|(index, _)| reaches(*index, *id))
+                            .map(|(_, event)| event.clone())
+                            .collect();
+                        (!reached.is_empty()).then_some(Work::Each(dyn_handle, Cow::Owned(reached)))
+                    }
+                    HandlerType::Limited(limited) => {
+                        let admitted: Vec<Arc<dyn DynEvent>> = events
+                            .iter()
+                            .filter(|event| limited.admit(event, self.scheduler(), &self.in_flight))
+                            .cloned()
+                            .collect();
+                        (!admitted.is_empty())
+                            .then_some(Work::Each(&limited.handler, Cow::Owned(admitted)))
+                    }
+                    HandlerType::Metadata(_) | HandlerType::Ordered(_) => None,
+                    HandlerType::Partitioned(partitioned) => {
+                        // each stripe of keys gets its own thread, so that different keys are
+                        // handled in parallel while each key's events stay in order
+                        queued.extend(partitioned.split(events).into_iter().map(
+                            |(stripe, events)| (*id, Work::Partition(partitioned, stripe, events)),
+                        ));
+                        None
+                    }
+                    HandlerType::Grouped(member) => {
+                        let assigned: Vec<Arc<dyn DynEvent>> = events
+                            .iter()
+                            .zip(&group_assignments)
+                            .filter(|(_, assigned)| assigned.contains(id))
+                            .map(|(event, _)| event.clone())
+                            .collect();
+                        (!assigned.is_empty()).then_some(Work::Member(member, assigned))
+                    }
+                    HandlerType::Topic { handler, .. } => {
+                        let matched: Vec<Arc<dyn DynEvent>> = events
+                            .iter()
+                            .zip(&topic_matches)
+                            .filter(|(_, matches)| matches.contains(id))
+                            .map(|(event, _)| event.clone())
+                            .collect();
+                        (!matched.is_empty()).then_some(Work::Each(handler, Cow::Owned(matched)))
+                    }
+                    HandlerType::Batch(batched) => {
+                        let batches = batched.push(events, flush);
+                        (!batches.is_empty()).then_some(Work::Batches(&batched.handler, batches))
+                    }
+                    HandlerType::SyncMut(mutex) => {
+                        let reached: Vec<Arc<dyn DynEvent>> = events
+                            .iter()
+                            .enumerate()
+                            .filter(|(index, _)| reaches(*index, *id))
+                            .map(|(_, event)| event.clone())
+                            .collect();
+                        (!reached.is_empty()).then(|| {
+                            Work::Mut(mutex.as_ref(), lock_handler(mutex).label(), reached)
+                        })
+                    }
+                };
+
+            queued.extend(work.map(|work| (*id, work)));
+        }
+        let instruments = Instruments {
+            trace: trace.as_deref(),
+            watchdog: watchdog.as_deref(),
+        };
+        run.execute(&self.strategy, queued, instruments);
+
+        self.routes.finish(routed.into_iter().flatten());
+        self.missed_deadlines
+            .fetch_add(run.missed_deadlines.len() as u64, Ordering::SeqCst);
+        MISSED_DEADLINES
+            .with(|missed| missed.set(missed.get() + run.missed_deadlines.len() as u64));
+
+        if !run.failed_on.is_empty() && !self.retries.is_empty() {
+            self.retry(&handlers, &fallbacks, &run.failed_on);
+        }
+
+        if !run.panicked.is_empty()
+            && *self
+                .panic_policy
+                .read()
+                .expect("Panic policy lock poisoned")
+                == PanicPolicy::UnsubscribeOffender
+        {
+            for id in &run.panicked {
+                self.remove(*id);
+            }
+        }
+
+        errors.extend(run.errors);
+        errors.extend(
+            run.failures
+                .into_iter()
+                .map(|failure| Box::new(failure) as Box<dyn std::any::Any + Send + 'static>),
+        );
+        errors
+    }
+
+    /// Schedule retries of the events that handlers with a retry policy failed on, in the
+    /// background, passing them to the dead letter handlers once the policy gives up on them
+    fn retry(
+        &self,
+        handlers: &HashMap<usize, Arc<HandlerType>>,
+        fallbacks: &HashMap<usize, Fallback>,
+        failed_on: &HashMap<usize, Vec<Arc<dyn DynEvent>>>,
+    ) {
+        let dead_letters: Vec<Arc<dyn DynHandle>> = fallbacks
+            .iter()
+            .filter(|(_, fallback)| **fallback == Fallback::DeadLetter)
+            .filter_map(|(id, _)| handlers.get(id)?.dyn_handle().cloned())
+            .collect();
+        for (id, events) in failed_on {
+            if self.retries.get(*id).is_none() {
+                continue;
+            }
+            let handler = Arc::new(match handlers.get(id).map(Arc::as_ref) {
+                Some(HandlerType::SyncMut(mutex)) => Retried::Mut(mutex.clone()),
+                Some(handler) => match handler.dyn_handle() {
+                    Some(handler) => Retried::Handler(handler.clone()),
+                    None => continue,
+                },
+                None => continue,
+            });
+            for event in events {
+                Retry {
+                    retries: self.retries.clone(),
+                    id: *id,
+                    handler: handler.clone(),
+                    event: event.clone(),
+                    dead_letters: dead_letters.clone(),
+                    timer: self.scheduler().timer(),
+                    failed: 1,
+                    _guard: self.in_flight.start(),
+                }
+                .schedule();
+            }
+        }
+    }
+
+    /// The subscribed handlers, copied out of the handler lock so that they can be run without
+    /// holding it, and so subscribe and unsubscribe from within a handler
+    fn handlers(&self) -> HashMap<usize, Arc<HandlerType>> {
+        self.handlers.read().expect("Handler lock poisoned").clone()
+    }
+
+    /// Whether the handler of a subscription is ready for more events. Subscriptions that have
+    /// gone are, so that nothing waits on them.
+    fn handler_ready(&self, id: usize) -> bool {
+        let handlers = self.handlers.read().expect("Handler lock poisoned");
+        handlers.get(&id).is_none_or(|handler| handler.poll_ready())
+    }
+}
+
+/// Events waiting to be run by a handler on one of the Publisher's threads
+enum Work<'a> {
+    /// Run the handler once for each event, in order
+    Each(&'a Arc<dyn DynHandle>, Cow<'a, [Arc<dyn DynEvent>]>),
+    /// Run the handler once for each batch of events, in order
+    Batches(&'a Arc<dyn DynHandleBatch>, Vec<Vec<Arc<dyn DynEvent>>>),
+    /// Run a group member once for each event assigned to it, in order
+    Member(&'a Member, Vec<Arc<dyn DynEvent>>),
+    /// Run the handler once for each event, in order, while holding the lock for their stripe
+    Partition(&'a Partitioned, usize, Vec<Arc<dyn DynEvent>>),
+    /// Run the mut handler with the given label once for each event, in order, while holding its
+    /// lock. Each mut handler has a lock of its own, so different ones can run at the same time.
+    Mut(
+        &'a Mutex<dyn DynHandleMut>,
+        &'static str,
+        Vec<Arc<dyn DynEvent>>,
+    ),
+}
+
+impl Work<'_> {
+    /// Run the work for the subscription with the given ID, noting whether its handler panicked
+    /// and the events it failed on
+    fn run_for(self, id: usize, instruments: Instruments) -> HandlerRun {
+        let watchdog = instruments.watchdog;
+        let mut run = match instruments.trace {
+            Some(trace) => {
+                let (label, events) = self.describe();
+                trace.span(label, events, || self.run(watchdog))
+            }
+            None => self.run(watchdog),
+        };
+        if !run.errors.is_empty() {
+            run.panicked.insert(id);
+        }
+        if !run.failed.is_empty() {
+            let failed = std::mem::take(&mut run.failed);
+            run.failed_on.insert(id, failed);
+        }
+        run
+    }
+
+    /// Type of the first event the work covers
+    fn event_type(&self) -> Option<&'static str> {
+        let first = match self {
+            Work::Each(_, events) => events.first(),
+            Work::Batches(_, batches) => batches.iter().flatten().next(),
+            Work::Member(_, events) | Work::Partition(_, _, events) | Work::Mut(_, _, events) => {
+                events.first()
+            }
+        };
+        first.map(|event| event.type_name())
+    }
+
+    /// Label of the handler the work is for, and how many events it covers
+    fn describe(&self) -> (&'static str, usize) {
+        match self {
+            Work::Each(handler, events) => (handler.label(), events.len()),
+            Work::Batches(handler, batches) => {
+                (handler.label(), batches.iter().map(Vec::len).sum())
+            }
+            Work::Member(member, events) => (member.handler.label(), events.len()),
+            Work::Partition(partitioned, _, events) => (partitioned.handler.label(), events.len()),
+            Work::Mut(_, label, events) => (label, events.len()),
+        }
+    }
+
+    fn run(self, watchdog: Option<&Watchdog>) -> HandlerRun {
+        let mut run = HandlerRun::default();
+        match self {
+            Work::Each(handler, events) => run.handle_each(handler, &events, watchdog),
+            Work::Member(member, events) => {
+                for event in &events {
+                    run.handle_each(&member.handler, std::slice::from_ref(event), watchdog);
+                    member.done();
+                }
+            }
+            Work::Partition(partitioned, stripe, events) => {
+                let _lock = partitioned.lock(stripe);
+                run.handle_each(&partitioned.handler, &events, watchdog);
+            }
+            Work::Mut(mutex, label, events) => run.handle_mut(mutex, label, &events, watchdog),
+            Work::Batches(handler, batches) => {
+                for events in batches {
+                    let batch: Vec<&dyn DynEvent> =
+                        events.iter().map(|event| event.as_ref()).collect();
+                    let handle = || std::panic::catch_unwind(|| handler.dyn_handle_batch(&batch));
+                    let result = match watchdog.zip(batch.first()) {
+                        Some((watchdog, first)) => {
+                            watchdog.time(handler.label(), first.type_name(), handle)
+                        }
+                        _ => handle(),
+                    };
+                    if let Err(e) = result {
+                        run.errors.push(e);
+                    }
+                    for event in batch {
+                        run.check_deadline(event);
+                    }
+                }
+            }
+        }
+        run
+    }
+}
+
+/// What handlers are measured with as they run
+#[derive(Clone, Copy)]
+struct Instruments<'a> {
+    trace: Option<&'a ChromeTrace>,
+    watchdog: Option<&'a Watchdog>,
+}
+
+/// Outcome of running one or more handlers over a set of events
+#[derive(Default)]
+struct HandlerRun {
+    errors: Vec<Box<dyn std::any::Any + Send + 'static>>,
+    /// Subscriptions whose handlers panicked
+    panicked: HashSet<usize>,
+    /// Events each subscription's handler panicked on or returned an error for, where the handler
+    /// can be given them again one at a time
+    failed_on: HashMap<usize, Vec<Arc<dyn DynEvent>>>,
+    /// Events the handler of the work being run failed on, until they are keyed by its
+    /// subscription
+    failed: Vec<Arc<dyn DynEvent>>,
+    /// Errors returned by fallible handlers, which are kept apart from their panics
+    failures: Vec<HandlerError>,
+    /// Sequence numbers of events that were still being handled after their deadline
+    missed_deadlines: HashSet<u64>,
+}
+
+impl HandlerRun {
+    fn handle_each(
+        &mut self,
+        handler: &Arc<dyn DynHandle>,
+        events: &[Arc<dyn DynEvent>],
+        watchdog: Option<&Watchdog>,
+    ) {
+        for event in events {
+            let handle = || std::panic::catch_unwind(|| handler.dyn_try_handle(event.as_ref()));
+            let result = match watchdog {
+                Some(watchdog) => watchdog.time(handler.label(), event.type_name(), handle),
+                None => handle(),
+            };
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => {
+                    self.failures.push(HandlerError {
+                        handler: handler.label(),
+                        error,
+                    });
+                    self.failed.push(event.clone());
+                }
+                Err(e) => {
+                    self.errors.push(e);
+                    self.failed.push(event.clone());
+                }
+            }
+            self.check_deadline(event.as_ref());
+        }
+    }
+
+    fn handle_mut(
+        &mut self,
+        mutex: &Mutex<dyn DynHandleMut>,
+        label: &'static str,
+        events: &[Arc<dyn DynEvent>],
+        watchdog: Option<&Watchdog>,
+    ) {
+        // the guard is dropped inside the catch_unwind, so a panic poisons the mutex and the
+        // handler is skipped from then on, as its state may have been left half updated
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut handler_guard = match mutex.lock() {
+                Ok(handler_guard) => handler_guard,
+                Err(poisoned) => {
+                    let handler = poisoned.into_inner();
+                    return events
+                        .iter()
+                        .any(|event| handler.accepts(event.as_ref()))
+                        .then_some(HandlerError {
+                            handler: label,
+                            error: Box::new(HandlerPoisoned),
+                        });
+                }
+            };
+            for event in events {
+                let result = match watchdog {
+                    Some(watchdog) => watchdog.time(label, event.type_name(), || {
+                        handler_guard.dyn_try_handle_mut(event.as_ref())
+                    }),
+                    None => handler_guard.dyn_try_handle_mut(event.as_ref()),
+                };
+                if let Err(error) = result {
+                    self.failures.push(HandlerError {
+                        handler: label,
+                        error,
+                    });
+                    self.failed.push(event.clone());
+                }
+                self.check_deadline(event.as_ref());
+            }
+            None
+        }));
+        // a mut handler that panicked is poisoned, so only the events it returned errors for are
+        // kept for retries
+        match result {
+            Ok(poisoned) => self.failures.extend(poisoned),
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    /// Run the queued work according to `strategy`, merging in the outcome
+    fn execute(
+        &mut self,
+        strategy: &DispatchStrategy,
+        queued: Vec<(usize, Work)>,
+        instruments: Instruments,
+    ) {
+        let outcome = Mutex::new(HandlerRun::default());
+        let jobs: Vec<(JobInfo, Job)> = queued
+            .into_iter()
+            .map(|(id, work)| {
+                let (handler, events) = work.describe();
+                let telemetry = HandlerTelemetry::new(handler, work.event_type(), events);
+                let outcome = &outcome;
+                let job = Box::new(move || {
+                    // handler panics are caught as they run, so this only catches the panics of
+                    // instruments and the like
+                    let run = || {
+                        std::panic::catch_unwind(AssertUnwindSafe(|| work.run_for(id, instruments)))
+                            .unwrap_or_else(|e| HandlerRun {
+                                errors: vec![e],
+                                ..Default::default()
+                            })
+                    };
+                    let run = telemetry.observe(run, HandlerRun::outcome);
+                    outcome.lock().expect("Outcome mutex poisoned").merge(run);
+                }) as Job;
+                (JobInfo { handler, events }, job)
+            })
+            .collect();
+        strategy.dispatch(jobs);
+        self.merge(outcome.into_inner().expect("Outcome mutex poisoned"));
+    }
+
+    fn outcome(&self) -> Outcome {
+        if !self.errors.is_empty() {
+            Outcome::Panicked
+        } else if !self.failures.is_empty() {
+            Outcome::Failed
+        } else {
+            Outcome::Ok
+        }
+    }
+
+    fn check_deadline(&mut self, event: &dyn DynEvent) {
+        if let Some(deadline) = event.dyn_deadline()
+            && platform::instant().is_some_and(|now| now > deadline)
+        {
+            let sequence = event.metadata().map_or(0, |metadata| metadata.sequence);
+            self.missed_deadlines.insert(sequence);
+        }
+    }
+
+    fn merge(&mut self, run: HandlerRun) {
+        self.errors.extend(run.errors);
+        self.failures.extend(run.failures);
+        self.panicked.extend(run.panicked);
+        for (id, events) in run.failed_on {
+            self.failed_on.entry(id).or_default().extend(events);
+        }
+        self.missed_deadlines.extend(run.missed_deadlines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Backoff, Deadline, Event, Handle, HandleBatch, HandleMut, HandlerPanic, Owned, Partition,
+        Race2,
+    };
+
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct TestEvent;
+    impl Event for TestEvent {}
+
+    struct TestHandler {
+        called: Arc<Mutex<bool>>,
+    }
+    impl DynHandle for TestHandler {
+        fn dyn_handle(&self, _event: &dyn DynEvent) {
+            let mut called = self.called.lock().unwrap();
+            *called = true;
+        }
+    }
+
+    struct TestHandlerMut {
+        called: Arc<Mutex<bool>>,
+    }
+    impl DynHandleMut for TestHandlerMut {
+        fn dyn_handle_mut(&mut self, _event: &dyn DynEvent) {
+            let mut called = self.called.lock().unwrap();
+            *called = true;
+        }
+    }
+
+    struct PanicHandler;
+    impl DynHandle for PanicHandler {
+        fn dyn_handle(&self, _event: &dyn DynEvent) {
+            panic!("handler panic");
+        }
+    }
+
+    #[test]
+    fn test_subscribe_with_and_publish() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let handler = TestHandler {
+            called: called.clone(),
+        };
+        publisher.subscribe(handler);
+        let _ = publisher.publish(TestEvent);
+        assert!(*called.lock().unwrap());
+    }
+
+    #[test]
+    fn test_subscribe_and_publish() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let handler = TestHandler {
+            called: called.clone(),
+        };
+        publisher.subscribe(handler);
+        let _ = publisher.publish(TestEvent);
+        assert!(*called.lock().unwrap());
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let handler = TestHandler {
+            called: called.clone(),
+        };
+        let id = publisher.subscribe(handler);
+        publisher.unsubscribe(id).unwrap();
+        let _ = publisher.publish(TestEvent);
+        assert!(!*called.lock().unwrap());
+    }
+
+    #[test]
+    fn test_publish_error() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe(PanicHandler);
+        let result = publisher.publish(TestEvent);
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn test_subscribe_mut_and_publish() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let handler = TestHandlerMut {
+            called: called.clone(),
+        };
+        publisher.subscribe_mut(handler);
+        let _ = publisher.publish(TestEvent);
+        assert!(*called.lock().unwrap());
+    }
+
+    #[test]
+    fn test_unsubsribe_mut() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let handler = TestHandlerMut {
+            called: called.clone(),
+        };
+        let id = publisher.subscribe_mut(handler);
+        publisher.unsubscribe_mut(id).unwrap();
+        let _ = publisher.publish(TestEvent);
+        assert!(!*called.lock().unwrap());
+    }
+
+    #[test]
+    fn test_mut_handlers_that_panic_are_poisoned_until_recovered() {
+        struct Tally(i32);
+        impl HandleMut for Tally {
+            type EventType = NumberEvent;
+
+            fn handle_mut(&mut self, event: NumberEvent) {
+                assert!(event.0 >= 0, "negative number");
+                self.0 += event.0;
+            }
+        }
+
+        let mut publisher = Publisher::default();
+        let id = publisher.subscribe_mut(Tally(0));
+        let received = record_numbers(&mut publisher);
+
+        assert!(publisher.publish(NumberEvent(-1)).is_err());
+        assert_eq!(publisher.is_poisoned(id), Ok(true));
+        let errors = publisher.try_publish(NumberEvent(2)).unwrap_err();
+        assert!(errors.panics.is_empty());
+        assert_eq!(errors.failures.len(), 1);
+        assert_eq!(errors.failures[0].downcast_ref(), Some(&HandlerPoisoned));
+        // events the handler doesn't take aren't reported
+        assert!(publisher.publish(TestEvent).is_ok());
+
+        publisher.recover(id).unwrap();
+        assert_eq!(publisher.is_poisoned(id), Ok(false));
+        publisher.publish(NumberEvent(3)).unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![-1, 2, 3]);
+
+        let tally = publisher.unsubscribe_mut(id).unwrap();
+        let tally = tally.downcast::<Mutex<Tally>>().unwrap();
+        assert_eq!(tally.lock().unwrap().0, 3);
+    }
+
+    #[test]
+    fn test_subscribe_with_mut_takes_closures_that_are_not_sync() {
+        let mut publisher = Publisher::default();
+        let totals = Arc::new(Mutex::new(Vec::new()));
+        let totals_clone = totals.clone();
+        // a Cell can be sent to another thread but not shared between them
+        let total = std::cell::Cell::new(0);
+        publisher.subscribe_with_mut(move |event: NumberEvent| {
+            total.set(total.get() + event.0);
+            totals_clone.lock().unwrap().push(total.get());
+        });
+
+        publisher.publish(NumberEvent(1)).unwrap();
+        publisher.publish(NumberEvent(2)).unwrap();
+        assert_eq!(*totals.lock().unwrap(), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_different_mut_handlers_run_at_the_same_time() {
+        // each handler waits for the other to start, which it can only do if they run in parallel
+        struct Rendezvous {
+            arrived: Arc<AtomicUsize>,
+            met: bool,
+        }
+        impl HandleMut for Rendezvous {
+            type EventType = Ping;
+
+            fn handle_mut(&mut self, _event: Ping) {
+                self.arrived.fetch_add(1, Ordering::SeqCst);
+                let started = Instant::now();
+                while started.elapsed() < Duration::from_secs(5) {
+                    if self.arrived.load(Ordering::SeqCst) == 2 {
+                        self.met = true;
+                        return;
+                    }
+                    thread::yield_now();
+                }
+            }
+        }
+
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::ScopedThreads {
+            max: 2,
+            stack_size: None,
+        });
+        let arrived = Arc::new(AtomicUsize::new(0));
+        let ids: Vec<SubscriptionId> = (0..2)
+            .map(|_| {
+                publisher.subscribe_mut(Rendezvous {
+                    arrived: arrived.clone(),
+                    met: false,
+                })
+            })
+            .collect();
+        publisher.publish(Ping(0)).unwrap();
+
+        for id in ids {
+            let handler = publisher.unsubscribe_mut(id).unwrap();
+            let handler = handler.downcast::<Mutex<Rendezvous>>().unwrap();
+            assert!(handler.lock().unwrap().met);
+        }
+    }
+
+    #[test]
+    fn test_publish_to_both_handler_types() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let called_mut = Arc::new(Mutex::new(false));
+        let handler = TestHandler {
+            called: called.clone(),
+        };
+        let handler_mut = TestHandlerMut {
+            called: called_mut.clone(),
+        };
+        publisher.subscribe(handler);
+        publisher.subscribe_mut(handler_mut);
+        let _ = publisher.publish(TestEvent);
+        assert!(*called.lock().unwrap());
+        assert!(*called_mut.lock().unwrap());
+    }
+
+    struct RecordingMiddleware {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        flow: Flow,
+    }
+    impl Middleware for RecordingMiddleware {
+        fn before(&self, _event: &dyn DynEvent, _metadata: &mut Metadata) -> Flow {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} before", self.name));
+            self.flow
+        }
+
+        fn after(&self, _event: &dyn DynEvent, _metadata: &Metadata) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} after", self.name));
+        }
+    }
+
+    #[test]
+    fn test_middleware_runs_around_handlers() {
+        let mut publisher = Publisher::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+        publisher.subscribe_with(move |_event: TestEvent| {
+            handler_log.lock().unwrap().push(String::from("handler"));
+        });
+        publisher.add_middleware(RecordingMiddleware {
+            name: "first",
+            log: log.clone(),
+            flow: Flow::Continue,
+        });
+        publisher.add_middleware(RecordingMiddleware {
+            name: "second",
+            log: log.clone(),
+            flow: Flow::Continue,
+        });
+        let _ = publisher.publish(TestEvent);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "first before",
+                "second before",
+                "handler",
+                "second after",
+                "first after"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_middleware_can_stop_dispatch() {
+        let mut publisher = Publisher::default();
+        let called = Arc::new(Mutex::new(false));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        publisher.subscribe(TestHandler {
+            called: called.clone(),
+        });
+        publisher.add_middleware(RecordingMiddleware {
+            name: "blocker",
+            log: log.clone(),
+            flow: Flow::Stop,
+        });
+        let result = publisher.publish(TestEvent);
+        assert!(result.is_ok());
+        assert!(!*called.lock().unwrap());
+        assert_eq!(*log.lock().unwrap(), vec!["blocker before"]);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct NumberEvent(i32);
+    impl Event for NumberEvent {}
+
+    struct TaggingMiddleware;
+    impl Middleware for TaggingMiddleware {
+        fn before(&self, _event: &dyn DynEvent, metadata: &mut Metadata) -> Flow {
+            metadata.source = Some(String::from("tagged"));
+            Flow::Continue
+        }
+    }
+
+    #[test]
+    fn test_middleware_can_mutate_metadata() {
+        let mut publisher = Publisher::default();
+        let source = Arc::new(Mutex::new(None));
+        let source_clone = source.clone();
+        publisher.add_middleware(TaggingMiddleware);
+        publisher.subscribe_envelope(move |envelope: Envelope<NumberEvent>| {
+            *source_clone.lock().unwrap() = envelope.metadata.source;
+        });
+        let _ = publisher.publish(NumberEvent(1));
+        assert_eq!(source.lock().unwrap().as_deref(), Some("tagged"));
+    }
+
+    #[test]
+    fn test_subscribe_envelope_receives_metadata() {
+        let mut publisher = Publisher::with_source("test");
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_envelope(move |envelope: Envelope<NumberEvent>| {
+            received_clone.lock().unwrap().push(envelope);
+        });
+        let _ = publisher.publish(NumberEvent(1));
+        let _ = publisher.publish_correlated(NumberEvent(2), 1);
+        let _ = publisher.publish(NumberEvent(3));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[0].payload, NumberEvent(1));
+        assert_eq!(received[0].metadata.sequence, 1);
+        assert_eq!(received[0].metadata.correlation_id, 1);
+        assert_eq!(received[0].metadata.source.as_deref(), Some("test"));
+        assert_eq!(received[1].metadata.sequence, 2);
+        assert_eq!(received[1].metadata.correlation_id, 1);
+        assert_eq!(received[2].metadata.sequence, 3);
+        assert_eq!(received[2].metadata.correlation_id, 3);
+    }
+
+    #[test]
+    fn test_subscribe_join_correlated_joins_events_from_the_same_chain() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_join_correlated(move |number: NumberEvent, test: TestEvent| {
+            received_clone.lock().unwrap().push((number.0, test));
+        });
+
+        let _ = publisher.publish_correlated(NumberEvent(1), 10);
+        let _ = publisher.publish_correlated(NumberEvent(2), 20);
+        let _ = publisher.publish_correlated(TestEvent, 20);
+        assert_eq!(received.lock().unwrap().len(), 1);
+        assert_eq!(received.lock().unwrap()[0].0, 2);
+
+        let _ = publisher.publish_correlated(TestEvent, 10);
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert_eq!(received.lock().unwrap()[1].0, 1);
+    }
+
+    #[test]
+    fn test_subscribe_race_timeout_publishes_timeout_when_nothing_arrives() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_race_timeout(
+            move |outcome: Race2<NumberEvent, RaceTimedOut>| {
+                received_clone.lock().unwrap().push(outcome);
+            },
+            Duration::from_millis(20),
+        );
+
+        let _ = publisher.publish_correlated(TestEvent, 1);
+        let _ = publisher.publish_correlated(TestEvent, 2);
+        let _ = publisher.publish_correlated(NumberEvent(2), 2);
+        thread::sleep(Duration::from_millis(100));
+        let _ = publisher.publish_correlated(NumberEvent(1), 1);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0], Race2::First(NumberEvent(2)));
+        assert!(matches!(&received[1], Race2::Second(timed_out) if timed_out.correlation_id == 1));
+    }
+
+    #[test]
+    fn test_subscribe_topic_receives_events_published_to_its_topic() {
+        let mut publisher = Publisher::default();
+        let topic_received = Arc::new(Mutex::new(Vec::new()));
+        let topic_received_clone = topic_received.clone();
+        publisher.subscribe_topic(
+            "physics/collision",
+            Handler::new(move |event: NumberEvent| {
+                topic_received_clone.lock().unwrap().push(event.0)
+            }),
+        );
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher
+            .subscribe_with(move |event: NumberEvent| received_clone.lock().unwrap().push(event.0));
+
+        let _ = publisher.publish_to("physics/collision", NumberEvent(1));
+        let _ = publisher.publish_to("audio/collision", NumberEvent(2));
+        let _ = publisher.publish(NumberEvent(3));
+
+        assert_eq!(*topic_received.lock().unwrap(), vec![1]);
+        assert_eq!(*received.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_subscribe_topic_matches_wildcards_until_unsubscribed() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let id = publisher.subscribe_topic(
+            "sensors/+/temperature",
+            Handler::new(move |event: NumberEvent| received_clone.lock().unwrap().push(event.0)),
+        );
+
+        let _ = publisher.publish_to("sensors/kitchen/temperature", NumberEvent(1));
+        let _ = publisher.publish_to("sensors/kitchen/humidity", NumberEvent(2));
+        let _ = publisher.publish_to("sensors/hall/temperature", NumberEvent(3));
+        publisher.unsubscribe(id).unwrap();
+        let _ = publisher.publish_to("sensors/hall/temperature", NumberEvent(4));
+
+        assert_eq!(*received.lock().unwrap(), vec![1, 3]);
+    }
+
+    #[derive(Clone)]
+    struct AccountEvent {
+        account: u64,
+        sequence: u64,
+    }
+    impl Event for AccountEvent {
+        fn as_partition(&self) -> Option<&dyn Partition> {
+            Some(self)
+        }
+    }
+    impl Partition for AccountEvent {
+        fn partition_key(&self) -> u64 {
+            self.account
+        }
+    }
+
+    #[test]
+    fn test_subscribe_partitioned_handles_each_key_in_order_one_at_a_time() {
+        let mut publisher = Publisher::default();
+        let running = Arc::new(Mutex::new(HashSet::new()));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_partitioned(Handler::new(move |event: AccountEvent| {
+            assert!(running.lock().unwrap().insert(event.account));
+            thread::sleep(Duration::from_millis(1));
+            received_clone
+                .lock()
+                .unwrap()
+                .push((event.account, event.sequence));
+            running.lock().unwrap().remove(&event.account);
+        }));
+
+        let events = (0..20).map(|sequence| AccountEvent {
+            account: sequence % 4,
+            sequence,
+        });
+        assert!(publisher.publish_all(events).is_ok());
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 20);
+        for account in 0..4 {
+            let sequences: Vec<u64> = received
+                .iter()
+                .filter(|(received_account, _)| *received_account == account)
+                .map(|(_, sequence)| *sequence)
+                .collect();
+            assert_eq!(sequences, (account..20).step_by(4).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_subscribe_group_delivers_each_event_to_one_member() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut ids = Vec::new();
+        for worker in 0..2 {
+            let received_clone = received.clone();
+            ids.push(publisher.subscribe_group(
+                "workers",
+                Handler::new(move |event: NumberEvent| {
+                    received_clone.lock().unwrap().push((worker, event.0))
+                }),
+            ));
+        }
+        let broadcast = Arc::new(Mutex::new(Vec::new()));
+        let broadcast_clone = broadcast.clone();
+        publisher.subscribe_with(move |event: NumberEvent| {
+            broadcast_clone.lock().unwrap().push(event.0)
+        });
+
+        let _ = publisher.publish_all((0..4).map(NumberEvent));
+        publisher.unsubscribe(ids[0]).unwrap();
+        let _ = publisher.publish(NumberEvent(4));
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, vec![(0, 0), (0, 2), (1, 1), (1, 3), (1, 4)]);
+        assert_eq!(*broadcast.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_set_routing_shares_events_between_handlers() {
+        let mut publisher = Publisher::default();
+        publisher.set_routing::<NumberEvent>(Routing::RoundRobin);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for worker in 0..2 {
+            let received_clone = received.clone();
+            publisher.subscribe_with(move |event: NumberEvent| {
+                received_clone.lock().unwrap().push((worker, event.0))
+            });
+        }
+        let tests = Arc::new(AtomicUsize::new(0));
+        let tests_clone = tests.clone();
+        publisher.subscribe_with(move |_event: TestEvent| {
+            tests_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let _ = publisher.publish_all((0..4).map(NumberEvent));
+        let _ = publisher.publish(TestEvent);
+        publisher.set_routing::<NumberEvent>(Routing::Broadcast);
+        let _ = publisher.publish(NumberEvent(4));
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(
+            received,
+            vec![(0, 0), (0, 2), (0, 4), (1, 1), (1, 3), (1, 4)]
+        );
+        assert_eq!(tests.load(Ordering::SeqCst), 1);
+    }
+
+    #[derive(Clone)]
+    struct Ping(u32);
+    impl Event for Ping {}
+    impl Request for Ping {
+        type Response = u32;
+    }
+
+    #[test]
+    fn test_request_collects_responses() {
+        let mut publisher = Publisher::default();
+        publisher.respond(|ping: Ping| ping.0 + 1);
+        publisher.respond(|ping: Ping| ping.0 * 10);
+        let plain = Arc::new(AtomicUsize::new(0));
+        let plain_clone = plain.clone();
+        publisher.subscribe_with(move |_ping: Ping| {
+            plain_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let mut responses = publisher.request(Ping(2));
+        responses.sort();
+        assert_eq!(responses, vec![3, 20]);
+        assert_eq!(plain.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_publish_collect_gathers_handler_outputs() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_collect_with(|event: NumberEvent| event.0 > 0);
+        publisher.subscribe_collect_with(|event: NumberEvent| event.0 % 2 == 0);
+        publisher.subscribe_collect_with(|event: NumberEvent| event.0.to_string());
+        let plain = Arc::new(AtomicUsize::new(0));
+        let plain_clone = plain.clone();
+        publisher.subscribe_with(move |_event: NumberEvent| {
+            plain_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let mut verdicts = publisher.publish_collect::<_, bool>(NumberEvent(3));
+        verdicts.sort();
+        assert_eq!(verdicts, vec![false, true]);
+        assert_eq!(
+            publisher.publish_collect::<_, String>(NumberEvent(3)),
+            vec![String::from("3")]
+        );
+        assert!(publisher.publish_fold(NumberEvent(4), true, |all, ok: bool| all && ok));
+        assert!(!publisher.publish_fold(NumberEvent(-4), true, |all, ok: bool| all && ok));
+        assert!(publisher.publish_collect::<_, bool>(TestEvent).is_empty());
+        assert_eq!(plain.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_ordered_handlers_can_stop_propagation() {
+        let mut publisher = Publisher::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        publisher.subscribe_ordered(0, move |event: NumberEvent| {
+            seen_clone.lock().unwrap().push(("window", event.0));
+            ControlFlow::Continue(())
+        });
+        let seen_clone = seen.clone();
+        publisher.subscribe_ordered(10, move |event: NumberEvent| {
+            seen_clone.lock().unwrap().push(("dialog", event.0));
+            if event.0 > 0 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        let plain = Arc::new(AtomicUsize::new(0));
+        let plain_clone = plain.clone();
+        publisher.subscribe_with(move |_event: NumberEvent| {
+            plain_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        publisher
+            .publish_all(vec![NumberEvent(1), NumberEvent(0)])
+            .unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![("dialog", 1), ("dialog", 0), ("window", 0)]
+        );
+        assert_eq!(plain.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_sampling_can_be_changed_while_publishing() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: NumberEvent| {
+            received_clone.lock().unwrap().push(event.0);
+        });
+
+        publisher.set_sampling::<NumberEvent>(Sampling::Sampled(2));
+        publisher.publish_all((0..4).map(NumberEvent)).unwrap();
+        publisher.set_sampling::<NumberEvent>(Sampling::Muted);
+        publisher.publish(NumberEvent(4)).unwrap();
+        assert_eq!(publisher.sampling::<NumberEvent>(), Sampling::Muted);
+        publisher.set_sampling::<NumberEvent>(Sampling::Broadcast);
+        publisher.publish(NumberEvent(5)).unwrap();
+
+        assert_eq!(*received.lock().unwrap(), vec![0, 2, 5]);
+    }
+
+    #[test]
+    fn test_first_handler_to_cancel_swallows_the_event() {
+        let mut publisher = Publisher::default();
+        let swallowed_by = Arc::new(Mutex::new(Vec::new()));
+        for widget in ["button", "panel"] {
+            let swallowed_by = swallowed_by.clone();
+            publisher.subscribe_with(move |click: Arc<Cancellable<NumberEvent>>| {
+                if click.0 > 0 && click.cancel() {
+                    swallowed_by.lock().unwrap().push(widget);
+                }
+            });
+        }
+
+        assert!(publisher.publish_cancellable(NumberEvent(1)).unwrap());
+        assert_eq!(swallowed_by.lock().unwrap().len(), 1);
+        assert!(!publisher.publish_cancellable(NumberEvent(0)).unwrap());
+
+        // a handler panicking doesn't hide that another cancelled the event
+        publisher.subscribe(PanicHandler);
+        let (cancelled, errors) = publisher.publish_cancellable(NumberEvent(2)).unwrap_err();
+        assert!(cancelled);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_publish_with_only_builds_events_someone_receives() {
+        let mut publisher = Publisher::default();
+        let built = Arc::new(AtomicUsize::new(0));
+        let build = |n| {
+            let built = built.clone();
+            move || {
+                built.fetch_add(1, Ordering::SeqCst);
+                NumberEvent(n)
+            }
+        };
+
+        publisher.subscribe_with(|_event: TestEvent| {});
+        publisher.publish_with(build(1)).unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 0);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: Arc<NumberEvent>| {
+            received_clone.lock().unwrap().push(event.0);
+        });
+        publisher.publish_with(build(2)).unwrap();
+        publisher.set_sampling::<NumberEvent>(Sampling::Muted);
+        publisher.publish_with(build(3)).unwrap();
+
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+        assert_eq!(*received.lock().unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_events_published_before_seal_reach_late_subscribers() {
+        let mut publisher = Publisher::default();
+        publisher.hold();
+        publisher.publish(NumberEvent(1)).unwrap();
+        publisher
+            .publish_all(vec![NumberEvent(2), NumberEvent(3)])
+            .unwrap();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: NumberEvent| {
+            received_clone.lock().unwrap().push(event.0);
+        });
+        assert!(received.lock().unwrap().is_empty());
+
+        publisher.seal().unwrap();
+        publisher.publish(NumberEvent(4)).unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_resume_unwind_policy_panics_on_the_publishing_thread() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe(PanicHandler);
+        publisher.set_panic_policy(PanicPolicy::ResumeUnwindOnCaller);
+
+        let payload = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = publisher.publish(TestEvent);
+        }))
+        .unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"handler panic"));
+
+        publisher.set_panic_policy(PanicPolicy::Collect);
+        assert!(publisher.publish(TestEvent).is_err());
+    }
+
+    #[test]
+    fn test_unsubscribe_offender_policy_removes_handlers_that_panic() {
+        let mut publisher = Publisher::default();
+        let id = publisher.subscribe(PanicHandler);
+        let received = record_numbers(&mut publisher);
+        publisher.set_panic_policy(PanicPolicy::UnsubscribeOffender);
+
+        assert!(publisher.publish(NumberEvent(1)).is_err());
+        assert!(publisher.publish(NumberEvent(2)).is_ok());
+        assert_eq!(
+            publisher.unsubscribe(id).err(),
+            Some(UnsubscribeError::Stale)
+        );
+        assert_eq!(*received.lock().unwrap(), [1, 2]);
+
+        publisher.subscribe(PanicHandler);
+        publisher.set_panic_policy(PanicPolicy::LogAndContinue);
+        assert!(publisher.publish(NumberEvent(3)).is_ok());
+        assert_eq!(publisher.handler_count(), 2);
+    }
+
+    #[test]
+    fn test_panics_logged_and_continued_are_passed_to_the_panic_hook() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_named("explodes", PanicHandler);
+        publisher.set_panic_policy(PanicPolicy::LogAndContinue);
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let log = logged.clone();
+        publisher.on_panic(move |handler, message| {
+            log.lock()
+                .unwrap()
+                .push(format!("{handler} panicked: {message}"));
+        });
+
+        assert!(publisher.publish(TestEvent).is_ok());
+        assert_eq!(
+            *logged.lock().unwrap(),
+            ["explodes panicked: handler panic"]
+        );
+
+        publisher.set_panic_policy(PanicPolicy::Collect);
+        assert!(publisher.publish(TestEvent).is_err());
+        assert_eq!(logged.lock().unwrap().len(), 1);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Negative(i32);
+    impl fmt::Display for Negative {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} is negative", self.0)
+        }
+    }
+    impl std::error::Error for Negative {}
+
+    struct RejectNegative;
+    impl TryHandle for RejectNegative {
+        type EventType = NumberEvent;
+        type Error = Negative;
+        fn try_handle(&self, event: NumberEvent) -> Result<(), Negative> {
+            if event.0 < 0 {
+                Err(Negative(event.0))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    struct CountNegative(usize);
+    impl TryHandleMut for CountNegative {
+        type EventType = NumberEvent;
+        type Error = Negative;
+        fn try_handle_mut(&mut self, event: NumberEvent) -> Result<(), Negative> {
+            self.0 += 1;
+            if event.0 < 0 {
+                Err(Negative(event.0))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_fallible_handler_errors_are_kept_apart_from_panics() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_try(RejectNegative);
+        publisher.subscribe_try_mut(CountNegative(0));
+        publisher.subscribe(PanicHandler);
+        publisher.set_panic_policy(PanicPolicy::LogAndContinue);
+
+        assert!(publisher.try_publish(NumberEvent(1)).is_ok());
+        let errors = publisher.try_publish(NumberEvent(-2)).unwrap_err();
+        assert!(errors.panics.is_empty());
+        assert_eq!(errors.failures.len(), 2);
+        for failure in &errors.failures {
+            assert_eq!(failure.downcast_ref(), Some(&Negative(-2)));
+        }
+
+        publisher.set_panic_policy(PanicPolicy::Collect);
+        let errors = publisher.publish(NumberEvent(-3)).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].downcast_ref::<HandlerError>().is_none());
+        let failure = errors[2].downcast_ref::<HandlerError>().unwrap();
+        assert_eq!(
+            failure.to_string(),
+            format!("{}: -3 is negative", failure.handler)
+        );
+    }
+
+    #[test]
+    fn test_sequential_strategy_runs_handlers_on_the_publishing_thread() {
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Sequential);
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..3 {
+            let threads = threads.clone();
+            publisher.subscribe_with(move |_event: TestEvent| {
+                threads.lock().unwrap().push(thread::current().id());
+            });
+        }
+        publisher.subscribe(PanicHandler);
+
+        assert_eq!(publisher.publish(TestEvent).unwrap_err().len(), 1);
+        assert_eq!(*threads.lock().unwrap(), vec![thread::current().id(); 3]);
+    }
+
+    #[test]
+    fn test_executor_strategy_runs_every_job() {
+        struct Counting(AtomicUsize);
+        impl crate::Executor for Counting {
+            fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+                for job in jobs {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                    job();
+                }
+            }
+        }
+
+        let executor = Arc::new(Counting(AtomicUsize::new(0)));
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Executor(executor.clone()));
+        let received = Arc::new(AtomicUsize::new(0));
+        for _ in 0..2 {
+            let received = received.clone();
+            publisher.subscribe_with(move |_event: TestEvent| {
+                received.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        publisher.publish(TestEvent).unwrap();
+        assert_eq!(executor.0.load(Ordering::SeqCst), 2);
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_executors_can_order_jobs_by_handler() {
+        /// Runs the jobs of handlers named "urgent::..." first, then the rest in parallel
+        struct UrgentFirst;
+        impl crate::Executor for UrgentFirst {
+            fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+                crate::SequentialExecutor.execute(jobs)
+            }
+
+            fn dispatch<'a>(&self, jobs: Vec<(JobInfo, Job<'a>)>) {
+                let (urgent, rest): (Vec<_>, Vec<_>) = jobs
+                    .into_iter()
+                    .partition(|(info, _)| info.handler.starts_with("urgent::"));
+                crate::SequentialExecutor.dispatch(urgent);
+                crate::ScopedThreadExecutor {
+                    max: 4,
+                    stack_size: None,
+                }
+                .dispatch(rest);
+            }
+        }
+
+        let mut publisher =
+            Publisher::with_strategy(DispatchStrategy::Executor(Arc::new(UrgentFirst)));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for name in ["log", "urgent::input", "metrics", "urgent::audio"] {
+            let order = order.clone();
+            publisher.subscribe_named(
+                name,
+                Handler::new(move |_event: TestEvent| order.lock().unwrap().push(name)),
+            );
+        }
+        publisher.subscribe(PanicHandler);
+
+        assert_eq!(publisher.publish(TestEvent).unwrap_err().len(), 1);
+        let order = order.lock().unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(order[..2].iter().all(|name| name.starts_with("urgent::")));
+    }
+
+    #[test]
+    fn test_scoped_threads_strategy_sets_handler_stack_size() {
+        // uses around 8MiB of stack, more than the default for spawned threads
+        fn recurse(depth: usize) -> u8 {
+            let frame = std::hint::black_box([depth as u8; 4096]);
+            if depth == 0 {
+                frame[0]
+            } else {
+                frame[1].wrapping_add(recurse(depth - 1))
+            }
+        }
+
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::ScopedThreads {
+            max: 2,
+            stack_size: Some(32 * 1024 * 1024),
+        });
+        publisher.subscribe_with(|_event: TestEvent| {
+            recurse(2048);
+        });
+
+        assert!(publisher.publish(TestEvent).is_ok());
+    }
+
+    fn record_numbers(publisher: &mut Publisher) -> Arc<Mutex<Vec<i32>>> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: NumberEvent| {
+            received_clone.lock().unwrap().push(event.0);
+        });
+        received
+    }
+
+    #[test]
+    fn test_transactions_publish_every_event_or_none() {
+        let mut publisher = Publisher::default();
+        let received = record_numbers(&mut publisher);
+        let mut other = Publisher::default();
+        let other_received = record_numbers(&mut other);
+
+        let aborted = publisher.transaction(|tx| {
+            tx.publish(NumberEvent(1));
+            tx.publish_on(&other, NumberEvent(2));
+            Err("insufficient funds")
+        });
+        assert!(matches!(
+            aborted,
+            Err(TransactionError::Aborted("insufficient funds"))
+        ));
+        assert!(received.lock().unwrap().is_empty());
+
+        let committed = publisher.transaction(|tx| {
+            tx.publish(NumberEvent(3));
+            tx.publish_on(&other, NumberEvent(4));
+            tx.publish(NumberEvent(5));
+            assert!(received.lock().unwrap().is_empty());
+            Ok::<(), &str>(())
+        });
+        assert!(committed.is_ok());
+        assert_eq!(*received.lock().unwrap(), [3, 5]);
+        assert_eq!(*other_received.lock().unwrap(), [4]);
+    }
+
+    #[test]
+    fn test_child_publishers_bubble_filtered_events_up_to_their_parent() {
+        let mut parent = Publisher::default();
+        let mut child = parent.child(Link::default().bubble_up().filter(|event| {
+            event
+                .get_data()
+                .downcast_ref::<NumberEvent>()
+                .is_some_and(|event| event.0 > 0)
+        }));
+        let parent_received = record_numbers(&mut parent);
+        let child_received = record_numbers(&mut child);
+
+        child.publish(NumberEvent(1)).unwrap();
+        child.publish(NumberEvent(-1)).unwrap();
+        parent.publish(NumberEvent(2)).unwrap();
+
+        assert_eq!(*parent_received.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*child_received.lock().unwrap(), vec![1, -1]);
+    }
+
+    #[test]
+    fn test_child_publishers_receive_events_pushed_down_without_echoing_them() {
+        let mut parent = Publisher::default();
+        let mut child = parent.child(Link::default().bubble_up().push_down());
+        let mut isolated = parent.child(Link::default());
+        let parent_received = record_numbers(&mut parent);
+        let child_received = record_numbers(&mut child);
+        let isolated_received = record_numbers(&mut isolated);
+
+        parent.publish(NumberEvent(1)).unwrap();
+        child.publish(NumberEvent(2)).unwrap();
+        isolated.publish(NumberEvent(3)).unwrap();
+
+        assert_eq!(*parent_received.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*child_received.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*isolated_received.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn test_bridges_forward_filtered_events_without_echoing_them() {
+        let mut first = Publisher::default();
+        let mut second = Publisher::default();
+        first.bridge_to(&second, |event| event.get_data().is::<NumberEvent>());
+        second.bridge_to(&first, |_event| true);
+        let first_received = record_numbers(&mut first);
+        let second_received = record_numbers(&mut second);
+        let tests = Arc::new(AtomicUsize::new(0));
+        let tests_clone = tests.clone();
+        second.subscribe_with(move |_event: TestEvent| {
+            tests_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        first.publish(NumberEvent(1)).unwrap();
+        first.publish(TestEvent).unwrap();
+        second.publish(NumberEvent(2)).unwrap();
+
+        assert_eq!(*first_received.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*second_received.lock().unwrap(), vec![1, 2]);
+        assert_eq!(tests.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_merged_publishers_receive_events_from_every_source() {
+        let mut local = Publisher::default();
+        let mut remote = Publisher::default();
+        let mut merged = Publisher::merge([&mut local, &mut remote]);
+        let received = record_numbers(&mut merged);
+        let local_received = record_numbers(&mut local);
+
+        local.publish(NumberEvent(1)).unwrap();
+        remote.publish(NumberEvent(2)).unwrap();
+        merged.publish(NumberEvent(3)).unwrap();
+
+        assert_eq!(*received.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(*local_received.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_transforms_publish_derived_events_after_the_source() {
+        #[derive(Clone)]
+        struct Doubled(i32);
+        impl Event for Doubled {}
+
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Sequential);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let numbers = received.clone();
+        publisher.subscribe_envelope(move |event: Envelope<NumberEvent>| {
+            numbers
+                .lock()
+                .unwrap()
+                .push((event.payload.0, event.metadata.correlation_id));
+        });
+        let doubled = received.clone();
+        publisher.subscribe_envelope(move |event: Envelope<Doubled>| {
+            doubled
+                .lock()
+                .unwrap()
+                .push((event.payload.0, event.metadata.correlation_id));
+        });
+        let id =
+            publisher.transform(|event: NumberEvent| (event.0 > 0).then_some(Doubled(event.0 * 2)));
+
+        publisher.publish(NumberEvent(2)).unwrap();
+        publisher.publish(NumberEvent(-1)).unwrap();
+        publisher.unsubscribe(id).unwrap();
+        publisher.publish(NumberEvent(3)).unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![(2, 1), (4, 1), (-1, 3), (3, 4)]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_handlers_run_as_tasks() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_async(move |event: NumberEvent| {
+            let received = received_clone.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                received.lock().unwrap().push(event.0);
+            }
+        });
+        publisher.subscribe_async(|event: NumberEvent| async move {
+            assert!(event.0 < 0, "not negative");
+        });
+
+        publisher.publish_async(NumberEvent(1)).await.unwrap();
+        let report = tokio::task::block_in_place(|| {
+            publisher.publish_and_wait(NumberEvent(2), Duration::from_secs(5))
+        });
+        assert!(!report.timed_out);
+        assert_eq!(report.errors.len(), 2);
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_publish_after_publishes_once_delay_has_passed() {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe_with(move |event: NumberEvent| {
+            sender.lock().unwrap().send(event).unwrap();
+        });
+        let start = Instant::now();
+        publisher.publish_after(NumberEvent(7), Duration::from_millis(20));
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, NumberEvent(7));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_publish_after_can_be_cancelled() {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe_with(move |event: NumberEvent| {
+            sender.lock().unwrap().send(event).unwrap();
+        });
+        let cancelled = publisher.publish_after(NumberEvent(1), Duration::from_millis(10));
+        publisher.publish_after(NumberEvent(2), Duration::from_millis(30));
+        cancelled.cancel();
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, NumberEvent(2));
+    }
+
+    #[test]
+    fn test_publish_every_repeats_until_cancelled() {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe_with(move |event: NumberEvent| {
+            sender.lock().unwrap().send(event).unwrap();
+        });
+        let handle = publisher.publish_every(|| NumberEvent(5), Duration::from_millis(5));
+
+        for _ in 0..3 {
+            let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(received, NumberEvent(5));
+        }
+        handle.cancel();
+        // drain anything already in flight when the handle was cancelled
+        thread::sleep(Duration::from_millis(20));
+        while receiver.try_recv().is_ok() {}
+        assert!(receiver.recv_timeout(Duration::from_millis(30)).is_err());
+    }
+
+    #[test]
+    fn test_scheduled_events_can_be_listed_and_cancelled() {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe_with(move |event: NumberEvent| {
+            sender.lock().unwrap().send(event).unwrap();
+        });
+        let first = publisher.publish_after(NumberEvent(1), Duration::from_secs(60));
+        publisher.publish_after(Ping(1), Duration::from_secs(30));
+        publisher.publish_every(|| NumberEvent(2), Duration::from_secs(90));
+        publisher.publish_after(NumberEvent(3), Duration::from_millis(10));
+
+        let scheduled = publisher.scheduled();
+        let names: Vec<&str> = scheduled.iter().map(|info| info.type_name).collect();
+        assert_eq!(names.len(), 4);
+        assert!(names[0].ends_with("NumberEvent"));
+        assert!(names[1].ends_with("Ping"));
+        assert_eq!(scheduled[2].id, first.id());
+        assert_eq!(scheduled[3].period, Some(Duration::from_secs(90)));
+
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            NumberEvent(3)
+        );
+        assert_eq!(publisher.scheduled().len(), 3);
+
+        assert!(publisher.cancel_scheduled(first.id()));
+        assert!(first.is_cancelled());
+        assert!(!publisher.cancel_scheduled(first.id()));
+        assert_eq!(publisher.cancel_all_scheduled::<NumberEvent>(), 1);
+        let remaining: Vec<&str> = publisher
+            .scheduled()
+            .iter()
+            .map(|info| info.type_name)
+            .collect();
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining[0].ends_with("Ping"));
     }
 
     #[test]
-    fn test_subscribe_mut_and_publish() {
+    fn test_publish_all_delivers_events_in_order() {
         let mut publisher = Publisher::default();
-        let called = Arc::new(Mutex::new(false));
-        let handler = TestHandlerMut {
-            called: called.clone(),
-        };
-        publisher.subscribe_mut(handler);
-        let _ = publisher.publish(TestEvent);
-        assert!(*called.lock().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: NumberEvent| {
+            received_clone.lock().unwrap().push(event.0);
+        });
+        let result = publisher.publish_all((0..100).map(NumberEvent));
+        assert!(result.is_ok());
+        assert_eq!(*received.lock().unwrap(), (0..100).collect::<Vec<_>>());
     }
 
     #[test]
-    fn test_unsubsribe_mut() {
+    fn test_publish_all_collects_errors_from_every_event() {
         let mut publisher = Publisher::default();
-        let called = Arc::new(Mutex::new(false));
-        let handler = TestHandlerMut {
-            called: called.clone(),
-        };
-        let id = publisher.subscribe_mut(handler);
-        publisher.unsubscribe_mut(id);
-        let _ = publisher.publish(TestEvent);
-        assert!(!*called.lock().unwrap());
+        publisher.subscribe(PanicHandler);
+        let result = publisher.publish_all(vec![TestEvent, TestEvent, TestEvent]);
+        assert_eq!(result.unwrap_err().len(), 3);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct UrgentEvent {
+        id: i32,
+        deadline: Instant,
+    }
+    impl Event for UrgentEvent {
+        fn as_deadline(&self) -> Option<&dyn Deadline> {
+            Some(self)
+        }
+    }
+    impl Deadline for UrgentEvent {
+        fn deadline(&self) -> Instant {
+            self.deadline
+        }
     }
 
     #[test]
-    fn test_publish_to_both_handler_types() {
+    fn test_publish_all_delivers_earliest_deadline_first() {
         let mut publisher = Publisher::default();
-        let called = Arc::new(Mutex::new(false));
-        let called_mut = Arc::new(Mutex::new(false));
-        let handler = TestHandler {
-            called: called.clone(),
-        };
-        let handler_mut = TestHandlerMut {
-            called: called_mut.clone(),
-        };
-        publisher.subscribe(handler);
-        publisher.subscribe_mut(handler_mut);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: UrgentEvent| {
+            received_clone.lock().unwrap().push(event.id);
+        });
+        let now = Instant::now() + Duration::from_secs(60);
+        let _ = publisher.publish_all(vec![
+            UrgentEvent {
+                id: 3,
+                deadline: now + Duration::from_secs(3),
+            },
+            UrgentEvent {
+                id: 1,
+                deadline: now + Duration::from_secs(1),
+            },
+            UrgentEvent {
+                id: 2,
+                deadline: now + Duration::from_secs(2),
+            },
+        ]);
+        assert_eq!(*received.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(publisher.missed_deadlines(), 0);
+    }
+
+    #[test]
+    fn test_missed_deadlines_are_counted() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_with(|_event: UrgentEvent| {});
+        publisher.subscribe_with(|_event: UrgentEvent| {});
+        let _ = publisher.publish(UrgentEvent {
+            id: 1,
+            deadline: Instant::now(),
+        });
+        assert_eq!(publisher.missed_deadlines(), 1);
+    }
+
+    #[test]
+    fn test_publish_and_wait_reports_missed_deadlines() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_with(|_event: UrgentEvent| {});
+        let report = publisher.publish_and_wait(
+            UrgentEvent {
+                id: 1,
+                deadline: Instant::now() + Duration::from_secs(60),
+            },
+            Duration::from_secs(1),
+        );
+        assert!(!report.missed_deadline);
+        let report = publisher.publish_and_wait(
+            UrgentEvent {
+                id: 2,
+                deadline: Instant::now(),
+            },
+            Duration::from_secs(1),
+        );
+        assert!(report.missed_deadline);
+    }
+
+    struct TestBatchHandler {
+        batches: Arc<Mutex<Vec<Vec<i32>>>>,
+    }
+    impl HandleBatch for TestBatchHandler {
+        type EventType = NumberEvent;
+        fn handle_batch(&self, events: Vec<NumberEvent>) {
+            let values = events.into_iter().map(|event| event.0).collect();
+            self.batches.lock().unwrap().push(values);
+        }
+    }
+
+    #[test]
+    fn test_subscribe_batch_delivers_full_batches_and_flushes_the_rest() {
+        let mut publisher = Publisher::default();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        publisher.subscribe_batch(
+            TestBatchHandler {
+                batches: batches.clone(),
+            },
+            3,
+        );
+        let _ = publisher.publish_all((1..=4).map(NumberEvent));
         let _ = publisher.publish(TestEvent);
-        assert!(*called.lock().unwrap());
-        assert!(*called_mut.lock().unwrap());
+        let _ = publisher.publish(NumberEvent(5));
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1, 2, 3]]);
+
+        let _ = publisher.flush();
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1, 2, 3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn test_dropping_publisher_flushes_batches() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut publisher = Publisher::default();
+            publisher.subscribe_batch(
+                TestBatchHandler {
+                    batches: batches.clone(),
+                },
+                10,
+            );
+            let _ = publisher.publish(NumberEvent(1));
+        }
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1]]);
+    }
+
+    #[test]
+    fn test_publish_and_wait_waits_for_debounced_handlers() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_limited(
+            Handler::new(move |event: NumberEvent| {
+                if event.0 < 0 {
+                    panic!("negative");
+                }
+                received_clone.lock().unwrap().push(event.0)
+            }),
+            RateLimit::Debounce(Duration::from_millis(20)),
+        );
+
+        let report = publisher.publish_and_wait(NumberEvent(1), Duration::from_secs(5));
+        assert!(report.is_ok());
+        assert_eq!(*received.lock().unwrap(), vec![1]);
+
+        let report = publisher.publish_and_wait(NumberEvent(-1), Duration::from_secs(5));
+        assert!(!report.timed_out);
+        assert_eq!(report.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_publish_and_wait_delivers_batches_and_times_out() {
+        let mut publisher = Publisher::default();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        publisher.subscribe_batch(
+            TestBatchHandler {
+                batches: batches.clone(),
+            },
+            10,
+        );
+        publisher.subscribe_limited(
+            Handler::new(|_event: NumberEvent| {}),
+            RateLimit::Debounce(Duration::from_secs(60)),
+        );
+
+        let report = publisher.publish_and_wait(NumberEvent(1), Duration::from_millis(20));
+        assert!(report.timed_out);
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1]]);
+    }
+
+    /// Not Clone, so can only be delivered shared or exclusively
+    #[derive(Debug, PartialEq)]
+    struct Resource(i32);
+
+    struct SharedResource(Resource);
+    impl Event for SharedResource {
+        const DELIVERY: Delivery = Delivery::Shared;
+    }
+
+    struct ExclusiveResource(Resource);
+    impl Event for ExclusiveResource {
+        const DELIVERY: Delivery = Delivery::Exclusive;
+    }
+
+    #[test]
+    fn test_shared_events_are_delivered_by_arc() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..2 {
+            let received_clone = received.clone();
+            publisher.subscribe_with(move |event: Arc<SharedResource>| {
+                received_clone.lock().unwrap().push(event);
+            });
+        }
+        let _ = publisher.publish(SharedResource(Resource(1)));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(Arc::ptr_eq(&received[0], &received[1]));
+        assert_eq!(received[0].0, Resource(1));
+    }
+
+    #[test]
+    fn test_exclusive_events_are_moved_into_the_first_accepting_handler() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        publisher.subscribe_with(|_event: NumberEvent| {});
+        for name in ["first", "second"] {
+            let received_clone = received.clone();
+            publisher.subscribe_with(move |event: Owned<ExclusiveResource>| {
+                received_clone.lock().unwrap().push((name, event.0.0));
+            });
+        }
+        let _ = publisher.publish(ExclusiveResource(Resource(1)));
+        let _ = publisher.publish(ExclusiveResource(Resource(2)));
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![("first", Resource(1)), ("first", Resource(2))]
+        );
+    }
+
+    #[test]
+    fn test_clone_events_can_be_received_by_arc() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(None));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: Arc<NumberEvent>| {
+            *received_clone.lock().unwrap() = Some(event);
+        });
+        let _ = publisher.publish(NumberEvent(3));
+        assert_eq!(received.lock().unwrap().as_deref(), Some(&NumberEvent(3)));
+    }
+
+    #[test]
+    fn test_subscribe_metadata_sees_every_event_without_payloads() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_metadata(move |info: EventInfo| {
+            received_clone.lock().unwrap().push(info);
+        });
+        publisher.subscribe_with(|_event: Owned<ExclusiveResource>| {});
+        let _ = publisher.publish(NumberEvent(1));
+        let _ = publisher.publish(ExclusiveResource(Resource(2)));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received[0].type_name.ends_with("NumberEvent"));
+        assert_eq!(received[0].size, std::mem::size_of::<NumberEvent>());
+        assert_eq!(received[0].metadata.sequence, 1);
+        assert!(received[1].type_name.ends_with("ExclusiveResource"));
+        assert_eq!(received[1].metadata.sequence, 2);
+    }
+
+    #[test]
+    fn test_panic_formatter_replaces_panic_payloads() {
+        let mut publisher = Publisher::default();
+        publisher.set_panic_formatter(
+            PanicFormatter::default().redact(|message| message.replace("hunter2", "***")),
+        );
+        publisher
+            .subscribe_with(|event: NumberEvent| panic!("password is hunter2, got {}", event.0));
+
+        let errors = publisher.publish(NumberEvent(1)).unwrap_err();
+        let message = errors[0].downcast_ref::<PanicMessage>().unwrap();
+        assert_eq!(message.message, "password is ***, got 1");
+        assert!(!message.truncated);
+    }
+
+    #[test]
+    fn test_shed_subscriptions_stop_receiving_events_under_load() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let optional = received.clone();
+        let essential = received.clone();
+        let optional_id = publisher.subscribe_with(move |event: NumberEvent| {
+            optional.lock().unwrap().push(("optional", event.0))
+        });
+        publisher.subscribe_with(move |event: NumberEvent| {
+            essential.lock().unwrap().push(("essential", event.0))
+        });
+        publisher.shed_above(optional_id, LoadTier::Normal).unwrap();
+
+        let _ = publisher.publish(NumberEvent(1));
+        // every publish counts towards the queue depth, so this puts the load above Normal
+        publisher.set_load_thresholds(LoadThresholds {
+            elevated_depth: 1,
+            ..Default::default()
+        });
+        assert_eq!(publisher.load_tier(), LoadTier::Normal);
+        let _ = publisher.publish(NumberEvent(2));
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(
+            received,
+            vec![("essential", 1), ("essential", 2), ("optional", 1)]
+        );
+    }
+
+    #[test]
+    fn test_paused_subscriptions_drop_events_until_resumed() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let id = publisher
+            .subscribe_with(move |event: NumberEvent| received_clone.lock().unwrap().push(event.0));
+
+        publisher.pause(id).unwrap();
+        publisher.publish(NumberEvent(1)).unwrap();
+        publisher.resume(id).unwrap();
+        publisher.publish(NumberEvent(2)).unwrap();
+
+        assert_eq!(*received.lock().unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_buffered_pauses_deliver_missed_events_on_resume() {
+        let mut publisher = Publisher::default();
+        let others = record_numbers(&mut publisher);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let id = publisher
+            .subscribe_with(move |event: NumberEvent| received_clone.lock().unwrap().push(event.0));
+
+        publisher.pause_buffered(id).unwrap();
+        publisher.publish(NumberEvent(1)).unwrap();
+        publisher.publish(NumberEvent(2)).unwrap();
+        assert!(received.lock().unwrap().is_empty());
+
+        publisher.resume(id).unwrap();
+        publisher.publish(NumberEvent(3)).unwrap();
+
+        assert_eq!(*received.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(*others.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_groups_are_paused_and_unsubscribed_together() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for name in ["ui", "ui", "core"] {
+            let received = received.clone();
+            let id = publisher.subscribe_with(move |event: NumberEvent| {
+                received.lock().unwrap().push((name, event.0))
+            });
+            publisher.add_to_group(id, name).unwrap();
+        }
+        let received_clone = received.clone();
+        publisher.subscribe_group(
+            "ui",
+            Handler::new(move |event: NumberEvent| {
+                received_clone.lock().unwrap().push(("worker", event.0))
+            }),
+        );
+
+        publisher.pause_group("ui");
+        publisher.publish(NumberEvent(1)).unwrap();
+        publisher.resume_group("ui").unwrap();
+        publisher.publish(NumberEvent(2)).unwrap();
+        publisher.unsubscribe_group("ui");
+        publisher.publish(NumberEvent(3)).unwrap();
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(
+            received,
+            vec![
+                ("core", 1),
+                ("core", 2),
+                ("core", 3),
+                ("ui", 2),
+                ("ui", 2),
+                ("worker", 2)
+            ]
+        );
+        assert_eq!(publisher.shared.handlers.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_subscribe_all_subscribes_handlers_of_different_types_together() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handlers: Vec<Box<dyn DynHandle>> = (0..3)
+            .map(|offset| {
+                let received = received.clone();
+                Box::new(Handler::new(move |event: NumberEvent| {
+                    received.lock().unwrap().push(event.0 + offset)
+                })) as Box<dyn DynHandle>
+            })
+            .chain([Box::new(PanicHandler) as Box<dyn DynHandle>])
+            .collect();
+
+        let ids = publisher.subscribe_all_to_group("config", handlers);
+        assert_eq!(ids.len(), 4);
+        assert_eq!(publisher.publish(NumberEvent(10)).unwrap_err().len(), 1);
+        publisher.unsubscribe(ids[3]).unwrap();
+        publisher.publish(NumberEvent(20)).unwrap();
+        publisher.unsubscribe_group("config");
+        publisher.publish(NumberEvent(30)).unwrap();
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, [10, 11, 12, 20, 21, 22]);
+        assert_eq!(publisher.handler_count(), 0);
+    }
+
+    #[test]
+    fn test_unsubscribing_stale_or_foreign_ids_fails() {
+        let mut publisher = Publisher::default();
+        let mut other = Publisher::default();
+        let received = record_numbers(&mut other);
+        let id = publisher.subscribe_with(|_event: NumberEvent| {});
+        let other_id = other.subscribe_with(|_event: NumberEvent| {});
+
+        assert_eq!(
+            publisher.unsubscribe(other_id).err(),
+            Some(UnsubscribeError::WrongPublisher)
+        );
+        other.publish(NumberEvent(1)).unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![1]);
+
+        assert!(publisher.unsubscribe(id).is_ok());
+        assert_eq!(
+            publisher.unsubscribe(id).err(),
+            Some(UnsubscribeError::Stale)
+        );
+    }
+
+    #[test]
+    fn test_configuring_stale_or_foreign_ids_fails() {
+        let mut publisher = Publisher::default();
+        let mut other = Publisher::default();
+        let id = publisher.subscribe_with(|_event: NumberEvent| {});
+        let other_id = other.subscribe_with(|_event: NumberEvent| {});
+
+        assert_eq!(
+            publisher.pause(other_id),
+            Err(UnsubscribeError::WrongPublisher)
+        );
+        assert_eq!(
+            publisher.add_to_group(other_id, "ui"),
+            Err(UnsubscribeError::WrongPublisher)
+        );
+        assert_eq!(
+            publisher.is_poisoned(other_id),
+            Err(UnsubscribeError::WrongPublisher)
+        );
+        let errors = publisher.resume(other_id).unwrap_err();
+        assert_eq!(
+            errors[0].downcast_ref(),
+            Some(&UnsubscribeError::WrongPublisher)
+        );
+        assert_eq!(publisher.pause(id), Ok(()));
+
+        publisher.unsubscribe(id).unwrap();
+        assert_eq!(publisher.pause_buffered(id), Err(UnsubscribeError::Stale));
+        assert_eq!(
+            publisher.shed_above(id, LoadTier::Normal),
+            Err(UnsubscribeError::Stale)
+        );
+        assert_eq!(
+            publisher.on_overflow(id, Overflow::Drop),
+            Err(UnsubscribeError::Stale)
+        );
+        assert_eq!(
+            publisher.set_retry_policy(id, RetryPolicy::new(2, Backoff::Immediate)),
+            Err(UnsubscribeError::Stale)
+        );
+        assert_eq!(publisher.recover(id), Err(UnsubscribeError::Stale));
+        assert_eq!(publisher.is_poisoned(id), Err(UnsubscribeError::Stale));
+        let errors = publisher.resume(id).unwrap_err();
+        assert_eq!(errors[0].downcast_ref(), Some(&UnsubscribeError::Stale));
+    }
+
+    #[test]
+    fn test_unsubscribed_handlers_keep_their_state_and_can_be_resubscribed() {
+        #[derive(Default)]
+        struct Tally(i32);
+        impl HandleMut for Tally {
+            type EventType = NumberEvent;
+
+            fn handle_mut(&mut self, event: NumberEvent) {
+                self.0 += event.0;
+            }
+        }
+
+        let mut publisher = Publisher::default();
+        let id = publisher.subscribe_mut(Tally::default());
+        publisher.publish(NumberEvent(2)).unwrap();
+        publisher.publish(NumberEvent(3)).unwrap();
+
+        let unsubscribed = publisher.unsubscribe_mut(id).unwrap();
+        let tally = unsubscribed.downcast::<Mutex<Tally>>().unwrap();
+        assert_eq!(tally.lock().unwrap().0, 5);
+        assert!(unsubscribed.downcast::<Tally>().is_none());
+
+        let mut other = Publisher::default();
+        let id = other.resubscribe(unsubscribed);
+        other.publish(NumberEvent(4)).unwrap();
+        publisher.publish(NumberEvent(100)).unwrap();
+        assert_eq!(tally.lock().unwrap().0, 9);
+        assert!(other.unsubscribe(id).is_ok());
+    }
+
+    #[test]
+    fn test_introspection_describes_handlers_and_the_events_they_take() {
+        let mut publisher = Publisher::default();
+        let numbers = publisher.subscribe(Handler::new(|_event: NumberEvent| {}));
+        let pings = publisher.transform(|ping: Ping| Some(NumberEvent(ping.0 as i32)));
+        let metadata = publisher.subscribe_metadata(|_info| {});
+        // takes every type of event, without saying so
+        let tests = publisher.subscribe_mut(TestHandlerMut {
+            called: Arc::default(),
+        });
+
+        assert_eq!(publisher.handler_count(), 4);
+        assert_eq!(
+            publisher.handlers_for::<NumberEvent>(),
+            [numbers, metadata, tests]
+        );
+        assert_eq!(publisher.handlers_for::<Ping>(), [pings, metadata, tests]);
+        let types: Vec<&str> = publisher
+            .subscribed_event_types()
+            .into_iter()
+            .map(|name| name.rsplit("::").next().unwrap())
+            .collect();
+        assert_eq!(types, ["NumberEvent", "Ping"]);
+
+        let handlers = publisher.handlers();
+        let ids: Vec<SubscriptionId> = handlers.iter().map(|info| info.id).collect();
+        assert_eq!(ids, [numbers, pings, metadata, tests]);
+        let kinds: Vec<&str> = handlers.iter().map(|info| info.kind).collect();
+        assert_eq!(kinds, ["sync", "transform", "metadata", "mut"]);
+        assert!(handlers[0].event_type.unwrap().ends_with("NumberEvent"));
+        assert!(handlers[3].label.ends_with("TestHandlerMut"));
+        assert_eq!(handlers[3].event_type, None);
+
+        let debug = format!("{publisher:?}");
+        assert!(debug.contains("TestHandlerMut"));
+        assert!(debug.contains("NumberEvent"));
+    }
+
+    /// Hands events on to a worker, and isn't ready for more once `limit` are waiting
+    #[derive(Clone)]
+    struct Mailbox {
+        depth: Arc<AtomicUsize>,
+        limit: usize,
+        received: Arc<Mutex<Vec<i32>>>,
+    }
+
+    impl Mailbox {
+        fn new(limit: usize) -> Self {
+            Mailbox {
+                depth: Arc::default(),
+                limit,
+                received: Arc::default(),
+            }
+        }
+
+        fn received(&self) -> Vec<i32> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    impl Handle for Mailbox {
+        type EventType = NumberEvent;
+
+        fn handle(&self, event: NumberEvent) {
+            self.depth.fetch_add(1, Ordering::SeqCst);
+            self.received.lock().unwrap().push(event.0);
+        }
+
+        fn poll_ready(&self) -> bool {
+            self.depth.load(Ordering::SeqCst) < self.limit
+        }
+    }
+
+    #[test]
+    fn test_overflow_policies_apply_to_handlers_that_are_not_ready() {
+        let mut publisher = Publisher::default();
+        let (unlimited, dropping, buffering) = (Mailbox::new(2), Mailbox::new(2), Mailbox::new(2));
+        publisher.subscribe(unlimited.clone());
+        let id = publisher.subscribe(dropping.clone());
+        publisher.on_overflow(id, Overflow::Drop).unwrap();
+        let id = publisher.subscribe(buffering.clone());
+        publisher.on_overflow(id, Overflow::Buffer).unwrap();
+
+        for n in 1..=4 {
+            publisher.publish(NumberEvent(n)).unwrap();
+        }
+        assert_eq!(unlimited.received(), [1, 2, 3, 4]);
+        assert_eq!(dropping.received(), [1, 2]);
+        assert_eq!(buffering.received(), [1, 2]);
+
+        dropping.depth.store(0, Ordering::SeqCst);
+        buffering.depth.store(0, Ordering::SeqCst);
+        publisher.publish(NumberEvent(5)).unwrap();
+        assert_eq!(dropping.received(), [1, 2, 5]);
+        // the backlog fills the mailbox again, so the new event waits its turn
+        assert_eq!(buffering.received(), [1, 2, 3, 4]);
+
+        buffering.depth.store(0, Ordering::SeqCst);
+        publisher.flush().unwrap();
+        assert_eq!(buffering.received(), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_blocking_overflow_waits_for_the_handler_to_be_ready() {
+        let mut publisher = Publisher::default();
+        let (blocking, impatient) = (Mailbox::new(1), Mailbox::new(1));
+        let id = publisher.subscribe(blocking.clone());
+        publisher
+            .on_overflow(id, Overflow::Block(Duration::from_secs(5)))
+            .unwrap();
+        let id = publisher.subscribe(impatient.clone());
+        publisher
+            .on_overflow(id, Overflow::Block(Duration::from_millis(1)))
+            .unwrap();
+        publisher.publish(NumberEvent(1)).unwrap();
+
+        let depth = blocking.depth.clone();
+        let draining = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            depth.store(0, Ordering::SeqCst);
+        });
+        let started = Instant::now();
+        publisher.publish(NumberEvent(2)).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        draining.join().unwrap();
+
+        assert_eq!(blocking.received(), [1, 2]);
+        assert_eq!(impatient.received(), [1]);
+    }
+
+    #[test]
+    fn test_topology_dot_links_event_types_handlers_and_transforms() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_named("record", Handler::new(|_event: NumberEvent| {}));
+        publisher.transform(|event: NumberEvent| Some(Ping(event.0 as u32)));
+        publisher.subscribe_metadata(|_info| {});
+
+        let dot = publisher.topology_dot();
+        let number = std::any::type_name::<NumberEvent>();
+        let ping = std::any::type_name::<Ping>();
+
+        assert!(dot.starts_with("digraph crier {"));
+        assert!(dot.contains("subscription0 [shape=ellipse, label=\"record\"];"));
+        assert!(dot.contains(&format!("\"{number}\" -> subscription0;")));
+        assert!(dot.contains(&format!("\"{number}\" -> subscription1;")));
+        assert!(dot.contains(&format!("subscription1 -> \"{ping}\";")));
+        assert!(dot.contains("every_event -> subscription2;"));
+    }
+
+    #[test]
+    fn test_default_handlers_only_receive_events_no_other_handler_takes() {
+        let mut publisher = Publisher::default();
+        let fallback = Arc::new(Mutex::new(Vec::new()));
+        let fallback_clone = fallback.clone();
+        publisher.subscribe_default(Handler::new(move |event: Ping| {
+            fallback_clone.lock().unwrap().push(event.0)
+        }));
+        let own = Arc::new(Mutex::new(Vec::new()));
+        let own_clone = own.clone();
+
+        publisher.publish(Ping(1)).unwrap();
+        let id =
+            publisher.subscribe_with(move |event: Ping| own_clone.lock().unwrap().push(event.0));
+        publisher.publish(Ping(2)).unwrap();
+        publisher.unsubscribe(id).unwrap();
+        publisher.publish(Ping(3)).unwrap();
+
+        assert_eq!(*fallback.lock().unwrap(), [1, 3]);
+        assert_eq!(*own.lock().unwrap(), [2]);
+    }
+
+    #[test]
+    fn test_dead_letters_receive_events_no_other_handler_takes() {
+        let mut publisher = Publisher::default();
+        let dead = Arc::new(Mutex::new(Vec::new()));
+        let dead_clone = dead.clone();
+        publisher.subscribe_dead_letter(move |letter: DeadLetter| {
+            let number = letter.downcast_ref::<NumberEvent>().map(|event| event.0);
+            dead_clone.lock().unwrap().push((letter.type_name, number));
+        });
+        publisher.subscribe_default(Handler::new(|_event: Ping| {}));
+
+        publisher.publish(NumberEvent(1)).unwrap();
+        publisher.publish(Ping(2)).unwrap();
+        let received = record_numbers(&mut publisher);
+        publisher.publish(NumberEvent(3)).unwrap();
+
+        assert_eq!(
+            *dead.lock().unwrap(),
+            [(std::any::type_name::<NumberEvent>(), Some(1))]
+        );
+        assert_eq!(*received.lock().unwrap(), [3]);
+    }
+
+    #[test]
+    fn test_events_that_exhaust_their_retries_are_dead_lettered() {
+        let mut publisher = Publisher::default();
+        let attempts = Arc::new(Mutex::new(0));
+        let attempts_clone = attempts.clone();
+        let id = publisher.subscribe_with(move |_event: NumberEvent| {
+            *attempts_clone.lock().unwrap() += 1;
+            panic!("handler panic");
+        });
+        let backoff = Backoff::Fixed(Duration::from_millis(1));
+        publisher
+            .set_retry_policy(id, RetryPolicy::new(3, backoff))
+            .unwrap();
+        let dead = Arc::new(Mutex::new(Vec::new()));
+        let dead_clone = dead.clone();
+        publisher.subscribe_dead_letter(move |letter: DeadLetter| {
+            let number = letter.downcast_ref::<NumberEvent>().map(|event| event.0);
+            dead_clone.lock().unwrap().push(number);
+        });
+
+        let report = publisher.publish_and_wait(NumberEvent(1), Duration::from_secs(5));
+        assert_eq!(report.errors.len(), 1);
+        assert!(!report.timed_out);
+        assert_eq!(*attempts.lock().unwrap(), 3);
+        assert_eq!(*dead.lock().unwrap(), [Some(1)]);
+    }
+
+    #[test]
+    fn test_handlers_that_return_errors_are_retried() {
+        struct Flaky(Arc<Mutex<u32>>);
+        impl TryHandle for Flaky {
+            type EventType = NumberEvent;
+            type Error = Negative;
+            fn try_handle(&self, event: NumberEvent) -> Result<(), Negative> {
+                let mut attempts = self.0.lock().unwrap();
+                *attempts += 1;
+                if *attempts <= 2 {
+                    Err(Negative(event.0))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        let mut publisher = Publisher::default();
+        let attempts = Arc::new(Mutex::new(0));
+        let id = publisher.subscribe_try(Flaky(attempts.clone()));
+        publisher
+            .set_retry_policy(id, RetryPolicy::new(5, Backoff::Immediate))
+            .unwrap();
+        let dead = Arc::new(Mutex::new(0));
+        let dead_clone = dead.clone();
+        publisher.subscribe_dead_letter(move |_letter: DeadLetter| {
+            *dead_clone.lock().unwrap() += 1;
+        });
+
+        let report = publisher.publish_and_wait(NumberEvent(-1), Duration::from_secs(5));
+        assert!(!report.timed_out);
+        assert_eq!(*attempts.lock().unwrap(), 3);
+        assert_eq!(*dead.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_mut_handler_errors_that_exhaust_their_retries_are_dead_lettered() {
+        let mut publisher = Publisher::default();
+        let id = publisher.subscribe_try_mut(CountNegative(0));
+        publisher
+            .set_retry_policy(id, RetryPolicy::new(3, Backoff::Immediate))
+            .unwrap();
+        let dead = Arc::new(Mutex::new(Vec::new()));
+        let dead_clone = dead.clone();
+        publisher.subscribe_dead_letter(move |letter: DeadLetter| {
+            let number = letter.downcast_ref::<NumberEvent>().map(|event| event.0);
+            dead_clone.lock().unwrap().push(number);
+        });
+
+        let report = publisher.publish_and_wait(NumberEvent(-1), Duration::from_secs(5));
+        assert!(!report.timed_out);
+        assert_eq!(*dead.lock().unwrap(), [Some(-1)]);
+        let unsubscribed = publisher.unsubscribe(id).unwrap();
+        let handler = unsubscribed
+            .downcast::<Mutex<Fallible<CountNegative>>>()
+            .unwrap();
+        assert_eq!(handler.lock().unwrap().handler().0, 3);
+    }
+
+    #[test]
+    fn test_named_handlers_are_labelled_and_their_panics_attributed() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_named(
+            "audio::on_pause",
+            Handler::new(|_event: NumberEvent| panic!("device lost")),
+        );
+        publisher.subscribe(Handler::new(|_event: NumberEvent| panic!("unnamed")));
+        assert_eq!(publisher.handlers()[0].label, "audio::on_pause");
+        assert!(format!("{publisher:?}").contains("audio::on_pause"));
+
+        let errors = publisher.publish(NumberEvent(1)).unwrap_err();
+        let named: Vec<&HandlerPanic> = errors
+            .iter()
+            .filter_map(|error| error.downcast_ref::<HandlerPanic>())
+            .collect();
+        assert_eq!(named.len(), 1);
+        assert_eq!(named[0].handler, "audio::on_pause");
+        let mut messages: Vec<&str> = errors
+            .iter()
+            .map(|error| crate::panic_message(error.as_ref()).unwrap())
+            .collect();
+        messages.sort();
+        assert_eq!(messages, ["device lost", "unnamed"]);
+
+        publisher.set_panic_formatter(PanicFormatter::default());
+        let mut messages: Vec<String> = publisher
+            .publish(NumberEvent(2))
+            .unwrap_err()
+            .iter()
+            .map(|error| error.downcast_ref::<PanicMessage>().unwrap().to_string())
+            .collect();
+        messages.sort();
+        assert_eq!(messages, ["audio::on_pause: device lost", "unnamed"]);
     }
 }
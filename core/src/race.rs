@@ -0,0 +1,262 @@
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet, VecDeque},
+    panic::RefUnwindSafe,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{DynEvent, DynHandle, Event, FromEvent, ScheduleHandle};
+
+/// Number of decided correlation IDs each race remembers, so that losers arriving late are ignored
+const DECIDED_CAPACITY: usize = 1024;
+
+static NEXT_RACE: AtomicUsize = AtomicUsize::new(0);
+
+/// Published by a race subscribed with `Publisher::subscribe_race_timeout` when nothing it was
+/// waiting for arrived in time. Include it in the race to handle the timeout, for example with
+/// `|outcome: Race3<Success, Failure, RaceTimedOut>|`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RaceTimedOut {
+    /// The correlation ID of the chain of events that timed out
+    pub correlation_id: u64,
+    /// Identifies the race that timed out, so that other races don't react to it
+    race: usize,
+}
+
+impl Event for RaceTimedOut {}
+
+/// The winner of a race between event types, which a race subscription's closure takes as its
+/// argument. Implemented by `Race2`, `Race3` and `Race4`.
+pub trait Race: Sized + Send + 'static {
+    /// Take the event if it is of one of the types in the race
+    #[doc(hidden)]
+    fn from_event(event: &dyn DynEvent) -> Option<Self>;
+
+    /// Whether the event is of one of the types in the race
+    #[doc(hidden)]
+    fn accepts(event: &dyn DynEvent) -> bool;
+}
+
+macro_rules! impl_race {
+    ($(#[$doc:meta])* $Race:ident { $($Variant:ident($T:ident)),+ }) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum $Race<$($T),+> {
+            $($Variant($T)),+
+        }
+
+        impl<$($T: FromEvent + Send),+> Race for $Race<$($T),+> {
+            fn from_event(event: &dyn DynEvent) -> Option<Self> {
+                let type_id = event.get_data().type_id();
+                $(
+                    if type_id == TypeId::of::<$T::Source>() {
+                        return $T::from_event(event).map($Race::$Variant);
+                    }
+                )+
+                None
+            }
+
+            fn accepts(event: &dyn DynEvent) -> bool {
+                let type_id = event.get_data().type_id();
+                $(type_id == TypeId::of::<$T::Source>())||+
+            }
+        }
+    };
+}
+
+impl_race!(
+    /// Whichever of two event types arrived first
+    Race2 { First(A), Second(B) }
+);
+impl_race!(
+    /// Whichever of three event types arrived first
+    Race3 { First(A), Second(B), Third(C) }
+);
+impl_race!(
+    /// Whichever of four event types arrived first
+    Race4 { First(A), Second(B), Third(C), Fourth(D) }
+);
+
+type Schedule = dyn Fn(Instant, Box<dyn FnOnce() + Send>) -> ScheduleHandle + Send + Sync;
+type PublishTimeout = dyn Fn(RaceTimedOut) + Send + Sync;
+
+/// How a race times out. The Publisher provides the means to schedule the timeout and publish the
+/// `RaceTimedOut` event, since the handler can't reach the Publisher itself.
+pub(crate) struct Timeout {
+    pub(crate) after: Duration,
+    pub(crate) schedule: Box<Schedule>,
+    pub(crate) publish: Arc<PublishTimeout>,
+}
+
+#[derive(Default)]
+struct Races {
+    /// Chains with a timeout running, which haven't been decided yet
+    pending: HashMap<u64, ScheduleHandle>,
+    /// Chains whose timeout elapsed, waiting for the `RaceTimedOut` event to arrive
+    timed_out: HashSet<u64>,
+    decided: HashSet<u64>,
+    /// Order the chains were decided in, so the oldest can be forgotten
+    decided_order: VecDeque<u64>,
+}
+
+impl Races {
+    fn decide(&mut self, key: u64) {
+        if let Some(handle) = self.pending.remove(&key) {
+            handle.cancel();
+        }
+        self.timed_out.remove(&key);
+        if self.decided.insert(key) {
+            self.decided_order.push_back(key);
+        }
+        if self.decided_order.len() > DECIDED_CAPACITY
+            && let Some(oldest) = self.decided_order.pop_front()
+        {
+            self.decided.remove(&oldest);
+        }
+    }
+}
+
+/// Handler that runs with the first event of the types in `R` for each correlation ID, and ignores
+/// the rest. Created by `Publisher::subscribe_race` and `Publisher::subscribe_race_timeout`.
+pub(crate) struct RaceHandler<R, F> {
+    handler: F,
+    race: usize,
+    races: Arc<Mutex<Races>>,
+    timeout: Option<Timeout>,
+    winner: std::marker::PhantomData<fn(R)>,
+}
+
+impl<R, F> RefUnwindSafe for RaceHandler<R, F> {}
+
+impl<R: Race, F: Fn(R) + Send + Sync + 'static> RaceHandler<R, F> {
+    pub(crate) fn new(handler: F, timeout: Option<Timeout>) -> Self {
+        RaceHandler {
+            handler,
+            race: NEXT_RACE.fetch_add(1, Ordering::Relaxed),
+            races: Arc::default(),
+            timeout,
+            winner: std::marker::PhantomData,
+        }
+    }
+
+    /// Start the timeout for a chain the first time an event from it is seen
+    fn start_timeout(&self, races: &mut Races, key: u64) {
+        let Some(timeout) = &self.timeout else {
+            return;
+        };
+
+        let state = self.races.clone();
+        let publish = timeout.publish.clone();
+        let race = self.race;
+        let handle = (timeout.schedule)(
+            Instant::now() + timeout.after,
+            Box::new(move || {
+                let expired = {
+                    let mut races = state.lock().expect("Race mutex poisoned");
+                    races.pending.remove(&key).is_some() && races.timed_out.insert(key)
+                };
+                if expired {
+                    publish(RaceTimedOut {
+                        correlation_id: key,
+                        race,
+                    });
+                }
+            }),
+        );
+        races.pending.insert(key, handle);
+    }
+}
+
+impl<R: Race, F: Fn(R) + Send + Sync + 'static> DynHandle for RaceHandler<R, F> {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let Some(key) = event.metadata().map(|metadata| metadata.correlation_id) else {
+            return;
+        };
+        let timed_out = event.get_data().downcast_ref::<RaceTimedOut>();
+        if timed_out.is_some_and(|timed_out| timed_out.race != self.race) {
+            return;
+        }
+
+        let winner = {
+            let mut races = self.races.lock().expect("Race mutex poisoned");
+            if races.decided.contains(&key) {
+                return;
+            }
+
+            if timed_out.is_some() {
+                // the timeout decides the race whether or not anything is waiting for it
+                races.decide(key);
+                R::from_event(event)
+            } else if races.timed_out.contains(&key) {
+                None
+            } else if R::accepts(event) {
+                let winner = R::from_event(event);
+                if winner.is_some() {
+                    races.decide(key);
+                }
+                winner
+            } else {
+                if !races.pending.contains_key(&key) {
+                    self.start_timeout(&mut races, key);
+                }
+                None
+            }
+        };
+
+        if let Some(winner) = winner {
+            (self.handler)(winner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Metadata, envelope::Published};
+    use std::time::SystemTime;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Success(i32);
+    impl Event for Success {}
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Failure(i32);
+    impl Event for Failure {}
+
+    fn correlated<T: Event>(event: T, correlation_id: u64) -> Published {
+        let metadata = Metadata {
+            timestamp: SystemTime::now(),
+            sequence: 0,
+            correlation_id,
+            source: None,
+            remote: false,
+            peer: None,
+            topic: None,
+        };
+        Published::new(event, metadata)
+    }
+
+    #[test]
+    fn test_runs_with_the_first_event_of_each_chain() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let handler = RaceHandler::new(
+            move |outcome: Race2<Success, Failure>| received_clone.lock().unwrap().push(outcome),
+            None,
+        );
+
+        handler.dyn_handle(&correlated(Failure(1), 1));
+        handler.dyn_handle(&correlated(Success(2), 1));
+        handler.dyn_handle(&correlated(Success(3), 2));
+        handler.dyn_handle(&correlated(Failure(4), 2));
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![Race2::Second(Failure(1)), Race2::First(Success(3))]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{DynEvent, DynHandle, ScheduleHandle, scheduler::Scheduler, wait::InFlight};
+
+/// Limits how often a subscription's handler is run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimit {
+    /// Run the handler for at most one event per interval, dropping any others that arrive before
+    /// the interval has passed
+    Throttle(Duration),
+    /// Run the handler only once no new events have arrived for the given quiet period, with the
+    /// last event that arrived
+    Debounce(Duration),
+}
+
+/// A handler subscribed with a RateLimit, along with the state needed to enforce it
+pub(crate) struct Limited {
+    pub(crate) handler: Arc<dyn DynHandle>,
+    limit: RateLimit,
+    state: Mutex<LimitState>,
+}
+
+#[derive(Default)]
+struct LimitState {
+    last_run: Option<Instant>,
+    pending: Option<ScheduleHandle>,
+}
+
+impl Limited {
+    pub(crate) fn new(handler: Arc<dyn DynHandle>, limit: RateLimit) -> Self {
+        Limited {
+            handler,
+            limit,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Decide what to do with an event. Returns true if the handler should run for it straight
+    /// away. Debounced events are instead handed to the scheduler to run once things go quiet,
+    /// and counted as in flight until they have run or been replaced.
+    pub(crate) fn admit(
+        &self,
+        event: &Arc<dyn DynEvent>,
+        scheduler: &Scheduler,
+        in_flight: &Arc<InFlight>,
+    ) -> bool {
+        if !self.handler.accepts(event.as_ref()) {
+            return false;
+        }
+
+        let mut state = self.state.lock().expect("Rate limit mutex poisoned");
+        match self.limit {
+            RateLimit::Throttle(interval) => {
+                let now = Instant::now();
+                match state.last_run {
+                    Some(last_run) if now.duration_since(last_run) < interval => false,
+                    _ => {
+                        state.last_run = Some(now);
+                        true
+                    }
+                }
+            }
+            RateLimit::Debounce(quiet) => {
+                if let Some(pending) = state.pending.take() {
+                    pending.cancel();
+                }
+                let handler = self.handler.clone();
+                let event = event.clone();
+                let guard = in_flight.start();
+                state.pending = Some(scheduler.schedule(Instant::now() + quiet, move || {
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                        handler.dyn_handle(event.as_ref())
+                    }));
+                    if let Err(error) = result {
+                        guard.fail(error);
+                    }
+                }));
+                false
+            }
+        }
+    }
+
+    /// Cancel any debounced event waiting to be delivered
+    pub(crate) fn cancel_pending(&self) {
+        if let Ok(mut state) = self.state.lock()
+            && let Some(pending) = state.pending.take()
+        {
+            pending.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler};
+    use std::{sync::mpsc, thread};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Moved(i32);
+    impl Event for Moved {}
+
+    #[derive(Clone)]
+    struct Other;
+    impl Event for Other {}
+
+    fn recording_handler() -> (Arc<dyn DynHandle>, mpsc::Receiver<i32>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let handler =
+            Handler::new(move |event: Moved| sender.lock().unwrap().send(event.0).unwrap());
+        (Arc::new(handler), receiver)
+    }
+
+    #[test]
+    fn test_throttle_admits_one_event_per_interval() {
+        let scheduler = Scheduler::new(None);
+        let in_flight = Arc::default();
+        let (handler, _receiver) = recording_handler();
+        let limited = Limited::new(handler, RateLimit::Throttle(Duration::from_millis(50)));
+        let event: Arc<dyn DynEvent> = Arc::new(Moved(1));
+
+        assert!(limited.admit(&event, &scheduler, &in_flight));
+        assert!(!limited.admit(&event, &scheduler, &in_flight));
+        thread::sleep(Duration::from_millis(60));
+        assert!(limited.admit(&event, &scheduler, &in_flight));
+    }
+
+    #[test]
+    fn test_throttle_ignores_events_the_handler_does_not_accept() {
+        let scheduler = Scheduler::new(None);
+        let in_flight = Arc::default();
+        let (handler, _receiver) = recording_handler();
+        let limited = Limited::new(handler, RateLimit::Throttle(Duration::from_secs(60)));
+        let other: Arc<dyn DynEvent> = Arc::new(Other);
+        let event: Arc<dyn DynEvent> = Arc::new(Moved(1));
+
+        assert!(!limited.admit(&other, &scheduler, &in_flight));
+        assert!(limited.admit(&event, &scheduler, &in_flight));
+    }
+
+    #[test]
+    fn test_debounce_delivers_only_the_last_event() {
+        let scheduler = Scheduler::new(None);
+        let in_flight = Arc::default();
+        let (handler, receiver) = recording_handler();
+        let limited = Limited::new(handler, RateLimit::Debounce(Duration::from_millis(30)));
+
+        for value in 1..=3 {
+            let event: Arc<dyn DynEvent> = Arc::new(Moved(value));
+            assert!(!limited.admit(&event, &scheduler, &in_flight));
+        }
+
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), 3);
+        assert!(receiver.recv_timeout(Duration::from_millis(60)).is_err());
+    }
+}
@@ -0,0 +1,629 @@
+//! A bridge that shares events between Publishers through Redis pub/sub, built on the `redis`
+//! client.
+//!
+//! Each event type registered with the bridge's EventRegistry is mapped to the channel
+//! `<prefix>:<type tag>`. Events published locally are sent to their channel, and messages
+//! arriving on those channels are published locally as events, so every instance of a service
+//! connected to the same Redis sees the same events. Messages are JSON objects holding the event
+//! along with an ID for the bridge that sent it, which is used to skip the bridge's own messages
+//! when Redis sends them back:
+//!
+//! ```json
+//! {"origin": "4242-1718000000000000000-0", "event": {"user": "ferris"}}
+//! ```
+//!
+//! If either connection to Redis is lost, the bridge reconnects, subscribing again on the
+//! connection that receives messages. Events that can't be published while Redis is unreachable
+//! are dropped.
+
+use std::{
+    error::Error,
+    io,
+    net::ToSocketAddrs,
+    panic::RefUnwindSafe,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use redis::{
+    Client, Connection, ErrorKind, IntoConnectionInfo, PubSub, RedisConnectionInfo, RedisError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{DynEvent, DynHandle, EventRegistry, EventTooLarge, SerializedEvent};
+
+/// Largest event, in bytes, that will be sent or accepted, unless set lower with
+/// `RedisOptions::max_event_size`. Larger events are dropped.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long to wait to connect to Redis before giving up on an event or a reconnection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait between attempts to reconnect the connection that receives messages
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// How often the connection that receives messages checks whether the bridge has been dropped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where and how to connect to Redis
+#[derive(Clone, Debug)]
+pub struct RedisOptions {
+    addr: String,
+    password: Option<String>,
+    channel_prefix: String,
+    max_event_size: usize,
+}
+
+impl RedisOptions {
+    /// Connect to the Redis server at `addr`, e.g. `"localhost:6379"`. Channels are prefixed with
+    /// `crier` unless configured otherwise.
+    pub fn new(addr: impl Into<String>) -> Self {
+        RedisOptions {
+            addr: addr.into(),
+            password: None,
+            channel_prefix: String::from("crier"),
+            max_event_size: MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Authenticate with `password` after connecting
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Map each event type to the channel `<prefix>:<type tag>`
+    pub fn channel_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.channel_prefix = prefix.into();
+        self
+    }
+
+    /// Drop events that serialize to more than `bytes`, rather than sending them, failing with
+    /// EventTooLarge. Larger messages from Redis are skipped. Can't be set higher than
+    /// MAX_MESSAGE_SIZE.
+    pub fn max_event_size(mut self, bytes: usize) -> Self {
+        self.max_event_size = bytes.min(MAX_MESSAGE_SIZE);
+        self
+    }
+
+    fn channel(&self, tag: &str) -> String {
+        format!("{}:{tag}", self.channel_prefix)
+    }
+
+    fn tag<'a>(&self, channel: &'a str) -> Option<&'a str> {
+        channel
+            .strip_prefix(&self.channel_prefix)?
+            .strip_prefix(':')
+    }
+
+    /// A client that connects and authenticates as configured
+    fn client(&self) -> io::Result<Client> {
+        let addr = self.addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing")
+        })?;
+        let mut settings = RedisConnectionInfo::default();
+        if let Some(password) = &self.password {
+            settings = settings.set_password(password);
+        }
+        let info = (addr.ip().to_string(), addr.port())
+            .into_connection_info()
+            .map_err(io_error)?
+            .set_redis_settings(settings);
+
+        Client::open(info).map_err(io_error)
+    }
+}
+
+/// A message as it's sent through Redis
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    origin: String,
+    event: serde_json::Value,
+}
+
+/// Handler that publishes events to Redis. Subscribed to the Publisher by
+/// `Publisher::bridge_redis`.
+pub(crate) struct RedisForwarder {
+    origin: String,
+    options: Arc<RedisOptions>,
+    registry: Arc<EventRegistry>,
+    client: Client,
+    /// None once the connection has been lost, until the next event reconnects
+    connection: Mutex<Option<Connection>>,
+}
+
+impl RefUnwindSafe for RedisForwarder {}
+
+impl RedisForwarder {
+    fn send(&self, channel: &str, message: &[u8]) -> io::Result<()> {
+        let mut connection = self.connection.lock().expect("Redis mutex poisoned");
+        // a connection Redis has closed is usually only noticed when using it, so retry once on
+        // a fresh connection
+        let mut last_error = None;
+        for _ in 0..2 {
+            let connected = match connection.as_mut() {
+                Some(connected) => connected,
+                None => connection.insert(
+                    self.client
+                        .get_connection_with_timeout(CONNECT_TIMEOUT)
+                        .map_err(io_error)?,
+                ),
+            };
+            match redis::cmd("PUBLISH")
+                .arg(channel)
+                .arg(message)
+                .exec(connected)
+            {
+                Ok(()) => return Ok(()),
+                // Redis refused the command, but the connection is fine
+                Err(e) if !e.is_io_error() => return Err(io_error(e)),
+                Err(e) => {
+                    *connection = None;
+                    last_error = Some(io_error(e));
+                }
+            }
+        }
+
+        Err(last_error.expect("send was attempted"))
+    }
+}
+
+impl DynHandle for RedisForwarder {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.accepts(event) {
+            return Ok(());
+        }
+        let Ok(serialized) = self.registry.serialize(event) else {
+            return Ok(());
+        };
+        EventTooLarge::check("redis", &serialized, self.options.max_event_size)?;
+        let SerializedEvent { tag, payload } = serialized;
+        let Ok(event) = serde_json::from_slice(&payload) else {
+            return Ok(());
+        };
+        let message = Message {
+            origin: self.origin.clone(),
+            event,
+        };
+        if let Ok(message) = serde_json::to_vec(&message) {
+            let _ = self.send(&self.options.channel(&tag), &message);
+        }
+        Ok(())
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        // events that came through Redis are not sent back to it
+        let remote = event.metadata().is_some_and(|metadata| metadata.remote);
+        !remote && self.registry.is_registered(event)
+    }
+
+    fn accepts_remote(&self) -> bool {
+        false
+    }
+}
+
+/// Connections to Redis that relay events in both directions. Created by
+/// `Publisher::bridge_redis`. Disconnects and unsubscribes from the Publisher when dropped.
+pub struct RedisBridge {
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    unsubscribe: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl RedisBridge {
+    /// Connect to Redis and subscribe to the channel of every registered event type, passing
+    /// every event received to `publish` until it returns false. Returns the bridge along with the
+    /// handler that publishes events to Redis.
+    pub(crate) fn connect<F>(
+        options: RedisOptions,
+        registry: Arc<EventRegistry>,
+        publish: F,
+    ) -> io::Result<(Self, RedisForwarder)>
+    where
+        F: Fn(Box<dyn DynEvent>) -> bool + Send + 'static,
+    {
+        // a connection that has subscribed to channels can't publish to them, so events are
+        // published on a second connection
+        let client = options.client()?;
+        let connection = client
+            .get_connection_with_timeout(CONNECT_TIMEOUT)
+            .map_err(io_error)?;
+
+        let mut tags: Vec<&str> = registry.tags().collect();
+        tags.sort_unstable();
+        let channels: Vec<String> = tags.iter().map(|tag| options.channel(tag)).collect();
+        let origin = origin();
+        let options = Arc::new(options);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (connected, first) = mpsc::channel();
+        let thread = {
+            let subscriber = Subscriber {
+                client: client.clone(),
+                channels,
+                origin: origin.clone(),
+                stopped: stopped.clone(),
+                options: options.clone(),
+                registry: registry.clone(),
+            };
+            thread::Builder::new()
+                .name(String::from("crier-redis"))
+                .spawn(move || subscriber.run(connected, &publish))?
+        };
+        match first.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(io::Error::other("Redis thread stopped")),
+        }
+
+        let bridge = RedisBridge {
+            stopped,
+            thread: Some(thread),
+            unsubscribe: None,
+        };
+        let forwarder = RedisForwarder {
+            origin,
+            options,
+            registry,
+            client,
+            connection: Mutex::new(Some(connection)),
+        };
+
+        Ok((bridge, forwarder))
+    }
+
+    /// Run `unsubscribe` when the bridge is dropped, to remove its forwarder from the Publisher
+    pub(crate) fn on_drop(&mut self, unsubscribe: impl FnOnce() + Send + 'static) {
+        self.unsubscribe = Some(Box::new(unsubscribe));
+    }
+}
+
+impl Drop for RedisBridge {
+    fn drop(&mut self) {
+        // dropping the forwarder closes the publishing connection
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+        self.stopped.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            // cut short any wait to reconnect
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// An ID for a bridge that is unique among the bridges connected to the same Redis
+fn origin() -> String {
+    static BRIDGES: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+
+    format!(
+        "{}-{nanos}-{}",
+        std::process::id(),
+        BRIDGES.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Receives messages on the bridge's thread
+struct Subscriber {
+    client: Client,
+    channels: Vec<String>,
+    origin: String,
+    stopped: Arc<AtomicBool>,
+    options: Arc<RedisOptions>,
+    registry: Arc<EventRegistry>,
+}
+
+impl Subscriber {
+    /// Publish the messages Redis sends until the bridge is dropped or the Publisher is gone,
+    /// reconnecting and subscribing again whenever the connection is lost. Whether the first
+    /// connection succeeds is sent through `connected`, and the thread stops if it doesn't.
+    fn run(
+        self,
+        connected: mpsc::Sender<io::Result<()>>,
+        publish: &dyn Fn(Box<dyn DynEvent>) -> bool,
+    ) {
+        let mut connected = Some(connected);
+        while !self.stopped.load(Ordering::SeqCst) {
+            let mut connection = match self.client.get_connection_with_timeout(CONNECT_TIMEOUT) {
+                Ok(connection) => connection,
+                Err(e) => {
+                    if self.retry(&mut connected, e) {
+                        continue;
+                    }
+                    return;
+                }
+            };
+            let mut pubsub = connection.as_pubsub();
+            match pubsub
+                .subscribe(&self.channels)
+                .and_then(|()| pubsub.set_read_timeout(Some(POLL_INTERVAL)))
+            {
+                Ok(()) => {}
+                Err(e) => {
+                    if self.retry(&mut connected, e) {
+                        continue;
+                    }
+                    return;
+                }
+            }
+            if let Some(connected) = connected.take() {
+                let _ = connected.send(Ok(()));
+            }
+            if !self.receive(&mut pubsub, publish) {
+                return;
+            }
+            thread::park_timeout(RECONNECT_DELAY);
+        }
+    }
+
+    /// Report a failure to make the first connection through `connected`, returning false to
+    /// stop the thread, or wait to try again after a later one
+    fn retry(
+        &self,
+        connected: &mut Option<mpsc::Sender<io::Result<()>>>,
+        error: RedisError,
+    ) -> bool {
+        if let Some(connected) = connected.take() {
+            let _ = connected.send(Err(io_error(error)));
+            return false;
+        }
+        thread::park_timeout(RECONNECT_DELAY);
+        true
+    }
+
+    /// Publish messages until the connection is lost, returning true, or the bridge is dropped or
+    /// the Publisher is gone, returning false
+    fn receive(&self, pubsub: &mut PubSub, publish: &dyn Fn(Box<dyn DynEvent>) -> bool) -> bool {
+        while !self.stopped.load(Ordering::SeqCst) {
+            let message = match pubsub.get_message() {
+                Ok(message) => message,
+                Err(e) if e.is_timeout() => continue,
+                Err(_) => return true,
+            };
+            let Some(tag) = self.options.tag(message.get_channel_name()) else {
+                continue;
+            };
+            let Ok(message) = serde_json::from_slice::<Message>(message.get_payload_bytes()) else {
+                continue;
+            };
+            if message.origin == self.origin {
+                continue;
+            }
+
+            let serialized = SerializedEvent {
+                tag: String::from(tag),
+                payload: message.event.to_string().into_bytes(),
+            };
+            if EventTooLarge::check("redis", &serialized, self.options.max_event_size).is_err() {
+                continue;
+            }
+            if let Ok(event) = self.registry.deserialize(&serialized)
+                && !publish(event)
+            {
+                return false;
+            }
+        }
+
+        false
+    }
+}
+
+fn io_error(error: RedisError) -> io::Error {
+    let kind = if error.kind() == ErrorKind::AuthenticationFailed {
+        io::ErrorKind::PermissionDenied
+    } else if error.is_connection_refusal() {
+        io::ErrorKind::ConnectionRefused
+    } else if error.is_timeout() {
+        io::ErrorKind::TimedOut
+    } else {
+        io::ErrorKind::Other
+    };
+    io::Error::new(kind, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Publisher, SerializableEvent};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct SignedIn {
+        user: String,
+    }
+    impl Event for SignedIn {}
+    impl SerializableEvent for SignedIn {
+        const TYPE_TAG: &'static str = "signed_in";
+    }
+
+    fn registry() -> Arc<EventRegistry> {
+        let mut registry = EventRegistry::default();
+        registry.register::<SignedIn>();
+        Arc::new(registry)
+    }
+
+    /// A reply from Redis, in version 2 of its serialization protocol
+    #[derive(Debug, PartialEq)]
+    enum Reply {
+        Simple(String),
+        Integer(i64),
+        Bulk(Vec<u8>),
+        Array(Vec<Reply>),
+    }
+
+    fn bulk(value: &[u8]) -> Reply {
+        Reply::Bulk(value.to_vec())
+    }
+
+    fn encode(reply: &Reply) -> Vec<u8> {
+        match reply {
+            Reply::Simple(value) => format!("+{value}\r\n").into_bytes(),
+            Reply::Integer(value) => format!(":{value}\r\n").into_bytes(),
+            Reply::Bulk(value) => {
+                let mut encoded = format!("${}\r\n", value.len()).into_bytes();
+                encoded.extend_from_slice(value);
+                encoded.extend_from_slice(b"\r\n");
+                encoded
+            }
+            Reply::Array(parts) => {
+                let mut encoded = format!("*{}\r\n", parts.len()).into_bytes();
+                for part in parts {
+                    encoded.extend(encode(part));
+                }
+                encoded
+            }
+        }
+    }
+
+    /// Read a command, which clients send as an array of bulk strings
+    fn read_command(reader: &mut impl BufRead) -> Vec<Vec<u8>> {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let len: usize = line.trim_end().strip_prefix('*').unwrap().parse().unwrap();
+        (0..len)
+            .map(|_| {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                let len: usize = line.trim_end().strip_prefix('$').unwrap().parse().unwrap();
+                let mut arg = vec![0; len + 2];
+                reader.read_exact(&mut arg).unwrap();
+                arg.truncate(len);
+                arg
+            })
+            .collect()
+    }
+
+    /// Accept a client and answer the commands it sends on connecting, returning its socket
+    fn accept(listener: &TcpListener) -> (TcpStream, BufReader<TcpStream>) {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(socket.try_clone().unwrap());
+        // the client tells Redis its name and version
+        for _ in 0..2 {
+            assert_eq!(read_command(&mut reader)[..2], [&b"CLIENT"[..], b"SETINFO"]);
+            socket
+                .write_all(&encode(&Reply::Simple(String::from("OK"))))
+                .unwrap();
+        }
+        (socket, reader)
+    }
+
+    /// Accept the subscribing client, returning its socket and the channels it subscribed to
+    fn accept_subscriber(listener: &TcpListener) -> (TcpStream, Vec<Vec<u8>>) {
+        let (mut socket, mut reader) = accept(listener);
+        let command = read_command(&mut reader);
+        assert_eq!(command[0], b"SUBSCRIBE");
+        let channels = command[1..].to_vec();
+        for (count, channel) in channels.iter().enumerate() {
+            let reply = Reply::Array(vec![
+                bulk(b"subscribe"),
+                bulk(channel),
+                Reply::Integer(count as i64 + 1),
+            ]);
+            socket.write_all(&encode(&reply)).unwrap();
+        }
+        (socket, channels)
+    }
+
+    fn send_message(socket: &mut TcpStream, channel: &[u8], origin: &str, user: &str) {
+        let message = serde_json::to_vec(&Message {
+            origin: String::from(origin),
+            event: serde_json::json!({ "user": user }),
+        })
+        .unwrap();
+        let reply = Reply::Array(vec![bulk(b"message"), bulk(channel), bulk(&message)]);
+        socket.write_all(&encode(&reply)).unwrap();
+    }
+
+    fn receiving_publisher() -> (Publisher, mpsc::Receiver<String>) {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe(Handler::new(move |event: SignedIn| {
+            sender.lock().unwrap().send(event.user).unwrap()
+        }));
+        (publisher, receiver)
+    }
+
+    #[test]
+    fn test_bridge_relays_events_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (mut publisher, receiver) = receiving_publisher();
+
+        let bridge = thread::spawn(move || {
+            let options = RedisOptions::new(addr.to_string()).channel_prefix("app");
+            let bridge = publisher.bridge_redis(options, registry()).unwrap();
+            (publisher, bridge)
+        });
+        let (mut publishing, mut publishing_reader) = accept(&listener);
+        let (mut subscribing, channels) = accept_subscriber(&listener);
+        assert_eq!(channels, [b"app:signed_in"]);
+        let (mut publisher, _bridge) = bridge.join().unwrap();
+
+        // local events are published to Redis
+        let sent = thread::spawn(move || {
+            let _ = publisher.publish(SignedIn {
+                user: String::from("ferris"),
+            });
+            publisher
+        });
+        let command = read_command(&mut publishing_reader);
+        publishing.write_all(&encode(&Reply::Integer(1))).unwrap();
+        let publisher = sent.join().unwrap();
+        assert_eq!(command[..2], [&b"PUBLISH"[..], b"app:signed_in"]);
+        let sent: Message = serde_json::from_slice(&command[2]).unwrap();
+        assert_eq!(sent.event, serde_json::json!({ "user": "ferris" }));
+        assert_eq!(receiver.recv().unwrap(), "ferris");
+
+        // messages from other bridges are published locally, but the bridge's own are skipped
+        send_message(&mut subscribing, b"app:signed_in", &sent.origin, "echo");
+        send_message(&mut subscribing, b"app:signed_in", "elsewhere", "crab");
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "crab"
+        );
+        drop(publisher);
+    }
+
+    #[test]
+    fn test_bridge_reconnects_and_subscribes_again() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (mut publisher, receiver) = receiving_publisher();
+
+        let bridge = thread::spawn(move || {
+            let options = RedisOptions::new(addr.to_string());
+            let bridge = publisher.bridge_redis(options, registry()).unwrap();
+            (publisher, bridge)
+        });
+        let _publishing = accept(&listener);
+        let (subscribing, _) = accept_subscriber(&listener);
+        drop(subscribing);
+        let (mut subscribing, channels) = accept_subscriber(&listener);
+        assert_eq!(channels, [b"crier:signed_in"]);
+        let (_publisher, _bridge) = bridge.join().unwrap();
+
+        send_message(&mut subscribing, b"crier:signed_in", "elsewhere", "crab");
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "crab"
+        );
+    }
+}
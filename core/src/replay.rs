@@ -0,0 +1,532 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    panic::{self, AssertUnwindSafe, RefUnwindSafe},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DynEvent, DynHandle, EventRegistry, HandlerError, Named, Publisher, SerializeError,
+    SerializedEvent,
+};
+
+/// A serialized event along with when it was published, so that it can be replayed with the
+/// same timing, and its number in the recording
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub timestamp: SystemTime,
+    /// Numbers the records of a recording in the order they were made, starting from 0 unless
+    /// the Recorder was given a first sequence number
+    #[serde(default)]
+    pub sequence: u64,
+    pub event: SerializedEvent,
+}
+
+/// Handler that records every published event whose type is registered with its EventRegistry,
+/// along with the time it was published, and passes it to a sink, e.g. to write a log that can be
+/// replayed with `Publisher::replay`
+pub struct Recorder {
+    registry: Arc<EventRegistry>,
+    sink: Box<dyn Fn(RecordedEvent) + Send + Sync>,
+    /// Sequence number of the next record
+    next: AtomicU64,
+}
+
+impl RefUnwindSafe for Recorder {}
+
+impl Recorder {
+    pub fn new<F>(registry: Arc<EventRegistry>, sink: F) -> Self
+    where
+        F: Fn(RecordedEvent) + Send + Sync + 'static,
+    {
+        Recorder {
+            registry,
+            sink: Box::new(sink),
+            next: AtomicU64::new(0),
+        }
+    }
+
+    /// Number records from `sequence`, such as the one after the last record of a recording
+    /// that is being added to
+    pub fn with_first_sequence(mut self, sequence: u64) -> Self {
+        self.next = AtomicU64::new(sequence);
+        self
+    }
+}
+
+impl DynHandle for Recorder {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        if let Ok(serialized) = self.registry.serialize(event) {
+            (self.sink)(RecordedEvent {
+                timestamp: event
+                    .metadata()
+                    .map_or_else(SystemTime::now, |metadata| metadata.timestamp),
+                sequence: self.next.fetch_add(1, Ordering::SeqCst),
+                event: serialized,
+            })
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        self.registry.is_registered(event)
+    }
+}
+
+/// How far each named subscription has got through a recording, as the sequence number of the
+/// last record it acknowledged. Kept alongside the recording, such as in a file next to it, so
+/// that once a service restarts, `resume` hands each subscription the records it hadn't finished
+/// with, rather than the whole recording again or only what is recorded from then on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Positions {
+    acknowledged: BTreeMap<String, u64>,
+}
+
+impl Positions {
+    /// Sequence number of the last record the subscription named `name` acknowledged, if any
+    pub fn position(&self, name: &str) -> Option<u64> {
+        self.acknowledged.get(name).copied()
+    }
+
+    /// Note that the subscription named `name` has finished with every record up to and
+    /// including `sequence`. Positions never move backwards.
+    pub fn acknowledge(&mut self, name: &str, sequence: u64) {
+        let position = self.acknowledged.entry(String::from(name)).or_default();
+        *position = (*position).max(sequence);
+    }
+
+    /// Hand `handler` each of `records` after its acknowledged position, in order, acknowledging
+    /// each one it handles without panicking or returning an error. Stops at the first record
+    /// that can't be decoded with `registry` or that the handler fails on, which is handed to it
+    /// again on the next resume.
+    pub fn resume<H, I>(
+        &mut self,
+        handler: &Named<H>,
+        registry: &EventRegistry,
+        records: I,
+    ) -> Result<(), ReplayError>
+    where
+        H: DynHandle,
+        I: IntoIterator<Item = RecordedEvent>,
+    {
+        let name = handler.name();
+        let position = self.position(name);
+        let records = records
+            .into_iter()
+            .enumerate()
+            .filter(|(_, record)| position.is_none_or(|position| record.sequence > position));
+        for (index, record) in records {
+            let event = registry
+                .deserialize(&record.event)
+                .map_err(|error| ReplayError::Decode { index, error })?;
+            match panic::catch_unwind(AssertUnwindSafe(|| handler.dyn_try_handle(&event))) {
+                Ok(Ok(())) => self.acknowledge(name, record.sequence),
+                Ok(Err(error)) => {
+                    let error = HandlerError {
+                        handler: name,
+                        error,
+                    };
+                    return Err(ReplayError::Handler(vec![Box::new(error)]));
+                }
+                Err(payload) => return Err(ReplayError::Handler(vec![payload])),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Shrinks a log written by a Recorder by dropping the records of state-like event types that a
+/// later record of the same type and partition key supersedes, once they are older than a
+/// retention horizon. Records within the horizon are all kept, so the recent history can still be
+/// replayed in full, while the log as a whole only grows with the number of keys. Events that
+/// aren't partitioned have a single key for their whole type.
+/// # Examples
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use crier::{Compaction, Event, EventRegistry, RecordedEvent, SerializableEvent};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Clone, Event, Serialize, Deserialize)]
+/// struct Temperature(f32);
+/// impl SerializableEvent for Temperature {
+///     const TYPE_TAG: &'static str = "temperature";
+/// }
+///
+/// let mut registry = EventRegistry::default();
+/// registry.register::<Temperature>();
+/// let start = SystemTime::UNIX_EPOCH;
+/// let records: Vec<RecordedEvent> = (0..10)
+///     .map(|minute| RecordedEvent {
+///         timestamp: start + Duration::from_secs(60 * minute),
+///         sequence: minute,
+///         event: registry.serialize(&Temperature(20.0)).unwrap(),
+///     })
+///     .collect();
+///
+/// let compaction = Compaction::new(Duration::from_secs(120)).keep_latest("temperature");
+/// // the readings from the last two minutes are kept, which supersede all the earlier ones
+/// assert_eq!(compaction.compact(&registry, records).len(), 3);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Compaction {
+    horizon: Duration,
+    keyed: HashSet<&'static str>,
+}
+
+impl Compaction {
+    /// Compact records that are more than `horizon` older than the newest record in the log.
+    /// Nothing is compacted until event types are marked with `keep_latest`.
+    pub fn new(horizon: Duration) -> Self {
+        Compaction {
+            horizon,
+            keyed: HashSet::new(),
+        }
+    }
+
+    /// Mark the event type registered under `tag` as keyed-latest, so that only the newest of its
+    /// records for each partition key is kept beyond the horizon
+    pub fn keep_latest(mut self, tag: &'static str) -> Self {
+        self.keyed.insert(tag);
+        self
+    }
+
+    /// The records worth keeping out of `records`, in the order they were recorded. Records of
+    /// keyed-latest types are decoded with `registry` to find their partition keys, and are kept
+    /// if they can't be decoded, since there's no telling which records they supersede.
+    pub fn compact(
+        &self,
+        registry: &EventRegistry,
+        records: Vec<RecordedEvent>,
+    ) -> Vec<RecordedEvent> {
+        let Some(newest) = records.iter().map(|record| record.timestamp).max() else {
+            return records;
+        };
+        let horizon = newest
+            .checked_sub(self.horizon)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        // walk back from the newest record, so that a record's key has already been seen if a
+        // later record supersedes it
+        let mut seen = HashSet::new();
+        let mut kept: Vec<bool> = records
+            .iter()
+            .rev()
+            .map(|record| {
+                let tag = record.event.tag.as_str();
+                if !self.keyed.contains(tag) {
+                    return true;
+                }
+                let Ok(event) = registry.deserialize(&record.event) else {
+                    return true;
+                };
+                let superseded = !seen.insert((tag, event.dyn_partition_key()));
+                !superseded || record.timestamp >= horizon
+            })
+            .collect();
+        kept.reverse();
+
+        records
+            .into_iter()
+            .zip(kept)
+            .filter_map(|(record, kept)| kept.then_some(record))
+            .collect()
+    }
+}
+
+/// How quickly `Publisher::replay` publishes recorded events
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReplaySpeed {
+    /// Keep the gaps between events as they were recorded, so a replay looks like the original
+    /// run
+    #[default]
+    Realtime,
+    /// Scale the gaps between events down by the given factor, e.g. 2.0 to replay at double
+    /// speed or 0.5 for slow motion. Factors of zero or less replay as `Instant`.
+    Fast(f64),
+    /// Publish every event straight away
+    Instant,
+}
+
+impl ReplaySpeed {
+    /// How long after the first event one recorded `elapsed` after it is published
+    fn scale(self, elapsed: Duration) -> Duration {
+        match self {
+            ReplaySpeed::Realtime => elapsed,
+            ReplaySpeed::Fast(factor) if factor > 0.0 => elapsed.div_f64(factor),
+            ReplaySpeed::Fast(_) | ReplaySpeed::Instant => Duration::ZERO,
+        }
+    }
+}
+
+/// Reasons a replay didn't go cleanly
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The record at `index` couldn't be deserialized, so the replay stopped before it
+    Decode { index: usize, error: SerializeError },
+    /// Handlers of some of the records panicked or returned errors. `Publisher::replay` carries
+    /// on past them, while `Positions::resume` stops at the first.
+    Handler(Vec<Box<dyn Any + Send + 'static>>),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Decode { index, error } => {
+                write!(f, "failed to decode record {index}: {error}")
+            }
+            ReplayError::Handler(errors) => {
+                write!(f, "{} handlers failed during replay", errors.len())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Publish each of `records` on `publisher` at the time `speed` calls for, measured from when the
+/// first is published
+pub(crate) fn replay<I>(
+    publisher: &mut Publisher,
+    registry: &EventRegistry,
+    records: I,
+    speed: ReplaySpeed,
+) -> Result<(), ReplayError>
+where
+    I: IntoIterator<Item = RecordedEvent>,
+{
+    let mut errors = Vec::new();
+    let mut first: Option<(SystemTime, Instant)> = None;
+    for (index, record) in records.into_iter().enumerate() {
+        let event = registry
+            .deserialize(&record.event)
+            .map_err(|error| ReplayError::Decode { index, error })?;
+        let (recorded, started) = *first.get_or_insert_with(|| (record.timestamp, Instant::now()));
+        // events recorded out of order are published straight after the one before them
+        let elapsed = record
+            .timestamp
+            .duration_since(recorded)
+            .unwrap_or_default();
+        let due = started + speed.scale(elapsed);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+
+        if let Err(e) = publisher.publish(event) {
+            errors.extend(e);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ReplayError::Handler(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Partition, SerializableEvent};
+    use std::sync::{Mutex, atomic::AtomicBool};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Moved(u32);
+    impl Event for Moved {}
+    impl SerializableEvent for Moved {
+        const TYPE_TAG: &'static str = "moved";
+    }
+
+    fn registry() -> Arc<EventRegistry> {
+        let mut registry = EventRegistry::default();
+        registry.register::<Moved>();
+        Arc::new(registry)
+    }
+
+    /// Record three events, 40ms apart
+    fn record() -> Vec<RecordedEvent> {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let records_clone = records.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe(Recorder::new(registry(), move |record| {
+            records_clone.lock().unwrap().push(record)
+        }));
+        for step in 0..3 {
+            let _ = publisher.publish(Moved(step));
+        }
+
+        let mut records = std::mem::take(&mut *records.lock().unwrap());
+        let start = records[0].timestamp;
+        for (index, record) in records.iter_mut().enumerate() {
+            record.timestamp = start + Duration::from_millis(40 * index as u64);
+        }
+        records
+    }
+
+    fn replay_timed(speed: ReplaySpeed) -> (Vec<u32>, Duration) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe_with(move |event: Moved| received_clone.lock().unwrap().push(event.0));
+
+        let started = Instant::now();
+        publisher.replay(&registry(), record(), speed).unwrap();
+        let elapsed = started.elapsed();
+
+        let received = received.lock().unwrap().clone();
+        (received, elapsed)
+    }
+
+    #[test]
+    fn test_replay_preserves_timing_at_the_chosen_scale() {
+        let (received, elapsed) = replay_timed(ReplaySpeed::Realtime);
+        assert_eq!(received, [0, 1, 2]);
+        assert!(elapsed >= Duration::from_millis(80));
+
+        let (received, elapsed) = replay_timed(ReplaySpeed::Fast(4.0));
+        assert_eq!(received, [0, 1, 2]);
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_millis(80));
+
+        let (received, elapsed) = replay_timed(ReplaySpeed::Instant);
+        assert_eq!(received, [0, 1, 2]);
+        assert!(elapsed < Duration::from_millis(20));
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        player: u64,
+        x: i32,
+    }
+    impl Event for Position {
+        fn as_partition(&self) -> Option<&dyn Partition> {
+            Some(self)
+        }
+    }
+    impl Partition for Position {
+        fn partition_key(&self) -> u64 {
+            self.player
+        }
+    }
+    impl SerializableEvent for Position {
+        const TYPE_TAG: &'static str = "position";
+    }
+
+    #[test]
+    fn test_compaction_keeps_the_latest_of_each_key_beyond_the_horizon() {
+        let mut registry = EventRegistry::default();
+        registry.register::<Moved>().register::<Position>();
+        let start = SystemTime::UNIX_EPOCH;
+        let events: Vec<Box<dyn DynEvent>> = vec![
+            Box::new(Position { player: 1, x: 0 }),
+            Box::new(Position { player: 2, x: 0 }),
+            Box::new(Moved(0)),
+            Box::new(Position { player: 1, x: 1 }),
+            Box::new(Position { player: 1, x: 2 }),
+            Box::new(Position { player: 1, x: 3 }),
+        ];
+        let records: Vec<RecordedEvent> = events
+            .iter()
+            .enumerate()
+            .map(|(second, event)| RecordedEvent {
+                timestamp: start + Duration::from_secs(second as u64),
+                sequence: second as u64,
+                event: registry.serialize(event).unwrap(),
+            })
+            .collect();
+
+        let compaction = Compaction::new(Duration::from_secs(1)).keep_latest("position");
+        let kept: Vec<RecordedEvent> = compaction.compact(&registry, records.clone());
+        // player 2's only position and the unmarked event are kept, along with everything in the
+        // last second, which supersedes player 1's older positions
+        assert_eq!(
+            kept,
+            [
+                records[1].clone(),
+                records[2].clone(),
+                records[4].clone(),
+                records[5].clone(),
+            ]
+        );
+
+        let unmarked = Compaction::new(Duration::ZERO);
+        assert_eq!(unmarked.compact(&registry, records.clone()), records);
+    }
+
+    #[test]
+    fn test_named_subscriptions_resume_from_their_acknowledged_position() {
+        let records = record();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.sequence)
+                .collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let crashing = Arc::new(AtomicBool::new(true));
+        let crashing_clone = crashing.clone();
+        let handler = Named::new(
+            "mover",
+            Handler::new(move |event: Moved| {
+                if event.0 == 1 && crashing_clone.load(Ordering::SeqCst) {
+                    panic!("crashed");
+                }
+                received_clone.lock().unwrap().push(event.0);
+            }),
+        );
+
+        let mut positions = Positions::default();
+        let result = positions.resume(&handler, &registry(), records.clone());
+        assert!(matches!(result, Err(ReplayError::Handler(_))));
+        assert_eq!(positions.position("mover"), Some(0));
+
+        // after a restart, with the positions read back from where they were stored
+        let stored = serde_json::to_vec(&positions).unwrap();
+        let mut positions: Positions = serde_json::from_slice(&stored).unwrap();
+        crashing.store(false, Ordering::SeqCst);
+        positions
+            .resume(&handler, &registry(), records.clone())
+            .unwrap();
+        positions.resume(&handler, &registry(), records).unwrap();
+        assert_eq!(*received.lock().unwrap(), [0, 1, 2]);
+        assert_eq!(positions.position("mover"), Some(2));
+        assert_eq!(positions.position("other"), None);
+    }
+
+    #[test]
+    fn test_recorders_can_number_from_a_given_sequence() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let records_clone = records.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe(
+            Recorder::new(registry(), move |record: RecordedEvent| {
+                records_clone.lock().unwrap().push(record.sequence)
+            })
+            .with_first_sequence(3),
+        );
+        let _ = publisher.publish(Moved(0));
+        let _ = publisher.publish(Moved(1));
+        assert_eq!(*records.lock().unwrap(), [3, 4]);
+    }
+
+    #[test]
+    fn test_replay_stops_at_records_that_fail_to_decode() {
+        let mut records = record();
+        records[1].event.tag = String::from("unknown");
+        let mut publisher = Publisher::default();
+
+        let result = publisher.replay(&registry(), records, ReplaySpeed::Instant);
+        assert!(matches!(result, Err(ReplayError::Decode { index: 1, .. })));
+    }
+}
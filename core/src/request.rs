@@ -0,0 +1,137 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    panic::RefUnwindSafe,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{Delivery, DynEvent, DynHandle, Event};
+
+/// An Event that asks the handlers subscribed to it with `Publisher::respond` for a reply
+pub trait Request: Event + Clone {
+    type Response: Send + 'static;
+}
+
+/// A request as it travels through a Publisher, along with somewhere for responders to put their
+/// replies. Only responders receive it, so handlers subscribed to the request type itself don't.
+pub(crate) struct Requested<R: Request> {
+    pub(crate) request: R,
+    pub(crate) responses: Arc<Mutex<Vec<R::Response>>>,
+}
+
+impl<R: Request> RefUnwindSafe for Requested<R> {}
+
+impl<R: Request> Event for Requested<R> {
+    const DELIVERY: Delivery = Delivery::Shared;
+}
+
+/// Handler subscribed with `Publisher::respond`
+pub(crate) struct Responder<R, F> {
+    respond: F,
+    request: PhantomData<fn(R)>,
+}
+
+impl<R, F> RefUnwindSafe for Responder<R, F> {}
+
+impl<R: Request, F: Fn(R) -> R::Response + Send + Sync> Responder<R, F> {
+    pub(crate) fn new(respond: F) -> Self {
+        Responder {
+            respond,
+            request: PhantomData,
+        }
+    }
+}
+
+impl<R: Request, F: Fn(R) -> R::Response + Send + Sync> DynHandle for Responder<R, F> {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        if let Some(requested) = event.get_data().downcast_ref::<Requested<R>>() {
+            let response = (self.respond)(requested.request.clone());
+            requested
+                .responses
+                .lock()
+                .expect("Response mutex poisoned")
+                .push(response);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event.get_data().is::<Requested<R>>()
+    }
+}
+
+struct RepliesState<T> {
+    responses: Option<Vec<T>>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by `Publisher::request_async`, which resolves to the responses once every
+/// responder has replied
+pub struct Replies<T> {
+    state: Arc<Mutex<RepliesState<T>>>,
+}
+
+impl<T> Replies<T> {
+    /// Create the future along with the function that completes it
+    pub(crate) fn new() -> (Self, impl FnOnce(Vec<T>)) {
+        let state = Arc::new(Mutex::new(RepliesState {
+            responses: None,
+            waker: None,
+        }));
+        let completer = state.clone();
+        let complete = move |responses| {
+            let mut state = completer.lock().expect("Replies mutex poisoned");
+            state.responses = Some(responses);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        };
+
+        (Replies { state }, complete)
+    }
+}
+
+impl<T> Future for Replies<T> {
+    type Output = Vec<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<T>> {
+        let mut state = self.state.lock().expect("Replies mutex poisoned");
+        match state.responses.take() {
+            Some(responses) => Poll::Ready(responses),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{task::Wake, thread};
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    #[test]
+    fn test_replies_resolve_once_completed() {
+        let (mut replies, complete) = Replies::new();
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut replies).poll(&mut cx), Poll::Pending);
+
+        let completer = thread::spawn(move || complete(vec![1, 2]));
+        completer.join().unwrap();
+        assert_eq!(
+            Pin::new(&mut replies).poll(&mut cx),
+            Poll::Ready(vec![1, 2])
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    DynEvent, DynHandle, DynHandleMut, HandlerPoisoned, scheduler::Timer, wait::InFlightGuard,
+};
+
+/// How long to wait before each retry of an event a handler failed to handle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// Retry straight away
+    Immediate,
+    /// Wait the same time before every retry
+    Fixed(Duration),
+    /// Wait `initial` before the first retry, then twice as long before each one after that, up
+    /// to `max`
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    /// How long to wait after the given number of attempts have failed
+    fn delay(self, failed: u32) -> Duration {
+        match self {
+            Backoff::Immediate => Duration::ZERO,
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 2u32.saturating_pow(failed.saturating_sub(1));
+                initial.saturating_mul(factor).min(max)
+            }
+        }
+    }
+}
+
+/// How a subscription's handler is retried when it panics or returns an error while handling an
+/// event. Once it has
+/// failed `max_attempts` times, counting the first, the event is passed to the handlers
+/// subscribed with `Publisher::subscribe_dead_letter`. Set with `Publisher::set_retry_policy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, backoff: Backoff) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff,
+        }
+    }
+}
+
+/// The retry policy of each subscription that has one
+#[derive(Default)]
+pub(crate) struct Retries {
+    policies: RwLock<HashMap<usize, RetryPolicy>>,
+}
+
+impl Retries {
+    pub(crate) fn set(&self, id: usize, policy: RetryPolicy) {
+        self.policies
+            .write()
+            .expect("Retry lock poisoned")
+            .insert(id, policy);
+    }
+
+    pub(crate) fn get(&self, id: usize) -> Option<RetryPolicy> {
+        self.policies
+            .read()
+            .expect("Retry lock poisoned")
+            .get(&id)
+            .copied()
+    }
+
+    /// Forget a subscription's policy, which stops any retries of its handler still to come
+    pub(crate) fn remove(&self, id: usize) {
+        self.policies
+            .write()
+            .expect("Retry lock poisoned")
+            .remove(&id);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.policies
+            .read()
+            .expect("Retry lock poisoned")
+            .is_empty()
+    }
+}
+
+/// The handler an event is retried on
+pub(crate) enum Retried {
+    Handler(Arc<dyn DynHandle>),
+    Mut(Arc<Mutex<dyn DynHandleMut>>),
+}
+
+impl Retried {
+    fn try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self {
+            Retried::Handler(handler) => handler.dyn_try_handle(event),
+            Retried::Mut(mutex) => match mutex.lock() {
+                Ok(mut handler) => handler.dyn_try_handle_mut(event),
+                Err(_) => Err(Box::new(HandlerPoisoned)),
+            },
+        }
+    }
+}
+
+/// An event that a handler with a retry policy failed to handle, waiting to be retried
+pub(crate) struct Retry {
+    pub(crate) retries: Arc<Retries>,
+    pub(crate) id: usize,
+    pub(crate) handler: Arc<Retried>,
+    pub(crate) event: Arc<dyn DynEvent>,
+    /// Handlers the event is passed to if every attempt fails
+    pub(crate) dead_letters: Vec<Arc<dyn DynHandle>>,
+    pub(crate) timer: Timer,
+    /// Number of attempts that have failed so far
+    pub(crate) failed: u32,
+    /// Keeps the retry counted as background work until it succeeds or gives up
+    pub(crate) _guard: InFlightGuard,
+}
+
+impl Retry {
+    /// Schedule the next attempt according to the subscription's policy, or give up on the event
+    /// if it has run out of attempts or been unsubscribed
+    pub(crate) fn schedule(self) {
+        let Some(policy) = self.retries.get(self.id) else {
+            return;
+        };
+        if self.failed >= policy.max_attempts {
+            for handler in &self.dead_letters {
+                let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    handler.dyn_handle(self.event.as_ref())
+                }));
+            }
+            return;
+        }
+
+        let due = Instant::now() + policy.backoff.delay(self.failed);
+        let timer = self.timer.clone();
+        timer.schedule(due, move || self.attempt());
+    }
+
+    fn attempt(mut self) {
+        if self.retries.get(self.id).is_none() {
+            return;
+        }
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            self.handler.try_handle(self.event.as_ref())
+        }));
+        if !matches!(result, Ok(Ok(()))) {
+            self.failed += 1;
+            self.schedule();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delays() {
+        let millis = Duration::from_millis;
+        assert_eq!(Backoff::Immediate.delay(3), Duration::ZERO);
+        assert_eq!(Backoff::Fixed(millis(5)).delay(3), millis(5));
+        let exponential = Backoff::Exponential {
+            initial: millis(10),
+            max: millis(35),
+        };
+        let delays: Vec<Duration> = (1..=4).map(|failed| exponential.delay(failed)).collect();
+        assert_eq!(delays, [millis(10), millis(20), millis(35), millis(35)]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use crate::{Balance, DynEvent, group::choose};
+
+/// How a Publisher routes events of a given type to the handlers subscribed to them. Set with
+/// `Publisher::set_routing`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Routing {
+    /// Every handler receives every event
+    #[default]
+    Broadcast,
+    /// Each event goes to one handler, with handlers taking turns in the order they subscribed
+    RoundRobin,
+    /// Each event goes to one handler, picked at random
+    Random,
+    /// Each event goes to the handler with the fewest events of load-balanced types still being
+    /// delivered to it
+    LeastLoaded,
+}
+
+#[derive(Default)]
+struct RouteState {
+    /// Whose turn it is for each round robin event type
+    next: HashMap<TypeId, usize>,
+    /// Number of events each handler has been routed that are still being delivered
+    busy: HashMap<usize, usize>,
+}
+
+/// The routing chosen for each event type, along with the state needed to balance them
+#[derive(Default)]
+pub(crate) struct Routes {
+    policies: RwLock<HashMap<TypeId, Routing>>,
+    state: Mutex<RouteState>,
+}
+
+impl Routes {
+    pub(crate) fn set(&self, event_type: TypeId, routing: Routing) {
+        let mut policies = self.policies.write().expect("Routing lock poisoned");
+        if routing == Routing::Broadcast {
+            policies.remove(&event_type);
+        } else {
+            policies.insert(event_type, routing);
+        }
+    }
+
+    /// Whether every event type is broadcast
+    pub(crate) fn is_empty(&self) -> bool {
+        self.policies
+            .read()
+            .expect("Routing lock poisoned")
+            .is_empty()
+    }
+
+    /// The routing for the event's type
+    pub(crate) fn routing(&self, event: &dyn DynEvent) -> Routing {
+        self.policies
+            .read()
+            .expect("Routing lock poisoned")
+            .get(&event.get_data().type_id())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Choose which of `candidates` receives an event whose type is load-balanced. The chosen
+    /// handler counts as loaded until `finish` is called with its ID.
+    pub(crate) fn route(&self, event: &dyn DynEvent, candidates: &[usize]) -> Option<usize> {
+        let balance = match self.routing(event) {
+            Routing::Broadcast => return None,
+            Routing::RoundRobin => Balance::RoundRobin,
+            Routing::Random => Balance::Random,
+            Routing::LeastLoaded => Balance::LeastBusy,
+        };
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let mut state = self.state.lock().expect("Routing mutex poisoned");
+        let state = &mut *state;
+        let next = state.next.entry(event.get_data().type_id()).or_default();
+        let index = choose(balance, candidates.len(), next, |index| {
+            state.busy.get(&candidates[index]).copied().unwrap_or(0)
+        });
+        let chosen = candidates[index];
+        *state.busy.entry(chosen).or_default() += 1;
+
+        Some(chosen)
+    }
+
+    /// Record that the events routed to `ids` have been delivered
+    pub(crate) fn finish(&self, ids: impl IntoIterator<Item = usize>) {
+        let mut state = self.state.lock().expect("Routing mutex poisoned");
+        for id in ids {
+            if let Some(busy) = state.busy.get_mut(&id) {
+                *busy -= 1;
+                if *busy == 0 {
+                    state.busy.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+
+    #[derive(Clone)]
+    struct Job;
+    impl Event for Job {}
+
+    #[derive(Clone)]
+    struct Notice;
+    impl Event for Notice {}
+
+    #[test]
+    fn test_routes_only_load_balanced_types() {
+        let routes = Routes::default();
+        routes.set(TypeId::of::<Job>(), Routing::RoundRobin);
+
+        assert_eq!(routes.route(&Notice, &[1, 2]), None);
+        assert_eq!(routes.route(&Job, &[1, 2]), Some(1));
+        assert_eq!(routes.route(&Job, &[1, 2]), Some(2));
+        assert_eq!(routes.route(&Job, &[]), None);
+
+        routes.set(TypeId::of::<Job>(), Routing::Broadcast);
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn test_least_loaded_avoids_handlers_still_busy() {
+        let routes = Routes::default();
+        routes.set(TypeId::of::<Job>(), Routing::LeastLoaded);
+
+        assert_eq!(routes.route(&Job, &[1, 2]), Some(1));
+        assert_eq!(routes.route(&Job, &[1, 2]), Some(2));
+        assert_eq!(routes.route(&Job, &[1, 2]), Some(1));
+        routes.finish([1, 1]);
+        assert_eq!(routes.route(&Job, &[1, 2]), Some(1));
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{
+        RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::DynEvent;
+
+/// How many of the events of a given type a Publisher delivers, such as to turn noisy diagnostic
+/// events down in production without redeploying. Set with `Publisher::set_sampling`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sampling {
+    /// Every event is delivered
+    #[default]
+    Broadcast,
+    /// One in every `n` events is delivered, starting with the first. `Sampled(0)` and
+    /// `Sampled(1)` deliver every event.
+    Sampled(u64),
+    /// No events are delivered
+    Muted,
+}
+
+/// The sampling chosen for each event type, along with how many events of each sampled type have
+/// been published
+#[derive(Default)]
+pub(crate) struct Samplers {
+    policies: RwLock<HashMap<TypeId, (Sampling, AtomicU64)>>,
+}
+
+impl Samplers {
+    pub(crate) fn set(&self, event_type: TypeId, sampling: Sampling) {
+        let mut policies = self.policies.write().expect("Sampling lock poisoned");
+        if sampling == Sampling::Broadcast {
+            policies.remove(&event_type);
+        } else {
+            policies.insert(event_type, (sampling, AtomicU64::new(0)));
+        }
+    }
+
+    pub(crate) fn get(&self, event_type: TypeId) -> Sampling {
+        self.policies
+            .read()
+            .expect("Sampling lock poisoned")
+            .get(&event_type)
+            .map(|(sampling, _)| *sampling)
+            .unwrap_or_default()
+    }
+
+    /// Whether an event should be delivered under its type's sampling
+    pub(crate) fn admit(&self, event: &dyn DynEvent) -> bool {
+        let policies = self.policies.read().expect("Sampling lock poisoned");
+        match policies.get(&event.get_data().type_id()) {
+            None => true,
+            Some((Sampling::Broadcast, _)) => true,
+            Some((Sampling::Muted, _)) => false,
+            Some((Sampling::Sampled(n), published)) => {
+                published.fetch_add(1, Ordering::SeqCst) % (*n).max(1) == 0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+
+    #[derive(Clone)]
+    struct FrameTime;
+    impl Event for FrameTime {}
+
+    #[derive(Clone)]
+    struct Crash;
+    impl Event for Crash {}
+
+    #[test]
+    fn test_sampling_admits_one_in_n_until_changed() {
+        let samplers = Samplers::default();
+        samplers.set(TypeId::of::<FrameTime>(), Sampling::Sampled(3));
+
+        let admitted: Vec<bool> = (0..6).map(|_| samplers.admit(&FrameTime)).collect();
+        assert_eq!(admitted, vec![true, false, false, true, false, false]);
+        assert!(samplers.admit(&Crash));
+
+        samplers.set(TypeId::of::<FrameTime>(), Sampling::Muted);
+        assert!(!samplers.admit(&FrameTime));
+        samplers.set(TypeId::of::<FrameTime>(), Sampling::Broadcast);
+        assert!(samplers.admit(&FrameTime));
+        assert_eq!(samplers.get(TypeId::of::<FrameTime>()), Sampling::Broadcast);
+    }
+}
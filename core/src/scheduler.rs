@@ -0,0 +1,352 @@
+use std::{
+    any::TypeId,
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    sync::{
+        Arc, Condvar, Mutex, Weak,
+        atomic::{self, AtomicBool, AtomicU64},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Source of the IDs of scheduled events
+static NEXT_SCHEDULE: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies an event scheduled for later publication, for cancelling it with
+/// `Publisher::cancel_scheduled`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScheduleId(u64);
+
+/// Handle to an event scheduled for later publication. Can be used to cancel the event before it
+/// is published.
+#[derive(Clone, Debug)]
+pub struct ScheduleHandle {
+    id: ScheduleId,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ScheduleHandle {
+    pub(crate) fn new() -> Self {
+        ScheduleHandle {
+            id: ScheduleId(NEXT_SCHEDULE.fetch_add(1, atomic::Ordering::Relaxed)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn id(&self) -> ScheduleId {
+        self.id
+    }
+
+    /// Stop the scheduled event from being published. Has no effect if it has already been
+    /// published.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, atomic::Ordering::SeqCst);
+    }
+
+    /// Whether `cancel` has been called on this handle or any of its clones
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(atomic::Ordering::SeqCst)
+    }
+}
+
+/// An event waiting to be published by `Publisher::publish_after` or `Publisher::publish_every`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledInfo {
+    pub id: ScheduleId,
+    /// Name of the event's type
+    pub type_name: &'static str,
+    /// When the event is next due to be published
+    pub due: Instant,
+    /// How often the event is published, if it is recurring
+    pub period: Option<Duration>,
+}
+
+/// Events scheduled by a Publisher that are still waiting to be published
+#[derive(Default)]
+pub(crate) struct Pending {
+    events: Mutex<HashMap<ScheduleId, (TypeId, ScheduleHandle, ScheduledInfo)>>,
+}
+
+impl Pending {
+    pub(crate) fn insert(
+        &self,
+        handle: &ScheduleHandle,
+        event_type: (TypeId, &'static str),
+        due: Instant,
+        period: Option<Duration>,
+    ) {
+        let info = ScheduledInfo {
+            id: handle.id,
+            type_name: event_type.1,
+            due,
+            period,
+        };
+        self.events
+            .lock()
+            .expect("Pending mutex poisoned")
+            .insert(handle.id, (event_type.0, handle.clone(), info));
+    }
+
+    /// Forget an event once it has been published
+    pub(crate) fn remove(&self, id: ScheduleId) {
+        self.events
+            .lock()
+            .expect("Pending mutex poisoned")
+            .remove(&id);
+    }
+
+    /// Record when a recurring event is next due
+    pub(crate) fn reschedule(&self, id: ScheduleId, due: Instant) {
+        let mut events = self.events.lock().expect("Pending mutex poisoned");
+        if let Some((_, _, info)) = events.get_mut(&id) {
+            info.due = due;
+        }
+    }
+
+    /// The events still waiting, soonest first. Events cancelled through their handles are
+    /// forgotten.
+    pub(crate) fn list(&self) -> Vec<ScheduledInfo> {
+        let mut events = self.events.lock().expect("Pending mutex poisoned");
+        events.retain(|_, (_, handle, _)| !handle.is_cancelled());
+        let mut infos: Vec<ScheduledInfo> =
+            events.values().map(|(_, _, info)| info.clone()).collect();
+        infos.sort_by_key(|info| (info.due, info.id));
+        infos
+    }
+
+    /// Cancel the events matching `cancel`, returning how many were still waiting
+    pub(crate) fn cancel(&self, cancel: impl Fn(ScheduleId, TypeId) -> bool) -> usize {
+        let mut events = self.events.lock().expect("Pending mutex poisoned");
+        let mut cancelled = 0;
+        events.retain(|id, (event_type, handle, _)| {
+            if handle.is_cancelled() {
+                return false;
+            }
+            if !cancel(*id, *event_type) {
+                return true;
+            }
+            handle.cancel();
+            cancelled += 1;
+            false
+        });
+        cancelled
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A job waiting in the scheduler's queue
+struct Task {
+    due: Instant,
+    id: u64,
+    handle: ScheduleHandle,
+    job: Job,
+}
+
+// Tasks are ordered so that the BinaryHeap, which is a max-heap, pops the task that is due soonest.
+// Tasks due at the same instant run in the order they were scheduled.
+impl Ord for Task {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .due
+            .cmp(&self.due)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Task {}
+
+#[derive(Default)]
+struct Queue {
+    tasks: BinaryHeap<Task>,
+    next_id: u64,
+    shutdown: bool,
+}
+
+/// Runs jobs at a given instant on a dedicated timer thread
+pub(crate) struct Scheduler {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// Start the scheduler thread, with a stack of `stack_size` bytes if set
+    pub(crate) fn new(stack_size: Option<usize>) -> Self {
+        let queue: Arc<(Mutex<Queue>, Condvar)> = Arc::default();
+        let thread_queue = queue.clone();
+        let mut builder = thread::Builder::new().name(String::from("crier-scheduler"));
+        if let Some(stack_size) = stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        let thread = builder
+            .spawn(move || run(&thread_queue))
+            .expect("Failed to spawn scheduler thread");
+
+        Scheduler {
+            queue,
+            thread: Some(thread),
+        }
+    }
+
+    /// Run `job` on the timer thread once `due` has passed, unless it is cancelled first
+    pub(crate) fn schedule<F>(&self, due: Instant, job: F) -> ScheduleHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let handle = ScheduleHandle::new();
+        self.schedule_with_handle(due, handle.clone(), job);
+
+        handle
+    }
+
+    /// Run `job` on the timer thread once `due` has passed, unless `handle` is cancelled first.
+    /// Used to reschedule recurring jobs under the handle that was returned for the first run.
+    pub(crate) fn schedule_with_handle<F>(&self, due: Instant, handle: ScheduleHandle, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        push(&self.queue, due, handle, Box::new(job));
+    }
+
+    /// A handle that jobs can schedule more jobs with, which doesn't keep the scheduler running
+    pub(crate) fn timer(&self) -> Timer {
+        Timer(Arc::downgrade(&self.queue))
+    }
+}
+
+/// Schedules jobs on a Scheduler for as long as it is running
+#[derive(Clone)]
+pub(crate) struct Timer(Weak<(Mutex<Queue>, Condvar)>);
+
+impl Timer {
+    /// Run `job` on the timer thread once `due` has passed. Returns false, without running it, if
+    /// the scheduler has stopped.
+    pub(crate) fn schedule<F>(&self, due: Instant, job: F) -> bool
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match self.0.upgrade() {
+            Some(queue) => {
+                push(&queue, due, ScheduleHandle::new(), Box::new(job));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn push(queue: &(Mutex<Queue>, Condvar), due: Instant, handle: ScheduleHandle, job: Job) {
+    let (lock, condvar) = queue;
+    let mut queue = lock.lock().expect("Scheduler mutex poisoned");
+    let id = queue.next_id;
+    queue.next_id += 1;
+    queue.tasks.push(Task {
+        due,
+        id,
+        handle,
+        job,
+    });
+    condvar.notify_one();
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.queue;
+        if let Ok(mut queue) = lock.lock() {
+            queue.shutdown = true;
+        }
+        condvar.notify_one();
+
+        if let Some(thread) = self.thread.take() {
+            // the scheduler can be dropped from one of its own jobs if that job held the last
+            // reference to its Publisher, in which case the thread exits on its own
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn run(queue: &(Mutex<Queue>, Condvar)) {
+    let (lock, condvar) = queue;
+    let mut guard = lock.lock().expect("Scheduler mutex poisoned");
+    loop {
+        if guard.shutdown {
+            return;
+        }
+
+        let now = Instant::now();
+        match guard.tasks.peek() {
+            None => {
+                guard = condvar.wait(guard).expect("Scheduler mutex poisoned");
+            }
+            Some(task) if task.handle.is_cancelled() => {
+                guard.tasks.pop();
+            }
+            Some(task) if task.due > now => {
+                let timeout = task.due - now;
+                guard = condvar
+                    .wait_timeout(guard, timeout)
+                    .expect("Scheduler mutex poisoned")
+                    .0;
+            }
+            Some(_) => {
+                let task = guard.tasks.pop().expect("Task disappeared from queue");
+                drop(guard);
+                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(task.job));
+                guard = lock.lock().expect("Scheduler mutex poisoned");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    fn test_runs_jobs_in_due_order() {
+        let scheduler = Scheduler::new(None);
+        let (sender, receiver) = mpsc::channel();
+        let now = Instant::now();
+        for (delay, value) in [(30, 3), (10, 1), (20, 2)] {
+            let sender = sender.clone();
+            scheduler.schedule(now + Duration::from_millis(delay), move || {
+                sender.send(value).unwrap()
+            });
+        }
+        let received: Vec<i32> = receiver.iter().take(3).collect();
+        assert_eq!(received, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_cancelled_jobs_do_not_run() {
+        let scheduler = Scheduler::new(None);
+        let (sender, receiver) = mpsc::channel();
+        let cancelled_sender = sender.clone();
+        let now = Instant::now();
+        let handle = scheduler.schedule(now + Duration::from_millis(10), move || {
+            cancelled_sender.send("cancelled").unwrap()
+        });
+        scheduler.schedule(now + Duration::from_millis(20), move || {
+            sender.send("kept").unwrap()
+        });
+        handle.cancel();
+        assert!(handle.is_cancelled());
+        assert_eq!(receiver.recv().unwrap(), "kept");
+    }
+}
@@ -0,0 +1,100 @@
+use std::{any, panic::RefUnwindSafe, sync::Arc, time::Instant};
+
+use crate::{Delivery, DynEvent, envelope::Published};
+
+type LinkFilter = Arc<dyn Fn(&dyn DynEvent) -> bool + Send + Sync>;
+
+/// How a child Publisher created with `Publisher::child` is linked to its parent. By default
+/// events don't cross the link in either direction.
+#[derive(Clone, Default)]
+pub struct Link {
+    pub(crate) bubble_up: bool,
+    pub(crate) push_down: bool,
+    /// Whether the link is a bridge between unrelated Publishers rather than to a child
+    pub(crate) bridge: bool,
+    filter: Option<LinkFilter>,
+}
+
+impl Link {
+    /// Also publish events published on the child on its parent
+    pub fn bubble_up(mut self) -> Self {
+        self.bubble_up = true;
+        self
+    }
+
+    /// Also publish events published on the parent on the child
+    pub fn push_down(mut self) -> Self {
+        self.push_down = true;
+        self
+    }
+
+    /// Only let events for which `filter` returns true cross the link
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&dyn DynEvent) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// A link that forwards events for which `filter` returns true over a bridge
+    pub(crate) fn bridge<F>(filter: F) -> Self
+    where
+        F: Fn(&dyn DynEvent) -> bool + Send + Sync + 'static,
+    {
+        Link {
+            push_down: true,
+            bridge: true,
+            ..Link::default().filter(filter)
+        }
+    }
+
+    pub(crate) fn admits(&self, event: &dyn DynEvent) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(event))
+    }
+}
+
+/// An event that has crossed a Link from the Publisher it was published on. `UP` is true for
+/// events bubbled up from a child or forwarded over a bridge, which are never pushed down again,
+/// and false for events pushed down from a parent, which never bubble back up.
+pub(crate) struct Relayed<const UP: bool>(pub(crate) Arc<Published>);
+
+impl<const UP: bool> RefUnwindSafe for Relayed<UP> {}
+
+impl<const UP: bool> DynEvent for Relayed<UP> {
+    fn get_data(&self) -> &dyn any::Any {
+        self.0.get_data()
+    }
+
+    fn dyn_deadline(&self) -> Option<Instant> {
+        self.0.dyn_deadline()
+    }
+
+    fn dyn_partition_key(&self) -> Option<u64> {
+        self.0.dyn_partition_key()
+    }
+
+    fn delivery(&self) -> Delivery {
+        self.0.delivery()
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.0.type_name()
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    fn shared_data(&self) -> Option<Arc<dyn any::Any + Send + Sync>> {
+        self.0.shared_data()
+    }
+
+    fn into_shared(self: Box<Self>) -> Arc<dyn any::Any + Send + Sync> {
+        self.0.payload.clone()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn any::Any + Send> {
+        Box::new(self.0.payload.clone())
+    }
+}
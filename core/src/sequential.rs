@@ -0,0 +1,393 @@
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use core::{any, panic::RefUnwindSafe};
+
+use crate::{
+    Delivery, DynEvent, DynHandle, DynHandleMut, Event, FromEvent, Handler, TickSource,
+    timer::{Timer, Timers},
+};
+
+enum Subscriber {
+    Shared(Box<dyn DynHandle>),
+    Mut(Box<dyn DynHandleMut>),
+    /// A handler that only receives the latest event once `quiet` ticks pass without another
+    Debounced {
+        handler: Box<dyn DynHandle>,
+        quiet: u64,
+        pending: Option<(u64, Sequenced)>,
+    },
+}
+
+/// Publisher that runs every handler on the publishing thread, one after another in the order
+/// they subscribed. It doesn't need threads or `std`, so it is the Publisher to use with just
+/// `alloc` when the `std` feature is turned off, such as on embedded targets. Panics in handlers
+/// are not caught.
+/// # Examples
+/// ```
+/// use crier::{Event, SequentialPublisher};
+///
+/// #[derive(Clone, Event)]
+/// struct ButtonPressed(u8);
+///
+/// let mut publisher = SequentialPublisher::new();
+/// publisher.subscribe_with(|event: ButtonPressed| assert_eq!(event.0, 2));
+/// publisher.publish(ButtonPressed(2));
+/// ```
+#[derive(Default)]
+pub struct SequentialPublisher {
+    handlers: BTreeMap<usize, Subscriber>,
+    handler_count: usize,
+    ticks: Option<Box<dyn TickSource + Send + Sync>>,
+    timers: Timers,
+}
+
+impl SequentialPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a SequentialPublisher whose timers read the time from `ticks`
+    pub fn with_ticks<S>(ticks: S) -> Self
+    where
+        S: TickSource + Send + Sync + 'static,
+    {
+        SequentialPublisher {
+            ticks: Some(Box::new(ticks)),
+            ..Self::default()
+        }
+    }
+
+    /// Subscribe a handler to the publisher so that the handler receives all published events.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe<T>(&mut self, handler: T) -> usize
+    where
+        T: DynHandle + 'static,
+    {
+        self.insert(Subscriber::Shared(Box::new(handler)))
+    }
+
+    // Subscribe a closure to events of its input type.
+    // Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_with<T, F>(&mut self, handler: F) -> usize
+    where
+        T: FromEvent,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        self.subscribe(Handler::new(handler))
+    }
+
+    pub fn subscribe_mut<T>(&mut self, handler: T) -> usize
+    where
+        T: DynHandleMut + 'static,
+    {
+        self.insert(Subscriber::Mut(Box::new(handler)))
+    }
+
+    /// Subscribe a handler that only receives the latest of a burst of events, once `quiet`
+    /// ticks have passed without another. Events with `Delivery::Exclusive` are never debounced.
+    /// Returns the ID needed to `unsubscribe` the handler.
+    pub fn subscribe_debounced<T>(&mut self, handler: T, quiet: u64) -> usize
+    where
+        T: DynHandle + 'static,
+    {
+        self.insert(Subscriber::Debounced {
+            handler: Box::new(handler),
+            quiet,
+            pending: None,
+        })
+    }
+
+    pub fn unsubscribe(&mut self, id: usize) {
+        self.handlers.remove(&id);
+    }
+
+    /// Publish `event` once `delay` ticks have passed. Returns the ID needed to `cancel` it.
+    pub fn publish_after<T>(&mut self, event: T, delay: u64) -> usize
+    where
+        T: DynEvent,
+    {
+        self.schedule(delay, Timer::Once(Box::new(event)))
+    }
+
+    /// Publish an event made by `event_factory` every `period` ticks, starting `period` ticks
+    /// from now. Returns the ID needed to `cancel` it.
+    pub fn publish_every<T, F>(&mut self, mut event_factory: F, period: u64) -> usize
+    where
+        T: Event,
+        F: FnMut() -> T + Send + 'static,
+    {
+        let make = Box::new(move || Box::new(event_factory()) as Box<dyn DynEvent>);
+        self.schedule(period, Timer::Every { make, period })
+    }
+
+    /// Stop a timer set with `publish_after` or `publish_every` from firing again
+    pub fn cancel(&mut self, id: usize) {
+        self.timers.cancel(id);
+    }
+
+    /// Fire every timer and debounced handler that is due, in the order they are due. Call this
+    /// regularly, e.g. from the application's main loop. Does nothing without a TickSource.
+    pub fn poll(&mut self) {
+        let Some(now) = self.ticks.as_ref().map(|ticks| ticks.ticks()) else {
+            return;
+        };
+
+        while let Some((due, id, timer)) = self.timers.next_due(now) {
+            match timer {
+                Timer::Once(event) => self.publish(event),
+                Timer::Every { mut make, period } => {
+                    self.publish(make());
+                    self.timers
+                        .insert(due + period.max(1), id, Timer::Every { make, period });
+                }
+            }
+        }
+
+        for handler in self.handlers.values_mut() {
+            if let Subscriber::Debounced {
+                handler, pending, ..
+            } = handler
+                && pending.as_ref().is_some_and(|(due, _)| *due <= now)
+                && let Some((_, event)) = pending.take()
+            {
+                handler.dyn_handle(&event);
+            }
+        }
+    }
+
+    /// Publish an event to every subscribed handler in turn. Events with `Delivery::Exclusive` are
+    /// moved into the first handler that accepts them instead.
+    pub fn publish<T>(&mut self, event: T)
+    where
+        T: DynEvent,
+    {
+        if event.delivery() == Delivery::Exclusive {
+            let event = Box::new(event);
+            for handler in self.handlers.values_mut() {
+                match handler {
+                    Subscriber::Shared(handler) if handler.accepts(event.as_ref()) => {
+                        return handler.dyn_handle_owned(event.into_any());
+                    }
+                    Subscriber::Mut(handler) if handler.accepts(event.as_ref()) => {
+                        return handler.dyn_handle_mut_owned(event.into_any());
+                    }
+                    _ => {}
+                }
+            }
+            return;
+        }
+
+        let now = self.now();
+        let event = Sequenced::new(event);
+        for handler in self.handlers.values_mut() {
+            match handler {
+                Subscriber::Shared(handler) => handler.dyn_handle(&event),
+                Subscriber::Mut(handler) => handler.dyn_handle_mut(&event),
+                Subscriber::Debounced {
+                    handler,
+                    quiet,
+                    pending,
+                } => {
+                    if handler.accepts(&event) {
+                        *pending = Some((now + *quiet, event.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.ticks.as_ref().map_or(0, |ticks| ticks.ticks())
+    }
+
+    fn schedule(&mut self, delay: u64, timer: Timer) -> usize {
+        self.handler_count += 1;
+        self.timers
+            .insert(self.now() + delay, self.handler_count, timer);
+
+        self.handler_count
+    }
+
+    fn insert(&mut self, subscriber: Subscriber) -> usize {
+        self.handler_count += 1;
+        self.handlers.insert(self.handler_count, subscriber);
+
+        self.handler_count
+    }
+}
+
+/// An event being published by a SequentialPublisher. The event is kept behind an `Arc` so that
+/// handlers can share it without cloning it.
+#[derive(Clone)]
+struct Sequenced {
+    payload: Arc<dyn any::Any + Send + Sync>,
+    partition_key: Option<u64>,
+    delivery: Delivery,
+    type_name: &'static str,
+    size: usize,
+}
+
+impl RefUnwindSafe for Sequenced {}
+
+impl Sequenced {
+    fn new<T: DynEvent>(event: T) -> Self {
+        let partition_key = event.dyn_partition_key();
+        let delivery = event.delivery();
+        let type_name = event.type_name();
+        let size = event.size();
+        Sequenced {
+            payload: Box::new(event).into_shared(),
+            partition_key,
+            delivery,
+            type_name,
+            size,
+        }
+    }
+}
+
+impl DynEvent for Sequenced {
+    fn get_data(&self) -> &dyn any::Any {
+        self.payload.as_ref()
+    }
+
+    fn dyn_partition_key(&self) -> Option<u64> {
+        self.partition_key
+    }
+
+    fn delivery(&self) -> Delivery {
+        self.delivery
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn shared_data(&self) -> Option<Arc<dyn any::Any + Send + Sync>> {
+        Some(self.payload.clone())
+    }
+
+    fn into_shared(self: Box<Self>) -> Arc<dyn any::Any + Send + Sync> {
+        self.payload
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn any::Any + Send> {
+        Box::new(self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Owned};
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    };
+
+    #[derive(Clone)]
+    struct Tick(u32);
+    impl Event for Tick {}
+
+    struct Frame(u32);
+    impl Event for Frame {
+        const DELIVERY: Delivery = Delivery::Shared;
+    }
+
+    struct Buffer(u32);
+    impl Event for Buffer {
+        const DELIVERY: Delivery = Delivery::Exclusive;
+    }
+
+    #[test]
+    fn test_handlers_run_in_order_of_subscription() {
+        let mut publisher = SequentialPublisher::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |tick: Tick| received_clone.lock().unwrap().push(tick.0));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |frame: Arc<Frame>| {
+            received_clone.lock().unwrap().push(frame.0 * 10)
+        });
+        let received_clone = received.clone();
+        let id = publisher
+            .subscribe_with(move |tick: Tick| received_clone.lock().unwrap().push(tick.0 * 100));
+
+        publisher.publish(Tick(1));
+        publisher.publish(Frame(2));
+        publisher.unsubscribe(id);
+        publisher.publish(Tick(3));
+        assert_eq!(*received.lock().unwrap(), vec![1, 100, 20, 3]);
+    }
+
+    #[test]
+    fn test_exclusive_events_are_moved_into_the_first_accepting_handler() {
+        let mut publisher = SequentialPublisher::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        publisher.subscribe_with(|_tick: Tick| {});
+        for name in ["first", "second"] {
+            let received_clone = received.clone();
+            publisher.subscribe_with(move |buffer: Owned<Buffer>| {
+                received_clone.lock().unwrap().push((name, buffer.0.0))
+            });
+        }
+
+        publisher.publish(Buffer(4));
+        assert_eq!(*received.lock().unwrap(), vec![("first", 4)]);
+    }
+
+    #[test]
+    fn test_timers_fire_when_polled_after_their_tick() {
+        let now = Arc::new(AtomicU64::new(0));
+        let clock = now.clone();
+        let mut publisher = SequentialPublisher::with_ticks(move || clock.load(Ordering::SeqCst));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |tick: Tick| received_clone.lock().unwrap().push(tick.0));
+        let mut count = 0;
+        let every = publisher.publish_every(
+            move || {
+                count += 1;
+                Tick(count * 10)
+            },
+            4,
+        );
+        publisher.publish_after(Tick(1), 3);
+        let cancelled = publisher.publish_after(Tick(2), 3);
+        publisher.cancel(cancelled);
+
+        publisher.poll();
+        now.store(9, Ordering::SeqCst);
+        publisher.poll();
+        publisher.cancel(every);
+        now.store(20, Ordering::SeqCst);
+        publisher.poll();
+        assert_eq!(*received.lock().unwrap(), vec![1, 10, 20]);
+    }
+
+    #[test]
+    fn test_debounced_handlers_receive_the_latest_event_once_quiet() {
+        let now = Arc::new(AtomicU64::new(0));
+        let clock = now.clone();
+        let mut publisher = SequentialPublisher::with_ticks(move || clock.load(Ordering::SeqCst));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_debounced(
+            Handler::new(move |tick: Tick| received_clone.lock().unwrap().push(tick.0)),
+            5,
+        );
+
+        publisher.publish(Tick(1));
+        now.store(3, Ordering::SeqCst);
+        publisher.publish(Tick(2));
+        now.store(6, Ordering::SeqCst);
+        publisher.poll();
+        assert!(received.lock().unwrap().is_empty());
+        now.store(8, Ordering::SeqCst);
+        publisher.poll();
+        publisher.poll();
+        assert_eq!(*received.lock().unwrap(), vec![2]);
+    }
+}
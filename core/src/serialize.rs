@@ -0,0 +1,726 @@
+use std::{
+    any::{self, TypeId},
+    collections::HashMap,
+    error::Error,
+    fmt, io,
+    panic::RefUnwindSafe,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    BufferPool, Compressor, DictionaryCompression, DynEvent, DynHandle, Event, PooledBuffer,
+};
+
+/// An Event that can be serialized, e.g. to persist it or send it to another process
+pub trait SerializableEvent: Event + Serialize + DeserializeOwned {
+    /// Identifies the event's type once serialized. Must be unique among the event types
+    /// registered with an EventRegistry.
+    const TYPE_TAG: &'static str;
+}
+
+/// A serialized event: the type tag of the event along with its serialized payload
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerializedEvent {
+    pub tag: String,
+    pub payload: Vec<u8>,
+}
+
+/// Errors that can occur when serializing or deserializing events
+#[derive(Debug)]
+pub enum SerializeError {
+    /// The event's type has not been registered with the EventRegistry
+    UnregisteredType,
+    /// No event type has been registered with the given tag
+    UnknownTag(String),
+    /// The payload could not be encoded or decoded
+    Codec(serde_json::Error),
+    /// A custom Codec could not encode or decode the payload
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+    /// The checksum stored with a record doesn't match its contents, so it was corrupted in
+    /// storage or in transit
+    Corrupt { expected: u32, actual: u32 },
+    /// The payload didn't fit in the buffer it was being encoded into
+    BufferTooSmall { capacity: usize },
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeError::UnregisteredType => write!(f, "event type is not registered"),
+            SerializeError::UnknownTag(tag) => write!(f, "no event type registered for tag {tag}"),
+            SerializeError::Codec(e) => write!(f, "failed to encode or decode event: {e}"),
+            SerializeError::Custom(e) => write!(f, "failed to encode or decode event: {e}"),
+            SerializeError::Corrupt { expected, actual } => write!(
+                f,
+                "record is corrupt: checksum is {actual:08x} but should be {expected:08x}"
+            ),
+            SerializeError::BufferTooSmall { capacity } => {
+                write!(f, "payload doesn't fit in a buffer of {capacity} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SerializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerializeError::Codec(e) => Some(e),
+            SerializeError::Custom(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SerializeError {
+    fn from(e: serde_json::Error) -> Self {
+        SerializeError::Codec(e)
+    }
+}
+
+/// Published in place of a record that failed its integrity check, such as a bridge frame whose
+/// checksum doesn't match its contents. The record itself is skipped, since nothing in it can be
+/// trusted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorruptRecordSkipped {
+    /// The checksum stored with the record
+    pub expected: u32,
+    /// The checksum of the record's contents as received
+    pub actual: u32,
+}
+
+impl Event for CorruptRecordSkipped {}
+
+impl From<CorruptRecordSkipped> for SerializeError {
+    fn from(corrupt: CorruptRecordSkipped) -> Self {
+        SerializeError::Corrupt {
+            expected: corrupt.expected,
+            actual: corrupt.actual,
+        }
+    }
+}
+
+/// Returned by a bridge in place of an event whose serialized form is larger than the bridge's
+/// maximum event size. The event is dropped rather than sent, and counted by the
+/// `crier_bridge_events_oversized_total` metric when the `metrics` feature is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventTooLarge {
+    /// The bridge that dropped the event, e.g. `"mqtt"`
+    pub bridge: &'static str,
+    /// Type tag of the event
+    pub tag: String,
+    /// Size of the serialized event, in bytes
+    pub size: usize,
+    /// The bridge's maximum event size, in bytes
+    pub limit: usize,
+}
+
+impl EventTooLarge {
+    /// Check an event serialized for `bridge` against its maximum event size, counting it if it's
+    /// too large
+    pub(crate) fn check(
+        bridge: &'static str,
+        event: &SerializedEvent,
+        limit: usize,
+    ) -> Result<(), EventTooLarge> {
+        if event.payload.len() <= limit {
+            return Ok(());
+        }
+        let error = EventTooLarge {
+            bridge,
+            tag: event.tag.clone(),
+            size: event.payload.len(),
+            limit,
+        };
+        crate::telemetry::oversized(&error);
+        Err(error)
+    }
+}
+
+impl fmt::Display for EventTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} event of {} bytes exceeds the {} bridge's limit of {} bytes",
+            self.tag, self.size, self.bridge, self.limit
+        )
+    }
+}
+
+impl std::error::Error for EventTooLarge {}
+
+/// The CRC-32 of `bytes`, as used by zlib and Ethernet
+pub(crate) fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// Encodes and decodes events of type `T` in a format of its own, in place of the JSON that
+/// SerializableEvents are encoded as. Useful for types generated from other formats, like
+/// protobuf or flatbuffers, which can then cross bridges without being converted to a serde type
+/// first. Register one with `EventRegistry::register_with`.
+pub trait Codec<T>: Send + Sync + 'static {
+    fn encode(&self, event: &T) -> Result<Vec<u8>, SerializeError>;
+
+    fn decode(&self, payload: &[u8]) -> Result<T, SerializeError>;
+
+    /// Encode the event into `buffer`, returning the number of bytes written, or failing with
+    /// `SerializeError::BufferTooSmall` if it doesn't fit. Used when events are encoded into
+    /// buffers of the caller's, as by a PooledSerializingHandler. By default the event is encoded
+    /// with `encode` and copied in, so codecs that can write in place should override this.
+    fn encode_into(&self, event: &T, buffer: &mut [u8]) -> Result<usize, SerializeError> {
+        let payload = self.encode(event)?;
+        let capacity = buffer.len();
+        buffer
+            .get_mut(..payload.len())
+            .ok_or(SerializeError::BufferTooSmall { capacity })?
+            .copy_from_slice(&payload);
+        Ok(payload.len())
+    }
+}
+
+type SerializeFn = Arc<dyn Fn(&dyn any::Any) -> Result<Vec<u8>, SerializeError> + Send + Sync>;
+type SerializeIntoFn =
+    Arc<dyn Fn(&dyn any::Any, &mut [u8]) -> Result<usize, SerializeError> + Send + Sync>;
+type DeserializeFn = Arc<dyn Fn(&[u8]) -> Result<Box<dyn DynEvent>, SerializeError> + Send + Sync>;
+
+/// The set of event types that can be serialized and deserialized. Deserialized events can be
+/// published like any other event, and handlers receive them as their concrete types.
+#[derive(Default)]
+pub struct EventRegistry {
+    serializers: HashMap<TypeId, (&'static str, SerializeFn)>,
+    /// Serialize into buffers of the caller's, rather than ones of their own
+    buffered_serializers: HashMap<TypeId, SerializeIntoFn>,
+    deserializers: HashMap<&'static str, DeserializeFn>,
+}
+
+impl EventRegistry {
+    /// Register an event type so that it can be serialized and deserialized
+    pub fn register<T: SerializableEvent>(&mut self) -> &mut Self {
+        self.serializers.insert(
+            TypeId::of::<T>(),
+            (T::TYPE_TAG, Arc::new(serialize_payload::<T>)),
+        );
+        self.buffered_serializers
+            .insert(TypeId::of::<T>(), Arc::new(serialize_payload_into::<T>));
+        self.deserializers
+            .insert(T::TYPE_TAG, Arc::new(deserialize_payload::<T>));
+        self
+    }
+
+    /// Register an event type that is encoded and decoded by `codec` under `tag`, rather than
+    /// with serde. Registering a SerializableEvent this way overrides its usual encoding.
+    pub fn register_with<T, C>(&mut self, tag: &'static str, codec: C) -> &mut Self
+    where
+        T: Event,
+        C: Codec<T>,
+    {
+        let codec = Arc::new(codec);
+        let encoder = codec.clone();
+        let buffered_encoder = codec.clone();
+        self.buffered_serializers.insert(
+            TypeId::of::<T>(),
+            Arc::new(move |data: &dyn any::Any, buffer: &mut [u8]| {
+                let event = data
+                    .downcast_ref::<T>()
+                    .ok_or(SerializeError::UnregisteredType)?;
+                buffered_encoder.encode_into(event, buffer)
+            }),
+        );
+        self.serializers.insert(
+            TypeId::of::<T>(),
+            (
+                tag,
+                Arc::new(move |data: &dyn any::Any| {
+                    let event = data
+                        .downcast_ref::<T>()
+                        .ok_or(SerializeError::UnregisteredType)?;
+                    encoder.encode(event)
+                }),
+            ),
+        );
+        self.deserializers.insert(
+            tag,
+            Arc::new(move |payload: &[u8]| {
+                Ok(Box::new(codec.decode(payload)?) as Box<dyn DynEvent>)
+            }),
+        );
+        self
+    }
+
+    /// Type tags of every registered event type
+    pub fn tags(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.deserializers.keys().copied()
+    }
+
+    /// Type tags of every registered event type, along with the type they identify
+    pub(crate) fn tagged_types(&self) -> impl Iterator<Item = (&'static str, TypeId)> + '_ {
+        self.serializers
+            .iter()
+            .map(|(event_type, (tag, _))| (*tag, *event_type))
+    }
+
+    /// The type tag of the event's type, if it has been registered
+    pub(crate) fn tag_of(&self, event: &dyn DynEvent) -> Option<&'static str> {
+        self.serializers
+            .get(&event.get_data().type_id())
+            .map(|(tag, _)| *tag)
+    }
+
+    /// Whether the event's type has been registered
+    pub fn is_registered(&self, event: &dyn DynEvent) -> bool {
+        self.serializers.contains_key(&event.get_data().type_id())
+    }
+
+    /// Serialize any event whose type has been registered
+    pub fn serialize(&self, event: &dyn DynEvent) -> Result<SerializedEvent, SerializeError> {
+        let data = event.get_data();
+        let (tag, serialize) = self
+            .serializers
+            .get(&data.type_id())
+            .ok_or(SerializeError::UnregisteredType)?;
+
+        Ok(SerializedEvent {
+            tag: String::from(*tag),
+            payload: serialize(data)?,
+        })
+    }
+
+    /// Serialize the payload of any event whose type has been registered into `buffer`, rather
+    /// than a buffer of its own, returning the number of bytes written
+    pub fn serialize_into(
+        &self,
+        event: &dyn DynEvent,
+        buffer: &mut [u8],
+    ) -> Result<usize, SerializeError> {
+        let data = event.get_data();
+        let serialize = self
+            .buffered_serializers
+            .get(&data.type_id())
+            .ok_or(SerializeError::UnregisteredType)?;
+
+        serialize(data, buffer)
+    }
+
+    /// Deserialize an event whose type has been registered. The result can be passed straight to
+    /// `Publisher::publish`.
+    pub fn deserialize(
+        &self,
+        serialized: &SerializedEvent,
+    ) -> Result<Box<dyn DynEvent>, SerializeError> {
+        let deserialize = self
+            .deserializers
+            .get(serialized.tag.as_str())
+            .ok_or_else(|| SerializeError::UnknownTag(serialized.tag.clone()))?;
+
+        deserialize(&serialized.payload)
+    }
+}
+
+fn serialize_payload<T: SerializableEvent>(data: &dyn any::Any) -> Result<Vec<u8>, SerializeError> {
+    let event = data
+        .downcast_ref::<T>()
+        .ok_or(SerializeError::UnregisteredType)?;
+
+    Ok(serde_json::to_vec(event)?)
+}
+
+fn serialize_payload_into<T: SerializableEvent>(
+    data: &dyn any::Any,
+    buffer: &mut [u8],
+) -> Result<usize, SerializeError> {
+    let event = data
+        .downcast_ref::<T>()
+        .ok_or(SerializeError::UnregisteredType)?;
+
+    let capacity = buffer.len();
+    let mut cursor = io::Cursor::new(buffer);
+    // the only writing that can fail is running out of room in the buffer
+    serde_json::to_writer(&mut cursor, event).map_err(|e| {
+        if e.is_io() {
+            SerializeError::BufferTooSmall { capacity }
+        } else {
+            SerializeError::Codec(e)
+        }
+    })?;
+    Ok(cursor.position() as usize)
+}
+
+fn deserialize_payload<T: SerializableEvent>(
+    payload: &[u8],
+) -> Result<Box<dyn DynEvent>, SerializeError> {
+    let event: T = serde_json::from_slice(payload)?;
+
+    Ok(Box::new(event))
+}
+
+/// Handler that serializes every published event whose type is registered with its
+/// EventRegistry and passes it to a sink, e.g. to write it to disk or send it over the network.
+pub struct SerializingHandler {
+    registry: Arc<EventRegistry>,
+    sink: Box<dyn Fn(SerializedEvent) + Send + Sync>,
+}
+
+impl RefUnwindSafe for SerializingHandler {}
+
+impl SerializingHandler {
+    pub fn new<F>(registry: Arc<EventRegistry>, sink: F) -> Self
+    where
+        F: Fn(SerializedEvent) + Send + Sync + 'static,
+    {
+        SerializingHandler {
+            registry,
+            sink: Box::new(sink),
+        }
+    }
+
+    /// Compress the payloads passed to the sink with `compression`. The sink also receives the
+    /// dictionary record, and must keep every record in order for them to be decompressed.
+    pub fn with_compression<C: Compressor>(
+        mut self,
+        compression: DictionaryCompression<C>,
+    ) -> Self {
+        let sink = self.sink;
+        self.sink = Box::new(move |serialized| compression.compress(serialized, &sink));
+        self
+    }
+}
+
+impl DynHandle for SerializingHandler {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        if let Ok(serialized) = self.registry.serialize(event) {
+            (self.sink)(serialized)
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        self.registry.is_registered(event)
+    }
+}
+
+/// Like a SerializingHandler, but encodes each payload straight into a buffer lent out by a
+/// BufferPool, and passes it to the sink along with the event's type tag. The caller allocates
+/// the buffers, so handlers feeding GPUs or DMA engines can have payloads land in aligned or
+/// pinned memory without copying them, and nothing is allocated for each event. Events that don't
+/// fit in a buffer, or arrive while every buffer is in use, aren't passed on, and fail with
+/// `SerializeError::BufferTooSmall` or `BuffersExhausted`.
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use crier::{BufferPool, Event, EventRegistry, Publisher, PooledSerializingHandler, SerializableEvent};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Clone, Event, Serialize, Deserialize)]
+/// struct Vertex(f32, f32);
+/// impl SerializableEvent for Vertex {
+///     const TYPE_TAG: &'static str = "vertex";
+/// }
+///
+/// /// Aligned for the device the payloads are uploaded to
+/// #[repr(align(64))]
+/// struct Staging([u8; 256]);
+/// impl AsRef<[u8]> for Staging {
+///     fn as_ref(&self) -> &[u8] {
+///         &self.0
+///     }
+/// }
+/// impl AsMut<[u8]> for Staging {
+///     fn as_mut(&mut self) -> &mut [u8] {
+///         &mut self.0
+///     }
+/// }
+///
+/// let mut registry = EventRegistry::default();
+/// registry.register::<Vertex>();
+/// let pool = Arc::new(BufferPool::new([Staging([0; 256]), Staging([0; 256])]));
+/// let mut publisher = Publisher::default();
+/// publisher.subscribe(PooledSerializingHandler::new(Arc::new(registry), pool, |tag, buffer| {
+///     assert_eq!(tag, "vertex");
+///     assert_eq!(buffer.bytes(), b"[1.0,2.0]");
+///     // upload, then drop the buffer to hand it back to the pool
+/// }));
+/// publisher.publish(Vertex(1.0, 2.0)).unwrap();
+/// ```
+pub struct PooledSerializingHandler<B> {
+    registry: Arc<EventRegistry>,
+    pool: Arc<BufferPool<B>>,
+    sink: Box<dyn Fn(&'static str, PooledBuffer<B>) + Send + Sync>,
+}
+
+impl<B> RefUnwindSafe for PooledSerializingHandler<B> {}
+
+impl<B: AsMut<[u8]> + AsRef<[u8]>> PooledSerializingHandler<B> {
+    pub fn new<F>(registry: Arc<EventRegistry>, pool: Arc<BufferPool<B>>, sink: F) -> Self
+    where
+        F: Fn(&'static str, PooledBuffer<B>) + Send + Sync + 'static,
+    {
+        PooledSerializingHandler {
+            registry,
+            pool,
+            sink: Box::new(sink),
+        }
+    }
+}
+
+impl<B: AsMut<[u8]> + AsRef<[u8]> + Send + 'static> DynHandle for PooledSerializingHandler<B> {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(tag) = self.registry.tag_of(event) else {
+            return Ok(());
+        };
+        let mut buffer = self.pool.take()?;
+        buffer.fill(|bytes| self.registry.serialize_into(event, bytes))?;
+        (self.sink)(tag, buffer);
+        Ok(())
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        self.registry.is_registered(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuffersExhausted, Handler, Publisher};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Scored {
+        player: String,
+        points: u32,
+    }
+    impl Event for Scored {}
+    impl SerializableEvent for Scored {
+        const TYPE_TAG: &'static str = "scored";
+    }
+
+    #[derive(Clone)]
+    struct Unregistered;
+    impl Event for Unregistered {}
+
+    fn registry() -> EventRegistry {
+        let mut registry = EventRegistry::default();
+        registry.register::<Scored>();
+        registry
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let registry = registry();
+        let event = Scored {
+            player: String::from("ferris"),
+            points: 3,
+        };
+
+        let serialized = registry.serialize(&event).unwrap();
+        assert_eq!(serialized.tag, "scored");
+
+        let deserialized = registry.deserialize(&serialized).unwrap();
+        assert_eq!(
+            deserialized.get_data().downcast_ref::<Scored>(),
+            Some(&event)
+        );
+    }
+
+    /// Stands in for a type generated from a foreign format, which can't derive Serialize
+    struct Packet(u16);
+    impl Event for Packet {}
+
+    struct BigEndian;
+
+    impl Codec<Packet> for BigEndian {
+        fn encode(&self, event: &Packet) -> Result<Vec<u8>, SerializeError> {
+            Ok(event.0.to_be_bytes().to_vec())
+        }
+
+        fn decode(&self, payload: &[u8]) -> Result<Packet, SerializeError> {
+            let bytes = payload
+                .try_into()
+                .map_err(|e: std::array::TryFromSliceError| SerializeError::Custom(e.into()))?;
+            Ok(Packet(u16::from_be_bytes(bytes)))
+        }
+    }
+
+    #[test]
+    fn test_round_trip_with_custom_codec() {
+        let mut registry = registry();
+        registry.register_with("packet", BigEndian);
+
+        let serialized = registry.serialize(&Packet(258)).unwrap();
+        assert_eq!(serialized.tag, "packet");
+        assert_eq!(serialized.payload, vec![1, 2]);
+        let deserialized = registry.deserialize(&serialized).unwrap();
+        assert_eq!(
+            deserialized
+                .get_data()
+                .downcast_ref::<Packet>()
+                .map(|packet| packet.0),
+            Some(258)
+        );
+
+        let truncated = SerializedEvent {
+            tag: String::from("packet"),
+            payload: vec![1],
+        };
+        assert!(matches!(
+            registry.deserialize(&truncated),
+            Err(SerializeError::Custom(_))
+        ));
+    }
+
+    #[test]
+    fn test_custom_codecs_encode_into_buffers() {
+        let mut registry = registry();
+        registry.register_with("packet", BigEndian);
+
+        let mut buffer = [0; 4];
+        assert_eq!(
+            registry.serialize_into(&Packet(258), &mut buffer).unwrap(),
+            2
+        );
+        assert_eq!(buffer, [1, 2, 0, 0]);
+        assert!(matches!(
+            registry.serialize_into(&Packet(258), &mut [0; 1]),
+            Err(SerializeError::BufferTooSmall { capacity: 1 })
+        ));
+    }
+
+    #[repr(align(64))]
+    struct Aligned([u8; 64]);
+    impl AsRef<[u8]> for Aligned {
+        fn as_ref(&self) -> &[u8] {
+            &self.0
+        }
+    }
+    impl AsMut<[u8]> for Aligned {
+        fn as_mut(&mut self) -> &mut [u8] {
+            &mut self.0
+        }
+    }
+
+    #[test]
+    fn test_pooled_serializing_handler_encodes_into_the_pools_buffers() {
+        let pool = Arc::new(BufferPool::new([Aligned([0; 64]), Aligned([0; 64])]));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe(PooledSerializingHandler::new(
+            Arc::new(registry()),
+            pool.clone(),
+            move |tag, buffer| received_clone.lock().unwrap().push((tag, buffer)),
+        ));
+        let scored = |points| Scored {
+            player: String::from("ferris"),
+            points,
+        };
+
+        publisher.try_publish(scored(1)).unwrap();
+        publisher.try_publish(scored(2)).unwrap();
+        {
+            let received = received.lock().unwrap();
+            let (tag, buffer) = &received[0];
+            assert_eq!(*tag, "scored");
+            assert_eq!(
+                serde_json::from_slice::<Scored>(buffer.bytes()).unwrap(),
+                scored(1)
+            );
+            assert_eq!(buffer.bytes().as_ptr() as usize % 64, 0);
+        }
+
+        // both buffers are still held by the sink
+        let errors = publisher.try_publish(scored(3)).unwrap_err();
+        assert_eq!(errors.failures[0].downcast_ref(), Some(&BuffersExhausted));
+        received.lock().unwrap().clear();
+        assert_eq!(pool.available(), 2);
+        publisher.try_publish(scored(4)).unwrap();
+
+        let long = Scored {
+            player: "x".repeat(64),
+            points: 5,
+        };
+        let errors = publisher.try_publish(long).unwrap_err();
+        assert!(matches!(
+            errors.failures[0].downcast_ref(),
+            Some(SerializeError::BufferTooSmall { capacity: 64 })
+        ));
+    }
+
+    #[test]
+    fn test_checksum_matches_crc32() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_unregistered_types_and_tags_are_errors() {
+        let registry = registry();
+        assert!(matches!(
+            registry.serialize(&Unregistered),
+            Err(SerializeError::UnregisteredType)
+        ));
+        let unknown = SerializedEvent {
+            tag: String::from("unknown"),
+            payload: Vec::new(),
+        };
+        assert!(matches!(
+            registry.deserialize(&unknown),
+            Err(SerializeError::UnknownTag(tag)) if tag == "unknown"
+        ));
+    }
+
+    #[test]
+    fn test_deserialized_events_reach_typed_handlers() {
+        let registry = registry();
+        let serialized = registry
+            .serialize(&Scored {
+                player: String::from("ferris"),
+                points: 1,
+            })
+            .unwrap();
+
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe(Handler::new(move |event: Scored| {
+            received_clone.lock().unwrap().push(event.points)
+        }));
+        let _ = publisher.publish(registry.deserialize(&serialized).unwrap());
+
+        assert_eq!(*received.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_serializing_handler_only_serializes_registered_events() {
+        let serialized = Arc::new(Mutex::new(Vec::new()));
+        let serialized_clone = serialized.clone();
+        let mut publisher = Publisher::default();
+        publisher.subscribe(SerializingHandler::new(
+            Arc::new(registry()),
+            move |event| serialized_clone.lock().unwrap().push(event.tag),
+        ));
+
+        let _ = publisher.publish(Unregistered);
+        let _ = publisher.publish(Scored {
+            player: String::from("ferris"),
+            points: 2,
+        });
+
+        assert_eq!(*serialized.lock().unwrap(), vec![String::from("scored")]);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    any::Any,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{DynHandle, publisher::HandlerType, transform::Transform};
+
+/// Source of the IDs that tell Publishers apart
+static NEXT_PUBLISHER: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies the Publisher a subscription was made with. Every Publisher gets a new one, while
+/// handles to the same Publisher share it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct PublisherId(u64);
+
+impl Default for PublisherId {
+    fn default() -> Self {
+        PublisherId(NEXT_PUBLISHER.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Identifies a subscription, for unsubscribing it or changing how it receives events. Only the
+/// Publisher that made the subscription accepts its ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId {
+    publisher: u64,
+    id: usize,
+}
+
+impl SubscriptionId {
+    pub(crate) fn new(publisher: PublisherId, id: usize) -> Self {
+        SubscriptionId {
+            publisher: publisher.0,
+            id,
+        }
+    }
+
+    /// The ID of the subscription within `publisher`, if it was made with that Publisher
+    pub(crate) fn local(self, publisher: PublisherId) -> Result<usize, UnsubscribeError> {
+        if self.publisher == publisher.0 {
+            Ok(self.id)
+        } else {
+            Err(UnsubscribeError::WrongPublisher)
+        }
+    }
+}
+
+/// What a subscription handed to its Publisher
+pub(crate) enum Removed {
+    Handler(Arc<HandlerType>),
+    Transform(Transform),
+}
+
+/// A subscription removed by `Publisher::unsubscribe`, along with its handler, so that its state
+/// can be inspected or it can be subscribed again with `Publisher::resubscribe`
+pub struct Unsubscribed {
+    pub(crate) removed: Removed,
+    pub(crate) typed: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Unsubscribed {
+    /// The handler, unless it was a kind that isn't a DynHandle, like the closures of
+    /// `subscribe_metadata` or `transform`
+    pub fn handler(&self) -> Option<Arc<dyn DynHandle>> {
+        match &self.removed {
+            Removed::Handler(handler) => handler.dyn_handle().cloned(),
+            Removed::Transform(_) => None,
+        }
+    }
+
+    /// The handler as its own type `H`, if it was subscribed with `subscribe`. Handlers
+    /// subscribed with `subscribe_mut` are held in a Mutex, so ask for `Mutex<H>` instead.
+    pub fn downcast<H: Any + Send + Sync>(&self) -> Option<Arc<H>> {
+        self.typed.clone()?.downcast().ok()
+    }
+}
+
+/// Describes a subscription, for working out which handlers an event will reach
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandlerInfo {
+    pub id: SubscriptionId,
+    /// How the handler was subscribed, e.g. "sync" for `subscribe` or "topic" for `subscribe_topic`
+    pub kind: &'static str,
+    /// Name of the event type the handler takes, if it only takes one
+    pub event_type: Option<&'static str>,
+    /// Describes the handler, by default with the name of its type
+    pub label: &'static str,
+}
+
+/// Reasons a subscription can't be unsubscribed, or its settings changed, by the ID it was given
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnsubscribeError {
+    /// The subscription has already been unsubscribed
+    Stale,
+    /// The ID belongs to a subscription made with a different Publisher
+    WrongPublisher,
+}
+
+impl fmt::Display for UnsubscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsubscribeError::Stale => write!(f, "subscription has already been unsubscribed"),
+            UnsubscribeError::WrongPublisher => {
+                write!(f, "subscription was made with a different publisher")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnsubscribeError {}
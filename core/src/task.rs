@@ -0,0 +1,72 @@
+use std::{future::Future, marker::PhantomData, panic::RefUnwindSafe, sync::Arc};
+
+use tokio::runtime::Handle;
+
+use crate::{DynEvent, DynHandle, FromEvent, wait::InFlight};
+
+/// Handler subscribed with `Publisher::subscribe_async`, which runs the futures its closure
+/// returns as tasks on the tokio runtime it was subscribed from
+pub(crate) struct AsyncHandler<T, F> {
+    handle: F,
+    runtime: Handle,
+    in_flight: Arc<InFlight>,
+    event: PhantomData<fn(T)>,
+}
+
+impl<T, F> RefUnwindSafe for AsyncHandler<T, F> {}
+
+impl<T, F, Fut> AsyncHandler<T, F>
+where
+    T: FromEvent,
+    F: Fn(T) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    pub(crate) fn new(handle: F, runtime: Handle, in_flight: Arc<InFlight>) -> Self {
+        AsyncHandler {
+            handle,
+            runtime,
+            in_flight,
+            event: PhantomData,
+        }
+    }
+
+    /// Run the handler's future for `event` as a task, counting it as in flight until it is done
+    fn spawn(&self, event: T) {
+        let guard = self.in_flight.start();
+        let task = self.runtime.spawn((self.handle)(event));
+        self.runtime.spawn(async move {
+            if let Err(e) = task.await
+                && e.is_panic()
+            {
+                guard.fail(e.into_panic());
+            }
+        });
+    }
+}
+
+impl<T, F, Fut> DynHandle for AsyncHandler<T, F>
+where
+    T: FromEvent,
+    F: Fn(T) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        if let Some(event) = T::from_event(event) {
+            self.spawn(event);
+        }
+    }
+
+    fn dyn_handle_owned(&self, event: Box<dyn std::any::Any + Send>) {
+        if let Some(event) = T::from_owned(event) {
+            self.spawn(event);
+        }
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        event.get_data().is::<T::Source>()
+    }
+
+    fn accepts_type(&self, event_type: std::any::TypeId) -> bool {
+        event_type == std::any::TypeId::of::<T::Source>()
+    }
+}
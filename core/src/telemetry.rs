@@ -0,0 +1,509 @@
+//! Reports each publish, and each handler's run over the events published, to the `tracing` crate
+//! when the `tracing` feature is enabled, and to the `metrics` crate when the `metrics` feature
+//! is. Publishes get a `publish` span, and handlers a `handler` span inside it, each recording the
+//! event type, how long it took and how it ended. Handler spans are made on the publishing thread,
+//! so they are children of the publish span whichever thread the handler then runs on. The
+//! metrics are counters of the events published by type, of handler runs and of handler failures,
+//! and a histogram of how long handlers take, along with a counter of the events bridges drop for
+//! being too large. Without either feature, nothing is recorded.
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use std::time::{Duration, Instant};
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use crate::platform;
+
+/// How a publish, or a handler's run, ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Ok,
+    /// A fallible handler returned an error, or a poisoned handler was skipped
+    Failed,
+    Panicked,
+}
+
+impl Outcome {
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Failed => "failed",
+            Outcome::Panicked => "panicked",
+        }
+    }
+}
+
+/// Observes a publish from the moment its events are taken until their handlers have finished
+pub(crate) struct PublishTelemetry {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    started: Option<Instant>,
+    #[cfg(feature = "tracing")]
+    events: usize,
+}
+
+impl PublishTelemetry {
+    /// Start observing a publish, entering its span on the current thread until it finishes
+    pub(crate) fn start() -> Self {
+        PublishTelemetry {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "publish",
+                event_type = tracing::field::Empty,
+                events = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+                outcome = tracing::field::Empty,
+            )
+            .entered(),
+            #[cfg(feature = "tracing")]
+            started: platform::instant(),
+            #[cfg(feature = "tracing")]
+            events: 0,
+        }
+    }
+
+    /// Note an event being published. The span is labelled with the type of the first.
+    #[cfg_attr(
+        not(any(feature = "tracing", feature = "metrics")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn event(&mut self, event_type: &'static str) {
+        #[cfg(feature = "tracing")]
+        {
+            if self.events == 0 {
+                self.span.record("event_type", event_type);
+            }
+            self.events += 1;
+        }
+        #[cfg(feature = "metrics")]
+        metrics::counter!("crier_events_published_total", "event_type" => event_type).increment(1);
+    }
+
+    /// Finish observing the publish, given what its handlers reported
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn finish(self, errors: &[Box<dyn std::any::Any + Send + 'static>]) {
+        #[cfg(feature = "tracing")]
+        {
+            let outcome = match errors.is_empty() {
+                true => Outcome::Ok,
+                false => Outcome::Panicked,
+            };
+            self.span.record("events", self.events);
+            self.span.record("outcome", outcome.as_str());
+            if let Some(elapsed) = elapsed(self.started) {
+                self.span.record("duration_us", elapsed.as_micros() as u64);
+            }
+        }
+    }
+}
+
+/// Observes one handler's run over the events of a publish
+pub(crate) struct HandlerTelemetry {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "metrics")]
+    handler: &'static str,
+    #[cfg(feature = "metrics")]
+    event_type: Option<&'static str>,
+}
+
+impl HandlerTelemetry {
+    /// Describe a handler's run ahead of it, on the publishing thread, so that its span is a
+    /// child of the publish span even if the handler runs on another thread
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn new(
+        handler: &'static str,
+        event_type: Option<&'static str>,
+        events: usize,
+    ) -> Self {
+        HandlerTelemetry {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "handler",
+                handler,
+                event_type,
+                events,
+                duration_us = tracing::field::Empty,
+                outcome = tracing::field::Empty,
+            ),
+            #[cfg(feature = "metrics")]
+            handler,
+            #[cfg(feature = "metrics")]
+            event_type,
+        }
+    }
+
+    /// Run the handler inside its span, recording how long it took and its outcome
+    pub(crate) fn observe<R>(
+        self,
+        run: impl FnOnce() -> R,
+        outcome: impl FnOnce(&R) -> Outcome,
+    ) -> R {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        {
+            let started = platform::instant();
+            #[cfg(feature = "tracing")]
+            let result = self.span.in_scope(run);
+            #[cfg(not(feature = "tracing"))]
+            let result = run();
+            let outcome = outcome(&result);
+            let elapsed = elapsed(started);
+            #[cfg(feature = "tracing")]
+            {
+                self.span.record("outcome", outcome.as_str());
+                if let Some(elapsed) = elapsed {
+                    self.span.record("duration_us", elapsed.as_micros() as u64);
+                }
+            }
+            #[cfg(feature = "metrics")]
+            self.count(outcome, elapsed);
+            result
+        }
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        {
+            let _ = outcome;
+            run()
+        }
+    }
+
+    /// Count the handler's run, and its failure if it failed, and record how long it took
+    #[cfg(feature = "metrics")]
+    fn count(&self, outcome: Outcome, elapsed: Option<Duration>) {
+        let event_type = self.event_type.unwrap_or("");
+        metrics::counter!(
+            "crier_handler_invocations_total",
+            "handler" => self.handler,
+            "event_type" => event_type,
+        )
+        .increment(1);
+        if outcome != Outcome::Ok {
+            metrics::counter!(
+                "crier_handler_failures_total",
+                "handler" => self.handler,
+                "event_type" => event_type,
+                "outcome" => outcome.as_str(),
+            )
+            .increment(1);
+        }
+        if let Some(elapsed) = elapsed {
+            metrics::histogram!(
+                "crier_handler_duration_seconds",
+                "handler" => self.handler,
+                "event_type" => event_type,
+            )
+            .record(elapsed.as_secs_f64());
+        }
+    }
+}
+
+/// Count an event a bridge dropped for being larger than its maximum event size
+#[cfg(feature = "serde")]
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn oversized(error: &crate::EventTooLarge) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(
+        "crier_bridge_events_oversized_total",
+        "bridge" => error.bridge,
+        "event_type" => error.tag.clone(),
+    )
+    .increment(1);
+}
+
+/// Time since `started`, if the target can tell
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+fn elapsed(started: Option<Instant>) -> Option<Duration> {
+    Some(platform::instant()?.duration_since(started?))
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use crate::{DispatchStrategy, Event, Handler, Publisher};
+    use std::{
+        collections::HashMap,
+        fmt,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+    use tracing::{
+        Event as TracingEvent, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    #[derive(Clone)]
+    struct Click;
+    impl Event for Click {}
+
+    /// The name, parent and fields of a span
+    #[derive(Debug, Default)]
+    struct Recorded {
+        name: &'static str,
+        parent: Option<u64>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    impl Visit for Recorded {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.fields.insert(field.name(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields.insert(field.name(), String::from(value));
+        }
+    }
+
+    /// Keeps every span made while it is the default subscriber
+    #[derive(Clone, Default)]
+    struct Recorder {
+        next: Arc<AtomicU64>,
+        spans: Arc<Mutex<HashMap<u64, Recorded>>>,
+        current: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let id = self.next.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut recorded = Recorded {
+                name: attributes.metadata().name(),
+                parent: self.current.lock().unwrap().last().copied(),
+                ..Default::default()
+            };
+            attributes.record(&mut recorded);
+            self.spans.lock().unwrap().insert(id, recorded);
+            Id::from_u64(id)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            if let Some(recorded) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+                values.record(recorded);
+            }
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &TracingEvent<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.current.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.current.lock().unwrap().pop();
+        }
+    }
+
+    #[test]
+    fn test_publishes_and_handlers_are_traced() {
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Sequential);
+        publisher.subscribe_named("button", Handler::new(|_click: Click| {}));
+        publisher.subscribe_named("broken", Handler::new(|_click: Click| panic!("broken")));
+        let recorder = Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            assert!(publisher.publish(Click).is_err());
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let (publish_id, publish) = spans
+            .iter()
+            .find(|(_, span)| span.name == "publish")
+            .unwrap();
+        assert_eq!(publish.fields["event_type"], std::any::type_name::<Click>());
+        assert_eq!(publish.fields["events"], "1");
+        assert_eq!(publish.fields["outcome"], "panicked");
+        assert!(publish.fields.contains_key("duration_us"));
+
+        let mut handlers: Vec<&Recorded> = spans
+            .values()
+            .filter(|span| span.name == "handler")
+            .collect();
+        handlers.sort_by_key(|span| span.fields["handler"].clone());
+        let outcomes: Vec<(&str, &str)> = handlers
+            .iter()
+            .map(|span| {
+                (
+                    span.fields["handler"].as_str(),
+                    span.fields["outcome"].as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(outcomes, [("broken", "panicked"), ("button", "ok")]);
+        assert!(handlers.iter().all(|span| span.parent == Some(*publish_id)));
+        assert!(
+            handlers
+                .iter()
+                .all(|span| span.fields.contains_key("duration_us"))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod metrics_tests {
+    use crate::{DispatchStrategy, Event, Handler, Publisher};
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct Click;
+    impl Event for Click {}
+
+    /// What a counter or histogram was given, by its name and labels
+    #[derive(Clone, Default)]
+    struct Recorded {
+        counts: Arc<Mutex<Vec<(String, u64)>>>,
+        samples: Arc<Mutex<Vec<(String, f64)>>>,
+    }
+
+    /// The name and labels of a metric, as `name{label=value,..}`
+    fn describe(key: &Key) -> String {
+        let labels: Vec<String> = key
+            .labels()
+            .map(|label| format!("{}={}", label.key(), label.value()))
+            .collect();
+        format!("{}{{{}}}", key.name(), labels.join(","))
+    }
+
+    struct Handle {
+        key: String,
+        recorded: Recorded,
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            let mut counts = self.recorded.counts.lock().unwrap();
+            counts.push((self.key.clone(), value));
+        }
+
+        fn absolute(&self, _value: u64) {}
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, value: f64) {
+            let mut samples = self.recorded.samples.lock().unwrap();
+            samples.push((self.key.clone(), value));
+        }
+    }
+
+    impl Recorded {
+        fn handle(&self, key: &Key) -> Arc<Handle> {
+            Arc::new(Handle {
+                key: describe(key),
+                recorded: self.clone(),
+            })
+        }
+
+        fn total(&self, key: &str) -> u64 {
+            let counts = self.counts.lock().unwrap();
+            counts
+                .iter()
+                .filter(|(k, _)| k == key)
+                .map(|(_, n)| n)
+                .sum()
+        }
+    }
+
+    impl Recorder for Recorded {
+        fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
+        }
+
+        fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+        fn describe_histogram(
+            &self,
+            _key: KeyName,
+            _unit: Option<Unit>,
+            _description: SharedString,
+        ) {
+        }
+
+        fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[test]
+    fn test_publishes_and_handlers_are_counted() {
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Sequential);
+        publisher.subscribe_named("button", Handler::new(|_click: Click| {}));
+        publisher.subscribe_named("broken", Handler::new(|_click: Click| panic!("broken")));
+        let recorded = Recorded::default();
+        let click = std::any::type_name::<Click>();
+
+        metrics::with_local_recorder(&recorded, || {
+            assert!(publisher.publish(Click).is_err());
+            assert!(publisher.publish_all(vec![Click, Click]).is_err());
+        });
+
+        let published = format!("crier_events_published_total{{event_type={click}}}");
+        assert_eq!(recorded.total(&published), 3);
+        for handler in ["button", "broken"] {
+            let invocations =
+                format!("crier_handler_invocations_total{{handler={handler},event_type={click}}}");
+            assert_eq!(recorded.total(&invocations), 2);
+        }
+        let failures = format!(
+            "crier_handler_failures_total{{handler=broken,event_type={click},outcome=panicked}}"
+        );
+        assert_eq!(recorded.total(&failures), 2);
+        assert_eq!(recorded.total(&failures.replace("broken", "button")), 0);
+
+        let samples = recorded.samples.lock().unwrap();
+        assert_eq!(samples.len(), 4);
+        assert!(samples.iter().all(|(key, seconds)| {
+            key.starts_with("crier_handler_duration_seconds") && *seconds >= 0.0
+        }));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_events_too_large_for_a_bridge_are_counted() {
+        use crate::{EventRegistry, SerializableEvent, net::Forwarder};
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Clone, Serialize, Deserialize)]
+        struct Upload(String);
+        impl Event for Upload {}
+        impl SerializableEvent for Upload {
+            const TYPE_TAG: &'static str = "upload";
+        }
+
+        let mut registry = EventRegistry::default();
+        registry.register::<Upload>();
+        // nothing is listening, but the event is dropped before the Forwarder connects
+        let forwarder = Forwarder::new("127.0.0.1:1", Arc::new(registry))
+            .unwrap()
+            .with_max_event_size(4);
+        let mut publisher = Publisher::with_strategy(DispatchStrategy::Sequential);
+        publisher.subscribe(forwarder);
+        let recorded = Recorded::default();
+
+        metrics::with_local_recorder(&recorded, || {
+            assert!(
+                publisher
+                    .publish(Upload(String::from("too large")))
+                    .is_err()
+            );
+        });
+
+        let oversized = "crier_bridge_events_oversized_total{bridge=net,event_type=upload}";
+        assert_eq!(recorded.total(oversized), 1);
+    }
+}
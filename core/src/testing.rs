@@ -0,0 +1,191 @@
+//! Property-based checks of the Publisher's dispatch invariants, for running against your own
+//! handler types.
+//!
+//! `ops` generates random sequences of subscribes, unsubscribes and publishes of `Probe` events,
+//! and `check_dispatch_invariants` plays them against a Publisher that runs its handlers
+//! sequentially, asserting that:
+//!
+//! - every handler receives every Probe published while it is subscribed, exactly once and in order
+//! - no handler receives a Probe published after it was unsubscribed
+//! - every subscription is given a unique ID, which unsubscribes it
+//!
+//! # Examples
+//! ```
+//! use crier::{Handler, testing::{Probe, Recorder, check_dispatch_invariants, ops}};
+//! use proptest::test_runner::TestRunner;
+//!
+//! TestRunner::default()
+//!     .run(&ops(32), |ops| {
+//!         check_dispatch_invariants(&ops, |publisher, recorder: Recorder| {
+//!             publisher.subscribe(Handler::new(move |probe: Probe| recorder.record(&probe)))
+//!         })
+//!     })
+//!     .unwrap();
+//! ```
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use proptest::{
+    arbitrary::any,
+    collection, prop_assert, prop_assert_eq, prop_oneof,
+    sample::Index,
+    strategy::{Just, Strategy},
+    test_runner::TestCaseError,
+};
+
+use crate::{DispatchStrategy, Event, Publisher, SubscriptionId};
+
+/// The event published by `check_dispatch_invariants`, numbered in the order it was published
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe(pub u64);
+
+impl Event for Probe {}
+
+/// Records the Probes a handler under test receives. The handler must call `record` with every
+/// Probe it handles.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    received: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Recorder {
+    pub fn record(&self, probe: &Probe) {
+        self.received
+            .lock()
+            .expect("Recorder mutex poisoned")
+            .push(probe.0);
+    }
+
+    fn received(&self) -> Vec<u64> {
+        self.received
+            .lock()
+            .expect("Recorder mutex poisoned")
+            .clone()
+    }
+}
+
+/// A step of a sequence played by `check_dispatch_invariants`
+#[derive(Clone, Debug)]
+pub enum Op {
+    /// Subscribe a new handler
+    Subscribe,
+    /// Unsubscribe one of the handlers still subscribed, if there are any
+    Unsubscribe(Index),
+    /// Publish the next Probe
+    Publish,
+}
+
+/// Generates sequences of up to `max_len` Ops
+pub fn ops(max_len: usize) -> impl Strategy<Value = Vec<Op>> {
+    let op = prop_oneof![
+        1 => Just(Op::Subscribe),
+        1 => any::<Index>().prop_map(Op::Unsubscribe),
+        2 => Just(Op::Publish),
+    ];
+    collection::vec(op, 0..=max_len)
+}
+
+/// Play `ops` against a sequential Publisher, subscribing each handler with `subscribe`, which
+/// must return the ID of the subscription, and check that the dispatch invariants hold
+pub fn check_dispatch_invariants<F>(ops: &[Op], mut subscribe: F) -> Result<(), TestCaseError>
+where
+    F: FnMut(&mut Publisher, Recorder) -> SubscriptionId,
+{
+    let mut publisher = Publisher::with_strategy(DispatchStrategy::Sequential);
+    let mut ids = HashSet::new();
+    let mut subscribed: Vec<(SubscriptionId, Recorder, Vec<u64>)> = Vec::new();
+    let mut unsubscribed = Vec::new();
+    let mut published = 0;
+
+    for op in ops {
+        match op {
+            Op::Subscribe => {
+                let recorder = Recorder::default();
+                let id = subscribe(&mut publisher, recorder.clone());
+                prop_assert!(ids.insert(id), "{:?} was given out twice", id);
+                subscribed.push((id, recorder, Vec::new()));
+            }
+            Op::Unsubscribe(index) => {
+                if !subscribed.is_empty() {
+                    let (id, recorder, expected) = subscribed.remove(index.index(subscribed.len()));
+                    prop_assert!(publisher.unsubscribe(id).is_ok(), "{:?} was lost", id);
+                    unsubscribed.push((id, recorder, expected));
+                }
+            }
+            Op::Publish => {
+                published += 1;
+                let _ = publisher.publish(Probe(published));
+                for (_, _, expected) in &mut subscribed {
+                    expected.push(published);
+                }
+            }
+        }
+    }
+
+    for (id, recorder, expected) in subscribed.iter().chain(&unsubscribed) {
+        prop_assert_eq!(
+            recorder.received(),
+            expected.clone(),
+            "handler {:?} received the wrong probes",
+            id
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{Handle, HandleMut, Handler};
+
+    struct Counting {
+        recorder: Recorder,
+    }
+
+    impl HandleMut for Counting {
+        type EventType = Probe;
+
+        fn handle_mut(&mut self, event: Probe) {
+            self.recorder.record(&event);
+        }
+    }
+
+    struct Shared(Recorder);
+
+    impl Handle for Shared {
+        type EventType = Arc<Probe>;
+
+        fn handle(&self, event: Arc<Probe>) {
+            self.0.record(&event);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_closure_handlers_hold_invariants(ops in ops(32)) {
+            check_dispatch_invariants(&ops, |publisher, recorder| {
+                publisher.subscribe(Handler::new(move |probe: Probe| recorder.record(&probe)))
+            })?;
+        }
+
+        #[test]
+        fn test_mut_handlers_hold_invariants(ops in ops(32)) {
+            check_dispatch_invariants(&ops, |publisher, recorder| {
+                publisher.subscribe_mut(Counting { recorder })
+            })?;
+        }
+
+        #[test]
+        fn test_shared_handlers_hold_invariants(ops in ops(32)) {
+            check_dispatch_invariants(&ops, |publisher, recorder| {
+                publisher.subscribe(Shared(recorder))
+            })?;
+        }
+    }
+}
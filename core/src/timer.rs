@@ -0,0 +1,55 @@
+use alloc::{boxed::Box, collections::BTreeMap};
+
+use crate::DynEvent;
+
+/// Where a SequentialPublisher's timers get the time from, such as a hardware timer or a tick
+/// counter incremented by an interrupt. Ticks can be of whatever length suits the platform, as
+/// long as they never go backwards.
+pub trait TickSource {
+    fn ticks(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> TickSource for F {
+    fn ticks(&self) -> u64 {
+        self()
+    }
+}
+
+pub(crate) enum Timer {
+    /// Publish an event once
+    Once(Box<dyn DynEvent>),
+    /// Publish a new event every `period` ticks
+    Every {
+        make: Box<dyn FnMut() -> Box<dyn DynEvent> + Send>,
+        period: u64,
+    },
+}
+
+/// Timers waiting for their tick, earliest first
+#[derive(Default)]
+pub(crate) struct Timers {
+    /// Keyed by the tick each timer is due at, then by its ID so timers due together fire in the
+    /// order they were set
+    pending: BTreeMap<(u64, usize), Timer>,
+}
+
+impl Timers {
+    pub(crate) fn insert(&mut self, due: u64, id: usize, timer: Timer) {
+        self.pending.insert((due, id), timer);
+    }
+
+    pub(crate) fn cancel(&mut self, id: usize) {
+        self.pending.retain(|(_, timer_id), _| *timer_id != id);
+    }
+
+    /// Take the earliest timer that is due by `now`, along with its due tick and ID
+    pub(crate) fn next_due(&mut self, now: u64) -> Option<(u64, usize, Timer)> {
+        let entry = self.pending.first_entry()?;
+        let (due, id) = *entry.key();
+        if due > now {
+            return None;
+        }
+
+        Some((due, id, entry.remove()))
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet};
+
+/// The topic filters that subscriptions made with `Publisher::subscribe_topic` listen to, stored
+/// as a tree of `/`-separated levels so that matching a topic only visits the levels it names,
+/// however many filters there are. A `+` level matches any single level and a `#` level, which
+/// must come last, matches the rest of the topic, including none of it.
+#[derive(Default)]
+pub(crate) struct TopicTree {
+    root: Node,
+}
+
+#[derive(Default)]
+struct Node {
+    children: HashMap<String, Node>,
+    /// Subscriptions whose filter ends at this level
+    ids: HashSet<usize>,
+}
+
+impl Node {
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.ids.is_empty()
+    }
+
+    fn remove(&mut self, levels: &[&str], id: usize) {
+        match levels.split_first() {
+            None => {
+                self.ids.remove(&id);
+            }
+            Some((level, rest)) => {
+                if let Some(child) = self.children.get_mut(*level) {
+                    child.remove(rest, id);
+                    if child.is_empty() {
+                        self.children.remove(*level);
+                    }
+                }
+            }
+        }
+    }
+
+    fn matches(&self, levels: &[&str], matched: &mut HashSet<usize>) {
+        if let Some(rest) = self.children.get("#") {
+            matched.extend(&rest.ids);
+        }
+
+        match levels.split_first() {
+            None => matched.extend(&self.ids),
+            Some((level, rest)) => {
+                if let Some(child) = self.children.get(*level) {
+                    child.matches(rest, matched);
+                }
+                if let Some(any) = self.children.get("+") {
+                    any.matches(rest, matched);
+                }
+            }
+        }
+    }
+}
+
+impl TopicTree {
+    pub(crate) fn insert(&mut self, filter: &str, id: usize) {
+        let node = filter.split('/').fold(&mut self.root, |node, level| {
+            node.children.entry(level.to_string()).or_default()
+        });
+        node.ids.insert(id);
+    }
+
+    pub(crate) fn remove(&mut self, filter: &str, id: usize) {
+        let levels: Vec<&str> = filter.split('/').collect();
+        self.root.remove(&levels, id);
+    }
+
+    /// IDs of the subscriptions whose filters match `topic`
+    pub(crate) fn matches(&self, topic: &str) -> HashSet<usize> {
+        let levels: Vec<&str> = topic.split('/').collect();
+        let mut matched = HashSet::new();
+        self.root.matches(&levels, &mut matched);
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> TopicTree {
+        let mut tree = TopicTree::default();
+        tree.insert("sensors/kitchen/temperature", 0);
+        tree.insert("sensors/#", 1);
+        tree.insert("ui/+/click", 2);
+        tree.insert("#", 3);
+        tree.insert("sensors/+", 4);
+        tree
+    }
+
+    #[test]
+    fn test_matches_exact_and_wildcard_filters() {
+        let tree = tree();
+        assert_eq!(
+            tree.matches("sensors/kitchen/temperature"),
+            HashSet::from([0, 1, 3])
+        );
+        assert_eq!(tree.matches("sensors/kitchen"), HashSet::from([1, 3, 4]));
+        assert_eq!(tree.matches("sensors"), HashSet::from([1, 3]));
+        assert_eq!(tree.matches("ui/button/click"), HashSet::from([2, 3]));
+        assert_eq!(tree.matches("ui/button/hover"), HashSet::from([3]));
+    }
+
+    #[test]
+    fn test_remove_prunes_filters() {
+        let mut tree = tree();
+        tree.remove("sensors/kitchen/temperature", 0);
+        tree.remove("#", 3);
+        assert_eq!(
+            tree.matches("sensors/kitchen/temperature"),
+            HashSet::from([1])
+        );
+        assert!(!tree.root.children.contains_key("#"));
+
+        tree.remove("sensors/#", 1);
+        tree.remove("sensors/+", 4);
+        assert!(!tree.root.children.contains_key("sensors"));
+    }
+}
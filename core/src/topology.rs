@@ -0,0 +1,64 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use crate::{HandlerInfo, SubscriptionId};
+
+/// Render which handlers take which event types, and which event types transforms derive from
+/// which, as a Graphviz DOT graph. Event types are boxes, handlers are ellipses labelled with
+/// their names, and transforms are diamonds. Handlers that don't say which types they take are
+/// drawn taking from a single node standing for every event.
+pub(crate) fn dot(
+    handlers: &[HandlerInfo],
+    derived: &BTreeMap<SubscriptionId, &'static str>,
+) -> String {
+    let mut event_types: BTreeSet<&str> = BTreeSet::new();
+    let mut every_event = false;
+    let mut subscriptions = String::new();
+    let mut edges = String::new();
+    for (index, info) in handlers.iter().enumerate() {
+        let node = format!("subscription{index}");
+        let shape = match info.kind {
+            "transform" => "diamond",
+            _ => "ellipse",
+        };
+        let _ = writeln!(
+            subscriptions,
+            "    {node} [shape={shape}, label=\"{}\"];",
+            escape(info.label)
+        );
+        match info.event_type {
+            Some(event_type) => {
+                event_types.insert(event_type);
+                let _ = writeln!(edges, "    \"{}\" -> {node};", escape(event_type));
+            }
+            None => {
+                every_event = true;
+                let _ = writeln!(edges, "    every_event -> {node};");
+            }
+        }
+        if let Some(derived) = derived.get(&info.id) {
+            event_types.insert(derived);
+            let _ = writeln!(edges, "    {node} -> \"{}\";", escape(derived));
+        }
+    }
+
+    let mut dot = String::from("digraph crier {\n    rankdir=LR;\n");
+    for event_type in event_types {
+        let escaped = escape(event_type);
+        let _ = writeln!(dot, "    \"{escaped}\" [shape=box, label=\"{escaped}\"];");
+    }
+    if every_event {
+        dot.push_str("    every_event [shape=box, style=dashed, label=\"every event\"];\n");
+    }
+    dot.push_str(&subscriptions);
+    dot.push_str(&edges);
+    dot.push_str("}\n");
+    dot
+}
+
+/// Escape a name for use in a quoted DOT string
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,193 @@
+use std::{
+    cell::Cell,
+    fmt::Write as _,
+    io,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::platform;
+
+/// Source of the numbers that tell threads apart in traces
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A small number identifying the current thread, which is stable for as long as it runs
+fn thread_number() -> u64 {
+    THREAD.with(|thread| {
+        if thread.get() == 0 {
+            thread.set(NEXT_THREAD.fetch_add(1, Ordering::Relaxed));
+        }
+        thread.get()
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    Begin,
+    End,
+}
+
+struct Record {
+    name: &'static str,
+    phase: Phase,
+    /// Microseconds since the trace started
+    timestamp: u64,
+    thread: u64,
+    events: usize,
+}
+
+/// Records when each handler starts and finishes running while it is set on a Publisher with
+/// `Publisher::set_trace`, and writes the records out in the Chrome trace event format, which
+/// `about://tracing` and Perfetto can load, so that how handlers were scheduled from one frame to
+/// the next can be inspected alongside other traces. Handlers are named by their labels, so
+/// subscribe them with `subscribe_named` for a readable trace. Nothing is recorded on targets
+/// without a clock, like `wasm32-unknown-unknown`.
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use crier::{ChromeTrace, Event, Publisher};
+///
+/// #[derive(Clone, Event)]
+/// struct Frame;
+///
+/// let mut publisher = Publisher::default();
+/// publisher.subscribe_named("render", crier::Handler::new(|_frame: Frame| {}));
+/// let trace = Arc::new(ChromeTrace::new());
+/// publisher.set_trace(Some(trace.clone()));
+/// let _ = publisher.publish(Frame);
+/// publisher.set_trace(None);
+///
+/// assert!(trace.to_json().contains("\"name\":\"render\""));
+/// ```
+pub struct ChromeTrace {
+    started: Option<Instant>,
+    records: Mutex<Vec<Record>>,
+}
+
+impl Default for ChromeTrace {
+    fn default() -> Self {
+        ChromeTrace::new()
+    }
+}
+
+impl ChromeTrace {
+    /// Start a trace, which timestamps are measured from
+    pub fn new() -> Self {
+        ChromeTrace {
+            started: platform::instant(),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Number of begin and end records so far
+    pub fn len(&self) -> usize {
+        self.records.lock().expect("Trace mutex poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record `name` running over `events` events while `run` runs on this thread
+    pub(crate) fn span<R>(&self, name: &'static str, events: usize, run: impl FnOnce() -> R) -> R {
+        self.record(name, Phase::Begin, events);
+        let result = run();
+        self.record(name, Phase::End, events);
+        result
+    }
+
+    fn record(&self, name: &'static str, phase: Phase, events: usize) {
+        let Some(started) = self.started else {
+            return;
+        };
+        let Some(now) = platform::instant() else {
+            return;
+        };
+        let record = Record {
+            name,
+            phase,
+            timestamp: now.duration_since(started).as_micros() as u64,
+            thread: thread_number(),
+            events,
+        };
+        self.records
+            .lock()
+            .expect("Trace mutex poisoned")
+            .push(record);
+    }
+
+    /// The records so far as a JSON object in the Chrome trace event format
+    pub fn to_json(&self) -> String {
+        let records = self.records.lock().expect("Trace mutex poisoned");
+        let mut json = String::from("{\"traceEvents\":[");
+        for (index, record) in records.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let phase = match record.phase {
+                Phase::Begin => "B",
+                Phase::End => "E",
+            };
+            let _ = write!(
+                json,
+                "{{\"name\":\"{}\",\"cat\":\"handler\",\"ph\":\"{phase}\",\"ts\":{},\"pid\":1,\
+                 \"tid\":{},\"args\":{{\"events\":{}}}}}",
+                escape(record.name),
+                record.timestamp,
+                record.thread,
+                record.events,
+            );
+        }
+        json.push_str("]}");
+        json
+    }
+
+    /// Write the records so far to `writer` in the Chrome trace event format, e.g. to a file to
+    /// open in `about://tracing`
+    pub fn write_to(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(self.to_json().as_bytes())
+    }
+}
+
+/// Escape a string for use in JSON
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_are_recorded_as_begin_and_end_records() {
+        let trace = ChromeTrace::new();
+        let result = trace.span("physics \"step\"", 2, || 7);
+        assert_eq!(result, 7);
+        assert_eq!(trace.len(), 2);
+
+        let json = trace.to_json();
+        assert!(json.starts_with("{\"traceEvents\":[{\"name\":\"physics \\\"step\\\"\""));
+        assert!(json.contains("\"ph\":\"B\""));
+        assert!(json.contains("\"ph\":\"E\""));
+        assert!(json.contains("\"args\":{\"events\":2}"));
+        assert!(json.ends_with("]}"));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{any::Any, fmt};
+
+use crate::{DynEvent, Publisher, subscription::PublisherId};
+
+type Errors = Vec<Box<dyn Any + Send + 'static>>;
+
+/// Publishes a Publisher's share of a committed Transaction's events, if it is still around
+pub(crate) type Commit = Box<dyn Fn(Vec<Box<dyn DynEvent>>) -> Result<(), Errors>>;
+
+/// The events queued on one Publisher by a Transaction
+struct Queued {
+    publisher: PublisherId,
+    commit: Commit,
+    events: Vec<Box<dyn DynEvent>>,
+}
+
+/// Events queued by the closure passed to `Publisher::transaction`, which are only published
+/// once it returns Ok. Events can be queued on other Publishers too, with `publish_on`, so that
+/// a change spanning several buses is published on all of them or none.
+pub struct Transaction {
+    /// Queued events, grouped by Publisher in the order each was first published on
+    queued: Vec<Queued>,
+}
+
+impl Transaction {
+    /// Start a transaction on `publisher`, which `publish` queues events on
+    pub(crate) fn new(publisher: &Publisher) -> Self {
+        let (id, commit) = publisher.committer();
+        Transaction {
+            queued: vec![Queued {
+                publisher: id,
+                commit,
+                events: Vec::new(),
+            }],
+        }
+    }
+
+    /// Queue an event to be published on the Publisher the transaction was started on
+    pub fn publish<T>(&mut self, event: T)
+    where
+        T: DynEvent,
+    {
+        self.queued[0].events.push(Box::new(event));
+    }
+
+    /// Queue an event to be published on `publisher` when the transaction is committed. Events
+    /// queued on Publishers that have been dropped by then are discarded.
+    pub fn publish_on<T>(&mut self, publisher: &Publisher, event: T)
+    where
+        T: DynEvent,
+    {
+        let (id, commit) = publisher.committer();
+        let index = match self.queued.iter().position(|queued| queued.publisher == id) {
+            Some(index) => index,
+            None => {
+                self.queued.push(Queued {
+                    publisher: id,
+                    commit,
+                    events: Vec::new(),
+                });
+                self.queued.len() - 1
+            }
+        };
+        self.queued[index].events.push(Box::new(event));
+    }
+
+    /// Number of events queued so far, across every Publisher
+    pub fn len(&self) -> usize {
+        self.queued.iter().map(|queued| queued.events.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Publish every queued event, one Publisher at a time, each receiving its events in the
+    /// order they were queued as with `publish_all`
+    pub(crate) fn commit(self) -> Result<(), Errors> {
+        let mut errors = Vec::new();
+        for queued in self.queued {
+            if queued.events.is_empty() {
+                continue;
+            }
+            if let Err(e) = (queued.commit)(queued.events) {
+                errors.extend(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Reasons a transaction's events weren't all handled cleanly
+#[derive(Debug)]
+pub enum TransactionError<E> {
+    /// The closure returned an error, so none of the queued events were published
+    Aborted(E),
+    /// Every queued event was published, but some of their handlers panicked
+    Handler(Vec<Box<dyn Any + Send + 'static>>),
+}
+
+impl<E: fmt::Display> fmt::Display for TransactionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Aborted(e) => write!(f, "transaction aborted: {e}"),
+            TransactionError::Handler(errors) => {
+                write!(f, "{} handlers panicked in transaction", errors.len())
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for TransactionError<E> {}
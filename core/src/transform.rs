@@ -0,0 +1,41 @@
+use std::any::{self, TypeId};
+
+use crate::{DynEvent, Event, FromEvent};
+
+type Derive = Box<dyn Fn(&dyn DynEvent) -> Option<Box<dyn DynEvent>> + Send + Sync>;
+
+/// Derives an event of one type from each published event of another, registered with
+/// `Publisher::transform`
+pub(crate) struct Transform {
+    pub(crate) event_type: TypeId,
+    pub(crate) event_type_name: &'static str,
+    /// Name of the type of the events derived
+    pub(crate) derived_type_name: &'static str,
+    pub(crate) label: &'static str,
+    derive: Derive,
+}
+
+impl Transform {
+    pub(crate) fn new<A, B, F>(derive: F) -> Self
+    where
+        A: FromEvent,
+        B: Event,
+        F: Fn(A) -> Option<B> + Send + Sync + 'static,
+    {
+        Transform {
+            event_type: TypeId::of::<A::Source>(),
+            event_type_name: any::type_name::<A::Source>(),
+            derived_type_name: any::type_name::<B>(),
+            label: any::type_name::<F>(),
+            derive: Box::new(move |event| {
+                let derived = derive(A::from_event(event)?)?;
+                Some(Box::new(derived))
+            }),
+        }
+    }
+
+    /// The event derived from `event`, if it is of the source type and the transform produces one
+    pub(crate) fn apply(&self, event: &dyn DynEvent) -> Option<Box<dyn DynEvent>> {
+        (self.derive)(event)
+    }
+}
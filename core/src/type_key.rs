@@ -0,0 +1,210 @@
+use std::{
+    any::{self, Any, TypeId},
+    collections::HashMap,
+    mem,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+/// Identifies an event type by its name, which, unlike its `TypeId`, stays the same when the
+/// library defining it is reloaded. With the `dynamic-plugins` feature, handlers receive events of
+/// the types they take by key, so handlers in a hot-reloaded plugin keep receiving events
+/// published by the host, and the other way round.
+///
+/// Types are told apart by `std::any::type_name`, so two types with the same path are treated as
+/// the same type. Every copy of a type must come from the same definition, built by the same
+/// compiler: events are only handed over between copies whose sizes and alignments match, but
+/// their fields are assumed to match too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeKey(u64);
+
+impl TypeKey {
+    /// The key of `T`, noting this copy of `T` as one of its generations
+    pub fn of<T: 'static>() -> Self {
+        register(TypeId::of::<T>(), any::type_name::<T>())
+    }
+
+    /// The key of the type with the given name
+    pub const fn named(name: &str) -> Self {
+        // FNV-1a, which is the same on every platform and from one build to the next
+        let bytes = name.as_bytes();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+            i += 1;
+        }
+
+        TypeKey(hash)
+    }
+
+    /// How many times the type has been reloaded, counted by the distinct `TypeId`s seen for it,
+    /// starting from 0 for the first
+    pub fn generation(self) -> u64 {
+        registry()
+            .read()
+            .expect("Type registry lock poisoned")
+            .generations
+            .get(&self)
+            .map_or(0, |ids| ids.len().saturating_sub(1) as u64)
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    keys: HashMap<TypeId, TypeKey>,
+    /// Every `TypeId` seen for each key, oldest first
+    generations: HashMap<TypeKey, Vec<TypeId>>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Note that the type with the given `TypeId` is called `name`, returning its key
+pub(crate) fn register(type_id: TypeId, name: &str) -> TypeKey {
+    if let Some(key) = registry()
+        .read()
+        .expect("Type registry lock poisoned")
+        .keys
+        .get(&type_id)
+    {
+        return *key;
+    }
+
+    let key = TypeKey::named(name);
+    let mut registry = registry().write().expect("Type registry lock poisoned");
+    if registry.keys.insert(type_id, key).is_none() {
+        registry.generations.entry(key).or_default().push(type_id);
+    }
+    key
+}
+
+fn key_of(type_id: TypeId) -> Option<TypeKey> {
+    registry()
+        .read()
+        .expect("Type registry lock poisoned")
+        .keys
+        .get(&type_id)
+        .copied()
+}
+
+/// Whether the type with the given `TypeId` is a copy of `T`
+pub(crate) fn is_type<T: 'static>(type_id: TypeId) -> bool {
+    type_id == TypeId::of::<T>() || key_of(type_id) == Some(TypeKey::of::<T>())
+}
+
+/// Whether `data` is a `T`, or a copy of `T` that can be handed over as one
+pub(crate) fn is<T: 'static>(data: &dyn Any) -> bool {
+    data.is::<T>()
+        || (mem::size_of_val(data) == mem::size_of::<T>()
+            && mem::align_of_val(data) == mem::align_of::<T>()
+            && is_type::<T>(data.type_id()))
+}
+
+pub(crate) fn downcast_ref<T: 'static>(data: &dyn Any) -> Option<&T> {
+    if !is::<T>(data) {
+        return None;
+    }
+
+    // SAFETY: `data` is `T`, or a copy of `T` from another build of the library that defines it,
+    // with the same name, size and alignment, which `TypeKey` requires to have the same layout
+    Some(unsafe { &*(data as *const dyn Any as *const T) })
+}
+
+pub(crate) fn downcast_arc<T: 'static>(data: Arc<dyn Any + Send + Sync>) -> Option<Arc<T>> {
+    if !is::<T>(data.as_ref()) {
+        return None;
+    }
+
+    // SAFETY: as for `downcast_ref`, and an `Arc` of types with the same layout has the same
+    // layout itself
+    Some(unsafe { Arc::from_raw(Arc::into_raw(data) as *const T) })
+}
+
+pub(crate) fn downcast_box<T: 'static>(data: Box<dyn Any + Send>) -> Option<Box<T>> {
+    if !is::<T>(data.as_ref()) {
+        return None;
+    }
+
+    // SAFETY: as for `downcast_ref`
+    Some(unsafe { Box::from_raw(Box::into_raw(data) as *mut T) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Delivery, DynEvent, Event, Publisher};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Moved {
+        x: i32,
+        y: i32,
+    }
+    impl Event for Moved {}
+
+    /// Stands in for `Moved` as compiled into a plugin that has been reloaded, which has a
+    /// different `TypeId` but the same name and layout. Its fields are only ever read as `Moved`.
+    #[allow(dead_code)]
+    struct ReloadedMoved {
+        x: i32,
+        y: i32,
+    }
+
+    impl DynEvent for ReloadedMoved {
+        fn get_data(&self) -> &dyn Any {
+            self
+        }
+
+        fn delivery(&self) -> Delivery {
+            Delivery::Clone
+        }
+
+        fn type_name(&self) -> &'static str {
+            any::type_name::<Moved>()
+        }
+
+        fn size(&self) -> usize {
+            mem::size_of::<Self>()
+        }
+
+        fn into_shared(self: Box<Self>) -> Arc<dyn Any + Send + Sync> {
+            Arc::<Self>::from(self)
+        }
+
+        fn into_any(self: Box<Self>) -> Box<dyn Any + Send> {
+            self
+        }
+    }
+
+    #[test]
+    fn test_keys_are_derived_from_type_names() {
+        assert_eq!(
+            TypeKey::of::<Moved>(),
+            TypeKey::named(any::type_name::<Moved>())
+        );
+        assert_eq!(TypeKey::named("a::B"), TypeKey::named("a::B"));
+        assert_ne!(TypeKey::named("a::B"), TypeKey::named("a::C"));
+    }
+
+    #[test]
+    fn test_handlers_receive_events_from_reloaded_copies_of_their_types() {
+        let mut publisher = Publisher::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        publisher.subscribe_with(move |event: Moved| received_clone.lock().unwrap().push(event));
+        let key = TypeKey::of::<Moved>();
+        let generation = key.generation();
+
+        publisher.publish(Moved { x: 1, y: 2 }).unwrap();
+        publisher.publish(ReloadedMoved { x: 3, y: 4 }).unwrap();
+
+        assert_eq!(key.generation(), generation + 1);
+        assert_eq!(
+            *received.lock().unwrap(),
+            [Moved { x: 1, y: 2 }, Moved { x: 3, y: 4 }]
+        );
+    }
+}
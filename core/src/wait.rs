@@ -0,0 +1,123 @@
+use std::{
+    any::Any,
+    sync::{Arc, Condvar, Mutex},
+    time::Instant,
+};
+
+/// What happened to an event published with `Publisher::publish_and_wait`
+#[derive(Debug, Default)]
+pub struct PublishReport {
+    /// Errors returned by handlers, including ones that ran in the background
+    pub errors: Vec<Box<dyn Any + Send + 'static>>,
+    /// Whether the timeout elapsed before all background work had finished
+    pub timed_out: bool,
+    /// Whether the event had a `Deadline` that passed before its handlers ran
+    pub missed_deadline: bool,
+}
+
+impl PublishReport {
+    /// Whether every handler finished without error before the timeout
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && !self.timed_out
+    }
+}
+
+/// Keeps count of handler work a Publisher has left to run in the background, such as debounced
+/// handlers waiting for things to go quiet, so that callers can wait for it to finish
+#[derive(Default)]
+pub(crate) struct InFlight {
+    state: Mutex<InFlightState>,
+    finished: Condvar,
+}
+
+#[derive(Default)]
+struct InFlightState {
+    count: usize,
+    errors: Vec<Box<dyn Any + Send + 'static>>,
+}
+
+impl InFlight {
+    /// Record the start of some background work, which finishes when the guard is dropped
+    pub(crate) fn start(self: &Arc<Self>) -> InFlightGuard {
+        self.state.lock().expect("In-flight mutex poisoned").count += 1;
+        InFlightGuard(self.clone())
+    }
+
+    /// Number of pieces of background work that haven't finished yet
+    pub(crate) fn count(&self) -> usize {
+        self.state.lock().expect("In-flight mutex poisoned").count
+    }
+
+    /// Block until all background work has finished or `deadline` passes. Returns the errors
+    /// collected from the work that finished since the last wait, and whether the deadline passed.
+    pub(crate) fn wait(&self, deadline: Instant) -> (Vec<Box<dyn Any + Send + 'static>>, bool) {
+        let mut state = self.state.lock().expect("In-flight mutex poisoned");
+        let mut timed_out = false;
+        while state.count > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                timed_out = true;
+                break;
+            }
+            state = self
+                .finished
+                .wait_timeout(state, deadline - now)
+                .expect("In-flight mutex poisoned")
+                .0;
+        }
+
+        (std::mem::take(&mut state.errors), timed_out)
+    }
+}
+
+/// Marks a piece of background work as in flight until dropped
+pub(crate) struct InFlightGuard(Arc<InFlight>);
+
+impl InFlightGuard {
+    /// Record an error returned by the background work
+    pub(crate) fn fail(&self, error: Box<dyn Any + Send + 'static>) {
+        if let Ok(mut state) = self.0.state.lock() {
+            state.errors.push(error);
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.0.state.lock() {
+            state.count -= 1;
+        }
+        self.0.finished.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_wait_returns_once_work_finishes() {
+        let in_flight = Arc::new(InFlight::default());
+        let guard = in_flight.start();
+        let worker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            guard.fail(Box::new("failed"));
+        });
+
+        let (errors, timed_out) = in_flight.wait(Instant::now() + Duration::from_secs(5));
+        worker.join().unwrap();
+        assert!(!timed_out);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_wait_times_out() {
+        let in_flight = Arc::new(InFlight::default());
+        let _guard = in_flight.start();
+
+        let (errors, timed_out) = in_flight.wait(Instant::now() + Duration::from_millis(20));
+        assert!(errors.is_empty());
+        assert!(timed_out);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fmt, time::Duration};
+
+use crate::platform;
+
+/// A handler that took longer than a Watchdog's threshold to handle an event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowHandler {
+    /// Label of the handler, which is its name if it was subscribed with `subscribe_named`
+    pub handler: &'static str,
+    /// Name of the type of the event it was handling
+    pub event_type: &'static str,
+    pub elapsed: Duration,
+    pub threshold: Duration,
+}
+
+impl fmt::Display for SlowHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} took {:?} to handle {}, over the {:?} threshold",
+            self.handler, self.elapsed, self.event_type, self.threshold
+        )
+    }
+}
+
+type Callback = dyn Fn(&SlowHandler) + Send + Sync;
+
+/// Times each handler as it handles each event while it is set on a Publisher with
+/// `Publisher::set_watchdog`, and calls back about the ones that take longer than a threshold,
+/// e.g. to find the handlers behind frame hitches. The callback runs on the thread the handler
+/// ran on, straight after it, so it should be quick. Batch handlers are timed per batch, and
+/// ordered and metadata handlers aren't timed. Nothing is timed on targets without a clock, like
+/// `wasm32-unknown-unknown`.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use crier::{Publisher, Watchdog};
+///
+/// let mut publisher = Publisher::default();
+/// publisher.set_watchdog(Some(Watchdog::new(Duration::from_millis(2), |slow| {
+///     eprintln!("frame hitch: {slow}");
+/// })));
+/// ```
+pub struct Watchdog {
+    threshold: Duration,
+    callback: Box<Callback>,
+}
+
+impl Watchdog {
+    /// Call `callback` for every handler that takes longer than `threshold` to handle an event
+    pub fn new<F>(threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(&SlowHandler) + Send + Sync + 'static,
+    {
+        Watchdog {
+            threshold,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Write every handler that takes longer than `threshold` to handle an event to stderr
+    pub fn log(threshold: Duration) -> Self {
+        Watchdog::new(threshold, |slow| eprintln!("crier: {slow}"))
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Run `run`, which is `handler` handling an event of type `event_type`, calling back if it
+    /// takes too long
+    pub(crate) fn time<R>(
+        &self,
+        handler: &'static str,
+        event_type: &'static str,
+        run: impl FnOnce() -> R,
+    ) -> R {
+        let Some(started) = platform::instant() else {
+            return run();
+        };
+        let result = run();
+        if let Some(now) = platform::instant() {
+            let elapsed = now.duration_since(started);
+            if elapsed > self.threshold {
+                (self.callback)(&SlowHandler {
+                    handler,
+                    event_type,
+                    elapsed,
+                    threshold: self.threshold,
+                });
+            }
+        }
+        result
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Publisher};
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    #[derive(Clone)]
+    struct Frame(u64);
+    impl Event for Frame {}
+
+    #[test]
+    fn test_handlers_over_the_threshold_are_reported() {
+        let mut publisher = Publisher::default();
+        publisher.subscribe_named(
+            "render",
+            Handler::new(|frame: Frame| thread::sleep(Duration::from_millis(frame.0))),
+        );
+        publisher.subscribe_with(|_frame: Frame| {});
+        let slow = Arc::new(Mutex::new(Vec::new()));
+        let slow_clone = slow.clone();
+        publisher.set_watchdog(Some(Watchdog::new(
+            Duration::from_millis(20),
+            move |slow| slow_clone.lock().unwrap().push(slow.clone()),
+        )));
+
+        publisher.publish(Frame(0)).unwrap();
+        publisher.publish(Frame(30)).unwrap();
+        publisher.set_watchdog(None);
+        publisher.publish(Frame(30)).unwrap();
+
+        let slow = slow.lock().unwrap();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].handler, "render");
+        assert_eq!(slow[0].event_type, std::any::type_name::<Frame>());
+        assert!(slow[0].elapsed >= Duration::from_millis(30));
+    }
+}
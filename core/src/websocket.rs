@@ -0,0 +1,514 @@
+//! A bridge that pushes events to, and receives events from, WebSocket peers such as web
+//! dashboards.
+//!
+//! Start one with `Publisher::serve_websocket`. Every message is a JSON text message. After
+//! connecting, a peer sends a hello listing the type tags of the events it wants to receive, and
+//! the server replies with a hello listing the tags of the events it will accept:
+//!
+//! ```json
+//! {"wants": ["chat", "scored"]}
+//! ```
+//!
+//! From then on either side can send events, each as its type tag along with the event itself:
+//!
+//! ```json
+//! {"tag": "chat", "event": {"text": "hello"}}
+//! ```
+//!
+//! Only event types registered with the server's EventRegistry cross the bridge.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::RefUnwindSafe,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::{DynEvent, DynHandle, EventRegistry, EventTooLarge, SerializedEvent};
+
+/// Largest message, in bytes, that a peer may send, and the largest event sent to peers unless
+/// set lower with `WebSocketServer::set_max_event_size`
+pub const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// How often a connection stops waiting for messages from its peer to send any events queued for
+/// it
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The first message each side sends, listing the type tags of the events it wants
+#[derive(Debug, Serialize, Deserialize)]
+struct Hello {
+    wants: Vec<String>,
+}
+
+/// An event as it's sent over a WebSocket
+#[derive(Debug, Serialize, Deserialize)]
+struct Frame {
+    tag: String,
+    event: serde_json::Value,
+}
+
+/// A connected peer, along with the events it wants sent to it
+struct Peer {
+    wants: HashSet<String>,
+    outbox: mpsc::Sender<String>,
+}
+
+type Peers = Arc<Mutex<HashMap<u64, Peer>>>;
+
+/// Handler that sends events to every connected peer that wants them. Subscribed to the
+/// Publisher by `Publisher::serve_websocket`.
+pub(crate) struct Broadcaster {
+    registry: Arc<EventRegistry>,
+    peers: Peers,
+    max_event_size: Arc<AtomicUsize>,
+}
+
+impl RefUnwindSafe for Broadcaster {}
+
+impl DynHandle for Broadcaster {
+    fn dyn_handle(&self, event: &dyn DynEvent) {
+        let _ = self.dyn_try_handle(event);
+    }
+
+    fn dyn_try_handle(&self, event: &dyn DynEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.accepts(event) {
+            return Ok(());
+        }
+        let Ok(serialized) = self.registry.serialize(event) else {
+            return Ok(());
+        };
+        let peers = self.peers.lock().expect("WebSocket peers mutex poisoned");
+        if !peers
+            .values()
+            .any(|peer| peer.wants.contains(&serialized.tag))
+        {
+            return Ok(());
+        }
+        let max_event_size = self.max_event_size.load(Ordering::SeqCst);
+        EventTooLarge::check("websocket", &serialized, max_event_size)?;
+        let SerializedEvent { tag, payload } = serialized;
+        let Ok(event) = serde_json::from_slice(&payload) else {
+            return Ok(());
+        };
+        let frame = Frame {
+            tag: tag.clone(),
+            event,
+        };
+        let Ok(text) = serde_json::to_string(&frame) else {
+            return Ok(());
+        };
+
+        for peer in peers.values().filter(|peer| peer.wants.contains(&tag)) {
+            let _ = peer.outbox.send(text.clone());
+        }
+        Ok(())
+    }
+
+    fn accepts(&self, event: &dyn DynEvent) -> bool {
+        // events received from peers are not echoed back out
+        let remote = event.metadata().is_some_and(|metadata| metadata.remote);
+        !remote && self.registry.is_registered(event)
+    }
+
+    fn accepts_remote(&self) -> bool {
+        false
+    }
+}
+
+/// Accepts WebSocket connections, publishing the events peers send and sending them the events
+/// they ask for. Created by `Publisher::serve_websocket`. Stops listening, disconnects its peers
+/// and unsubscribes from the Publisher when dropped.
+pub struct WebSocketServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    max_event_size: Arc<AtomicUsize>,
+    thread: Option<thread::JoinHandle<()>>,
+    unsubscribe: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl WebSocketServer {
+    /// Listen on `addr`, passing every event received to `publish` until it returns false.
+    /// Returns the server along with the handler that sends events to its peers.
+    pub(crate) fn bind<F>(
+        addr: impl ToSocketAddrs,
+        registry: Arc<EventRegistry>,
+        publish: F,
+    ) -> io::Result<(Self, Broadcaster)>
+    where
+        F: Fn(Box<dyn DynEvent>) -> bool + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let peers: Peers = Arc::default();
+        let max_event_size = Arc::new(AtomicUsize::new(MAX_MESSAGE_SIZE));
+
+        let thread = {
+            let stopped = stopped.clone();
+            let peers = peers.clone();
+            let max_event_size = max_event_size.clone();
+            let registry = registry.clone();
+            let publish = Arc::new(publish);
+            thread::Builder::new()
+                .name(String::from("crier-websocket"))
+                .spawn(move || {
+                    accept(
+                        listener,
+                        &stopped,
+                        &peers,
+                        &max_event_size,
+                        &registry,
+                        &publish,
+                    )
+                })?
+        };
+
+        let server = WebSocketServer {
+            local_addr,
+            stopped,
+            max_event_size: max_event_size.clone(),
+            thread: Some(thread),
+            unsubscribe: None,
+        };
+        let broadcaster = Broadcaster {
+            registry,
+            peers,
+            max_event_size,
+        };
+
+        Ok((server, broadcaster))
+    }
+
+    /// Run `unsubscribe` when the server is dropped, to remove its Broadcaster from the Publisher
+    pub(crate) fn on_drop(&mut self, unsubscribe: impl FnOnce() + Send + 'static) {
+        self.unsubscribe = Some(Box::new(unsubscribe));
+    }
+
+    /// The address the server is bound to. Useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Drop events that serialize to more than `bytes`, rather than sending them to peers,
+    /// failing with EventTooLarge. Larger events from peers are skipped. Can't be set higher than
+    /// MAX_MESSAGE_SIZE.
+    pub fn set_max_event_size(&self, bytes: usize) {
+        self.max_event_size
+            .store(bytes.min(MAX_MESSAGE_SIZE), Ordering::SeqCst);
+    }
+}
+
+impl Drop for WebSocketServer {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // wake the accept loop so that it sees it has been stopped
+        let mut wake = self.local_addr;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(match wake.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake, Duration::from_secs(5));
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept<F>(
+    listener: TcpListener,
+    stopped: &Arc<AtomicBool>,
+    peers: &Peers,
+    max_event_size: &Arc<AtomicUsize>,
+    registry: &Arc<EventRegistry>,
+    publish: &Arc<F>,
+) where
+    F: Fn(Box<dyn DynEvent>) -> bool + Send + Sync + 'static,
+{
+    let mut next_id = 0;
+    for stream in listener.incoming() {
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        let id = next_id;
+        next_id += 1;
+        let stopped = stopped.clone();
+        let peers = peers.clone();
+        let max_event_size = max_event_size.clone();
+        let registry = registry.clone();
+        let publish = publish.clone();
+        let _ = thread::Builder::new()
+            .name(String::from("crier-websocket-peer"))
+            .spawn(move || {
+                let _ = serve(
+                    stream,
+                    id,
+                    &stopped,
+                    &peers,
+                    &max_event_size,
+                    &registry,
+                    publish.as_ref(),
+                );
+                if let Ok(mut peers) = peers.lock() {
+                    peers.remove(&id);
+                }
+            });
+    }
+}
+
+/// Exchange hellos with a peer, then relay events both ways until it disconnects, sends something
+/// invalid, or the server is stopped
+fn serve(
+    stream: TcpStream,
+    id: u64,
+    stopped: &AtomicBool,
+    peers: &Peers,
+    max_event_size: &AtomicUsize,
+    registry: &EventRegistry,
+    publish: &dyn Fn(Box<dyn DynEvent>) -> bool,
+) -> Result<(), tungstenite::Error> {
+    let mut socket = tungstenite::accept(stream).map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e,
+        tungstenite::HandshakeError::Interrupted(_) => tungstenite::Error::ConnectionClosed,
+    })?;
+
+    let Some(hello) = read_text(&mut socket, stopped)? else {
+        return Ok(());
+    };
+    let Ok(hello) = serde_json::from_str::<Hello>(&hello) else {
+        return socket.close(None);
+    };
+    let accepted = Hello {
+        wants: registry.tags().map(String::from).collect(),
+    };
+    let accepted = serde_json::to_string(&accepted).expect("Hello is always serializable");
+    socket.send(Message::text(accepted))?;
+
+    let (outbox, inbox) = mpsc::channel();
+    peers
+        .lock()
+        .expect("WebSocket peers mutex poisoned")
+        .insert(
+            id,
+            Peer {
+                wants: hello.wants.into_iter().collect(),
+                outbox,
+            },
+        );
+
+    while let Some(text) = read_text_or_send(&mut socket, stopped, &inbox)? {
+        let Ok(Frame { tag, event }) = serde_json::from_str(&text) else {
+            continue;
+        };
+        let serialized = SerializedEvent {
+            tag,
+            payload: event.to_string().into_bytes(),
+        };
+        let limit = max_event_size.load(Ordering::SeqCst);
+        if EventTooLarge::check("websocket", &serialized, limit).is_err() {
+            continue;
+        }
+        // events of types this side hasn't registered are skipped rather than ending the
+        // connection, so that peers don't have to be upgraded at the same time
+        if let Ok(event) = registry.deserialize(&serialized)
+            && !publish(event)
+        {
+            break;
+        }
+    }
+
+    socket.close(None)
+}
+
+/// Wait for the next text message from the peer. Returns None once the peer has disconnected or
+/// the server has been stopped.
+fn read_text(
+    socket: &mut WebSocket<TcpStream>,
+    stopped: &AtomicBool,
+) -> Result<Option<String>, tungstenite::Error> {
+    let (_, inbox) = mpsc::channel();
+    read_text_or_send(socket, stopped, &inbox)
+}
+
+/// Wait for the next text message from the peer, sending it any events that arrive in its inbox
+/// in the meantime. Returns None once the peer has disconnected or the server has been stopped.
+fn read_text_or_send(
+    socket: &mut WebSocket<TcpStream>,
+    stopped: &AtomicBool,
+    inbox: &mpsc::Receiver<String>,
+) -> Result<Option<String>, tungstenite::Error> {
+    socket.get_mut().set_read_timeout(Some(POLL_INTERVAL))?;
+    loop {
+        if stopped.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        for text in inbox.try_iter() {
+            socket.send(Message::text(text))?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => return Ok(Some(text.as_str().to_owned())),
+            Ok(Message::Close(_)) => return Ok(None),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Handler, Publisher, SerializableEvent};
+    use std::time::Instant;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat {
+        text: String,
+    }
+    impl Event for Chat {}
+    impl SerializableEvent for Chat {
+        const TYPE_TAG: &'static str = "chat";
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Scored(u32);
+    impl Event for Scored {}
+    impl SerializableEvent for Scored {
+        const TYPE_TAG: &'static str = "scored";
+    }
+
+    fn registry() -> Arc<EventRegistry> {
+        let mut registry = EventRegistry::default();
+        registry.register::<Chat>().register::<Scored>();
+        Arc::new(registry)
+    }
+
+    fn connect(server: &WebSocketServer, wants: &[&str]) -> (WebSocket<TcpStream>, Hello) {
+        let addr = server.local_addr();
+        let stream = TcpStream::connect(addr).unwrap();
+        let (mut socket, _) = tungstenite::client(format!("ws://{addr}"), stream).unwrap();
+        let hello = Hello {
+            wants: wants.iter().map(|tag| String::from(*tag)).collect(),
+        };
+        socket
+            .send(Message::text(serde_json::to_string(&hello).unwrap()))
+            .unwrap();
+        let reply = socket.read().unwrap().into_text().unwrap();
+
+        (socket, serde_json::from_str(&reply).unwrap())
+    }
+
+    #[test]
+    fn test_hello_advertises_registered_tags() {
+        let mut publisher = Publisher::default();
+        let server = publisher
+            .serve_websocket("127.0.0.1:0", registry())
+            .unwrap();
+
+        let (_socket, hello) = connect(&server, &[]);
+        let mut wants = hello.wants;
+        wants.sort();
+        assert_eq!(wants, vec!["chat", "scored"]);
+    }
+
+    #[test]
+    fn test_peers_receive_only_the_events_they_want() {
+        let mut publisher = Publisher::default();
+        let server = publisher
+            .serve_websocket("127.0.0.1:0", registry())
+            .unwrap();
+        let (mut socket, _) = connect(&server, &["scored"]);
+
+        // the peer is only registered once the server has sent its hello, so this event can't
+        // race ahead of it
+        let _ = publisher.publish(Chat {
+            text: String::from("ignored"),
+        });
+        let _ = publisher.publish(Scored(3));
+
+        let text = socket.read().unwrap().into_text().unwrap();
+        let frame: Frame = serde_json::from_str(&text).unwrap();
+        assert_eq!(frame.tag, "scored");
+        assert_eq!(frame.event, serde_json::json!(3));
+    }
+
+    #[test]
+    fn test_events_from_peers_are_published() {
+        let mut publisher = Publisher::default();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        publisher.subscribe(Handler::new(move |event: Chat| {
+            sender.lock().unwrap().send(event.text).unwrap()
+        }));
+        let server = publisher
+            .serve_websocket("127.0.0.1:0", registry())
+            .unwrap();
+        let (mut socket, _) = connect(&server, &["chat"]);
+
+        let frame = Frame {
+            tag: String::from("chat"),
+            event: serde_json::json!({ "text": "hello" }),
+        };
+        socket
+            .send(Message::text(serde_json::to_string(&frame).unwrap()))
+            .unwrap();
+
+        let received = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, "hello");
+
+        // the event is not echoed back to the peers
+        socket
+            .get_mut()
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        assert!(socket.read().is_err());
+    }
+
+    #[test]
+    fn test_dropping_the_server_disconnects_peers() {
+        let mut publisher = Publisher::default();
+        let server = publisher
+            .serve_websocket("127.0.0.1:0", registry())
+            .unwrap();
+        let (mut socket, _) = connect(&server, &["chat"]);
+
+        let started = Instant::now();
+        drop(server);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let closed = loop {
+            match socket.read() {
+                Ok(Message::Close(_)) => {}
+                Ok(_) => continue,
+                Err(_) => break true,
+            }
+        };
+        assert!(closed);
+    }
+}
@@ -1,16 +1,255 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Data, DeriveInput, Ident, Index, Meta, NestedMeta, Token, Type, Visibility, braced,
+    parse_macro_input,
+};
 
 /// Derive macro generating an impl of the trait Event
-#[proc_macro_derive(Event)]
+///
+/// Mark the type with `#[event(deadline)]` if it also implements `crier::Deadline` so that
+/// Publishers schedule it earliest-deadline-first, with `#[event(partition)]` if it also
+/// implements `crier::Partition` so that partitioned handlers see its key, and with
+/// `#[event(shared)]` or `#[event(exclusive)]` to choose how it is delivered to handlers (see
+/// `crier::Delivery`).
+#[proc_macro_derive(Event, attributes(event))]
 pub fn event_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = &input.ident;
 
+    let mut has_deadline = false;
+    let mut has_partition = false;
+    let mut delivery = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("event"))
+    {
+        match attr.parse_meta() {
+            Ok(Meta::List(list)) => {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("deadline") => {
+                            has_deadline = true
+                        }
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("partition") => {
+                            has_partition = true
+                        }
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("shared") => {
+                            delivery = Some(quote! { crier::Delivery::Shared })
+                        }
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("exclusive") => {
+                            delivery = Some(quote! { crier::Delivery::Exclusive })
+                        }
+                        other => {
+                            return syn::Error::new_spanned(other, "unknown event attribute")
+                                .to_compile_error()
+                                .into();
+                        }
+                    }
+                }
+            }
+            Ok(other) => {
+                return syn::Error::new_spanned(other, "expected #[event(...)]")
+                    .to_compile_error()
+                    .into();
+            }
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+
+    let deadline = has_deadline.then(|| {
+        quote! {
+            fn as_deadline(&self) -> Option<&dyn crier::Deadline> {
+                Some(self)
+            }
+        }
+    });
+
+    let partition = has_partition.then(|| {
+        quote! {
+            fn as_partition(&self) -> Option<&dyn crier::Partition> {
+                Some(self)
+            }
+        }
+    });
+
+    let delivery = delivery.map(|delivery| {
+        quote! {
+            const DELIVERY: crier::Delivery = #delivery;
+        }
+    });
+
+    let expanded = quote! {
+        impl crier::Event for #name {
+            #delivery
+            #deadline
+            #partition
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Derive macro generating an impl of the trait Diffable
+///
+/// Fields are compared with `PartialEq`, so every field's type must implement it. Fields of tuple
+/// structs are named by their index.
+#[proc_macro_derive(Diffable)]
+pub fn diffable_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new_spanned(name, "Diffable can only be derived for structs")
+            .to_compile_error()
+            .into();
+    };
+
+    let comparisons = data.fields.iter().enumerate().map(|(index, field)| {
+        let (member, field_name) = match &field.ident {
+            Some(ident) => (quote! { #ident }, ident.to_string()),
+            None => {
+                let index = Index::from(index);
+                (quote! { #index }, index.index.to_string())
+            }
+        };
+        quote! {
+            if self.#member != previous.#member {
+                changed.push(#field_name);
+            }
+        }
+    });
+
     let expanded = quote! {
-        impl crier::Event for #name {}
+        impl #impl_generics crier::Diffable for #name #ty_generics #where_clause {
+            fn changed_fields(&self, previous: &Self) -> ::std::vec::Vec<&'static str> {
+                #[allow(unused_mut)]
+                let mut changed = ::std::vec::Vec::new();
+                #(#comparisons)*
+                changed
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// A bus declared with `define_bus!`
+struct Bus {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    channels: Punctuated<Channel, Token![,]>,
+}
+
+/// One of a bus's channels, which carries a single event type
+struct Channel {
+    name: Ident,
+    event: Type,
+}
+
+impl Parse for Bus {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        let content;
+        braced!(content in input);
+        let channels = content.parse_terminated(Channel::parse)?;
+
+        Ok(Bus {
+            attrs,
+            vis,
+            name,
+            channels,
+        })
+    }
+}
+
+impl Parse for Channel {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let event = input.parse()?;
+
+        Ok(Channel { name, event })
+    }
+}
+
+/// Macro generating a typed wrapper around a `crier::Publisher`
+///
+/// `define_bus! { pub GameBus { player: PlayerEvent, net: NetEvent } }` declares a struct
+/// `GameBus` with a `publish_player` method that publishes `PlayerEvent`s and an `on_player`
+/// method that subscribes a closure to them, and likewise for `net`, so that application code can
+/// discover the events it can send and receive through its IDE. The Publisher behind the bus is
+/// available from its `publisher` method.
+#[proc_macro]
+pub fn define_bus(input: TokenStream) -> TokenStream {
+    let Bus {
+        attrs,
+        vis,
+        name,
+        channels,
+    } = parse_macro_input!(input as Bus);
+
+    let methods = channels.iter().map(|Channel { name, event }| {
+        let publish = format_ident!("publish_{}", name);
+        let on = format_ident!("on_{}", name);
+        let event_name = quote!(#event).to_string();
+        let publish_doc = format!("Publish a `{}` to the handlers subscribed with `{}`", event_name, on);
+        let on_doc = format!(
+            "Subscribe a closure to the `{}`s published with `{}`.\nReturns the ID needed to `unsubscribe` the handler.",
+            event_name, publish
+        );
+
+        quote! {
+            #[doc = #publish_doc]
+            pub fn #publish(
+                &mut self,
+                event: #event,
+            ) -> Result<(), Vec<Box<dyn std::any::Any + Send + 'static>>> {
+                self.publisher.publish(event)
+            }
+
+            #[doc = #on_doc]
+            pub fn #on<F>(&mut self, handler: F) -> crier::SubscriptionId
+            where
+                F: Fn(#event) + Send + Sync + 'static,
+            {
+                self.publisher.subscribe_with(handler)
+            }
+        }
+    });
+
+    let expanded = quote! {
+        #(#attrs)*
+        #[derive(Default)]
+        #vis struct #name {
+            publisher: crier::Publisher,
+        }
+
+        impl #name {
+            /// The Publisher behind the bus, for anything the typed methods don't cover
+            pub fn publisher(&mut self) -> &mut crier::Publisher {
+                &mut self.publisher
+            }
+
+            /// Remove a handler from the bus so that it stops receiving events. Fails if it has
+            /// already been unsubscribed, or was subscribed to a different bus.
+            pub fn unsubscribe(
+                &mut self,
+                id: crier::SubscriptionId,
+            ) -> Result<crier::Unsubscribed, crier::UnsubscribeError> {
+                self.publisher.unsubscribe(id)
+            }
+
+            #(#methods)*
+        }
     };
 
     TokenStream::from(expanded)